@@ -0,0 +1,84 @@
+use wasm_runtime_layer::{
+    backend::{AsContextMut, Extern, Value, WasmFunc, WasmGlobal, WasmInstance},
+    ValueType,
+};
+
+use crate::{Engine, Error, Instance};
+
+impl Instance {
+    /// The conventional name of the export through which a guest declares
+    /// its ABI version
+    pub const ABI_VERSION_EXPORT: &'static str = "__abi_version";
+
+    /// Reads the ABI version that the guest declares through its
+    /// [`Instance::ABI_VERSION_EXPORT`] export, which must either be an
+    /// `i32` global or a function with the signature `() -> i32`.
+    ///
+    /// Returns `Ok(None)` if the guest does not declare its ABI version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the export has an unexpected type or if calling
+    /// the export function fails.
+    pub fn abi_version(&self, mut ctx: impl AsContextMut<Engine>) -> Result<Option<u32>, Error> {
+        let version = match self.get_export(ctx.as_context(), Self::ABI_VERSION_EXPORT) {
+            None => return Ok(None),
+            Some(Extern::Global(global)) => global.get(ctx.as_context_mut()),
+            Some(Extern::Func(func))
+                if WasmFunc::ty(&func, ctx.as_context()).params().is_empty()
+                    && WasmFunc::ty(&func, ctx.as_context()).results() == [ValueType::I32] =>
+            {
+                let mut results = [Value::I32(0)];
+                func.call::<()>(ctx.as_context_mut(), &[], &mut results)
+                    .map_err(|err| Error::Trap(format!("{err:#}")))?;
+                let [result] = results;
+                result
+            },
+            Some(_) => return Err(abi_version_type_mismatch()),
+        };
+
+        match version {
+            #[allow(clippy::cast_sign_loss)]
+            Value::I32(version) => Ok(Some(version as u32)),
+            _ => Err(abi_version_type_mismatch()),
+        }
+    }
+
+    /// Performs a version handshake with the guest by reading its declared
+    /// ABI version, see [`Instance::abi_version`], and checking that it is
+    /// one of the `supported` versions of the host.
+    ///
+    /// This should be called right after instantiation, before calling any
+    /// other guest exports.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::IncompatibleAbi`] if the guest does not declare its
+    /// ABI version or declares an unsupported version, and any error
+    /// returned by [`Instance::abi_version`].
+    pub fn negotiate_abi_version(
+        &self,
+        ctx: impl AsContextMut<Engine>,
+        supported: &[u32],
+    ) -> Result<u32, Error> {
+        let found = self.abi_version(ctx)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?found, ?supported, "Instance::negotiate_abi_version");
+
+        match found {
+            Some(version) if supported.contains(&version) => Ok(version),
+            found => Err(Error::IncompatibleAbi {
+                found,
+                supported: supported.to_vec(),
+            }),
+        }
+    }
+}
+
+fn abi_version_type_mismatch() -> Error {
+    Error::Conversion(format!(
+        "the `{}` export must be an i32 global or a () -> i32 function",
+        Instance::ABI_VERSION_EXPORT
+    ))
+}
@@ -0,0 +1,264 @@
+use std::sync::{Arc, Mutex, Weak};
+
+use pyo3::{exceptions::PyRuntimeError, intern, prelude::*, sync::GILOnceCell, types::PyTuple};
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Value, WasmFunc},
+    ValueType,
+};
+
+use crate::{conversion::ValueExt, func::js_host_callable, store::StoreContextMut, Engine, Func};
+
+/// A scheduler that drives a [`Func`] from [`requestAnimationFrame`].
+///
+/// The driven function is called once per animation frame and receives the
+/// time in milliseconds that has elapsed since the previous frame, i.e. it
+/// has the signature `tick()`, `tick(dt: f32)`, or `tick(dt: f64)`. The first
+/// frame after starting or resuming the loop receives a `dt` of zero.
+///
+/// If the function returns an error, the loop is paused and the error can be
+/// retrieved using [`AnimationLoop::take_error`].
+///
+/// Frames that arrive while a call borrows the store of the function are
+/// skipped, and the next frame receives the time since the skipped one.
+///
+/// [`requestAnimationFrame`]: https://developer.mozilla.org/en-US/docs/Web/API/Window/requestAnimationFrame
+#[derive(Debug)]
+pub struct AnimationLoop {
+    /// The JavaScript loop driver object
+    driver: Py<PyAny>,
+    /// The first error returned by the driven function
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+}
+
+impl AnimationLoop {
+    /// Creates a new paused animation loop that calls `func` once per frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `func` does not have the signature `tick()`,
+    /// `tick(dt: f32)`, or `tick(dt: f64)`.
+    pub fn new<T>(
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        func: &Func,
+    ) -> anyhow::Result<Self> {
+        let mut store: StoreContextMut<T> = ctx.as_context_mut();
+
+        let ty = WasmFunc::ty(func, store.as_context());
+
+        if !matches!(ty.params(), [] | [ValueType::F32 | ValueType::F64]) {
+            anyhow::bail!(
+                "animation loop func must have the signature tick(), tick(dt: f32), or tick(dt: \
+                 f64) but has {ty}"
+            );
+        }
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?ty, "AnimationLoop::new");
+
+            let weak_store = store.as_weak_proof();
+
+            let func = func.clone();
+            let ty_clone = ty.clone();
+            let error = Arc::new(Mutex::new(None));
+            let tick_error = Arc::clone(&error);
+
+            let tick = Arc::new(move |args: Bound<PyTuple>| -> Result<Py<PyAny>, PyErr> {
+                let py = args.py();
+
+                let Some(mut strong_store) = Weak::upgrade(&weak_store) else {
+                    return Err(PyRuntimeError::new_err(
+                        "animation loop called after free of its associated store",
+                    ));
+                };
+
+                // the frame is skipped, but the loop keeps running, if a call
+                //  that borrows the store is in progress, e.g. when the tick
+                //  is invoked synchronously from inside a host function
+                if strong_store.is_borrowed() {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("skipping animation frame while its store is borrowed");
+
+                    return Ok(true.into_pyobject(py)?.to_owned().into_any().unbind());
+                }
+
+                // Safety:
+                //
+                // - The proof is constructed from a mutable store context
+                // - No call that borrows the store is in progress, as checked above
+                let mut store: StoreContextMut<T> =
+                    unsafe { StoreContextMut::from_proof_unchecked(&mut strong_store) };
+
+                let ty = &ty_clone;
+
+                let params = ty
+                    .params()
+                    .iter()
+                    .zip(args.iter())
+                    .map(|(ty, arg)| Value::from_py_typed(arg, *ty))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut results = vec![Value::I32(0); ty.results().len()];
+
+                #[cfg(feature = "tracing")]
+                let _span = tracing::trace_span!("call_animation_frame", ?params, ?ty).entered();
+
+                match func.call::<T>(store.as_context_mut(), &params, &mut results) {
+                    Ok(()) => Ok(true.into_pyobject(py)?.to_owned().into_any().unbind()),
+                    Err(err) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::error!("{err:?}");
+
+                        if let Ok(mut error) = tick_error.lock() {
+                            error.get_or_insert(err);
+                        }
+
+                        Ok(false.into_pyobject(py)?.to_owned().into_any().unbind())
+                    },
+                }
+            });
+
+            let tick = js_host_callable(py, &mut store, tick, &ty)?;
+            let driver = js_animation_loop_new(py)?.call1((tick,))?;
+
+            Ok(Self {
+                driver: driver.unbind(),
+                error,
+            })
+        })
+    }
+
+    /// Starts or resumes the animation loop.
+    ///
+    /// Starting an already running loop has no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the next frame cannot be scheduled.
+    pub fn start(&self) {
+        Python::with_gil(|py| -> Result<(), PyErr> {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("AnimationLoop::start");
+
+            self.driver.bind(py).call_method0(intern!(py, "start"))?;
+
+            Ok(())
+        })
+        .expect("AnimationLoop::start should not fail");
+    }
+
+    /// Pauses the animation loop by cancelling the next scheduled frame.
+    ///
+    /// Pausing an already paused loop has no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the next frame cannot be cancelled.
+    pub fn pause(&self) {
+        Python::with_gil(|py| -> Result<(), PyErr> {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("AnimationLoop::pause");
+
+            self.driver.bind(py).call_method0(intern!(py, "pause"))?;
+
+            Ok(())
+        })
+        .expect("AnimationLoop::pause should not fail");
+    }
+
+    /// Stops the animation loop and returns the error returned by the driven
+    /// function, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error that the driven function returned.
+    pub fn stop(self) -> anyhow::Result<()> {
+        self.pause();
+
+        self.take_error().map_or(Ok(()), Err)
+    }
+
+    /// Returns `true` if the next frame has been scheduled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the loop driver cannot be queried.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        Python::with_gil(|py| -> Result<bool, PyErr> {
+            self.driver
+                .bind(py)
+                .call_method0(intern!(py, "running"))?
+                .extract()
+        })
+        .expect("AnimationLoop::is_running should not fail")
+    }
+
+    /// Takes the error that the driven function returned, which paused the
+    /// loop, if any.
+    ///
+    /// Once the error has been taken, the loop can be resumed using
+    /// [`AnimationLoop::start`].
+    #[must_use]
+    pub fn take_error(&self) -> Option<anyhow::Error> {
+        self.error.lock().ok().and_then(|mut error| error.take())
+    }
+}
+
+impl Drop for AnimationLoop {
+    fn drop(&mut self) {
+        Python::with_gil(|py| {
+            // cancel the next frame so that the driver can be collected
+            let _ = self.driver.bind(py).call_method0(intern!(py, "pause"));
+        });
+    }
+}
+
+fn js_animation_loop_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_ANIMATION_LOOP_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_ANIMATION_LOOP_NEW
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function animationLoop(tick) {
+    let handle = null;
+    let last = null;
+
+    function frame(timestamp) {
+        handle = null;
+        const dt = (last === null) ? 0 : (timestamp - last);
+        last = timestamp;
+        if (tick(dt)) {
+            handle = requestAnimationFrame(frame);
+        } else {
+            last = null;
+        }
+    }
+
+    return {
+        start() {
+            if (handle === null) {
+                handle = requestAnimationFrame(frame);
+            }
+        },
+        pause() {
+            if (handle !== null) {
+                cancelAnimationFrame(handle);
+                handle = null;
+            }
+            last = null;
+        },
+        running() {
+            return (handle !== null);
+        },
+    };
+}
+animationLoop
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
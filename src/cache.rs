@@ -0,0 +1,201 @@
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyBytes};
+
+use crate::{
+    conversion::js_uint8_array_new, func::pyodide_run_sync, Error, Module, ModuleMetadata,
+};
+
+/// Name of the `IndexedDB` database that caches compiled modules
+const DATABASE: &str = "pyodide-webassembly-runtime-layer-modules";
+
+impl Module {
+    /// Starts storing this compiled module, together with its
+    /// [`ModuleMetadata`], in the browser's `IndexedDB` under the `key`, such
+    /// that it can be loaded again with [`Module::load_cached`] on a later
+    /// page load without recompiling it.
+    ///
+    /// An existing entry with the same `key` is replaced.
+    ///
+    /// Note that some browsers refuse to store a compiled
+    /// [`WebAssembly.Module`] in `IndexedDB`, in which case persisting fails
+    /// with a `DataCloneError`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `IndexedDB` is unavailable.
+    ///
+    /// [`WebAssembly.Module`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Module
+    pub fn persist(&self, key: &str) -> anyhow::Result<PendingModuleCache> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("Module::persist", key).entered();
+
+            let metadata = self.metadata().to_bytes();
+            let array = js_uint8_array_new(py)?.call1((metadata.len(),))?;
+            array.call_method1(intern!(py, "assign"), (metadata.as_slice(),))?;
+
+            let promise = js_module_cache(py)?
+                .call_method1(intern!(py, "put"), (DATABASE, key, self.as_js(py), array))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::EnvironmentUnavailable))?;
+
+            Ok(PendingModuleCache {
+                promise: promise.unbind(),
+            })
+        })
+    }
+
+    /// Starts loading the module that was stored under the `key` using
+    /// [`Module::persist`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `IndexedDB` is unavailable.
+    pub fn load_cached(key: &str) -> anyhow::Result<PendingModuleCache> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("Module::load_cached", key).entered();
+
+            let promise = js_module_cache(py)?
+                .call_method1(intern!(py, "get"), (DATABASE, key))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::EnvironmentUnavailable))?;
+
+            Ok(PendingModuleCache {
+                promise: promise.unbind(),
+            })
+        })
+    }
+}
+
+#[derive(Debug)]
+/// A [`Module`] cache access that is still in progress, see
+/// [`Module::persist`] and [`Module::load_cached`].
+pub struct PendingModuleCache {
+    /// The promise that resolves to the cached entry, or to `undefined`
+    promise: Py<PyAny>,
+}
+
+impl PendingModuleCache {
+    /// Returns the JavaScript promise that resolves once the cache access
+    /// has completed, which can be awaited from async Python code.
+    #[must_use]
+    pub fn as_awaitable(&self, py: Python) -> Py<PyAny> {
+        self.promise.clone_ref(py)
+    }
+
+    /// Reconstructs the cached module from the `resolved` value of the
+    /// awaited promise, see [`PendingModuleCache::as_awaitable`].
+    ///
+    /// Returns `None` if there is no cached module, e.g. because no module
+    /// was stored under the key or because it was stored by an incompatible
+    /// version of this crate, or if the access was [`Module::persist`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cached module does not match its metadata.
+    pub fn finish(&self, resolved: &Bound<PyAny>) -> anyhow::Result<Option<Module>> {
+        let py = resolved.py();
+
+        if resolved.is_none() {
+            return Ok(None);
+        }
+
+        let metadata: Bound<PyBytes> = resolved
+            .getattr(intern!(py, "metadata"))?
+            .call_method0(intern!(py, "to_bytes"))?
+            .extract()?;
+
+        let metadata = match ModuleMetadata::from_bytes(metadata.as_bytes()) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(%err, "ignoring incompatible cached module");
+                #[cfg(not(feature = "tracing"))]
+                let _ = err;
+
+                return Ok(None);
+            },
+        };
+
+        Module::from_parts(&resolved.getattr(intern!(py, "module"))?, &metadata).map(Some)
+    }
+
+    /// Blocks until the cache access has completed, see
+    /// [`PendingModuleCache::finish`].
+    ///
+    /// Blocking requires `pyodide.ffi.run_sync`, see [`PendingCall::wait`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if blocking is not supported or if the cache access
+    /// fails.
+    ///
+    /// [`PendingCall::wait`]: crate::PendingCall::wait
+    pub fn wait(self) -> anyhow::Result<Option<Module>> {
+        Python::with_gil(|py| {
+            let run_sync = pyodide_run_sync(py).map_err(|err| {
+                Error::EnvironmentUnavailable(format!(
+                    "blocking on IndexedDB requires pyodide.ffi.run_sync: {err}"
+                ))
+            })?;
+
+            let resolved = run_sync
+                .call1((self.promise.bind(py),))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::EnvironmentUnavailable))?;
+
+            self.finish(&resolved)
+        })
+    }
+}
+
+fn js_module_cache(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_MODULE_CACHE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_MODULE_CACHE
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r#"
+function moduleCache() {
+    function done(request) {
+        return new Promise((resolve, reject) => {
+            request.onsuccess = () => resolve(request.result);
+            request.onerror = () => reject(request.error);
+        });
+    }
+
+    function open(name) {
+        const open = indexedDB.open(name, 1);
+        open.onupgradeneeded = () => open.result.createObjectStore("modules");
+        return done(open);
+    }
+
+    return {
+        async put(name, key, module, metadata) {
+            const db = await open(name);
+            try {
+                const store = db.transaction("modules", "readwrite").objectStore("modules");
+                await done(store.put({ module, metadata }, key));
+            } finally {
+                db.close();
+            }
+            return null;
+        },
+        async get(name, key) {
+            const db = await open(name);
+            try {
+                const store = db.transaction("modules", "readonly").objectStore("modules");
+                const entry = await done(store.get(key));
+                return (entry === undefined) ? null : entry;
+            } finally {
+                db.close();
+            }
+        },
+    };
+}
+moduleCache()
+"#,))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
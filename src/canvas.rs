@@ -0,0 +1,225 @@
+use pyo3::{intern, prelude::*, sync::GILOnceCell};
+use wasm_runtime_layer::backend::AsContext;
+
+use crate::{conversion::ToPy, Engine, Memory};
+
+/// A helper that blits RGBA framebuffers from guest memory to a canvas.
+///
+/// The blitter draws to a [`CanvasRenderingContext2D`] using
+/// [`putImageData`]. Each framebuffer consists of `height` rows of `width`
+/// pixels with four bytes each, in RGBA order, where consecutive rows start
+/// `stride` bytes apart.
+///
+/// Since growing a guest memory detaches its buffer, the blitter never keeps
+/// views into guest memory. Instead, frames are copied into one of two
+/// [`ImageData`] buffers that the blitter owns. [`CanvasBlitter::capture`]
+/// copies a finished frame into the back buffer, which then becomes the
+/// front buffer that [`CanvasBlitter::present`] draws, e.g. from an
+/// [`AnimationLoop`](crate::AnimationLoop). A captured frame therefore stays
+/// intact even if the guest grows or overwrites its memory before it is
+/// presented.
+///
+/// [`CanvasRenderingContext2D`]: https://developer.mozilla.org/en-US/docs/Web/API/CanvasRenderingContext2D
+/// [`putImageData`]: https://developer.mozilla.org/en-US/docs/Web/API/CanvasRenderingContext2D/putImageData
+/// [`ImageData`]: https://developer.mozilla.org/en-US/docs/Web/API/ImageData
+#[derive(Debug)]
+pub struct CanvasBlitter {
+    /// The JavaScript blitter object
+    blitter: Py<PyAny>,
+    /// The width of a frame in pixels
+    width: u32,
+    /// The height of a frame in pixels
+    height: u32,
+}
+
+impl CanvasBlitter {
+    /// Number of bytes per RGBA pixel
+    const BYTES_PER_PIXEL: usize = 4;
+
+    /// Creates a new blitter for frames of `width` x `height` pixels, which
+    /// draws to the 2D canvas `context`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is empty or if the `ImageData` buffers
+    /// cannot be allocated.
+    pub fn new(context: &Bound<PyAny>, width: u32, height: u32) -> anyhow::Result<Self> {
+        if width == 0 || height == 0 {
+            anyhow::bail!("canvas blitter frame of {width}x{height} pixels must not be empty");
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(%context, width, height, "CanvasBlitter::new");
+
+        let blitter = js_canvas_blitter_new(context.py())?.call1((context, width, height))?;
+
+        Ok(Self {
+            blitter: blitter.unbind(),
+            width,
+            height,
+        })
+    }
+
+    /// Returns the width of a frame in pixels.
+    #[must_use]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of a frame in pixels.
+    #[must_use]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the number of bytes in a tightly packed row of a frame.
+    #[must_use]
+    pub fn row_len(&self) -> usize {
+        usize::try_from(self.width).map_or(usize::MAX, |width| {
+            width.saturating_mul(Self::BYTES_PER_PIXEL)
+        })
+    }
+
+    /// Copies the frame at `offset` in the guest `memory`, whose rows start
+    /// `stride` bytes apart, into the back buffer, which then becomes the
+    /// front buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `stride` is shorter than a row or if the frame
+    /// is out of bounds of the `memory`.
+    pub fn capture(
+        &self,
+        _ctx: impl AsContext<Engine>,
+        memory: &Memory,
+        offset: usize,
+        stride: usize,
+    ) -> anyhow::Result<()> {
+        let row_len = self.row_len();
+
+        if stride < row_len {
+            anyhow::bail!(
+                "canvas blitter stride of {stride} bytes is shorter than a row of {row_len} bytes"
+            );
+        }
+
+        let end = usize::try_from(self.height - 1)
+            .ok()
+            .and_then(|rows| stride.checked_mul(rows))
+            .and_then(|len| len.checked_add(row_len))
+            .and_then(|len| offset.checked_add(len))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "canvas blitter frame at offset {offset} with a stride of {stride} bytes \
+                     overflows the address space"
+                )
+            })?;
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(offset, stride, end, "CanvasBlitter::capture");
+
+            self.blitter.bind(py).call_method1(
+                intern!(py, "capture"),
+                (memory.to_py(py), offset, stride, end),
+            )?;
+
+            Ok(())
+        })
+    }
+
+    /// Draws the front buffer, i.e. the most recently captured frame, at
+    /// the position (`dx`, `dy`) of the canvas.
+    ///
+    /// Presenting before any frame has been captured has no effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame cannot be drawn to the canvas.
+    pub fn present(&self, dx: i32, dy: i32) -> anyhow::Result<()> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(dx, dy, "CanvasBlitter::present");
+
+            self.blitter
+                .bind(py)
+                .call_method1(intern!(py, "present"), (dx, dy))?;
+
+            Ok(())
+        })
+    }
+
+    /// Captures the tightly packed frame at `offset` in the guest `memory`
+    /// and immediately draws it at the position (`dx`, `dy`) of the canvas.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is out of bounds of the `memory` or if
+    /// it cannot be drawn to the canvas.
+    pub fn blit(
+        &self,
+        ctx: impl AsContext<Engine>,
+        memory: &Memory,
+        offset: usize,
+        dx: i32,
+        dy: i32,
+    ) -> anyhow::Result<()> {
+        self.capture(ctx, memory, offset, self.row_len())?;
+        self.present(dx, dy)
+    }
+}
+
+fn js_canvas_blitter_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_CANVAS_BLITTER_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_CANVAS_BLITTER_NEW
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function canvasBlitter(context, width, height) {
+    const frames = [new ImageData(width, height), new ImageData(width, height)];
+    const rowLen = width * 4;
+    let back = 0;
+    let captured = false;
+
+    return {
+        capture(memory, offset, stride, end) {
+            // always look up the current buffer, which growth may have replaced
+            const buffer = memory.buffer;
+            if (end > buffer.byteLength) {
+                throw new RangeError(
+                    `frame ends at ${end} beyond the memory of ${buffer.byteLength} bytes`
+                );
+            }
+
+            const data = frames[back].data;
+            if (stride === rowLen) {
+                data.set(new Uint8Array(buffer, offset, rowLen * height));
+            } else {
+                for (let row = 0; row < height; row++) {
+                    data.set(
+                        new Uint8Array(buffer, offset + (row * stride), rowLen),
+                        row * rowLen,
+                    );
+                }
+            }
+
+            back = 1 - back;
+            captured = true;
+        },
+        present(dx, dy) {
+            if (captured) {
+                context.putImageData(frames[1 - back], dx, dy);
+            }
+        },
+    };
+}
+canvasBlitter
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
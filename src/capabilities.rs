@@ -0,0 +1,228 @@
+use flagset::FlagSet;
+use pyo3::{intern, prelude::*, sync::GILOnceCell};
+
+use crate::{
+    features::{ProbeResult, WasmFeatureExtension},
+    Engine, Error,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+/// The capabilities of the browser's [`WebAssembly`] runtime, see
+/// [`Engine::capabilities`].
+///
+/// [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly
+pub struct Capabilities {
+    /// The supported WebAssembly feature extensions
+    wasm_features: FlagSet<WasmFeatureExtension>,
+    /// Whether JavaScript Promise Integration (JSPI) is available
+    jspi: bool,
+    /// Whether shared memories can be created, which requires the page to be
+    /// cross-origin isolated
+    shared_memory: bool,
+    /// Whether JavaScript `BigInt`s are supported
+    bigint: bool,
+    /// Whether the type reflection `WebAssembly.Function` API is available
+    js_types: bool,
+}
+
+impl Capabilities {
+    /// Returns an iterator over the names of the supported WebAssembly
+    /// feature extensions, e.g. `"bulk-memory"` or `"simd"`.
+    pub fn wasm_features(&self) -> impl Iterator<Item = &'static str> {
+        self.wasm_features
+            .into_iter()
+            .map(WasmFeatureExtension::name)
+    }
+
+    /// Checks if the WebAssembly feature extension with the `name`, e.g.
+    /// `"simd"`, is supported.
+    #[must_use]
+    pub fn supports_wasm_feature(&self, name: &str) -> bool {
+        self.wasm_features().any(|feature| feature == name)
+    }
+
+    /// Returns `true` if JavaScript Promise Integration (JSPI) is available,
+    /// which allows guests to suspend on asynchronous imports.
+    #[must_use]
+    pub const fn jspi(&self) -> bool {
+        self.jspi
+    }
+
+    /// Returns `true` if shared memories are available, which requires a
+    /// `SharedArrayBuffer` and a cross-origin isolated page.
+    #[must_use]
+    pub const fn shared_memory(&self) -> bool {
+        self.shared_memory
+    }
+
+    /// Returns `true` if JavaScript `BigInt`s, which are used for `i64`
+    /// values, are supported.
+    #[must_use]
+    pub const fn bigint(&self) -> bool {
+        self.bigint
+    }
+
+    /// Returns `true` if the type reflection `WebAssembly.Function` API is
+    /// available.
+    #[must_use]
+    pub const fn js_types(&self) -> bool {
+        self.js_types
+    }
+
+    /// Detects the capabilities of the runtime, caching the result.
+    pub(crate) fn detect(py: Python) -> Result<Self, Error> {
+        static CAPABILITIES: GILOnceCell<Capabilities> = GILOnceCell::new();
+
+        CAPABILITIES
+            .get_or_try_init(py, || {
+                let wasm_features = *WasmFeatureExtension::supported(py)?;
+
+                let probe = js_probe_capabilities(py)?.call0()?;
+
+                Ok(Self {
+                    wasm_features,
+                    jspi: probe.getattr(intern!(py, "jspi"))?.extract()?,
+                    shared_memory: probe.getattr(intern!(py, "sharedMemory"))?.extract()?,
+                    bigint: probe.getattr(intern!(py, "bigint"))?.extract()?,
+                    js_types: probe.getattr(intern!(py, "jsTypes"))?.extract()?,
+                })
+            })
+            .copied()
+            .map_err(|err: PyErr| {
+                Error::EnvironmentUnavailable(format!("failed to detect the capabilities: {err}"))
+            })
+    }
+}
+
+impl Engine {
+    /// Returns the capabilities of the browser's WebAssembly runtime, e.g.
+    /// the supported feature extensions and whether shared memories are
+    /// available.
+    ///
+    /// The capabilities are detected once and then cached, so that
+    /// applications can branch on them cheaply.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EnvironmentUnavailable`] if the capabilities cannot be
+    /// detected, e.g. because the code is not running inside Pyodide.
+    pub fn capabilities(&self) -> Result<Capabilities, Error> {
+        Python::with_gil(Capabilities::detect)
+    }
+
+    /// Returns the WebAssembly feature extensions that the browser supports,
+    /// e.g. to choose between SIMD and non-SIMD builds of a module before
+    /// compiling it.
+    ///
+    /// The supported feature extensions are probed once and then cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EnvironmentUnavailable`] if the feature extensions
+    /// cannot be probed, e.g. because the code is not running inside Pyodide.
+    pub fn supported_features(&self) -> Result<FlagSet<WasmFeatureExtension>, Error> {
+        Python::with_gil(|py| WasmFeatureExtension::supported(py).copied()).map_err(|err| {
+            Error::EnvironmentUnavailable(format!("failed to probe the features: {err}"))
+        })
+    }
+
+    /// Returns the WebAssembly feature extensions that the module `bytes`
+    /// require.
+    ///
+    /// The `bytes` are only validated and not compiled, so this check does
+    /// not require a browser.
+    #[must_use]
+    pub fn required_features(&self, bytes: &[u8]) -> FlagSet<WasmFeatureExtension> {
+        WasmFeatureExtension::required(bytes)
+    }
+
+    /// Returns the WebAssembly feature extensions that the module `bytes`
+    /// require but that the browser does not support, which is empty if the
+    /// module can be compiled.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EnvironmentUnavailable`] if the feature extensions
+    /// cannot be probed, e.g. because the code is not running inside Pyodide.
+    pub fn missing_features(&self, bytes: &[u8]) -> Result<FlagSet<WasmFeatureExtension>, Error> {
+        let supported = self.supported_features()?;
+
+        Ok(self.required_features(bytes) & !supported)
+    }
+
+    /// Runs the canary module of every WebAssembly feature extension and
+    /// returns the individual results, including how long each probe took,
+    /// e.g. to display them on a diagnostics page.
+    ///
+    /// Unlike [`Engine::capabilities`], the probes are not cached and run
+    /// again on every call.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EnvironmentUnavailable`] if the probes cannot be run,
+    /// e.g. because the code is not running inside Pyodide.
+    pub fn probe_wasm_features(&self) -> Result<Vec<ProbeResult>, Error> {
+        Python::with_gil(|py| {
+            WasmFeatureExtension::all()
+                .map(|extension| extension.probe(py))
+                .collect::<Result<_, _>>()
+        })
+        .map_err(|err| {
+            Error::EnvironmentUnavailable(format!("failed to probe the features: {err}"))
+        })
+    }
+}
+
+/// Checks that the page is [cross-origin isolated], which is required to use
+/// the `requirement`, e.g. shared memories or threads.
+///
+/// Returns [`Error::NotCrossOriginIsolated`] with guidance on the required
+/// HTTP headers otherwise.
+///
+/// [cross-origin isolated]: https://developer.mozilla.org/en-US/docs/Web/API/Window/crossOriginIsolated
+pub fn check_cross_origin_isolation(py: Python, requirement: &str) -> Result<(), Error> {
+    let isolated = py
+        .import(intern!(py, "js"))
+        .and_then(|js| js.getattr(intern!(py, "crossOriginIsolated")))
+        .and_then(|isolated| isolated.extract::<bool>())
+        .unwrap_or(false);
+
+    if isolated {
+        return Ok(());
+    }
+
+    Err(Error::NotCrossOriginIsolated(format!(
+        "{requirement} requires the page to be cross-origin isolated, which needs the page to be \
+         served with the `Cross-Origin-Opener-Policy: same-origin` and \
+         `Cross-Origin-Embedder-Policy: require-corp` HTTP headers, see \
+         https://developer.mozilla.org/en-US/docs/Web/API/Window/crossOriginIsolated"
+    )))
+}
+
+fn js_probe_capabilities(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_PROBE_CAPABILITIES: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_PROBE_CAPABILITIES
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function probeCapabilities() {
+    return {
+        jspi: (typeof WebAssembly.Suspending === 'function') ||
+            (typeof WebAssembly.promising === 'function'),
+        sharedMemory: (typeof SharedArrayBuffer === 'function') &&
+            (globalThis.crossOriginIsolated === true),
+        bigint: (typeof BigInt === 'function') && (typeof BigInt64Array === 'function'),
+        jsTypes: (typeof WebAssembly.Function === 'function'),
+    };
+}
+probeCapabilities
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
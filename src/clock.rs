@@ -0,0 +1,107 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+#[derive(Debug, Clone, Default)]
+/// The source of time for the host helpers that expose time to guests, e.g.
+/// the [`WasiShim`] clocks and the timestamps of the [`Mutation`] history.
+///
+/// [`WasiShim`]: crate::WasiShim
+/// [`Mutation`]: crate::Mutation
+pub enum Clock {
+    #[default]
+    /// The real time of the system
+    System,
+    /// A virtual clock that only advances when directed by the host
+    Virtual(VirtualClock),
+}
+
+impl Clock {
+    /// Returns the current wall-clock time.
+    #[must_use]
+    pub fn now(&self) -> SystemTime {
+        match self {
+            Self::System => SystemTime::now(),
+            Self::Virtual(clock) => clock.now(),
+        }
+    }
+
+    /// Returns the current monotonic time, measured from an unspecified but
+    /// fixed starting point.
+    #[must_use]
+    pub fn monotonic(&self) -> Duration {
+        static START: OnceLock<Instant> = OnceLock::new();
+
+        match self {
+            Self::System => START.get_or_init(Instant::now).elapsed(),
+            Self::Virtual(clock) => clock.elapsed(),
+        }
+    }
+
+    /// Returns the resolution of the clock.
+    #[must_use]
+    pub const fn resolution(&self) -> Duration {
+        match self {
+            Self::System => Duration::from_micros(1),
+            Self::Virtual(_) => Duration::from_nanos(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A virtual clock, whose time only advances when directed by the host, such
+/// that time-dependent guest logic can be simulated deterministically.
+///
+/// Clones of a virtual clock share the same time.
+pub struct VirtualClock {
+    /// The wall-clock time at which the clock was started
+    start: SystemTime,
+    /// The nanoseconds that have elapsed since the clock was started
+    elapsed: Arc<AtomicU64>,
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl VirtualClock {
+    /// Creates a new virtual clock that starts at the wall-clock `start`
+    /// time.
+    #[must_use]
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            elapsed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Advances the clock by the `duration`, saturating at the maximum
+    /// representable time.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+
+        let _ = self
+            .elapsed
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |elapsed| {
+                Some(elapsed.saturating_add(nanos))
+            });
+    }
+
+    /// Returns the time that has elapsed since the clock was started.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed.load(Ordering::Acquire))
+    }
+
+    /// Returns the current wall-clock time of the clock.
+    #[must_use]
+    pub fn now(&self) -> SystemTime {
+        self.start + self.elapsed()
+    }
+}
@@ -1,12 +1,17 @@
 use std::convert::Infallible;
 
-use pyo3::{exceptions::PyRuntimeError, intern, prelude::*, sync::GILOnceCell, types::IntoPyDict};
+use pyo3::{
+    intern,
+    prelude::*,
+    sync::GILOnceCell,
+    types::{IntoPyDict, PyString},
+};
 use wasm_runtime_layer::{
     backend::{Extern, Value},
     ValueType,
 };
 
-use crate::{Engine, ExternRef};
+use crate::{Engine, ExternRef, Func};
 
 /// Converts a Rust type to Python
 pub trait ToPy {
@@ -47,6 +52,21 @@ impl ToPy for Value<Engine> {
     }
 }
 
+/// Converts the `value` into a JavaScript value that can be stored in a
+/// reference-typed table or global slot
+///
+/// Unlike [`ToPy::to_py`], host [`Func`]s are converted into genuine
+/// WebAssembly functions, or rejected with a clear error if that is not
+/// supported.
+///
+/// [`Func`]: crate::Func
+pub fn to_py_for_ref_slot(py: Python, value: &Value<Engine>) -> anyhow::Result<Py<PyAny>> {
+    match value {
+        Value::FuncRef(Some(func)) => func.to_js_funcref(py),
+        value => Ok(value.to_py(py)),
+    }
+}
+
 impl ToPy for Extern<Engine> {
     fn to_py(&self, py: Python) -> Py<PyAny> {
         #[cfg(feature = "tracing")]
@@ -102,10 +122,7 @@ impl ValueExt for Value<Engine> {
                 if value.is_none() {
                     Ok(Self::FuncRef(None))
                 } else {
-                    Err(PyRuntimeError::new_err(
-                        "conversion to a function outside of a module export is not permitted as \
-                         its type signature is unknown",
-                    ))
+                    Ok(Self::FuncRef(Some(Func::from_js_funcref(value)?)))
                 }
             },
         }
@@ -115,25 +132,85 @@ impl ValueExt for Value<Engine> {
 pub trait ValueTypeExt {
     /// Converts this type into the canonical ABI kind
     ///
+    /// The descriptor strings are interned once per interpreter to avoid
+    /// allocating a new Python string for every descriptor.
+    ///
     /// See: <https://webassembly.github.io/spec/js-api/#globals>
-    fn as_js_descriptor(&self) -> &str;
+    fn as_js_descriptor<'py>(&self, py: Python<'py>) -> &Bound<'py, PyString>;
 }
 
 impl ValueTypeExt for ValueType {
-    fn as_js_descriptor(&self) -> &str {
+    fn as_js_descriptor<'py>(&self, py: Python<'py>) -> &Bound<'py, PyString> {
         match self {
-            Self::I32 => "i32",
-            Self::I64 => "i64",
-            Self::F32 => "f32",
-            Self::F64 => "f64",
-            Self::FuncRef => "anyfunc",
-            Self::ExternRef => "externref",
+            Self::I32 => intern!(py, "i32"),
+            Self::I64 => intern!(py, "i64"),
+            Self::F32 => intern!(py, "f32"),
+            Self::F64 => intern!(py, "f64"),
+            Self::FuncRef => intern!(py, "anyfunc"),
+            Self::ExternRef => intern!(py, "externref"),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A 128-bit `v128` SIMD vector value.
+///
+/// The JavaScript API cannot pass `v128` values to or from WebAssembly
+/// functions, and the [`ValueType`] of the [`wasm_runtime_layer`] API cannot
+/// represent them. Modules that use `v128` in the signatures of their imports
+/// and exports can still be parsed and instantiated, but the `v128` slots are
+/// reported as `externref`s and calling such functions from the host fails
+/// with a [`SignatureError::Unrepresentable`] error. Instead, `v128` values
+/// can be exchanged through memory using [`Memory::read_v128`] and
+/// [`Memory::write_v128`].
+///
+/// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+/// [`SignatureError::Unrepresentable`]: crate::SignatureError::Unrepresentable
+/// [`Memory::read_v128`]: crate::Memory::read_v128
+/// [`Memory::write_v128`]: crate::Memory::write_v128
+pub struct V128(Box<[u8; 16]>);
+
+impl V128 {
+    /// Creates a vector from its 16 bytes in little-endian order, i.e. in
+    /// the order in which they are stored in memory.
+    #[must_use]
+    pub fn from_le_bytes(bytes: [u8; 16]) -> Self {
+        Self(Box::new(bytes))
+    }
+
+    /// Returns the 16 bytes of the vector in little-endian order.
+    #[must_use]
+    pub fn to_le_bytes(&self) -> [u8; 16] {
+        *self.0
+    }
+
+    /// Creates a vector from its `u128` bit pattern.
+    #[must_use]
+    pub fn from_u128(bits: u128) -> Self {
+        Self::from_le_bytes(bits.to_le_bytes())
+    }
+
+    /// Returns the `u128` bit pattern of the vector.
+    #[must_use]
+    pub fn to_u128(&self) -> u128 {
+        u128::from_le_bytes(*self.0)
+    }
+}
+
+impl From<u128> for V128 {
+    fn from(bits: u128) -> Self {
+        Self::from_u128(bits)
+    }
+}
+
+impl From<V128> for u128 {
+    fn from(value: V128) -> Self {
+        value.to_u128()
+    }
+}
+
 fn i64_to_js_bigint(py: Python, v: i64) -> Bound<PyAny> {
-    fn object_wrapped_bigint(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+    fn object_wrapped_bigint(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
         static OBJECT_WRAPPED_BIGINT: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
 
         OBJECT_WRAPPED_BIGINT
@@ -157,7 +234,7 @@ fn i64_to_js_bigint(py: Python, v: i64) -> Bound<PyAny> {
 }
 
 fn try_i64_from_js_bigint(v: Bound<PyAny>) -> Result<i64, PyErr> {
-    fn js_bigint(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+    fn js_bigint(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
         static JS_BIG_INT: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
         JS_BIG_INT.import(py, "js", "BigInt")
     }
@@ -166,14 +243,25 @@ fn try_i64_from_js_bigint(v: Bound<PyAny>) -> Result<i64, PyErr> {
     js_bigint(v.py())?.call1((v,))?.extract()
 }
 
-pub fn js_uint8_array_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+pub fn js_uint8_array_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static JS_UINT8_ARRAY_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     JS_UINT8_ARRAY_NEW.import(py, "js.Uint8Array", "new")
 }
 
 /// Check if `object` is an instance of the JavaScript class with `constructor`.
-pub fn instanceof(object: &Bound<PyAny>, constructor: &Bound<PyAny>) -> Result<bool, PyErr> {
-    fn is_instance_of(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+///
+/// Since `instanceof` fails for objects that were created in another realm,
+/// e.g. an iframe or a worker, `object` is also accepted if its
+/// `Object.prototype.toString` brand matches `[object {tag}]`, where `tag` is
+/// the class's [`Symbol.toStringTag`], e.g. `WebAssembly.Memory`.
+///
+/// [`Symbol.toStringTag`]: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Symbol/toStringTag
+pub fn instanceof(
+    object: &Bound<PyAny>,
+    constructor: &Bound<PyAny>,
+    tag: &str,
+) -> Result<bool, PyErr> {
+    fn is_instance_of(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
         static IS_INSTANCE_OF: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
 
         IS_INSTANCE_OF
@@ -183,8 +271,9 @@ pub fn instanceof(object: &Bound<PyAny>, constructor: &Bound<PyAny>) -> Result<b
                     .getattr(intern!(py, "code"))?
                     .getattr(intern!(py, "run_js"))?
                     .call1((
-                        "function isInstanceOf(object, constructor){ return (object instanceof \
-                         constructor); } isInstanceOf",
+                        "function isInstanceOf(object, constructor, tag){ return (object \
+                         instanceof constructor) || (Object.prototype.toString.call(object) === \
+                         `[object ${tag}]`); } isInstanceOf",
                     ))?
                     .unbind())
             })
@@ -192,12 +281,12 @@ pub fn instanceof(object: &Bound<PyAny>, constructor: &Bound<PyAny>) -> Result<b
     }
 
     is_instance_of(object.py())?
-        .call1((object, constructor))?
+        .call1((object, constructor, tag))?
         .extract()
 }
 
 pub fn create_js_object(py: Python) -> Result<Bound<PyAny>, PyErr> {
-    fn js_object_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+    fn js_object_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
         static JS_OBJECT_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
         JS_OBJECT_NEW.import(py, "js.Object", "new")
     }
@@ -206,7 +295,7 @@ pub fn create_js_object(py: Python) -> Result<Bound<PyAny>, PyErr> {
 }
 
 pub fn py_to_js_proxy<T>(object: Bound<T>) -> Result<Bound<PyAny>, PyErr> {
-    fn to_js(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+    fn to_js(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
         static TO_JS: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
         TO_JS.import(py, "pyodide.ffi", "to_js")
     }
@@ -0,0 +1,143 @@
+use wasm_encoder::{CustomSection, RawSection};
+use wasmparser::{Encoding, Payload};
+
+use crate::Error;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A writer that stamps custom sections into module bytes before they are
+/// compiled.
+///
+/// Distribution pipelines can use it to stamp modules as they are loaded,
+/// e.g. with a host-assigned plugin id or a signature, without a separate
+/// toolchain step.
+///
+/// Every section that is added to the writer replaces all existing custom
+/// sections of the same name in the module, and is appended after all other
+/// sections. The other sections are copied unchanged.
+///
+/// ```rust,ignore
+/// let bytes = CustomSectionWriter::new()
+///     .with_section("plugin-id", b"4f0e2c")
+///     .write(&bytes)?;
+/// let module = Module::new(&engine, &bytes[..])?;
+/// ```
+pub struct CustomSectionWriter {
+    /// The names and contents of the custom sections
+    sections: Vec<(String, Vec<u8>)>,
+}
+
+impl CustomSectionWriter {
+    /// Creates a new writer without any custom sections.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sections: Vec::new(),
+        }
+    }
+
+    /// Adds the custom section `name` with the `data`, replacing any section
+    /// of the same name that was previously added to this writer.
+    #[must_use]
+    pub fn with_section(mut self, name: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        let name = name.into();
+        let data = data.into();
+
+        if let Some((_, existing)) = self
+            .sections
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            *existing = data;
+        } else {
+            self.sections.push((name, data));
+        }
+
+        self
+    }
+
+    /// Returns the names and contents of the custom sections, in the order
+    /// in which they are written.
+    pub fn sections(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.sections
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+    }
+
+    /// Writes the custom sections into the module `bytes`, replacing all
+    /// existing custom sections of the same names, and returns the stamped
+    /// module bytes.
+    ///
+    /// The `bytes` are only parsed as far as needed to find their sections,
+    /// and are neither validated nor compiled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Compile`] if the `bytes` cannot be parsed as a
+    /// module, e.g. since they encode a component.
+    pub fn write(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("CustomSectionWriter::write").entered();
+
+        let mut module = retain_custom_sections(bytes, |name, _| {
+            let replaced = self.sections.iter().any(|(section, _)| section == name);
+
+            #[cfg(feature = "tracing")]
+            if replaced {
+                tracing::debug!(name, "replacing custom section");
+            }
+
+            !replaced
+        })?;
+
+        for (name, data) in &self.sections {
+            module.section(&CustomSection {
+                name: name.into(),
+                data: data.into(),
+            });
+        }
+
+        Ok(module.finish())
+    }
+}
+
+/// Copies the sections of the module `bytes` into a new module, except for
+/// the custom sections with a name and data for which `retain` returns
+/// `false`
+///
+/// # Errors
+///
+/// Returns an [`Error::Compile`] if the `bytes` cannot be parsed as a module.
+pub fn retain_custom_sections<'a>(
+    bytes: &'a [u8],
+    mut retain: impl FnMut(&str, &'a [u8]) -> bool,
+) -> Result<wasm_encoder::Module, Error> {
+    let mut module = wasm_encoder::Module::new();
+
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        let payload = payload.map_err(|err| Error::Compile(err.to_string()))?;
+
+        match &payload {
+            Payload::Version {
+                encoding: Encoding::Component,
+                ..
+            } => {
+                return Err(Error::Compile(String::from(
+                    "custom sections can only be rewritten in modules, not components",
+                )))
+            },
+            Payload::CustomSection(section) if !retain(section.name(), section.data()) => {
+                continue;
+            },
+            _ => (),
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            module.section(&RawSection {
+                id,
+                data: &bytes[range],
+            });
+        }
+    }
+
+    Ok(module)
+}
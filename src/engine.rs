@@ -0,0 +1,574 @@
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell};
+
+use wasm_runtime_layer::backend::WasmEngine;
+
+#[cfg(feature = "ed25519")]
+use crate::ModuleVerifier;
+use crate::{
+    module::ModuleCache, Clock, Error, ExternRef, Func, Global, Instance, Memory, Module, Store,
+    StoreContext, StoreContextMut, Table, TableMaximumPolicy, UnsupportedWasmFeatureExtensionError,
+    WasiImportPolicy,
+};
+
+#[derive(Debug, Clone)]
+/// Runtime for [`WebAssembly`] web runtime.
+///
+/// An engine carries an [`EngineConfig`], which is shared by all of its
+/// clones and by all stores that are created from it. The default engine is
+/// cheap to create since all default engines share the same configuration.
+///
+/// If enabled with [`EngineConfig::with_shared_module_cache`], the engine
+/// also owns a cache of compiled modules, which is shared by all of its
+/// clones.
+///
+/// [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly
+pub struct Engine {
+    /// The shared engine configuration
+    config: Arc<EngineConfig>,
+    /// The shared cache of compiled modules, if enabled
+    module_cache: Option<Arc<ModuleCache>>,
+}
+
+/// The process-wide shared engine, see [`Engine::global`]
+static GLOBAL_ENGINE: OnceLock<Engine> = OnceLock::new();
+
+impl Default for Engine {
+    fn default() -> Self {
+        static DEFAULT_CONFIG: OnceLock<Arc<EngineConfig>> = OnceLock::new();
+
+        Self {
+            config: Arc::clone(DEFAULT_CONFIG.get_or_init(|| Arc::new(EngineConfig::new()))),
+            module_cache: None,
+        }
+    }
+}
+
+impl Engine {
+    /// Creates a new engine with the provided `config`.
+    #[must_use]
+    pub fn new(config: EngineConfig) -> Self {
+        Self {
+            module_cache: config
+                .shared_module_cache()
+                .then(|| Arc::new(ModuleCache::default())),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Returns the process-wide shared engine, which is initialized lazily on
+    /// first use.
+    ///
+    /// Applications with many independent modules can configure the shared
+    /// engine once at startup using [`Engine::configure_global`]. Otherwise,
+    /// it uses the default [`EngineConfig`].
+    #[must_use]
+    pub fn global() -> &'static Self {
+        GLOBAL_ENGINE.get_or_init(Self::default)
+    }
+
+    /// Initializes the process-wide shared engine, see [`Engine::global`],
+    /// with the provided `config`.
+    ///
+    /// The shared engine can only be configured once, before it is first
+    /// used.
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejected `config` if the shared engine has already been
+    /// initialized, either by an earlier call to this method or by a call to
+    /// [`Engine::global`].
+    pub fn configure_global(config: EngineConfig) -> Result<&'static Self, EngineConfig> {
+        let mut config = Some(config);
+
+        // the config is only taken if this call initializes the engine
+        let engine = GLOBAL_ENGINE.get_or_init(|| Self::new(config.take().unwrap_or_default()));
+
+        config.map_or(Ok(engine), Err)
+    }
+
+    /// Returns the configuration of this engine.
+    #[must_use]
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// Returns the number of compiled modules in the shared module cache,
+    /// see [`EngineConfig::with_shared_module_cache`].
+    #[must_use]
+    pub fn cached_modules(&self) -> usize {
+        self.module_cache.as_ref().map_or(0, |cache| cache.len())
+    }
+
+    /// Removes all compiled modules from the shared module cache, see
+    /// [`EngineConfig::with_shared_module_cache`].
+    ///
+    /// Modules that were already returned from the cache remain valid.
+    pub fn clear_module_cache(&self) {
+        if let Some(cache) = &self.module_cache {
+            cache.clear();
+        }
+    }
+
+    /// Returns the shared cache of compiled modules, if enabled
+    pub(crate) const fn module_cache(&self) -> Option<&Arc<ModuleCache>> {
+        self.module_cache.as_ref()
+    }
+
+    /// Returns the version of the Pyodide runtime, e.g. for diagnostics.
+    ///
+    /// The version is detected once and then cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EnvironmentUnavailable`] if the code is not running
+    /// inside Pyodide or if the Pyodide version is older than
+    /// [`PyodideVersion::MINIMUM_SUPPORTED`].
+    pub fn pyodide_version(&self) -> Result<PyodideVersion, Error> {
+        Python::with_gil(PyodideVersion::detect)
+    }
+}
+
+impl WasmEngine for Engine {
+    type ExternRef = ExternRef;
+    type Func = Func;
+    type Global = Global;
+    type Instance = Instance;
+    type Memory = Memory;
+    type Module = Module;
+    type Store<T> = Store<T>;
+    type StoreContext<'a, T: 'a> = StoreContext<'a, T>;
+    type StoreContextMut<'a, T: 'a> = StoreContextMut<'a, T>;
+    type Table = Table;
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+/// Configuration of an [`Engine`], e.g. feature overrides, limits, caches,
+/// and hooks, which applies to all stores created from the engine.
+///
+/// The configuration is built using builder-style methods and then passed to
+/// [`Engine::new`].
+pub struct EngineConfig {
+    /// The maximum nesting depth of host calls per store, if limited
+    max_host_call_depth: Option<usize>,
+    /// The number of mutations recorded per store, if enabled
+    mutation_history: Option<usize>,
+    /// The policy for unimplemented WASI imports
+    wasi_import_policy: WasiImportPolicy,
+    /// The source of time for host helpers
+    clock: Clock,
+    /// The formatter for the user-facing message of unsupported features
+    unsupported_feature_formatter: Option<fn(&UnsupportedWasmFeatureExtensionError) -> String>,
+    /// Whether the types of exports are verified against the JavaScript
+    /// objects on instantiation
+    strict_exports: bool,
+    /// Whether the start functions of compiled modules can be deferred
+    deferrable_start: bool,
+    /// The policy for table maximums that exceed the JavaScript API limit
+    table_maximum_policy: TableMaximumPolicy,
+    /// Whether the type reflection (js-types) proposal is required
+    require_type_reflection: bool,
+    /// Whether compiled modules are cached by the hash of their bytes
+    shared_module_cache: bool,
+    /// Whether compiled modules are instrumented for epoch interruption
+    epoch_interruption: bool,
+    /// The verifier of module signatures, if enforced
+    #[cfg(feature = "ed25519")]
+    module_verifier: Option<Arc<ModuleVerifier>>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineConfig {
+    /// The default maximum nesting depth of host calls per store, see
+    /// [`EngineConfig::with_max_host_call_depth`].
+    pub const DEFAULT_MAX_HOST_CALL_DEPTH: usize = 512;
+
+    /// Creates the default engine configuration.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_host_call_depth: Some(Self::DEFAULT_MAX_HOST_CALL_DEPTH),
+            mutation_history: None,
+            wasi_import_policy: WasiImportPolicy::Missing,
+            clock: Clock::System,
+            unsupported_feature_formatter: None,
+            strict_exports: false,
+            deferrable_start: false,
+            table_maximum_policy: TableMaximumPolicy::Error,
+            require_type_reflection: false,
+            shared_module_cache: false,
+            epoch_interruption: false,
+            #[cfg(feature = "ed25519")]
+            module_verifier: None,
+        }
+    }
+
+    /// Configures the maximum nesting depth of host calls per store, or
+    /// removes the limit if `None`.
+    ///
+    /// Deep recursion between guest and host functions can exhaust the
+    /// JavaScript or Python stack and crash the page. Beyond the limit, host
+    /// function calls instead fail with an [`Error::CallDepthExceeded`] trap,
+    /// which the caller can recover from.
+    #[must_use]
+    pub const fn with_max_host_call_depth(mut self, max_host_call_depth: Option<usize>) -> Self {
+        self.max_host_call_depth = max_host_call_depth;
+        self
+    }
+
+    /// Returns the maximum nesting depth of host calls per store, if limited.
+    #[must_use]
+    pub const fn max_host_call_depth(&self) -> Option<usize> {
+        self.max_host_call_depth
+    }
+
+    /// Configures whether each store records a debugging history of its most
+    /// recent `capacity` mutations, or disables the history if `None`.
+    ///
+    /// The history records the values passed to `Global::set` and
+    /// `Table::set` as well as the ranges of host-initiated memory writes,
+    /// together with their timestamps and host call depths. It can be
+    /// inspected with [`Store::mutation_history`] and is dumped as a
+    /// `tracing` event when a call traps.
+    ///
+    /// [`Store::mutation_history`]: crate::Store::mutation_history
+    #[must_use]
+    pub const fn with_mutation_history(mut self, capacity: Option<usize>) -> Self {
+        self.mutation_history = capacity;
+        self
+    }
+
+    /// Returns the number of mutations recorded per store, if enabled.
+    #[must_use]
+    pub const fn mutation_history(&self) -> Option<usize> {
+        self.mutation_history
+    }
+
+    /// Configures the policy for WASI function imports that a module
+    /// declares but that are not provided when it is instantiated.
+    ///
+    /// By default, unimplemented WASI imports remain missing and
+    /// instantiation fails with a link error.
+    #[must_use]
+    pub fn with_wasi_import_policy(mut self, policy: WasiImportPolicy) -> Self {
+        self.wasi_import_policy = policy;
+        self
+    }
+
+    /// Returns the policy for unimplemented WASI imports.
+    #[must_use]
+    pub const fn wasi_import_policy(&self) -> &WasiImportPolicy {
+        &self.wasi_import_policy
+    }
+
+    /// Configures the source of time for the host helpers of all stores,
+    /// e.g. the timestamps of the mutation history.
+    ///
+    /// By default, the [`Clock::System`] time is used. A [`Clock::Virtual`]
+    /// clock instead only advances when directed by the host, which makes
+    /// runs reproducible.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the source of time for host helpers.
+    #[must_use]
+    pub const fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Configures the formatter for the user-facing message of the
+    /// [`Error::UnsupportedFeature`] that is returned when a module requires
+    /// feature extensions that the browser does not support, e.g. to localize
+    /// the browser-upgrade guidance, or resets it to the default English
+    /// message if `None`.
+    ///
+    /// The structured [`UnsupportedWasmFeatureExtensionError`] remains
+    /// available as the source of the error.
+    #[must_use]
+    pub const fn with_unsupported_feature_formatter(
+        mut self,
+        formatter: Option<fn(&UnsupportedWasmFeatureExtensionError) -> String>,
+    ) -> Self {
+        self.unsupported_feature_formatter = formatter;
+        self
+    }
+
+    /// Configures whether the parsed types of exports are verified against
+    /// the actual JavaScript functions, globals, memories, and tables when a
+    /// module is instantiated, which catches mismatches between the module
+    /// bytes and the compiled module, e.g. introduced by post-processing
+    /// tools.
+    ///
+    /// The exports are verified against their types that are reflected using
+    /// the type reflection (js-types) proposal. Without it, only the
+    /// mutability of globals is probed by writing back their current value.
+    /// The strict mode is disabled by default, such that debug and release
+    /// builds behave the same.
+    #[must_use]
+    pub const fn with_strict_exports(mut self, strict_exports: bool) -> Self {
+        self.strict_exports = strict_exports;
+        self
+    }
+
+    /// Configures whether the WebAssembly type reflection (js-types) proposal
+    /// is required, which exposes the types of functions, globals, memories,
+    /// and tables to JavaScript.
+    ///
+    /// If required, instantiating a module fails with an
+    /// [`Error::EnvironmentUnavailable`] if the browser does not support the
+    /// proposal, and the types of all exports are verified against their
+    /// reflected types, as with [`EngineConfig::with_strict_exports`].
+    #[must_use]
+    pub const fn with_require_type_reflection(mut self, require_type_reflection: bool) -> Self {
+        self.require_type_reflection = require_type_reflection;
+        self
+    }
+
+    /// Returns whether the type reflection (js-types) proposal is required.
+    #[must_use]
+    pub const fn require_type_reflection(&self) -> bool {
+        self.require_type_reflection
+    }
+
+    /// Returns whether the types of exports are verified on instantiation.
+    #[must_use]
+    pub const fn strict_exports(&self) -> bool {
+        self.strict_exports
+    }
+
+    /// Configures whether the start functions of modules that are compiled
+    /// with this engine can be deferred using
+    /// [`InstanceOptions::defer_start`].
+    ///
+    /// The JavaScript API always runs the start function while instantiating
+    /// a module. Deferrable modules are therefore compiled without their
+    /// start section, exporting the start function under a hidden name
+    /// instead, which [`Instance::new_with_options`] calls unless the start
+    /// is deferred. Note that other JavaScript code which instantiates such a
+    /// module, e.g. from [`Module::as_js`], must call the start function
+    /// itself.
+    ///
+    /// [`InstanceOptions::defer_start`]: crate::InstanceOptions::defer_start
+    /// [`Instance::new_with_options`]: crate::Instance::new_with_options
+    /// [`Module::as_js`]: crate::Module::as_js
+    #[must_use]
+    pub const fn with_deferrable_start(mut self, deferrable_start: bool) -> Self {
+        self.deferrable_start = deferrable_start;
+        self
+    }
+
+    /// Returns whether the start functions of compiled modules can be
+    /// deferred.
+    #[must_use]
+    pub const fn deferrable_start(&self) -> bool {
+        self.deferrable_start
+    }
+
+    /// Configures the policy for table maximums that exceed the limit of the
+    /// JavaScript API, see [`Table::MAX_SIZE`], which applies both to the
+    /// tables that modules define or import and to tables that are created
+    /// by the host.
+    ///
+    /// By default, such tables fail with an error that identifies the
+    /// offending table, instead of with a generic `RangeError` or
+    /// `CompileError` from the browser.
+    #[must_use]
+    pub const fn with_table_maximum_policy(mut self, policy: TableMaximumPolicy) -> Self {
+        self.table_maximum_policy = policy;
+        self
+    }
+
+    /// Returns the policy for table maximums that exceed the limit of the
+    /// JavaScript API.
+    #[must_use]
+    pub const fn table_maximum_policy(&self) -> TableMaximumPolicy {
+        self.table_maximum_policy
+    }
+
+    /// Configures whether the engine caches compiled modules by the hash of
+    /// their bytes, such that compiling the same bytes again, e.g. for
+    /// another store, reuses the existing [`WebAssembly.Module`] and its
+    /// parsed signatures instead of compiling a new module.
+    ///
+    /// The cache is shared by all clones of the engine and lives as long as
+    /// the engine, see [`Engine::clear_module_cache`]. Unlike the
+    /// `module-cache` feature, it does not persist modules across page loads.
+    ///
+    /// [`WebAssembly.Module`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Module
+    #[must_use]
+    pub const fn with_shared_module_cache(mut self, shared_module_cache: bool) -> Self {
+        self.shared_module_cache = shared_module_cache;
+        self
+    }
+
+    /// Returns whether compiled modules are cached by the hash of their
+    /// bytes.
+    #[must_use]
+    pub const fn shared_module_cache(&self) -> bool {
+        self.shared_module_cache
+    }
+
+    /// Configures whether modules that are compiled with this engine are
+    /// instrumented for epoch interruption, such that long-running guest
+    /// calls can be aborted using [`Store::set_epoch_deadline`] or an
+    /// [`InterruptHandle`].
+    ///
+    /// Instrumented modules call a hidden JavaScript function import on every
+    /// function entry and loop iteration, which advances the epoch of the
+    /// store and checks for interruption. Since the browser runs no other
+    /// code on the same thread while the guest runs, the epoch measures the
+    /// progress of the guest instead of the passing of time. The
+    /// instrumentation slows down the guest and removes the `name` section
+    /// of the module, which is used for function names in stack traces.
+    /// Since the whole module is rewritten, a [`ModuleStream`] then also
+    /// copies the module bytes into Rust when it is finished.
+    ///
+    /// [`ModuleStream`]: crate::ModuleStream
+    /// [`Store::set_epoch_deadline`]: crate::Store::set_epoch_deadline
+    /// [`InterruptHandle`]: crate::InterruptHandle
+    #[must_use]
+    pub const fn with_epoch_interruption(mut self, epoch_interruption: bool) -> Self {
+        self.epoch_interruption = epoch_interruption;
+        self
+    }
+
+    /// Returns whether compiled modules are instrumented for epoch
+    /// interruption.
+    #[must_use]
+    pub const fn epoch_interruption(&self) -> bool {
+        self.epoch_interruption
+    }
+
+    /// Configures the `verifier` that checks the signature of every module
+    /// before it is compiled with this engine, or disables the verification
+    /// if `None`.
+    ///
+    /// With a verifier, [`Module::new`] and [`ModuleStream::finish`] fail
+    /// with an [`Error::Signature`] unless the module is signed by a trusted
+    /// key, see [`ModuleVerifier`]. Since the signature covers the whole
+    /// module, a [`ModuleStream`] then also buffers the module bytes in
+    /// Rust. Modules that are created from already compiled JavaScript
+    /// modules, e.g. with [`Module::from_parts`], are not verified.
+    ///
+    /// [`Module::new`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Module.html#method.new
+    /// [`ModuleStream::finish`]: crate::ModuleStream::finish
+    /// [`ModuleStream`]: crate::ModuleStream
+    /// [`Module::from_parts`]: crate::Module::from_parts
+    #[cfg(feature = "ed25519")]
+    #[must_use]
+    pub fn with_module_verifier(mut self, verifier: Option<ModuleVerifier>) -> Self {
+        self.module_verifier = verifier.map(Arc::new);
+        self
+    }
+
+    /// Returns the verifier of module signatures, if enforced.
+    #[cfg(feature = "ed25519")]
+    #[must_use]
+    pub const fn module_verifier(&self) -> Option<&Arc<ModuleVerifier>> {
+        self.module_verifier.as_ref()
+    }
+
+    /// Returns the formatter for the user-facing message of unsupported
+    /// features, if configured.
+    #[must_use]
+    pub const fn unsupported_feature_formatter(
+        &self,
+    ) -> Option<fn(&UnsupportedWasmFeatureExtensionError) -> String> {
+        self.unsupported_feature_formatter
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The version of the Pyodide runtime, see [`Engine::pyodide_version`].
+pub struct PyodideVersion {
+    /// The major version
+    pub major: u32,
+    /// The minor version
+    pub minor: u32,
+    /// The patch version
+    pub patch: u32,
+}
+
+impl PyodideVersion {
+    /// The oldest Pyodide version whose `pyodide.ffi` and `pyodide.code` APIs
+    /// are supported by this crate
+    pub const MINIMUM_SUPPORTED: Self = Self {
+        major: 0,
+        minor: 21,
+        patch: 0,
+    };
+
+    /// Detects the version of the running Pyodide runtime and checks that
+    /// it is supported, caching the result.
+    pub(crate) fn detect(py: Python) -> Result<Self, Error> {
+        static PYODIDE_VERSION: GILOnceCell<Result<PyodideVersion, Error>> = GILOnceCell::new();
+
+        PYODIDE_VERSION
+            .get_or_init(py, || {
+                let version = py
+                    .import(intern!(py, "pyodide"))
+                    .and_then(|pyodide| pyodide.getattr(intern!(py, "__version__")))
+                    .and_then(|version| version.extract::<String>())
+                    .map_err(|err| {
+                        Error::EnvironmentUnavailable(format!(
+                            "failed to detect the Pyodide version: {err}"
+                        ))
+                    })?;
+
+                let Some(parsed) = Self::parse(&version) else {
+                    return Err(Error::EnvironmentUnavailable(format!(
+                        "failed to parse the Pyodide version `{version}`"
+                    )));
+                };
+
+                #[cfg(feature = "tracing")]
+                tracing::debug!(version = %parsed, "detected Pyodide");
+
+                if parsed < Self::MINIMUM_SUPPORTED {
+                    return Err(Error::EnvironmentUnavailable(format!(
+                        "Pyodide {parsed} is not supported, please upgrade to Pyodide {} or newer",
+                        Self::MINIMUM_SUPPORTED
+                    )));
+                }
+
+                Ok(parsed)
+            })
+            .clone()
+    }
+
+    /// Parses a version string like `0.26.4` or `0.27.0a2`, ignoring any
+    /// pre-release suffix
+    fn parse(version: &str) -> Option<Self> {
+        let mut components = version.splitn(3, '.').map(|component| {
+            let digits = component
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(component, |end| &component[..end]);
+            digits.parse().ok()
+        });
+
+        Some(Self {
+            major: components.next()??,
+            minor: components.next()??,
+            patch: components.next().unwrap_or(Some(0))?,
+        })
+    }
+}
+
+impl fmt::Display for PyodideVersion {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
@@ -0,0 +1,144 @@
+use std::{error::Error as StdError, fmt};
+
+use pyo3::{intern, prelude::*};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// Categorised error that is returned by this crate's backend.
+///
+/// Errors are still returned as [`anyhow::Error`]s through the
+/// [`wasm_runtime_layer`] API, but they can be downcast into this type to
+/// programmatically match on the failure category.
+///
+/// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+pub enum Error {
+    /// The module requires WebAssembly feature extensions that are not
+    /// supported by the browser
+    ///
+    /// The missing feature extensions can be inspected by downcasting the
+    /// source of the error into an [`UnsupportedWasmFeatureExtensionError`].
+    ///
+    /// [`UnsupportedWasmFeatureExtensionError`]: crate::UnsupportedWasmFeatureExtensionError
+    UnsupportedFeature(String),
+    /// The module failed to compile, i.e. a `WebAssembly.CompileError`
+    Compile(String),
+    /// The module could not be linked with the provided imports, i.e. a
+    /// `WebAssembly.LinkError`
+    Link(String),
+    /// The guest trapped or a host function raised an exception, e.g. a
+    /// `WebAssembly.RuntimeError`
+    ///
+    /// Guest traps can be distinguished from host function errors by
+    /// downcasting the source of the error into a [`Trap`].
+    ///
+    /// [`Trap`]: crate::Trap
+    Trap(String),
+    /// A value could not be converted between Rust and JavaScript
+    Conversion(String),
+    /// An object was used with a store that it does not belong to
+    StoreMismatch(String),
+    /// The guest does not declare an ABI version that is supported by the
+    /// host, see [`Instance::negotiate_abi_version`]
+    ///
+    /// [`Instance::negotiate_abi_version`]: crate::Instance::negotiate_abi_version
+    IncompatibleAbi {
+        /// The ABI version declared by the guest, if any
+        found: Option<u32>,
+        /// The ABI versions supported by the host
+        supported: Vec<u32>,
+    },
+    /// A feature, e.g. shared memories or threads, was requested that
+    /// requires the page to be cross-origin isolated, but it is not
+    NotCrossOriginIsolated(String),
+    /// The JavaScript [`WebAssembly`] API is not available, e.g. because the
+    /// code is not running inside Pyodide
+    ///
+    /// [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly
+    EnvironmentUnavailable(String),
+    /// A host function was called while the maximum nesting depth of host
+    /// calls was already reached, see
+    /// [`EngineConfig::with_max_host_call_depth`], or a function was
+    /// re-entered beyond its limit, see [`Func::with_reentrancy_limit`]
+    ///
+    /// [`EngineConfig::with_max_host_call_depth`]: crate::EngineConfig::with_max_host_call_depth
+    /// [`Func::with_reentrancy_limit`]: crate::Func::with_reentrancy_limit
+    CallDepthExceeded {
+        /// The maximum nesting depth of calls
+        limit: usize,
+    },
+    /// A host function panicked, which is caught and converted into a trap
+    /// of the calling guest instead of unwinding across the Python and
+    /// JavaScript frames of the call
+    HostPanic(String),
+    /// The signature of a module is missing or does not verify, see
+    /// `ModuleVerifier`
+    Signature(String),
+}
+
+impl Error {
+    /// Classifies a JavaScript exception, raised while calling into the
+    /// [`WebAssembly`] API, by the name of the JavaScript error.
+    ///
+    /// Exceptions that are not `WebAssembly` errors are categorised using
+    /// `fallback`.
+    ///
+    /// [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly
+    pub(crate) fn from_js_exception(py: Python, err: &PyErr, fallback: fn(String) -> Self) -> Self {
+        let message = err.to_string();
+
+        let name = err
+            .value(py)
+            .getattr(intern!(py, "name"))
+            .and_then(|name| name.extract::<String>());
+
+        match name.as_deref() {
+            Ok("CompileError") => Self::Compile(message),
+            Ok("LinkError") => Self::Link(message),
+            Ok("RuntimeError") => Self::Trap(message),
+            _ => fallback(message),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedFeature(message) => {
+                write!(fmt, "unsupported WebAssembly feature: {message}")
+            },
+            Self::Compile(message) => write!(fmt, "failed to compile the module: {message}"),
+            Self::Link(message) => write!(fmt, "failed to link the module: {message}"),
+            Self::Trap(message) => write!(fmt, "trap: {message}"),
+            Self::Conversion(message) => write!(fmt, "failed to convert a value: {message}"),
+            Self::StoreMismatch(message) => write!(fmt, "store mismatch: {message}"),
+            Self::IncompatibleAbi {
+                found: Some(found),
+                supported,
+            } => write!(
+                fmt,
+                "the guest ABI version {found} is not supported by the host, which supports \
+                 {supported:?}"
+            ),
+            Self::IncompatibleAbi {
+                found: None,
+                supported,
+            } => write!(
+                fmt,
+                "the guest does not declare its ABI version, the host supports {supported:?}"
+            ),
+            Self::NotCrossOriginIsolated(message) => {
+                write!(fmt, "the page is not cross-origin isolated: {message}")
+            },
+            Self::EnvironmentUnavailable(message) => {
+                write!(fmt, "the WebAssembly environment is unavailable: {message}")
+            },
+            Self::CallDepthExceeded { limit } => {
+                write!(fmt, "host call depth exceeded the limit of {limit}")
+            },
+            Self::HostPanic(message) => write!(fmt, "host function panicked: {message}"),
+            Self::Signature(message) => write!(fmt, "invalid module signature: {message}"),
+        }
+    }
+}
+
+impl StdError for Error {}
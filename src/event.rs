@@ -0,0 +1,186 @@
+use std::sync::{Arc, Weak};
+
+use pyo3::{
+    exceptions::PyRuntimeError,
+    intern,
+    prelude::*,
+    types::{PyString, PyTuple},
+};
+use pyo3_error::PyErrChain;
+use wasm_runtime_layer::backend::{AsContext, AsContextMut, Value, WasmFunc};
+
+use crate::{conversion::ValueExt, func::js_host_callable, store::StoreContextMut, Engine, Func};
+
+/// A JavaScript event listener that forwards DOM events to a [`Func`].
+///
+/// The listener can be registered on any [`EventTarget`] using
+/// [`EventListener::add_to`] or by passing [`EventListener::as_js`] to
+/// `addEventListener` manually.
+///
+/// [`EventTarget`]: https://developer.mozilla.org/en-US/docs/Web/API/EventTarget
+#[derive(Debug)]
+pub struct EventListener {
+    /// The JavaScript-callable listener
+    listener: Py<PyAny>,
+}
+
+impl Clone for EventListener {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| Self {
+            listener: self.listener.clone_ref(py),
+        })
+    }
+}
+
+impl EventListener {
+    /// Creates a new event listener that calls `func` for every event it
+    /// receives.
+    ///
+    /// The event properties named in `fields`, e.g. `["clientX", "clientY"]`
+    /// for pointer coordinates, are converted into the parameters of `func`.
+    /// The results of `func` are discarded.
+    ///
+    /// Like host functions created by [`Func::new`], the listener only keeps
+    /// a weak reference to its store. Errors raised by `func` are thrown as
+    /// JavaScript exceptions, which the browser reports as uncaught errors
+    /// in event listeners.
+    ///
+    /// Events that are dispatched synchronously while a call borrows the
+    /// store, e.g. by calling `click()` from inside a host function, are not
+    /// forwarded to `func` and instead throw an exception.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of `fields` does not match the number
+    /// of parameters of `func`.
+    ///
+    /// [`Func::new`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Func.html#method.new
+    pub fn new<T>(
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        func: &Func,
+        fields: &[&str],
+    ) -> anyhow::Result<Self> {
+        let mut store: StoreContextMut<T> = ctx.as_context_mut();
+
+        let ty = WasmFunc::ty(func, store.as_context());
+
+        if ty.params().len() != fields.len() {
+            anyhow::bail!(
+                "event listener func has {} parameters but {} event fields were provided",
+                ty.params().len(),
+                fields.len()
+            );
+        }
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?ty, ?fields, "EventListener::new");
+
+            let weak_store = store.as_weak_proof();
+
+            let func = func.clone();
+            let ty_clone = ty.clone();
+            // intern the field names once instead of on every event
+            let fields = fields
+                .iter()
+                .map(|field| PyString::intern(py, field).unbind())
+                .collect::<Vec<_>>();
+
+            let listener = Arc::new(move |args: Bound<PyTuple>| -> Result<Py<PyAny>, PyErr> {
+                let py = args.py();
+
+                let Some(mut strong_store) = Weak::upgrade(&weak_store) else {
+                    return Err(PyRuntimeError::new_err(
+                        "event listener called after free of its associated store",
+                    ));
+                };
+
+                // `dispatchEvent` and e.g. `click` call listeners synchronously,
+                //  possibly from inside a call that already borrows the store
+                if strong_store.is_borrowed() {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("event listener called while its store is borrowed");
+
+                    return Err(PyRuntimeError::new_err(
+                        "event listener called while its associated store is borrowed by a call, \
+                         e.g. since the event was dispatched synchronously from a host function",
+                    ));
+                }
+
+                // Safety:
+                //
+                // - The proof is constructed from a mutable store context
+                // - No call that borrows the store is in progress, as checked above
+                let mut store: StoreContextMut<T> =
+                    unsafe { StoreContextMut::from_proof_unchecked(&mut strong_store) };
+
+                let ty = &ty_clone;
+
+                let event = args.get_item(0)?;
+
+                let params = ty
+                    .params()
+                    .iter()
+                    .zip(fields.iter())
+                    .map(|(ty, field)| Value::from_py_typed(event.getattr(field.bind(py))?, *ty))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut results = vec![Value::I32(0); ty.results().len()];
+
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("call_event_listener", ?params, ?ty).entered();
+
+                if let Err(err) = func.call::<T>(store.as_context_mut(), &params, &mut results) {
+                    #[cfg(feature = "tracing")]
+                    tracing::error!("{err:?}");
+                    return Err(PyErrChain::pyerr_from_err(py, err));
+                }
+
+                Ok(py.None())
+            });
+
+            let listener = js_host_callable(py, &mut store, listener, &ty)?;
+
+            Ok(Self {
+                listener: listener.unbind(),
+            })
+        })
+    }
+
+    /// Returns the JavaScript-callable listener
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.listener.clone_ref(py)
+    }
+
+    /// Registers this listener for events of `event_type` on the `target`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if calling `target.addEventListener` fails.
+    pub fn add_to(&self, target: &Bound<PyAny>, event_type: &str) -> anyhow::Result<()> {
+        let py = target.py();
+
+        target.call_method1(
+            intern!(py, "addEventListener"),
+            (event_type, self.listener.bind(py)),
+        )?;
+
+        Ok(())
+    }
+
+    /// Unregisters this listener for events of `event_type` from the `target`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if calling `target.removeEventListener` fails.
+    pub fn remove_from(&self, target: &Bound<PyAny>, event_type: &str) -> anyhow::Result<()> {
+        let py = target.py();
+
+        target.call_method1(
+            intern!(py, "removeEventListener"),
+            (event_type, self.listener.bind(py)),
+        )?;
+
+        Ok(())
+    }
+}
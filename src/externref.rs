@@ -1,6 +1,8 @@
 use std::{any::Any, sync::Arc};
 
 use pyo3::prelude::*;
+#[cfg(feature = "serde")]
+use pyo3::sync::GILOnceCell;
 use wasm_runtime_layer::backend::{AsContextMut, WasmExternRef};
 
 use crate::{
@@ -16,6 +18,9 @@ pub struct ExternRef {
     host: Option<Arc<AnyExternRef>>,
     /// The inner extern ref object, for guest access, opaque
     guest: Py<PyAny>,
+    /// The structured-cloneable JavaScript payload, which is sent instead of
+    /// the guest object to other JavaScript contexts, optional
+    cloneable: Option<Py<PyAny>>,
 }
 
 impl Clone for ExternRef {
@@ -23,6 +28,10 @@ impl Clone for ExternRef {
         Python::with_gil(|py| Self {
             host: self.host.clone(),
             guest: self.guest.clone_ref(py),
+            cloneable: self
+                .cloneable
+                .as_ref()
+                .map(|cloneable| cloneable.clone_ref(py)),
         })
     }
 }
@@ -43,6 +52,7 @@ impl WasmExternRef<Engine> for ExternRef {
             Ok(Self {
                 host: Some(object),
                 guest: guest.unbind(),
+                cloneable: None,
             })
         })
         .expect("ExternRef::new should not fail")
@@ -72,6 +82,96 @@ impl ToPy for ExternRef {
 }
 
 impl ExternRef {
+    /// Returns a shared owned handle to the host data of this extern ref.
+    ///
+    /// Unlike [`WasmExternRef::downcast`], which only borrows the data, the
+    /// returned [`Arc`] can outlive this extern ref, e.g. to move the data
+    /// into a spawned task.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this extern ref was not created by the host, or
+    /// if its data is not of type `T`.
+    pub fn downcast_arc<T: 'static + Send + Sync>(&self) -> anyhow::Result<Arc<T>> {
+        // Check if we have a host-accessible non-opaque reference to the data
+        let Some(object) = self.host.as_ref() else {
+            anyhow::bail!("extern ref is from a different source");
+        };
+
+        let Ok(object) = Arc::clone(object).downcast() else {
+            anyhow::bail!("incorrect extern ref type");
+        };
+
+        Ok(object)
+    }
+
+    /// Creates a new structured-cloneable extern ref from host data, which
+    /// can be sent to a [`RemoteInstance`] in another JavaScript context.
+    ///
+    /// The host can access the `object` like for [`WasmExternRef::new`].
+    /// When the extern ref is sent to another context, the `object` is
+    /// serialized into a plain JavaScript value, which is structured-cloned
+    /// into the other context instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `object` cannot be serialized.
+    ///
+    /// [`RemoteInstance`]: crate::RemoteInstance
+    #[cfg(feature = "serde")]
+    pub fn new_cloneable<T: 'static + Send + Sync + serde::Serialize>(
+        ctx: impl AsContextMut<Engine>,
+        object: T,
+    ) -> anyhow::Result<Self> {
+        let json = serde_json::to_string(&object)?;
+
+        let mut extern_ref = <Self as WasmExternRef<Engine>>::new(ctx, object);
+
+        Python::with_gil(|py| -> Result<(), PyErr> {
+            let cloneable = js_json_parse(py)?.call1((json,))?;
+            extern_ref.cloneable = Some(cloneable.unbind());
+            Ok(())
+        })?;
+
+        Ok(extern_ref)
+    }
+
+    /// Creates a new structured-cloneable extern ref that wraps the
+    /// JavaScript `value`, which can be sent to a [`RemoteInstance`] in
+    /// another JavaScript context.
+    ///
+    /// The guest receives the `value` itself, both in this and in the other
+    /// context. Since the extern ref is not created from host data, it
+    /// cannot be downcast. The `value` must support the [structured clone
+    /// algorithm], otherwise sending it to another context fails.
+    ///
+    /// [`RemoteInstance`]: crate::RemoteInstance
+    /// [structured clone algorithm]: https://developer.mozilla.org/en-US/docs/Web/API/Web_Workers_API/Structured_clone_algorithm
+    #[must_use]
+    pub fn from_js_value(value: &Bound<PyAny>) -> Self {
+        Self {
+            host: None,
+            guest: value.clone().unbind(),
+            cloneable: Some(value.clone().unbind()),
+        }
+    }
+
+    /// Returns `true` if this extern ref is structured-cloneable, i.e. if it
+    /// was created with `ExternRef::new_cloneable` or
+    /// [`ExternRef::from_js_value`].
+    #[must_use]
+    pub const fn is_cloneable(&self) -> bool {
+        self.cloneable.is_some()
+    }
+
+    /// Returns the structured-cloneable JavaScript payload of this extern
+    /// ref, if it is cloneable
+    pub(crate) fn to_cloneable(&self, py: Python) -> Option<Py<PyAny>> {
+        self.cloneable
+            .as_ref()
+            .map(|cloneable| cloneable.clone_ref(py))
+    }
+
     /// Creates a new extern ref from a Python value
     pub(crate) fn from_exported_externref(object: Bound<PyAny>) -> Self {
         // Check if this ExternRef comes from this source,
@@ -80,6 +180,7 @@ impl ExternRef {
             return Self {
                 host: None,
                 guest: object.unbind(),
+                cloneable: None,
             };
         };
 
@@ -88,6 +189,7 @@ impl ExternRef {
         Self {
             host: Some(host),
             guest: object.unbind(),
+            cloneable: None,
         }
     }
 }
@@ -99,3 +201,9 @@ struct PyExternRef {
     /// The inner extern ref data
     object: Arc<AnyExternRef>,
 }
+
+#[cfg(feature = "serde")]
+fn js_json_parse(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_JSON_PARSE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_JSON_PARSE.import(py, "js.JSON", "parse")
+}
@@ -1,4 +1,8 @@
-use std::{error::Error, fmt};
+use std::{
+    error::Error,
+    fmt,
+    time::{Duration, Instant},
+};
 
 use flagset::FlagSet;
 use pyo3::{prelude::*, sync::GILOnceCell};
@@ -6,13 +10,71 @@ use pyo3::{prelude::*, sync::GILOnceCell};
 use crate::conversion::js_uint8_array_new;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A module requires [`WasmFeatureExtension`]s that are not supported by the
+/// browser.
+///
+/// Compiling such a module fails with an [`Error::UnsupportedFeature`], which
+/// carries this error as its source, such that it can be downcast from the
+/// [`anyhow::Error`] to inspect the missing feature extensions.
+///
+/// The [`Display`](fmt::Display) implementation of this error is aimed at
+/// end users and includes guidance on upgrading the browser, while
+/// [`UnsupportedWasmFeatureExtensionError::summary`] provides a compact
+/// single-line format for logs. Applications can localize the user-facing
+/// message with [`EngineConfig::with_unsupported_feature_formatter`].
+///
+/// [`Error::UnsupportedFeature`]: crate::Error::UnsupportedFeature
+/// [`EngineConfig::with_unsupported_feature_formatter`]: crate::EngineConfig::with_unsupported_feature_formatter
 pub struct UnsupportedWasmFeatureExtensionError {
-    pub required: FlagSet<WasmFeatureExtension>,
-    pub supported: FlagSet<WasmFeatureExtension>,
+    /// The feature extensions that the module requires
+    required: FlagSet<WasmFeatureExtension>,
+    /// The feature extensions that the browser supports
+    supported: FlagSet<WasmFeatureExtension>,
 }
 
 impl UnsupportedWasmFeatureExtensionError {
-    pub fn check_support(py: Python, bytes: &[u8]) -> Result<Result<(), Self>, PyErr> {
+    /// Returns the feature extensions that the module requires.
+    #[must_use]
+    pub const fn required(&self) -> FlagSet<WasmFeatureExtension> {
+        self.required
+    }
+
+    /// Returns the feature extensions that the browser supports.
+    #[must_use]
+    pub const fn supported(&self) -> FlagSet<WasmFeatureExtension> {
+        self.supported
+    }
+
+    /// Returns the feature extensions that the module requires but that the
+    /// browser does not support.
+    #[must_use]
+    pub fn missing(&self) -> FlagSet<WasmFeatureExtension> {
+        self.required & (!self.supported)
+    }
+
+    /// Returns a compact single-line description of the missing feature
+    /// extensions, e.g. for logs.
+    #[must_use]
+    pub fn summary(&self) -> impl fmt::Display {
+        struct Summary(FlagSet<WasmFeatureExtension>);
+
+        impl fmt::Display for Summary {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("missing WebAssembly feature extensions:")?;
+
+                for (i, missing) in self.0.into_iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(fmt, "{separator}{missing}")?;
+                }
+
+                Ok(())
+            }
+        }
+
+        Summary(self.missing())
+    }
+
+    pub(crate) fn check_support(py: Python, bytes: &[u8]) -> Result<Result<(), Self>, PyErr> {
         let err = Self {
             required: WasmFeatureExtension::required(bytes),
             supported: *WasmFeatureExtension::supported(py)?,
@@ -24,6 +86,12 @@ impl UnsupportedWasmFeatureExtensionError {
 
         Ok(Err(err))
     }
+
+    /// Checks if the required `extension` is not supported.
+    #[must_use]
+    pub fn is_missing(&self, extension: WasmFeatureExtension) -> bool {
+        self.missing().contains(extension)
+    }
 }
 
 impl fmt::Display for UnsupportedWasmFeatureExtensionError {
@@ -35,7 +103,7 @@ impl fmt::Display for UnsupportedWasmFeatureExtensionError {
         )?;
         writeln!(fmt)?;
 
-        for missing in self.required & (!self.supported) {
+        for missing in self.missing() {
             writeln!(fmt, " - {missing}")?;
         }
 
@@ -52,22 +120,42 @@ impl Error for UnsupportedWasmFeatureExtensionError {}
 
 flagset::flags! {
     #[non_exhaustive]
+    /// A [WebAssembly feature extension], whose support by the browser is
+    /// detected by validating a small canary module that uses it.
+    ///
+    /// [WebAssembly feature extension]: https://webassembly.org/features/
     pub enum WasmFeatureExtension: u64 {
+        /// The bulk memory operations extension
         BulkMemory,
+        /// The exception handling extension
         Exceptions,
+        /// The extended constant expressions extension
         ExtendedConst,
+        /// The typed function references extension
         FunctionReferences,
+        /// The garbage collection extension
         GC,
+        /// The 64-bit memory extension
         Memory64,
+        /// The multiple memories extension
         MultiMemory,
+        /// The multi-value extension
         MultiValue,
+        /// The import and export of mutable globals extension
         MutableGlobal,
+        /// The reference types extension
         ReferenceTypes,
+        /// The relaxed SIMD extension
         RelaxedSimd,
+        /// The non-trapping float-to-int conversions extension
         SaturatingFloatToInt,
+        /// The sign-extension operators extension
         SignExtension,
+        /// The fixed-width SIMD extension
         Simd,
+        /// The tail calls extension
         TailCall,
+        /// The threads and atomics extension
         Threads,
     }
 }
@@ -96,6 +184,7 @@ impl From<WasmFeatureExtension> for wasmparser::WasmFeatures {
 }
 
 impl WasmFeatureExtension {
+    /// Returns the feature extensions that the WASM module `bytes` requires
     #[allow(clippy::too_many_lines)]
     #[must_use]
     pub fn required(bytes: &[u8]) -> FlagSet<Self> {
@@ -110,6 +199,13 @@ impl WasmFeatureExtension {
         required
     }
 
+    /// Returns the feature extensions that the browser supports, which are
+    /// probed once and then cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the canary modules cannot be passed to the
+    /// browser's `WebAssembly` API.
     pub fn supported(py: Python) -> Result<&'static FlagSet<Self>, PyErr> {
         static SUPPORTED_FEATURES: GILOnceCell<FlagSet<WasmFeatureExtension>> = GILOnceCell::new();
 
@@ -126,7 +222,48 @@ impl WasmFeatureExtension {
         })
     }
 
-    pub fn check_if_supported(self, py: Python) -> Result<bool, PyErr> {
+    /// Returns an iterator over all feature extensions.
+    pub fn all() -> impl Iterator<Item = Self> {
+        FlagSet::<Self>::full().into_iter()
+    }
+
+    /// Probes whether the browser supports this feature extension by
+    /// running its canary module, see [`WasmFeatureExtension::canary`].
+    ///
+    /// Unlike [`WasmFeatureExtension::supported`], the result is not cached,
+    /// such that every call runs the probe again and measures how long it
+    /// took.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the canary module cannot be passed to the
+    /// browser's `WebAssembly` API, e.g. because the code is not running
+    /// inside Pyodide.
+    pub fn probe(self, py: Python) -> Result<ProbeResult, PyErr> {
+        let start = Instant::now();
+        let supported = self.check_if_supported(py)?;
+        let duration = start.elapsed();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(extension = %self, supported, ?duration, "WasmFeatureExtension::probe");
+
+        Ok(ProbeResult {
+            extension: self,
+            supported,
+            duration,
+        })
+    }
+
+    /// Returns the bytes of the canary module, which only validates if the
+    /// browser supports this feature extension.
+    #[must_use]
+    pub const fn canary(self) -> &'static [u8] {
+        self.canary_bytes()
+    }
+
+    /// Checks if the browser supports this feature extension, without
+    /// caching the result
+    fn check_if_supported(self, py: Python) -> Result<bool, PyErr> {
         let canary = self.canary_bytes();
 
         if matches!(self, Self::MultiMemory) {
@@ -183,39 +320,76 @@ impl WasmFeatureExtension {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The result of probing the support for a [`WasmFeatureExtension`], see
+/// [`WasmFeatureExtension::probe`].
+pub struct ProbeResult {
+    /// The probed feature extension
+    extension: WasmFeatureExtension,
+    /// Whether the feature extension is supported
+    supported: bool,
+    /// How long the probe took
+    duration: Duration,
+}
+
+impl ProbeResult {
+    /// Returns the probed feature extension.
+    #[must_use]
+    pub const fn extension(&self) -> WasmFeatureExtension {
+        self.extension
+    }
+
+    /// Returns `true` if the probe passed, i.e. if the browser supports the
+    /// feature extension.
+    #[must_use]
+    pub const fn supported(&self) -> bool {
+        self.supported
+    }
+
+    /// Returns how long the probe took.
+    #[must_use]
+    pub const fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
 impl fmt::Display for WasmFeatureExtension {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            fmt,
-            "{}",
-            match self {
-                Self::BulkMemory => "bulk-memory",
-                Self::Exceptions => "exceptions",
-                Self::ExtendedConst => "extended-const",
-                Self::FunctionReferences => "function-references",
-                Self::GC => "gc",
-                Self::Memory64 => "memory64",
-                Self::MultiMemory => "multi-memory",
-                Self::MultiValue => "multi-value",
-                Self::MutableGlobal => "mutable-global",
-                Self::ReferenceTypes => "reference-types",
-                Self::RelaxedSimd => "relaxed-simd",
-                Self::SaturatingFloatToInt => "saturating-float-to-int",
-                Self::SignExtension => "sign-extension",
-                Self::Simd => "simd",
-                Self::TailCall => "tail-call",
-                Self::Threads => "threads",
-            }
-        )
+        fmt.write_str(self.name())
+    }
+}
+
+impl WasmFeatureExtension {
+    /// Returns the name of the feature extension, e.g. `bulk-memory`
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::BulkMemory => "bulk-memory",
+            Self::Exceptions => "exceptions",
+            Self::ExtendedConst => "extended-const",
+            Self::FunctionReferences => "function-references",
+            Self::GC => "gc",
+            Self::Memory64 => "memory64",
+            Self::MultiMemory => "multi-memory",
+            Self::MultiValue => "multi-value",
+            Self::MutableGlobal => "mutable-global",
+            Self::ReferenceTypes => "reference-types",
+            Self::RelaxedSimd => "relaxed-simd",
+            Self::SaturatingFloatToInt => "saturating-float-to-int",
+            Self::SignExtension => "sign-extension",
+            Self::Simd => "simd",
+            Self::TailCall => "tail-call",
+            Self::Threads => "threads",
+        }
     }
 }
 
-fn web_assembly_validate(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+fn web_assembly_validate(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_VALIDATE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_VALIDATE.import(py, "js.WebAssembly", "validate")
 }
 
-fn web_assembly_module_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+fn web_assembly_module_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_MODULE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_MODULE.import(py, "js.WebAssembly.Module", "new")
 }
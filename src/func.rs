@@ -1,55 +1,217 @@
 use std::{
-    any::TypeId,
+    any::{Any, TypeId},
+    fmt,
     marker::PhantomData,
-    sync::{Arc, Weak},
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, Weak,
+    },
 };
 
-use pyo3::{exceptions::PyRuntimeError, prelude::*, types::PyTuple, PyTypeInfo};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyTypeError},
+    intern,
+    prelude::*,
+    sync::GILOnceCell,
+    types::{PyList, PyTuple},
+};
 use pyo3_error::PyErrChain;
 use wasm_runtime_layer::{
     backend::{AsContext, AsContextMut, Value, WasmFunc, WasmStoreContext},
-    FuncType,
+    FuncType, ValueType,
 };
 use wobbly::sync::Wobbly;
 
 use crate::{
-    conversion::{py_to_js_proxy, ToPy, ValueExt},
-    store::StoreContextMut,
-    Engine,
+    conversion::{create_js_object, instanceof, py_to_js_proxy, ToPy, ValueExt, ValueTypeExt},
+    signature::{FuncSignature, SignatureError},
+    store::{CallHook, StoreBorrow, StoreContextMut, StoreProof},
+    tag::js_exception_to_error,
+    Capabilities, Engine, Error,
 };
 
 /// A bound function, which may be an export from a WASM [`Instance`] or a host
 /// function.
 ///
+/// If the browser supports the WebAssembly type reflection (js-types)
+/// proposal, host functions are created as genuine typed
+/// `WebAssembly.Function`s, which can be stored in `funcref` tables.
+///
 /// [`Instance`]: crate::instance::Instance
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Func {
     /// The inner function
     func: Py<PyAny>,
-    /// The function signature
-    ty: FuncType,
+    /// The function signature, with optional parameter and result names
+    signature: FuncSignature,
     /// The user state type of the context
     user_state: Option<TypeId>,
+    /// Whether the function is a genuine WebAssembly function, i.e. an
+    /// export or a typed host function
+    is_wasm_function: bool,
+    /// The Rust host function, which can be called directly from the host
+    direct: Option<Arc<DirectHostFunc>>,
+    /// The signature of a funcref whose type could not be reflected, which
+    /// is learned from its first call
+    lazy_signature: Option<Arc<OnceLock<FuncSignature>>>,
 }
 
 impl Clone for Func {
     fn clone(&self) -> Self {
         Python::with_gil(|py| Self {
             func: self.func.clone_ref(py),
-            ty: self.ty.clone(),
+            signature: self.signature.clone(),
             user_state: self.user_state,
+            is_wasm_function: self.is_wasm_function,
+            direct: self.direct.clone(),
+            lazy_signature: self.lazy_signature.clone(),
         })
     }
 }
 
 impl WasmFunc<Engine> for Func {
     fn new<T>(
-        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        ctx: impl AsContextMut<Engine, UserState = T>,
         ty: FuncType,
         func: impl 'static
             + Send
             + Sync
             + Fn(StoreContextMut<T>, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>,
+    ) -> Self {
+        Self::new_with_signature(ctx, FuncSignature::from(ty), func)
+    }
+
+    fn ty(&self, _ctx: impl AsContext<Engine>) -> FuncType {
+        self.ty_ref().clone()
+    }
+
+    fn call<T>(
+        &self,
+        mut ctx: impl AsContextMut<Engine>,
+        args: &[Value<Engine>],
+        results: &mut [Value<Engine>],
+    ) -> anyhow::Result<()> {
+        let mut store: StoreContextMut<_> = ctx.as_context_mut();
+        let _borrow = store.borrow_for_call();
+
+        if let Some(user_state) = self.user_state {
+            if user_state != non_static_type_id(store.data()) {
+                return Err(Error::StoreMismatch(String::from(
+                    "host func called with a store of a different user state type",
+                ))
+                .into());
+            }
+        }
+
+        let signature = self.learn_signature(args, results);
+
+        // https://webassembly.github.io/spec/js-api/#exported-function-exotic-objects
+        signature.validate_args(args)?;
+        signature.validate_results(results)?;
+
+        // host -> host calls within the same store bypass the JS trampoline
+        if let Some(direct) = &self.direct {
+            let proof = store.proof_mut();
+
+            if std::ptr::eq(direct.store.as_ptr(), Arc::as_ptr(proof)) {
+                if let Some(func) = direct.func.upgrade() {
+                    #[cfg(feature = "tracing")]
+                    let _span =
+                        tracing::debug_span!("call_host_direct", ?args, %signature).entered();
+
+                    return func(proof, args, results);
+                }
+            }
+        }
+
+        store.call_hook(CallHook::CallingWasm)?;
+
+        let result = Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("call_guest", ?args, %signature).entered();
+
+            let args = args.iter().map(|arg| arg.to_py(py));
+            let args = PyTuple::new(py, args)?;
+
+            let res = self.func.bind(py).call1(args).map_err(|err| {
+                #[cfg(feature = "tracing")]
+                store.dump_mutation_history_on_trap();
+                js_exception_to_error(py, &err, Error::Trap)
+            })?;
+
+            results_from_py(signature, res, results)
+        });
+
+        let returned = store.call_hook(CallHook::ReturningFromWasm);
+
+        result.and(returned)
+    }
+}
+
+impl Func {
+    /// Creates a new host function with the `signature`, like
+    /// [`Func::new_with_signature`], but first checks the signature with
+    /// [`FuncSignature::check_representable`], such that an unrepresentable
+    /// signature is reported when the host function is created instead of
+    /// failing on its first call.
+    ///
+    /// If the capabilities of the browser cannot be detected, e.g. since the
+    /// code is not running inside Pyodide, the check is skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SignatureError::Unrepresentable`] if the signature cannot
+    /// be represented in the browser's JavaScript API.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host function cannot be created in JavaScript, like
+    /// [`WasmFunc::new`].
+    pub fn try_new_with_signature<T>(
+        ctx: impl AsContextMut<Engine, UserState = T>,
+        signature: FuncSignature,
+        func: impl 'static
+            + Send
+            + Sync
+            + Fn(StoreContextMut<T>, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>,
+    ) -> Result<Self, SignatureError> {
+        match Python::with_gil(Capabilities::detect) {
+            Ok(capabilities) => signature.check_representable(&capabilities)?,
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%err, %signature, "skipping the representability check");
+                #[cfg(not(feature = "tracing"))]
+                let _ = err;
+            },
+        }
+
+        Ok(Self::new_with_signature(ctx, signature, func))
+    }
+
+    /// Creates a new host function with the `signature`, which may name its
+    /// parameters and results.
+    ///
+    /// The names are used in the diagnostics of the function, e.g. when
+    /// converting an argument or result fails.
+    ///
+    /// A signature that cannot be represented in the browser's JavaScript
+    /// API only fails when the host function is called, see
+    /// [`Func::try_new_with_signature`] to check it upfront.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host function cannot be created in JavaScript, like
+    /// [`WasmFunc::new`].
+    #[allow(clippy::too_many_lines)]
+    pub fn new_with_signature<T>(
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        signature: FuncSignature,
+        func: impl 'static
+            + Send
+            + Sync
+            + Fn(StoreContextMut<T>, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>,
     ) -> Self {
         Python::with_gil(|py| -> Result<Self, PyErr> {
             #[cfg(feature = "tracing")]
@@ -60,7 +222,33 @@ impl WasmFunc<Engine> for Func {
             let weak_store = store.as_weak_proof();
 
             let user_state = non_static_type_id(store.data());
-            let ty_clone = ty.clone();
+            let signature_clone = signature.clone();
+
+            let func = Arc::new(func);
+
+            let direct_func = Arc::clone(&func);
+            let direct = Arc::new(
+                move |proof: &mut Arc<StoreProof>,
+                      args: &[Value<Engine>],
+                      results: &mut [Value<Engine>]| {
+                    // Safety:
+                    //
+                    // - The proof is checked to belong to the store that this host func was created
+                    //   in, and thus has the same generic type T
+                    // - The proof is reborrowed from the mutable store context of the call
+                    let mut store = unsafe { StoreContextMut::<T>::from_proof_unchecked(proof) };
+
+                    store.enter_host_call()?;
+                    let result = catch_host_panic(|| {
+                        call_with_hooks(&mut store, |store| direct_func(store, args, results))
+                    });
+                    store.exit_host_call();
+
+                    result
+                },
+            );
+            let direct_store = weak_store.clone();
+            let direct = store.register_direct_host_func(direct);
 
             let func = Arc::new(move |args: Bound<PyTuple>| -> Result<Py<PyAny>, PyErr> {
                 let py = args.py();
@@ -76,29 +264,55 @@ impl WasmFunc<Engine> for Func {
                 // - The proof is constructed from a mutable store context
                 // - Calling a host function (from the host or from WASM) provides that call
                 //   with a mutable reborrow of the store context
-                let store = unsafe { StoreContextMut::from_proof_unchecked(&mut strong_store) };
+                let mut store: StoreContextMut<T> =
+                    unsafe { StoreContextMut::from_proof_unchecked(&mut strong_store) };
+                let _borrow = store.borrow_for_call();
 
-                let ty = &ty_clone;
+                let signature = &signature_clone;
 
-                let args = ty
+                let args = signature
+                    .ty()
                     .params()
                     .iter()
                     .zip(args.iter())
-                    .map(|(ty, arg)| Value::from_py_typed(arg, *ty))
+                    .enumerate()
+                    .map(|(i, (ty, arg))| {
+                        Value::from_py_typed(arg, *ty).map_err(|err| {
+                            let context = PyTypeError::new_err(format!(
+                                "{} of {signature}: {err}",
+                                signature.describe_param(i)
+                            ));
+                            context.set_cause(py, Some(err));
+                            context
+                        })
+                    })
                     .collect::<Result<Vec<_>, _>>()?;
-                let mut results = vec![Value::I32(0); ty.results().len()];
+                let mut results = vec![Value::I32(0); signature.ty().results().len()];
 
                 #[cfg(feature = "tracing")]
-                let _span = tracing::debug_span!("call_host", ?args, ?ty).entered();
+                let _span = tracing::debug_span!("call_host", ?args, %signature).entered();
+
+                store
+                    .enter_host_call()
+                    .map_err(|err| PyErrChain::pyerr_from_err(py, err))?;
+                let result = catch_host_panic(|| {
+                    call_with_hooks(&mut store, |store| func(store, &args, &mut results))
+                });
+                store.exit_host_call();
 
-                match func(store, &args, &mut results) {
+                match result {
                     Ok(()) => {
                         #[cfg(feature = "tracing")]
                         tracing::debug!(?results, "result");
                     },
+                    // panics are raised as a dedicated exception, which is
+                    //  converted back into an error when it reaches the host
                     Err(err) => {
                         #[cfg(feature = "tracing")]
                         tracing::error!("{err:?}");
+                        if let Some(Error::HostPanic(message)) = err.downcast_ref::<Error>() {
+                            return Err(HostPanicError::new_err(message.clone()));
+                        }
                         return Err(PyErrChain::pyerr_from_err(py, err));
                     },
                 }
@@ -114,109 +328,630 @@ impl WasmFunc<Engine> for Func {
                 Ok(results)
             });
 
-            let func = Bound::new(
-                py,
-                PyHostFunc {
-                    func: store.register_host_func(func),
-                    #[cfg(feature = "tracing")]
-                    ty: ty.clone(),
-                },
-            )?;
-            let func = py_to_js_proxy(func)?;
+            let proxy = js_host_callable(py, &mut store, func, signature.ty())?;
+
+            // build a genuine typed WebAssembly function if js-types is supported
+            let (func, is_wasm_function) = match web_assembly_function(py) {
+                Ok(web_assembly_function) => (
+                    web_assembly_function.call_method1(
+                        intern!(py, "new"),
+                        (
+                            store.func_type_descriptor(py, signature.ty(), js_func_type)?,
+                            &proxy,
+                        ),
+                    )?,
+                    true,
+                ),
+                Err(_) => (proxy.clone(), false),
+            };
+
+            let direct = DirectHostFunc {
+                store: direct_store,
+                func: direct,
+                proxy: proxy.unbind(),
+            };
 
             Ok(Self {
                 func: func.unbind(),
-                ty,
+                signature,
                 user_state: Some(user_state),
+                is_wasm_function,
+                direct: Some(Arc::new(direct)),
+                lazy_signature: None,
             })
         })
         .expect("Func::new should not fail")
     }
+}
 
-    fn ty(&self, _ctx: impl AsContext<Engine>) -> FuncType {
-        self.ty.clone()
+impl ToPy for Func {
+    fn to_py(&self, py: Python) -> Py<PyAny> {
+        self.func.clone_ref(py)
     }
+}
 
-    fn call<T>(
+impl Func {
+    /// Destroys the JavaScript proxy of this host function, after which
+    /// calling it from JavaScript fails
+    pub(crate) fn destroy_host_proxy(&self) {
+        let Some(direct) = &self.direct else {
+            return;
+        };
+
+        Python::with_gil(|py| {
+            if let Err(err) = direct.proxy.bind(py).call_method0(intern!(py, "destroy")) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(%err, "failed to destroy the host function proxy");
+                #[cfg(not(feature = "tracing"))]
+                let _ = err;
+            }
+        });
+    }
+
+    /// Returns the type of this function.
+    ///
+    /// Unlike [`Func::ty`], this method does not require a store context,
+    /// e.g. so that bindings generators or loggers can inspect the type.
+    ///
+    /// A `funcref` whose type could not be reflected, since the browser does
+    /// not support the WebAssembly type reflection (js-types) proposal, is
+    /// reported with the type `func()` until it learns its type from the
+    /// arguments and results of its first call, see [`Func::has_known_type`].
+    ///
+    /// [`Func::ty`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Func.html#method.ty
+    #[must_use]
+    pub fn ty_ref(&self) -> &FuncType {
+        self.current_signature().ty()
+    }
+
+    /// Returns the signature of this function, including the names of its
+    /// parameters and results if it was created with
+    /// [`Func::new_with_signature`].
+    #[must_use]
+    pub fn signature(&self) -> &FuncSignature {
+        self.current_signature()
+    }
+
+    /// Returns `true` if the type of this function is known.
+    ///
+    /// Functions are only of unknown type if they are `funcref`s, e.g. read
+    /// from a table or returned by the guest, whose type could not be
+    /// reflected since the browser does not support the WebAssembly type
+    /// reflection (js-types) proposal. Such functions learn their type from
+    /// the arguments and results of their first call with
+    /// [`WasmFunc::call`], after which calls with other types fail.
+    #[must_use]
+    pub fn has_known_type(&self) -> bool {
+        self.lazy_signature
+            .as_deref()
+            .map_or(true, |lazy_signature| lazy_signature.get().is_some())
+    }
+
+    /// Returns a host function with the same signature that forwards its
+    /// calls to this function, but fails with an
+    /// [`Error::CallDepthExceeded`] once more than `limit` calls through it
+    /// are active at the same time.
+    ///
+    /// A `limit` of `1` forbids re-entrant calls, e.g. when a guest export
+    /// calls a host import that calls the same export again, which the guest
+    /// may not be prepared for. See the [Re-entrancy](crate#re-entrancy)
+    /// section for the guarantees that re-entrant calls otherwise have.
+    ///
+    /// The limit only applies to calls through the returned function, so the
+    /// host should call, and the guest should import, the returned function
+    /// instead of this function.
+    #[must_use]
+    pub fn with_reentrancy_limit<T>(
         &self,
-        mut ctx: impl AsContextMut<Engine>,
-        args: &[Value<Engine>],
-        results: &mut [Value<Engine>],
+        ctx: impl AsContextMut<Engine, UserState = T>,
+        limit: usize,
+    ) -> Self {
+        let func = self.clone();
+        let limit = ReentrancyLimit::new(limit);
+
+        Self::new_with_signature(
+            ctx,
+            self.signature().clone(),
+            move |store: StoreContextMut<T>, args, results| {
+                limit.call(|| func.call::<T>(store, args, results))
+            },
+        )
+    }
+
+    /// Checks that this function can be used inside the store with the
+    /// `proof`, returning the reason why it cannot otherwise
+    pub(crate) fn check_store(&self, proof: &Arc<StoreProof>) -> Result<(), &'static str> {
+        let Some(direct) = &self.direct else {
+            return Ok(());
+        };
+
+        if std::ptr::eq(direct.store.as_ptr(), Arc::as_ptr(proof)) {
+            return Ok(());
+        }
+
+        if direct.store.strong_count() == 0 {
+            Err("is a host func whose store has already been dropped")
+        } else {
+            Err("is a host func that was created in a different store")
+        }
+    }
+
+    /// Checks that the `args` match the parameters of this function.
+    ///
+    /// [`WasmFunc::call`] performs the same check, but interactive callers
+    /// may use this method to validate user-provided arguments upfront.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of `args` or the type of any argument
+    /// does not match the signature of this function.
+    pub fn validate_args(&self, args: &[Value<Engine>]) -> Result<(), SignatureError> {
+        self.current_signature().validate_args(args)
+    }
+
+    /// Converts this function into a JavaScript value that can be stored in
+    /// a `funcref` table or global slot.
+    ///
+    /// Exported WebAssembly functions and typed host functions can be stored
+    /// directly. Without support for the js-types proposal, host functions
+    /// are plain JavaScript callables, which strict engines reject as
+    /// `anyfunc`s, so they are rejected with a clear error instead.
+    pub(crate) fn to_js_funcref(&self, py: Python) -> anyhow::Result<Py<PyAny>> {
+        if self.is_wasm_function {
+            return Ok(self.func.clone_ref(py));
+        }
+
+        Err(Error::Conversion(format!(
+            "host func {} cannot be stored in a funcref table or global since this browser does \
+             not support the WebAssembly type reflection (js-types) proposal, which is required \
+             to create host funcs as genuine WebAssembly functions",
+            self.current_signature()
+        ))
+        .into())
+    }
+
+    /// Returns the underlying JavaScript function, e.g. to pass it to
+    /// hand-written JavaScript glue code.
+    ///
+    /// Host functions are returned as the callable that is passed to the
+    /// guest, which is a genuine `WebAssembly.Function` only if the browser
+    /// supports the WebAssembly type reflection (js-types) proposal.
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.func.clone_ref(py)
+    }
+
+    /// Wraps the existing JavaScript function `func`, e.g. `console.log` or
+    /// an emscripten shim, with the type `ty`, such that it can be passed
+    /// directly into the [`Imports`] of an instance without a Rust
+    /// trampoline in between.
+    ///
+    /// The guest calls the function with the conversions of the JavaScript
+    /// API, e.g. passing `i64`s as `BigInt`s. Since the function does not
+    /// belong to a store, it can be imported by instances of any store. If
+    /// the browser supports the WebAssembly type reflection (js-types)
+    /// proposal, the function is wrapped as a genuine typed
+    /// `WebAssembly.Function`, which can also be stored in `funcref` tables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `func` is not a JavaScript function.
+    ///
+    /// [`Imports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Imports.html
+    pub fn from_js(func: &Bound<PyAny>, ty: FuncType) -> anyhow::Result<Self> {
+        let py = func.py();
+
+        if !instanceof(func, js_function(py)?, "Function")? {
+            return Err(Error::Conversion(format!(
+                "expected a JavaScript function but found {func:?}"
+            ))
+            .into());
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(%func, ?ty, "Func::from_js");
+
+        // build a genuine typed WebAssembly function if js-types is supported
+        let (func, is_wasm_function) = match web_assembly_function(py) {
+            Ok(web_assembly_function) => (
+                web_assembly_function
+                    .call_method1(intern!(py, "new"), (js_func_type(py, &ty)?, func))?,
+                true,
+            ),
+            Err(_) => (func.clone(), false),
+        };
+
+        Ok(Self {
+            func: func.unbind(),
+            signature: FuncSignature::from(ty),
+            user_state: None,
+            is_wasm_function,
+            direct: None,
+            lazy_signature: None,
+        })
+    }
+
+    /// Creates a new function from a Python value
+    ///
+    /// The function is marked as unrepresentable at the call boundary if its
+    /// type contains `v128` values, which are reported as `externref`s.
+    pub(crate) fn from_exported_function(
+        func: Bound<PyAny>,
+        ty: FuncType,
+        v128: bool,
+    ) -> anyhow::Result<Self> {
+        if !func.is_callable() {
+            anyhow::bail!("expected WebAssembly.Function but found {func:?} which is not callable");
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(%func, ?ty, v128, "Func::from_exported_function");
+
+        Ok(Self {
+            func: func.unbind(),
+            signature: FuncSignature::from(ty).with_v128(v128),
+            user_state: None,
+            is_wasm_function: true,
+            direct: None,
+            lazy_signature: None,
+        })
+    }
+
+    /// Verifies that the JavaScript `func`, described by `what` in errors, has
+    /// the type `ty` if its type can be reflected using the type reflection
+    /// (js-types) proposal
+    pub(crate) fn verify_exported_function(
+        func: &Bound<PyAny>,
+        what: &str,
+        ty: &FuncType,
     ) -> anyhow::Result<()> {
+        let Some((reflected, _)) = reflect_func_type(func)? else {
+            return Ok(());
+        };
+
+        if reflected.params() != ty.params() || reflected.results() != ty.results() {
+            anyhow::bail!("{what} is expected as {ty} but has the JavaScript type {reflected}");
+        }
+
+        Ok(())
+    }
+
+    /// Creates a new function from a JavaScript `funcref`, e.g. one that was
+    /// read from a table or returned by the guest
+    ///
+    /// If the browser supports the WebAssembly type reflection (js-types)
+    /// proposal, the type of the function is reflected. Otherwise, the
+    /// function is lazily typed and learns its signature from the arguments
+    /// and results of its first call.
+    pub(crate) fn from_js_funcref(func: Bound<PyAny>) -> Result<Self, PyErr> {
+        if !func.is_callable() {
+            return Err(PyTypeError::new_err(format!(
+                "expected a funcref but found {func:?} which is not callable"
+            )));
+        }
+
+        let (signature, lazy_signature) = match reflect_func_type(&func)? {
+            Some((ty, v128)) => (FuncSignature::from(ty).with_v128(v128), None),
+            None => (
+                FuncSignature::from(FuncType::new([], [])),
+                Some(Arc::new(OnceLock::new())),
+            ),
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(%func, %signature, lazy = lazy_signature.is_some(), "Func::from_js_funcref");
+
+        Ok(Self {
+            func: func.unbind(),
+            signature,
+            user_state: None,
+            is_wasm_function: true,
+            direct: None,
+            lazy_signature,
+        })
+    }
+
+    /// Returns the signature of this function, which may have been learned
+    /// from its first call
+    fn current_signature(&self) -> &FuncSignature {
+        self.lazy_signature
+            .as_deref()
+            .and_then(OnceLock::get)
+            .unwrap_or(&self.signature)
+    }
+
+    /// Returns the signature of this function, learning it from the types
+    /// of the `args` and `results` if this function is lazily typed and has
+    /// not been called before
+    fn learn_signature(&self, args: &[Value<Engine>], results: &[Value<Engine>]) -> &FuncSignature {
+        let Some(lazy_signature) = &self.lazy_signature else {
+            return &self.signature;
+        };
+
+        lazy_signature.get_or_init(|| {
+            let ty = FuncType::new(
+                args.iter().map(ValueExt::ty),
+                results.iter().map(ValueExt::ty),
+            );
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?ty, "learned the type of a lazily typed funcref");
+
+            FuncSignature::from(ty)
+        })
+    }
+}
+
+impl Func {
+    /// Starts an asynchronous call of this function with the `args`.
+    ///
+    /// Unlike [`WasmFunc::call`], this method does not block until the call
+    /// has completed. If the browser supports JavaScript Promise Integration
+    /// (JSPI), the guest function is wrapped with [`WebAssembly.promising`]
+    /// such that it may suspend on asynchronous imports. Otherwise, the call
+    /// is scheduled to run inside a `Promise`.
+    ///
+    /// The returned [`PendingCall`] can be awaited from Python, e.g. inside
+    /// `pyodide.ffi.run_until_complete`, using [`PendingCall::as_awaitable`],
+    /// or be waited upon from Rust using [`PendingCall::wait`].
+    ///
+    /// The returned [`PendingCall`] keeps the `ctx` mutably borrowed until it
+    /// is dropped, since host functions that are called by the guest
+    /// reborrow it. While the call is pending, the store is also marked as
+    /// borrowed, such that e.g. event listeners cannot re-enter it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `args` cannot be converted or if starting the
+    /// call fails.
+    ///
+    /// [`WebAssembly.promising`]: https://github.com/WebAssembly/js-promise-integration
+    pub fn call_async<'a>(
+        &self,
+        mut ctx: impl 'a + AsContextMut<Engine>,
+        args: &[Value<Engine>],
+    ) -> anyhow::Result<PendingCall<'a>> {
         Python::with_gil(|py| {
-            let store: StoreContextMut<_> = ctx.as_context_mut();
+            let mut store: StoreContextMut<_> = ctx.as_context_mut();
 
             if let Some(user_state) = self.user_state {
-                assert_eq!(user_state, non_static_type_id(store.data()));
+                if user_state != non_static_type_id(store.data()) {
+                    return Err(Error::StoreMismatch(String::from(
+                        "host func called with a store of a different user state type",
+                    ))
+                    .into());
+                }
             }
 
             #[cfg(feature = "tracing")]
-            let _span = tracing::debug_span!("call_guest", ?args, ?self.ty).entered();
+            let _span =
+                tracing::debug_span!("call_guest_async", ?args, signature = %self.current_signature())
+                    .entered();
 
-            // https://webassembly.github.io/spec/js-api/#exported-function-exotic-objects
-            assert_eq!(self.ty.params().len(), args.len());
-            assert_eq!(self.ty.results().len(), results.len());
+            if !self.has_known_type() {
+                return Err(Error::Conversion(String::from(
+                    "a funcref of unknown type must first be called with WasmFunc::call to learn \
+                     its type before it can be called asynchronously",
+                ))
+                .into());
+            }
+
+            self.validate_args(args)?;
 
             let args = args.iter().map(|arg| arg.to_py(py));
             let args = PyTuple::new(py, args)?;
 
-            let res = self.func.bind(py).call1(args)?;
+            // the guest starts running synchronously and keeps the store
+            //  borrowed until the pending call is dropped
+            let borrow = store.borrow_for_call();
 
-            #[cfg(feature = "tracing")]
-            tracing::debug!(%res, ?self.ty);
-
-            match (self.ty.results(), results) {
-                ([], []) => (),
-                ([ty], [result]) => *result = Value::from_py_typed(res, *ty)?,
-                (tys, results) => {
-                    let res: Bound<PyTuple> = PyTuple::type_object(py).call1((res,))?.extract()?;
-
-                    // https://webassembly.github.io/spec/js-api/#exported-function-exotic-objects
-                    assert_eq!(tys.len(), res.len());
-
-                    for ((ty, result), value) in self
-                        .ty
-                        .results()
-                        .iter()
-                        .zip(results.iter_mut())
-                        .zip(res.iter())
-                    {
-                        *result = Value::from_py_typed(value, *ty)?;
-                    }
-                },
-            }
+            let promise = js_call_promising(py)?
+                .call1((self.func.bind(py), args))
+                .map_err(|err| js_exception_to_error(py, &err, Error::Trap))?;
+
+            let mut pending = PendingCall::new(promise.unbind(), self.current_signature().clone());
+            pending.borrow = Some(borrow);
+            pending.ctx = Some(Box::new(ctx));
 
-            Ok(())
+            Ok(pending)
         })
     }
 }
 
-impl ToPy for Func {
-    fn to_py(&self, py: Python) -> Py<PyAny> {
-        self.func.clone_ref(py)
+/// An asynchronous call of a [`Func`] that has been started with
+/// [`Func::call_async`], or of a remote export that has been started with
+/// [`RemoteInstance::call`].
+///
+/// A pending call of a [`Func`] keeps its store context mutably borrowed,
+/// and marks the store as borrowed, until it is dropped.
+///
+/// [`RemoteInstance::call`]: crate::RemoteInstance::call
+pub struct PendingCall<'a> {
+    /// The JavaScript promise that settles with the results of the call
+    promise: Py<PyAny>,
+    /// The signature of the called function
+    signature: FuncSignature,
+    /// The borrow of the store that the guest runs in, which is released
+    /// before the store context
+    borrow: Option<StoreBorrow>,
+    /// The store context that the guest runs in, which host functions
+    /// reborrow while the call is pending
+    ctx: Option<Box<dyn 'a + KeepAlive>>,
+}
+
+/// A value that is only kept alive, e.g. the store context of a
+/// [`PendingCall`]
+trait KeepAlive {}
+
+impl<T> KeepAlive for T {}
+
+impl fmt::Debug for PendingCall<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PendingCall")
+            .field("promise", &self.promise)
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
     }
 }
 
-impl Func {
-    /// Creates a new function from a Python value
-    pub(crate) fn from_exported_function(func: Bound<PyAny>, ty: FuncType) -> anyhow::Result<Self> {
-        if !func.is_callable() {
-            anyhow::bail!("expected WebAssembly.Function but found {func:?} which is not callable");
+impl PendingCall<'_> {
+    /// Creates a new pending call from the `promise` of a call to a function
+    /// with the `signature`, which does not borrow any store
+    pub(crate) const fn new(promise: Py<PyAny>, signature: FuncSignature) -> Self {
+        Self {
+            promise,
+            signature,
+            borrow: None,
+            ctx: None,
         }
+    }
 
-        #[cfg(feature = "tracing")]
-        tracing::debug!(%func, ?ty, "Func::from_exported_function");
+    /// Returns the JavaScript promise of the call, which is awaitable from
+    /// Python.
+    ///
+    /// The value that the awaitable resolves to can be converted into the
+    /// results of the call using [`PendingCall::finish`].
+    #[must_use]
+    pub fn as_awaitable(&self, py: Python) -> Py<PyAny> {
+        self.promise.clone_ref(py)
+    }
 
-        Ok(Self {
-            func: func.unbind(),
-            ty,
-            user_state: None,
+    /// Converts the `resolved` value of the awaited promise, see
+    /// [`PendingCall::as_awaitable`], into the `results` of the call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of `results` does not match the
+    /// signature of the called function or if the results cannot be
+    /// converted.
+    pub fn finish(
+        &self,
+        resolved: Bound<PyAny>,
+        results: &mut [Value<Engine>],
+    ) -> anyhow::Result<()> {
+        self.signature.validate_results(results)?;
+
+        results_from_py(&self.signature, resolved, results)
+    }
+
+    /// Blocks until the call has completed and writes its `results`.
+    ///
+    /// Blocking requires `pyodide.ffi.run_sync`, which relies on JavaScript
+    /// Promise Integration (JSPI) and is only available in newer Pyodide
+    /// versions. In other contexts, the call must instead be awaited using
+    /// [`PendingCall::as_awaitable`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if blocking is not supported in the current context,
+    /// if the call fails, or if its results cannot be converted.
+    pub fn wait(self, results: &mut [Value<Engine>]) -> anyhow::Result<()> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("PendingCall::wait", %self.signature).entered();
+
+            let run_sync = pyodide_run_sync(py).map_err(|err| {
+                Error::EnvironmentUnavailable(format!(
+                    "blocking on an asynchronous call requires pyodide.ffi.run_sync: {err}"
+                ))
+            })?;
+
+            let resolved = run_sync
+                .call1((self.promise.bind(py),))
+                .map_err(|err| js_exception_to_error(py, &err, Error::Trap))?;
+
+            self.finish(resolved, results)
         })
     }
 }
 
+/// A Rust host function that is called with a proof of the store's mutable
+/// borrow, bypassing the JavaScript trampoline
+pub type DirectHostFuncFn = dyn 'static
+    + Send
+    + Sync
+    + Fn(&mut Arc<StoreProof>, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>;
+
+/// A host function that can be called directly from the host
+struct DirectHostFunc {
+    /// The store that the host function was created in
+    store: Weak<StoreProof>,
+    /// The Rust host function, which lives in the store
+    func: Wobbly<DirectHostFuncFn>,
+    /// The JavaScript proxy through which the host function is called from
+    /// JavaScript
+    proxy: Py<PyAny>,
+}
+
+impl fmt::Debug for DirectHostFunc {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("DirectHostFunc")
+            .field("store", &self.store.as_ptr())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The active calls through a function that was created with
+/// [`Func::with_reentrancy_limit`]
+struct ReentrancyLimit {
+    /// The number of currently active calls
+    active: AtomicUsize,
+    /// The maximum number of active calls
+    limit: usize,
+}
+
+impl ReentrancyLimit {
+    const fn new(limit: usize) -> Self {
+        Self {
+            active: AtomicUsize::new(0),
+            limit,
+        }
+    }
+
+    /// Runs the `call`, unless `limit` calls are already active, in which
+    /// case it fails with an [`Error::CallDepthExceeded`]
+    fn call(&self, call: impl FnOnce() -> anyhow::Result<()>) -> anyhow::Result<()> {
+        // host function panics are caught inside the call, so the active
+        //  calls are always decremented again
+        let depth = self.active.fetch_add(1, Ordering::Relaxed);
+
+        let result = if depth < self.limit {
+            call()
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(limit = self.limit, "function re-entered beyond its limit");
+
+            Err(Error::CallDepthExceeded { limit: self.limit }.into())
+        };
+
+        self.active.fetch_sub(1, Ordering::Relaxed);
+
+        result
+    }
+}
+
 pub type PyHostFuncFn = dyn 'static + Send + Sync + Fn(Bound<PyTuple>) -> Result<Py<PyAny>, PyErr>;
 
+/// Registers the host function `func` with the `store` and wraps it into a
+/// JavaScript-callable proxy
+pub fn js_host_callable<'py, T>(
+    py: Python<'py>,
+    store: &mut StoreContextMut<T>,
+    func: Arc<PyHostFuncFn>,
+    ty: &FuncType,
+) -> Result<Bound<'py, PyAny>, PyErr> {
+    let func = Bound::new(
+        py,
+        PyHostFunc {
+            func: store.register_host_func(func, ty),
+            #[cfg(feature = "tracing")]
+            ty: ty.clone(),
+        },
+    )?;
+
+    py_to_js_proxy(func)
+}
+
 #[pyclass(frozen)]
 struct PyHostFunc {
     func: Wobbly<PyHostFuncFn>,
@@ -237,8 +972,73 @@ impl PyHostFunc {
             ));
         };
 
-        func(args)
+        // host funcs created with Func::new already convert panics into
+        //  errors, but other host callables, e.g. WASI shims, may still panic
+        std::panic::catch_unwind(AssertUnwindSafe(|| func(args))).unwrap_or_else(|payload| {
+            let message = panic_message(payload.as_ref());
+
+            #[cfg(feature = "tracing")]
+            tracing::error!(message, "host callable panicked");
+
+            Err(HostPanicError::new_err(message))
+        })
+    }
+}
+
+pyo3::create_exception!(
+    pyodide_webassembly_runtime_layer,
+    HostPanicError,
+    PyRuntimeError,
+    "A host function panicked."
+);
+
+/// Calls the host function `func`, reporting the call to the call hook of the
+/// `store`, see [`Store::call_hook`]
+///
+/// [`Store::call_hook`]: crate::Store::call_hook
+fn call_with_hooks<T>(
+    store: &mut StoreContextMut<T>,
+    func: impl FnOnce(StoreContextMut<T>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    store.call_hook(CallHook::CallingHost)?;
+
+    let result = func(store.as_context_mut());
+    let returned = store.call_hook(CallHook::ReturningFromHost);
+
+    result.and(returned)
+}
+
+/// Calls the host function `func`, converting a panic into an
+/// [`Error::HostPanic`] instead of unwinding across the Python and
+/// JavaScript frames of the call
+fn catch_host_panic(func: impl FnOnce() -> anyhow::Result<()>) -> anyhow::Result<()> {
+    std::panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or_else(|payload| {
+        let message = panic_message(payload.as_ref());
+
+        #[cfg(feature = "tracing")]
+        tracing::error!(message, "host function panicked");
+
+        Err(Error::HostPanic(message).into())
+    })
+}
+
+/// Extracts the message of a panic `payload`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| String::from(*message))
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("Box<dyn Any>"))
+}
+
+/// Extracts the [`Error::HostPanic`] from a Python exception, if it was
+/// raised because a host function panicked
+pub fn host_panic_from_py(py: Python, err: &PyErr) -> Option<Error> {
+    if !err.is_instance_of::<HostPanicError>(py) {
+        return None;
     }
+
+    Some(Error::HostPanic(err.value(py).to_string()))
 }
 
 // Courtesy of David Tolnay:
@@ -264,3 +1064,226 @@ fn non_static_type_id<T: ?Sized>(_x: &T) -> TypeId {
         core::mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(&phantom_data)
     })
 }
+
+/// Converts the `value` of the result at `index` of the `signature`
+fn result_from_py_typed(
+    value: Bound<PyAny>,
+    ty: ValueType,
+    signature: &FuncSignature,
+    index: usize,
+) -> Result<Value<Engine>, Error> {
+    Value::from_py_typed(value, ty).map_err(|err| {
+        Error::Conversion(format!(
+            "{} of {signature}: {err}",
+            signature.describe_result(index)
+        ))
+    })
+}
+
+/// Converts the result `res` of calling a function with the `signature`
+/// into its `results`
+fn results_from_py(
+    signature: &FuncSignature,
+    res: Bound<PyAny>,
+    results: &mut [Value<Engine>],
+) -> anyhow::Result<()> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(%res, %signature);
+
+    match (signature.ty().results(), results) {
+        ([], []) => (),
+        ([ty], [result]) => *result = result_from_py_typed(res, *ty, signature, 0)?,
+        (tys, results) => {
+            // the returned JavaScript array is iterated lazily and each of its
+            //  elements is written directly into the results, without copying
+            //  the array into an intermediate tuple
+            let len = res
+                .len()
+                .map_err(|err| Error::Conversion(err.to_string()))?;
+
+            // https://webassembly.github.io/spec/js-api/#exported-function-exotic-objects
+            if tys.len() != len {
+                return Err(Error::Conversion(format!(
+                    "{signature} returned {len} results but {} were expected",
+                    tys.len()
+                ))
+                .into());
+            }
+
+            let values = res
+                .try_iter()
+                .map_err(|err| Error::Conversion(err.to_string()))?;
+
+            for (i, ((ty, result), value)) in
+                tys.iter().zip(results.iter_mut()).zip(values).enumerate()
+            {
+                let value = value.map_err(|err| Error::Conversion(err.to_string()))?;
+                *result = result_from_py_typed(value, *ty, signature, i)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn js_call_promising(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_CALL_PROMISING: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_CALL_PROMISING
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function callPromising(func, args) {
+    if (typeof WebAssembly.promising === 'function') {
+        try {
+            return WebAssembly.promising(func)(...args);
+        } catch (err) {
+            // only exported WebAssembly functions can be wrapped
+            if (!(err instanceof TypeError)) {
+                throw err;
+            }
+        }
+    }
+    return new Promise((resolve) => resolve(func(...args)));
+}
+callPromising
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+pub fn pyodide_run_sync(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static PYODIDE_RUN_SYNC: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    PYODIDE_RUN_SYNC.import(py, "pyodide.ffi", "run_sync")
+}
+
+/// Creates the js-types function type descriptor for the signature `ty`
+fn js_func_type<'py>(py: Python<'py>, ty: &FuncType) -> Result<Bound<'py, PyAny>, PyErr> {
+    let desc = create_js_object(py)?;
+    desc.setattr(
+        intern!(py, "parameters"),
+        js_array_from(py)?.call1((PyList::new(
+            py,
+            ty.params().iter().map(|ty| ty.as_js_descriptor(py)),
+        )?,))?,
+    )?;
+    desc.setattr(
+        intern!(py, "results"),
+        js_array_from(py)?.call1((PyList::new(
+            py,
+            ty.results().iter().map(|ty| ty.as_js_descriptor(py)),
+        )?,))?,
+    )?;
+    Ok(desc)
+}
+
+/// Reflects the type of the JavaScript `func` using the WebAssembly type
+/// reflection (js-types) proposal, returning [`None`] if it is not supported
+///
+/// Also returns whether the type contains `v128` values, which are reported
+/// as `externref`s.
+fn reflect_func_type(func: &Bound<PyAny>) -> Result<Option<(FuncType, bool)>, PyErr> {
+    let py = func.py();
+
+    let Ok(reflected) = func.call_method0(intern!(py, "type")) else {
+        return Ok(None);
+    };
+
+    let mut v128 = false;
+    let mut value_types = |types: Bound<PyAny>| -> Result<Vec<ValueType>, PyErr> {
+        types
+            .try_iter()?
+            .map(|ty| match ty?.extract::<String>()?.as_str() {
+                "i32" => Ok(ValueType::I32),
+                "i64" => Ok(ValueType::I64),
+                "f32" => Ok(ValueType::F32),
+                "f64" => Ok(ValueType::F64),
+                "anyfunc" | "funcref" => Ok(ValueType::FuncRef),
+                "externref" => Ok(ValueType::ExternRef),
+                "v128" => {
+                    v128 = true;
+                    Ok(ValueType::ExternRef)
+                },
+                ty => Err(PyTypeError::new_err(format!(
+                    "funcref has the unsupported value type {ty}"
+                ))),
+            })
+            .collect()
+    };
+
+    let params = value_types(reflected.getattr(intern!(py, "parameters"))?)?;
+    let results = value_types(reflected.getattr(intern!(py, "results"))?)?;
+
+    Ok(Some((FuncType::new(params, results), v128)))
+}
+
+fn web_assembly_function(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_FUNCTION: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_FUNCTION.import(py, "js.WebAssembly", "Function")
+}
+
+fn js_function(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_FUNCTION: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_FUNCTION.import(py, "js", "Function")
+}
+
+fn js_array_from(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_ARRAY_FROM: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_ARRAY_FROM.import(py, "js.Array", "from")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Re-enters the `limit` recursively until it fails, counting the
+    /// successfully entered calls in `depth`
+    fn reenter(limit: &ReentrancyLimit, depth: &mut usize) -> anyhow::Result<()> {
+        limit.call(|| {
+            *depth += 1;
+            reenter(limit, depth)
+        })
+    }
+
+    #[test]
+    fn reentrancy_limit() {
+        for max in [0, 1, 2, 8] {
+            let limit = ReentrancyLimit::new(max);
+
+            let mut depth = 0;
+            let err = reenter(&limit, &mut depth).expect_err("reentrancy should be limited");
+
+            assert_eq!(depth, max);
+            assert!(
+                matches!(err.downcast_ref(), Some(Error::CallDepthExceeded { limit }) if *limit == max),
+                "{err:?} should be a CallDepthExceeded error with limit {max}"
+            );
+
+            // the failed calls are no longer active
+            assert_eq!(limit.active.load(Ordering::Relaxed), 0);
+        }
+    }
+
+    #[test]
+    fn reentrancy_limit_sequential_calls() {
+        let limit = ReentrancyLimit::new(1);
+
+        // calls that are not nested do not count towards the limit
+        for _ in 0..4 {
+            limit
+                .call(|| Ok(()))
+                .expect("a non-nested call should succeed");
+        }
+
+        // errors of the call are forwarded and still release it
+        let err = limit
+            .call(|| Err(anyhow::anyhow!("guest trap")))
+            .expect_err("the call error should be forwarded");
+        assert_eq!(err.to_string(), "guest trap");
+        assert_eq!(limit.active.load(Ordering::Relaxed), 0);
+    }
+}
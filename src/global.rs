@@ -1,12 +1,13 @@
-use pyo3::{intern, prelude::*, sync::GILOnceCell};
+use pyo3::{exceptions::PyOverflowError, intern, prelude::*, sync::GILOnceCell, types::PyList};
 use wasm_runtime_layer::{
     backend::{AsContext, AsContextMut, Value, WasmGlobal},
-    GlobalType,
+    GlobalType, ValueType,
 };
 
 use crate::{
-    conversion::{create_js_object, instanceof, ToPy, ValueExt, ValueTypeExt},
-    Engine,
+    conversion::{create_js_object, instanceof, to_py_for_ref_slot, ToPy, ValueExt, ValueTypeExt},
+    history::MutationKind,
+    Engine, Error,
 };
 
 /// A global variable accesible as an import or export in a module.
@@ -33,7 +34,7 @@ impl Clone for Global {
 
 impl WasmGlobal<Engine> for Global {
     fn new(_ctx: impl AsContextMut<Engine>, value: Value<Engine>, mutable: bool) -> Self {
-        Python::with_gil(|py| -> Result<Self, PyErr> {
+        Python::with_gil(|py| -> anyhow::Result<Self> {
             #[cfg(feature = "tracing")]
             tracing::debug!(?value, mutable, "Global::new");
 
@@ -42,13 +43,16 @@ impl WasmGlobal<Engine> for Global {
             let desc = create_js_object(py)?;
             desc.setattr(
                 intern!(py, "value"),
-                ValueExt::ty(&value).as_js_descriptor(),
+                ValueExt::ty(&value).as_js_descriptor(py),
             )?;
             desc.setattr(intern!(py, "mutable"), mutable)?;
 
-            let value = value.to_py(py);
-
-            let global = web_assembly_global_new(py)?.call1((desc, value))?;
+            let global = if let Value::I64(value) = value {
+                assign_i64_global(py, &I64GlobalTarget::New(&desc), value)?
+            } else {
+                let value = to_py_for_ref_slot(py, &value)?;
+                web_assembly_global_new(py)?.call1((desc, value))?
+            };
 
             Ok(Self {
                 global: global.unbind(),
@@ -62,23 +66,38 @@ impl WasmGlobal<Engine> for Global {
         self.ty
     }
 
-    fn set(&self, _ctx: impl AsContextMut<Engine>, new_value: Value<Engine>) -> anyhow::Result<()> {
+    fn set(
+        &self,
+        mut ctx: impl AsContextMut<Engine>,
+        new_value: Value<Engine>,
+    ) -> anyhow::Result<()> {
         if !self.ty.mutable() {
             return Err(anyhow::anyhow!("Global is not mutable"));
         }
 
-        Python::with_gil(|py| {
+        Python::with_gil(|py| -> anyhow::Result<()> {
             let global = self.global.bind(py);
 
             #[cfg(feature = "tracing")]
             tracing::debug!(global = %global, ?self.ty, ?new_value, "Global::set");
 
-            let new_value = new_value.to_py(py);
-
-            global.setattr(intern!(py, "value"), new_value)?;
+            if let Value::I64(new_value) = new_value {
+                assign_i64_global(py, &I64GlobalTarget::Set(global), new_value)?;
+            } else {
+                let new_value = to_py_for_ref_slot(py, &new_value)?;
+                global.setattr(intern!(py, "value"), new_value)?;
+            }
 
             Ok(())
-        })
+        })?;
+
+        ctx.as_context_mut()
+            .record_mutation(|| MutationKind::GlobalSet {
+                ty: self.ty,
+                value: new_value,
+            });
+
+        Ok(())
     }
 
     fn get(&self, _ctx: impl AsContextMut<Engine>) -> Value<Engine> {
@@ -106,12 +125,116 @@ impl ToPy for Global {
 }
 
 impl Global {
+    /// Returns the type of this global.
+    ///
+    /// Unlike [`Global::ty`], this method does not require a store context,
+    /// e.g. so that bindings generators or loggers can inspect the type.
+    ///
+    /// [`Global::ty`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Global.html#method.ty
+    #[must_use]
+    pub const fn ty_ref(&self) -> &GlobalType {
+        &self.ty
+    }
+
+    /// Creates a new global for each pair of initial value and mutability in
+    /// `globals`, using a single call into JavaScript.
+    ///
+    /// This is faster than calling [`Global::new`] for each global, e.g. when
+    /// instantiating emscripten relocatable modules that import dozens of
+    /// globals.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any value cannot be converted or if any global
+    /// cannot be created.
+    ///
+    /// [`Global::new`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Global.html#method.new
+    pub fn new_many(
+        _ctx: impl AsContextMut<Engine>,
+        globals: &[(Value<Engine>, bool)],
+    ) -> anyhow::Result<Vec<Self>> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(count = globals.len(), "Global::new_many");
+
+            let types = PyList::new(
+                py,
+                globals
+                    .iter()
+                    .map(|(value, _)| ValueExt::ty(value).as_js_descriptor(py).clone()),
+            )?;
+            let mutables = PyList::new(py, globals.iter().map(|(_, mutable)| *mutable))?;
+            let values = PyList::empty(py);
+            for (value, _) in globals {
+                // i64s are passed as strings and converted into BigInts in
+                //  JavaScript, avoiding the Pyodide BigInt conversion
+                match value {
+                    Value::I64(value) => values.append(value.to_string())?,
+                    value => values.append(to_py_for_ref_slot(py, value)?)?,
+                }
+            }
+
+            let created = js_new_globals(py)?.call1((types, mutables, values))?;
+
+            globals
+                .iter()
+                .zip(created.try_iter()?)
+                .map(|((value, mutable), global)| {
+                    Ok(Self {
+                        global: global?.unbind(),
+                        ty: GlobalType::new(ValueExt::ty(value), *mutable),
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the underlying [`WebAssembly.Global`], e.g. to pass it to
+    /// hand-written JavaScript glue code.
+    ///
+    /// [`WebAssembly.Global`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Global
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.global.clone_ref(py)
+    }
+
+    /// Wraps the existing JavaScript `global`, e.g. one that was created by
+    /// hand-written JavaScript glue code, with the type `ty`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `global` is not a [`WebAssembly.Global`] or if it
+    /// does not have the type `ty`.
+    ///
+    /// [`WebAssembly.Global`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Global
+    pub fn from_js(global: &Bound<PyAny>, ty: GlobalType) -> anyhow::Result<Self> {
+        if !instanceof(
+            global,
+            web_assembly_global(global.py())?,
+            "WebAssembly.Global",
+        )? {
+            return Err(Error::Conversion(format!(
+                "expected WebAssembly.Global but found {global:?}"
+            ))
+            .into());
+        }
+
+        Self::verify_exported_global(global, "JavaScript global", ty)
+            .map_err(|err| Error::Conversion(err.to_string()))?;
+
+        Self::from_exported_global(global.clone(), ty)
+    }
+
     /// Creates a new global from a Python value
     pub(crate) fn from_exported_global(
         global: Bound<PyAny>,
         ty: GlobalType,
     ) -> anyhow::Result<Self> {
-        if !instanceof(&global, web_assembly_global(global.py())?)? {
+        if !instanceof(
+            &global,
+            web_assembly_global(global.py())?,
+            "WebAssembly.Global",
+        )? {
             anyhow::bail!("expected WebAssembly.Global but found {global}");
         }
 
@@ -125,12 +248,231 @@ impl Global {
     }
 }
 
-fn web_assembly_global(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+impl Global {
+    /// Verifies that the JavaScript `global`, described by `what` in errors,
+    /// has the type `ty`, e.g. to catch mismatches between the module bytes
+    /// and the compiled module that were introduced by post-processing tools
+    ///
+    /// With js-types, the type of the global is reflected directly. Otherwise,
+    /// its mutability is probed by writing back its current value, which only
+    /// succeeds for mutable globals and does not change the value. Globals
+    /// holding a `NaN` are skipped since writing them back could change the
+    /// `NaN` payload bits, as are globals whose value cannot be accessed
+    /// from JavaScript, e.g. `v128` globals.
+    pub(crate) fn verify_exported_global(
+        global: &Bound<PyAny>,
+        what: &str,
+        ty: GlobalType,
+    ) -> anyhow::Result<()> {
+        let py = global.py();
+
+        if let Ok(reflected) = global.call_method0(intern!(py, "type")) {
+            let mutable: bool = reflected.getattr(intern!(py, "mutable"))?.extract()?;
+            let value: String = reflected.getattr(intern!(py, "value"))?.extract()?;
+
+            let content_matches = value == ty.content().as_js_descriptor(py).to_str()?
+                || (ty.content() == ValueType::FuncRef && value == "funcref");
+
+            if mutable != ty.mutable() || !content_matches {
+                anyhow::bail!(
+                    "{what} is expected as {ty:?} but has the JavaScript type {{ value: {value}, \
+                     mutable: {mutable} }}"
+                );
+            }
+
+            return Ok(());
+        }
+
+        // v128 globals, which are typed as externrefs, throw when their value
+        //  is accessed from JavaScript, so their mutability cannot be probed
+        let Ok(value) = global.getattr(intern!(py, "value")) else {
+            return Ok(());
+        };
+
+        if value.extract::<f64>().is_ok_and(f64::is_nan) {
+            return Ok(());
+        }
+
+        // i64 values are read as Python ints, which Pyodide would convert
+        //  back into numbers that an i64 global rejects
+        let mutable = if ty.content() == ValueType::I64 {
+            let value: i64 = value.extract()?;
+            assign_i64_global(py, &I64GlobalTarget::Set(global), value).is_ok()
+        } else {
+            global.setattr(intern!(py, "value"), value).is_ok()
+        };
+
+        if mutable != ty.mutable() {
+            anyhow::bail!(
+                "{what} is expected as {}mutable but the JavaScript global is {}",
+                if ty.mutable() { "" } else { "im" },
+                if mutable { "mutable" } else { "immutable" },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The representation of i64 values that is passed to `WebAssembly.Global`
+enum I64Repr {
+    /// A `BigInt` that is wrapped in an `Object`, such that Pyodide does not
+    /// auto-convert it into a Python int
+    ObjectWrappedBigInt,
+    /// A plain `BigInt`, which is created on the JavaScript side
+    BigInt,
+    /// A number, which is only used for values that it can represent exactly
+    Number,
+}
+
+impl I64Repr {
+    const ALL: [Self; 3] = [Self::ObjectWrappedBigInt, Self::BigInt, Self::Number];
+
+    fn assign<'py>(
+        self,
+        py: Python<'py>,
+        target: &I64GlobalTarget<'_, 'py>,
+        v: i64,
+    ) -> Result<Bound<'py, PyAny>, PyErr> {
+        /// The largest integer that a JavaScript number can represent exactly
+        const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+        match (self, target) {
+            (Self::ObjectWrappedBigInt, I64GlobalTarget::New(desc)) => {
+                web_assembly_global_new(py)?.call1((desc, Value::<Engine>::I64(v).to_py(py)))
+            },
+            (Self::ObjectWrappedBigInt, I64GlobalTarget::Set(global)) => {
+                global.setattr(intern!(py, "value"), Value::<Engine>::I64(v).to_py(py))?;
+                Ok((*global).clone())
+            },
+            (Self::Number, _) if v.unsigned_abs() > MAX_SAFE_INTEGER.unsigned_abs() => {
+                Err(PyOverflowError::new_err(format!(
+                    "{v} cannot be represented exactly as a JavaScript number"
+                )))
+            },
+            (Self::BigInt | Self::Number, I64GlobalTarget::New(desc)) => {
+                js_assign_i64_global(py)?.call1((py.None(), desc, v, self == Self::BigInt))
+            },
+            (Self::BigInt | Self::Number, I64GlobalTarget::Set(global)) => {
+                js_assign_i64_global(py)?.call1((global, py.None(), v, self == Self::BigInt))
+            },
+        }
+    }
+}
+
+/// The `WebAssembly.Global` that an i64 value is assigned to
+enum I64GlobalTarget<'a, 'py> {
+    /// A new global with the descriptor is created
+    New(&'a Bound<'py, PyAny>),
+    /// The value of the existing global is set
+    Set(&'a Bound<'py, PyAny>),
+}
+
+/// Assigns the i64 value `v` to the `target` global
+///
+/// Engines disagree on which representations of i64 values they accept for
+/// `WebAssembly.Global`s, e.g. some older engines reject `Object`-wrapped
+/// `BigInt`s. Therefore, all [`I64Repr`]s are tried in order until one
+/// succeeds, which is then remembered and tried first for later assignments.
+fn assign_i64_global<'py>(
+    py: Python<'py>,
+    target: &I64GlobalTarget<'_, 'py>,
+    v: i64,
+) -> Result<Bound<'py, PyAny>, PyErr> {
+    static I64_REPR: GILOnceCell<I64Repr> = GILOnceCell::new();
+
+    let preferred = I64_REPR.get(py).copied();
+
+    let mut first_err = None;
+
+    for repr in preferred.into_iter().chain(
+        I64Repr::ALL
+            .into_iter()
+            .filter(|repr| Some(*repr) != preferred),
+    ) {
+        match repr.assign(py, target, v) {
+            Ok(global) => {
+                if preferred.is_none() {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(?repr, "using i64 representation for WebAssembly.Global");
+
+                    let _ = I64_REPR.set(py, repr);
+                }
+
+                return Ok(global);
+            },
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?repr, %err, "i64 representation rejected by WebAssembly.Global");
+
+                first_err.get_or_insert(err);
+            },
+        }
+    }
+
+    Err(first_err.unwrap_or_else(|| {
+        PyOverflowError::new_err("no i64 representation is accepted by WebAssembly.Global")
+    }))
+}
+
+fn js_assign_i64_global(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_ASSIGN_I64_GLOBAL: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_ASSIGN_I64_GLOBAL
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function assignI64Global(global, desc, v, bigint) {
+    const value = bigint ? BigInt(v) : Number(v);
+    if (global == null) {
+        return new WebAssembly.Global(desc, value);
+    }
+    global.value = value;
+    return global;
+}
+assignI64Global
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+fn js_new_globals(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_NEW_GLOBALS: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_NEW_GLOBALS
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function newGlobals(types, mutables, values) {
+    types = Array.from(types);
+    mutables = Array.from(mutables);
+    values = Array.from(values);
+    return types.map((value, i) => new WebAssembly.Global(
+        { value, mutable: mutables[i] },
+        value === 'i64' ? BigInt(values[i]) : values[i],
+    ));
+}
+newGlobals
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+fn web_assembly_global(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_GLOBAL: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_GLOBAL.import(py, "js.WebAssembly", "Global")
 }
 
-fn web_assembly_global_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+fn web_assembly_global_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_GLOBAL_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_GLOBAL_NEW.import(py, "js.WebAssembly.Global", "new")
 }
@@ -0,0 +1,126 @@
+use std::{collections::VecDeque, fmt, time::SystemTime};
+
+use wasm_runtime_layer::{backend::Value, GlobalType, TableType};
+
+use crate::Engine;
+
+#[derive(Debug, Clone)]
+/// A single recorded mutation of guest-visible state, see
+/// [`EngineConfig::with_mutation_history`].
+///
+/// [`EngineConfig::with_mutation_history`]: crate::EngineConfig::with_mutation_history
+pub struct Mutation {
+    /// The time at which the mutation happened
+    pub timestamp: SystemTime,
+    /// The nesting depth of host calls at the time of the mutation, which is
+    /// `0` if the mutation was made outside of any host function
+    pub host_call_depth: usize,
+    /// The kind of mutation
+    pub kind: MutationKind,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+/// The kind of a recorded [`Mutation`].
+pub enum MutationKind {
+    /// A global was set through [`Global::set`]
+    ///
+    /// [`Global::set`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Global.html#method.set
+    GlobalSet {
+        /// The type of the global
+        ty: GlobalType,
+        /// The new value of the global
+        value: Value<Engine>,
+    },
+    /// A table element was set through [`Table::set`]
+    ///
+    /// [`Table::set`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Table.html#method.set
+    TableSet {
+        /// The type of the table
+        ty: TableType,
+        /// The index of the element
+        index: u32,
+        /// The new value of the element
+        value: Value<Engine>,
+    },
+    /// Bytes were written into a memory by the host through
+    /// [`Memory::write`]
+    ///
+    /// [`Memory::write`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.write
+    MemoryWrite {
+        /// The offset of the first written byte
+        offset: usize,
+        /// The number of written bytes
+        len: usize,
+    },
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let since_epoch = self
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        write!(
+            fmt,
+            "[{}.{:03}s depth={}] ",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis(),
+            self.host_call_depth
+        )?;
+
+        match &self.kind {
+            MutationKind::GlobalSet { ty, value } => write!(fmt, "global {ty:?} = {value:?}"),
+            MutationKind::TableSet { ty, index, value } => {
+                write!(fmt, "table {ty:?}[{index}] = {value:?}")
+            },
+            MutationKind::MemoryWrite { offset, len } => {
+                write!(fmt, "memory[{offset}..{}] written", offset + len)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// A bounded history of the most recent mutations in a store
+pub struct MutationHistory {
+    /// The recorded mutations, oldest first
+    mutations: VecDeque<Mutation>,
+}
+
+impl MutationHistory {
+    /// Records the `mutation`, dropping the oldest mutations beyond the
+    /// `capacity`
+    pub fn record(&mut self, mutation: Mutation, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+
+        while self.mutations.len() >= capacity {
+            self.mutations.pop_front();
+        }
+
+        self.mutations.push_back(mutation);
+    }
+
+    /// Returns the recorded mutations, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Mutation> {
+        self.mutations.iter()
+    }
+
+    /// Removes all recorded mutations
+    pub fn clear(&mut self) {
+        self.mutations.clear();
+    }
+}
+
+impl fmt::Display for MutationHistory {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for mutation in &self.mutations {
+            writeln!(fmt, "{mutation}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,548 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyBytes};
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Extern, Imports, Value, WasmMemory},
+    ValueType,
+};
+
+use crate::{conversion::js_uint8_array_new, Engine, Func, FuncSignature, Instance, Memory};
+
+/// A standard import set that lets guests make HTTP requests through the
+/// browser's [`fetch`] API, restricted by an [`HttpPolicy`].
+///
+/// Since `fetch` is asynchronous, the import set provides a poll-based ABI
+/// in the `http` import module, where all pointers and lengths are `i32`s:
+///
+/// - `request(method_ptr, method_len, url_ptr, url_len, body_ptr, body_len) ->
+///   handle` starts a request and returns its non-negative handle, or
+///   [`HttpImports::DENIED`] if the policy rejects it.
+/// - `poll(handle) -> status` returns [`HttpImports::PENDING`] while the
+///   request is in flight and the HTTP status code once the response has
+///   arrived, or a negative error code.
+/// - `response_len(handle) -> len` returns the length of the response body.
+/// - `read_response(handle, ptr, len) -> read` copies up to `len` bytes of the
+///   response body to `ptr` and returns the number of copied bytes.
+/// - `close(handle) -> errno` releases the request.
+///
+/// All functions return [`HttpImports::UNKNOWN_HANDLE`] for handles that
+/// are unknown or not ready. The host can await [`HttpImports::settled`]
+/// between guest calls to wait until all requests have completed.
+///
+/// Since the guest's memory only exists after instantiation, it must be
+/// provided using [`HttpImports::bind_memory`] or
+/// [`HttpImports::bind_instance`] before the guest makes any requests.
+///
+/// [`fetch`]: https://developer.mozilla.org/en-US/docs/Web/API/Fetch_API
+#[derive(Debug, Clone)]
+pub struct HttpImports {
+    /// The state of the import set, which is shared with its imports
+    state: Arc<Mutex<HttpState>>,
+}
+
+#[derive(Debug)]
+/// The shared state of an [`HttpImports`]
+struct HttpState {
+    /// The policy that requests must satisfy
+    policy: HttpPolicy,
+    /// The guest memory from which requests are read
+    memory: Option<Memory>,
+    /// The JavaScript objects of the open requests, by handle
+    requests: BTreeMap<i32, Py<PyAny>>,
+    /// The handle of the next request
+    next_handle: i32,
+}
+
+impl HttpImports {
+    /// The error code of a request that the policy denied
+    pub const DENIED: i32 = -1;
+    /// The error code of a request that failed, e.g. due to a network error
+    pub const FAILED: i32 = -3;
+    /// The import module name of the HTTP import set
+    pub const MODULE: &'static str = "http";
+    /// The status or error code of a request that is still in flight
+    pub const PENDING: i32 = 0;
+    /// The error code of a response that exceeds the policy's maximum length
+    pub const TOO_LARGE: i32 = -4;
+    /// The error code of an unknown or unready request handle
+    pub const UNKNOWN_HANDLE: i32 = -2;
+
+    /// Creates a new HTTP import set that enforces the `policy`.
+    #[must_use]
+    pub fn new(policy: HttpPolicy) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(HttpState {
+                policy,
+                memory: None,
+                requests: BTreeMap::new(),
+                next_handle: 0,
+            })),
+        }
+    }
+
+    /// Defines the `http` imports in the `imports`.
+    #[allow(clippy::too_many_lines)]
+    pub fn define<T: 'static>(
+        &self,
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        imports: &mut Imports<Engine>,
+    ) {
+        let state = Arc::clone(&self.state);
+        let request = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("request")
+                .param("method_ptr", ValueType::I32)
+                .param("method_len", ValueType::I32)
+                .param("url_ptr", ValueType::I32)
+                .param("url_len", ValueType::I32)
+                .param("body_ptr", ValueType::I32)
+                .param("body_len", ValueType::I32)
+                .result("handle", ValueType::I32)
+                .build(),
+            move |ctx, args, results| {
+                let [method_ptr, method_len, url_ptr, url_len, body_ptr, body_len] = args else {
+                    anyhow::bail!("http.request called with invalid arguments {args:?}");
+                };
+
+                let memory = lock(&state).memory()?;
+
+                let method = read_string(&memory, &ctx, method_ptr, method_len)?;
+                let url = read_string(&memory, &ctx, url_ptr, url_len)?;
+                let body = read_bytes(&memory, &ctx, body_ptr, body_len)?;
+
+                let handle = lock(&state).request(&method, &url, &body)?;
+                results[0] = Value::I32(handle);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let poll = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("poll")
+                .param("handle", ValueType::I32)
+                .result("status", ValueType::I32)
+                .build(),
+            move |_ctx, args, results| {
+                let [Value::I32(handle)] = args else {
+                    anyhow::bail!("http.poll called with invalid arguments {args:?}");
+                };
+
+                results[0] = Value::I32(lock(&state).poll(*handle)?);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let response_len = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("response_len")
+                .param("handle", ValueType::I32)
+                .result("len", ValueType::I32)
+                .build(),
+            move |_ctx, args, results| {
+                let [Value::I32(handle)] = args else {
+                    anyhow::bail!("http.response_len called with invalid arguments {args:?}");
+                };
+
+                let len = lock(&state).with_response(*handle, |response| {
+                    Ok(i32::try_from(response.len()).unwrap_or(i32::MAX))
+                })?;
+                results[0] = Value::I32(len);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let read_response = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("read_response")
+                .param("handle", ValueType::I32)
+                .param("ptr", ValueType::I32)
+                .param("len", ValueType::I32)
+                .result("read", ValueType::I32)
+                .build(),
+            move |mut ctx, args, results| {
+                let [Value::I32(handle), Value::I32(ptr), Value::I32(len)] = args else {
+                    anyhow::bail!("http.read_response called with invalid arguments {args:?}");
+                };
+
+                let state = lock(&state);
+                let memory = state.memory()?;
+
+                #[allow(clippy::cast_sign_loss)]
+                let (ptr, len) = (*ptr as u32 as usize, *len as u32 as usize);
+
+                let read = state.with_response(*handle, |response| {
+                    let response = &response[..response.len().min(len)];
+                    memory.write(ctx.as_context_mut(), ptr, response)?;
+                    Ok(i32::try_from(response.len()).unwrap_or(i32::MAX))
+                })?;
+                drop(state);
+
+                results[0] = Value::I32(read);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let close = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("close")
+                .param("handle", ValueType::I32)
+                .result("errno", ValueType::I32)
+                .build(),
+            move |_ctx, args, results| {
+                let [Value::I32(handle)] = args else {
+                    anyhow::bail!("http.close called with invalid arguments {args:?}");
+                };
+
+                let request = lock(&state).requests.remove(handle);
+                results[0] = Value::I32(match request {
+                    Some(_) => 0,
+                    None => Self::UNKNOWN_HANDLE,
+                });
+
+                Ok(())
+            },
+        );
+
+        for (name, func) in [
+            ("request", request),
+            ("poll", poll),
+            ("response_len", response_len),
+            ("read_response", read_response),
+            ("close", close),
+        ] {
+            imports.define(Self::MODULE, name, Extern::Func(func));
+        }
+    }
+
+    /// Binds the import set to the guest `memory` from which requests are
+    /// read and into which responses are written.
+    pub fn bind_memory(&self, memory: &Memory) {
+        lock(&self.state).memory = Some(memory.clone());
+    }
+
+    /// Binds the import set to the memory that the `instance` exports under
+    /// the `name`, e.g. `"memory"`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `instance` does not export a memory with the
+    /// `name`.
+    pub fn bind_instance(
+        &self,
+        ctx: impl AsContext<Engine>,
+        instance: &Instance,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let memory = instance.get_memory(ctx, name)?;
+
+        self.bind_memory(&memory);
+
+        Ok(())
+    }
+
+    /// Returns a JavaScript promise that resolves once all open requests
+    /// have completed, which can be awaited from async Python code between
+    /// guest calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the promise cannot be created.
+    pub fn settled(&self, py: Python) -> Result<Py<PyAny>, PyErr> {
+        let state = lock(&self.state);
+
+        let requests = state
+            .requests
+            .values()
+            .map(|request| request.bind(py).getattr(intern!(py, "done")))
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(state);
+
+        Ok(js_promise_all(py)?.call1((requests,))?.unbind())
+    }
+}
+
+impl HttpState {
+    fn memory(&self) -> anyhow::Result<Memory> {
+        self.memory
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("http imports called before the guest memory was bound"))
+    }
+
+    /// Starts the request if the policy allows it and returns its handle
+    fn request(&mut self, method: &str, url: &str, body: &[u8]) -> anyhow::Result<i32> {
+        if !self.policy.allows(method, url) {
+            return Ok(HttpImports::DENIED);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(method, url, body_len = body.len(), "guest HTTP request");
+
+        let request = Python::with_gil(|py| -> Result<Py<PyAny>, PyErr> {
+            let body = if body.is_empty() {
+                py.None().into_bound(py)
+            } else {
+                let array = js_uint8_array_new(py)?.call1((body.len(),))?;
+                array.call_method1(intern!(py, "assign"), (body,))?;
+                array
+            };
+
+            Ok(js_http_request(py)?.call1((method, url, body))?.unbind())
+        })?;
+
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.checked_add(1).unwrap_or(0);
+        self.requests.insert(handle, request);
+
+        Ok(handle)
+    }
+
+    /// Returns the status of the request with the `handle`
+    fn poll(&self, handle: i32) -> anyhow::Result<i32> {
+        let Some(request) = self.requests.get(&handle) else {
+            return Ok(HttpImports::UNKNOWN_HANDLE);
+        };
+
+        Python::with_gil(|py| {
+            let request = request.bind(py);
+
+            if !request.getattr(intern!(py, "error"))?.is_none() {
+                return Ok(HttpImports::FAILED);
+            }
+
+            let status = request.getattr(intern!(py, "status"))?;
+            if status.is_none() {
+                return Ok(HttpImports::PENDING);
+            }
+
+            let len: usize = request
+                .getattr(intern!(py, "body"))?
+                .getattr(intern!(py, "length"))?
+                .extract()?;
+            if self.policy.max_response_len.is_some_and(|max| len > max) {
+                return Ok(HttpImports::TOO_LARGE);
+            }
+
+            Ok(status.extract()?)
+        })
+    }
+
+    /// Calls `f` with the response body of the completed request with the
+    /// `handle`
+    fn with_response(
+        &self,
+        handle: i32,
+        f: impl FnOnce(&[u8]) -> anyhow::Result<i32>,
+    ) -> anyhow::Result<i32> {
+        if self.poll(handle)? < 100 {
+            return Ok(HttpImports::UNKNOWN_HANDLE);
+        }
+
+        let Some(request) = self.requests.get(&handle) else {
+            return Ok(HttpImports::UNKNOWN_HANDLE);
+        };
+
+        let body = Python::with_gil(|py| -> Result<Vec<u8>, PyErr> {
+            let body: Bound<PyBytes> = request
+                .bind(py)
+                .getattr(intern!(py, "body"))?
+                .call_method0(intern!(py, "to_bytes"))?
+                .extract()?;
+            Ok(body.as_bytes().to_vec())
+        })?;
+
+        f(&body)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The policy that the HTTP requests of guests must satisfy, see
+/// [`HttpImports`].
+///
+/// The default policy denies all requests.
+pub struct HttpPolicy {
+    /// The allowed origins, or `None` if all origins are allowed
+    origins: Option<Vec<String>>,
+    /// The allowed upper-case methods
+    methods: Vec<String>,
+    /// The maximum length of a response body, if limited
+    max_response_len: Option<usize>,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpPolicy {
+    /// Creates a new policy that denies all requests.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            origins: Some(Vec::new()),
+            methods: Vec::new(),
+            max_response_len: None,
+        }
+    }
+
+    /// Allows requests to the `origin`, e.g. `"https://example.com"`.
+    #[must_use]
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        if let Some(origins) = &mut self.origins {
+            origins.push(origin.trim_end_matches('/').to_ascii_lowercase());
+        }
+        self
+    }
+
+    /// Allows requests to any origin.
+    #[must_use]
+    pub fn allow_any_origin(mut self) -> Self {
+        self.origins = None;
+        self
+    }
+
+    /// Allows requests with the HTTP `method`, e.g. `"GET"`.
+    #[must_use]
+    pub fn allow_method(mut self, method: &str) -> Self {
+        self.methods.push(method.to_ascii_uppercase());
+        self
+    }
+
+    /// Limits the length of response bodies that guests can read to
+    /// `max_response_len` bytes.
+    #[must_use]
+    pub const fn max_response_len(mut self, max_response_len: usize) -> Self {
+        self.max_response_len = Some(max_response_len);
+        self
+    }
+
+    /// Checks whether a request with the `method` to the `url` is allowed
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn allows(&self, method: &str, url: &str) -> bool {
+        self.denial(method, url).map_or(true, |reason| {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(method, url, reason, "denied guest HTTP request");
+
+            false
+        })
+    }
+
+    /// Returns the reason why a request with the `method` to the `url` is
+    /// denied, if it is
+    fn denial(&self, method: &str, url: &str) -> Option<&'static str> {
+        if !self
+            .methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method))
+        {
+            return Some("method is not allowed");
+        }
+
+        let Some(origins) = &self.origins else {
+            return None;
+        };
+
+        let Some(origin) = url_origin(url) else {
+            return Some("url is not absolute");
+        };
+
+        if origins.contains(&origin) {
+            None
+        } else {
+            Some("origin is not allowed")
+        }
+    }
+}
+
+/// Returns the lower-case origin, i.e. `scheme://host[:port]`, of the
+/// absolute `url`
+pub fn url_origin(url: &str) -> Option<String> {
+    let scheme_end = url.find("://")? + 3;
+    let origin_end = url[scheme_end..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |end| scheme_end + end);
+
+    Some(url[..origin_end].to_ascii_lowercase())
+}
+
+fn lock(state: &Mutex<HttpState>) -> MutexGuard<'_, HttpState> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reads the bytes at `ptr..ptr+len` from the guest `memory`
+pub fn read_bytes(
+    memory: &Memory,
+    ctx: &impl AsContext<Engine>,
+    ptr: &Value<Engine>,
+    len: &Value<Engine>,
+) -> anyhow::Result<Vec<u8>> {
+    let (Value::I32(ptr), Value::I32(len)) = (ptr, len) else {
+        anyhow::bail!("guest pointer and length must be i32s, found {ptr:?} and {len:?}");
+    };
+
+    #[allow(clippy::cast_sign_loss)]
+    let (ptr, len) = (*ptr as u32 as usize, *len as u32 as usize);
+
+    let mut bytes = vec![0; len];
+    memory.read(ctx.as_context(), ptr, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Reads the UTF-8 string at `ptr..ptr+len` from the guest `memory`
+pub fn read_string(
+    memory: &Memory,
+    ctx: &impl AsContext<Engine>,
+    ptr: &Value<Engine>,
+    len: &Value<Engine>,
+) -> anyhow::Result<String> {
+    Ok(String::from_utf8(read_bytes(memory, ctx, ptr, len)?)?)
+}
+
+fn js_http_request(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_HTTP_REQUEST: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_HTTP_REQUEST
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function httpRequest(method, url, body) {
+    const request = { status: null, body: null, error: null };
+    request.done = fetch(url, { method, body }).then(async (response) => {
+        request.body = new Uint8Array(await response.arrayBuffer());
+        request.status = response.status;
+    }).catch((error) => {
+        request.error = String(error);
+    });
+    return request;
+}
+httpRequest
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+fn js_promise_all(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_PROMISE_ALL: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_PROMISE_ALL.import(py, "js.Promise", "all")
+}
@@ -1,15 +1,29 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    error::Error as StdError,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+};
 
+use anyhow::Context;
 use fxhash::FxHashMap;
-use pyo3::{intern, prelude::*, sync::GILOnceCell};
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyList};
 use wasm_runtime_layer::{
-    backend::{AsContext, AsContextMut, Export, Extern, Imports, WasmInstance, WasmModule},
-    ExportType, ExternType,
+    backend::{
+        AsContext, AsContextMut, Export, Extern, Imports, WasmInstance, WasmModule,
+        WasmStoreContext,
+    },
+    ExportType, ExternType, ImportType,
 };
 
 use crate::{
-    conversion::{create_js_object, ToPy},
-    Engine, Func, Global, Memory, Module, Table,
+    conversion::{create_js_object, instanceof, ToPy},
+    interrupt::{EPOCH_CHECK_MODULE, EPOCH_CHECK_NAME},
+    module::{StartFunction, DEFERRED_START_EXPORT},
+    store::{CallHook, StoreContextMut},
+    tag::js_exception_to_error,
+    wasi::apply_wasi_import_policy,
+    Capabilities, Engine, Error, Func, Global, Memory, Module, Table, Tag,
 };
 
 /// An instantiated instance of a WASM [`Module`].
@@ -17,81 +31,776 @@ use crate::{
 /// This type wraps a [`WebAssembly.Instance`] from the JavaScript API.
 ///
 /// [`WebAssembly.Instance`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Instance
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Instance {
     /// The inner instance
-    instance: Py<PyAny>,
-    /// The exports of the instance
-    exports: Arc<FxHashMap<String, Extern<Engine>>>,
+    instance: Arc<Py<PyAny>>,
+    /// The exports of the instance, which are materialized lazily
+    exports: Arc<LazyExports>,
+    /// The deferred start function, until it is run
+    start: Arc<Mutex<Option<Py<PyAny>>>>,
 }
 
-impl Clone for Instance {
-    fn clone(&self) -> Self {
-        Python::with_gil(|py| Self {
-            instance: self.instance.clone_ref(py),
-            exports: self.exports.clone(),
-        })
+impl WasmInstance<Engine> for Instance {
+    fn new(
+        store: impl AsContextMut<Engine>,
+        module: &Module,
+        imports: &Imports<Engine>,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_options(store, module, imports, &InstanceOptions::new())
+    }
+
+    fn exports(&self, _store: impl AsContext<Engine>) -> Box<dyn Iterator<Item = Export<Engine>>> {
+        Box::new(
+            self.exports
+                .module
+                .exports()
+                .filter_map(|ExportType { name, ty }| {
+                    match self.exports.get_or_materialize(name, ty) {
+                        Ok(value) => Some(Export {
+                            name: String::from(name),
+                            value,
+                        }),
+                        Err(err) => {
+                            #[cfg(feature = "tracing")]
+                            tracing::error!(name, "Instance::exports failed: {err:?}");
+                            #[cfg(not(feature = "tracing"))]
+                            let _ = err;
+
+                            None
+                        },
+                    }
+                })
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    fn get_export(&self, store: impl AsContext<Engine>, name: &str) -> Option<Extern<Engine>> {
+        match self.try_get_export(store, name) {
+            Ok(export) => export,
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::error!(name, "Instance::get_export failed: {err:?}");
+                #[cfg(not(feature = "tracing"))]
+                let _ = err;
+
+                None
+            },
+        }
     }
 }
 
-impl WasmInstance<Engine> for Instance {
-    fn new(
-        _store: impl AsContextMut<Engine>,
+impl Instance {
+    /// Instantiates the `module` with the `imports` in the `store`, using
+    /// the provided instantiation `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instantiation fails, e.g. because the
+    /// `imports` do not satisfy the imports of the `module`, because they
+    /// contain host funcs from a different store, or because its start
+    /// function traps.
+    ///
+    /// Missing imports, import type mismatches, link errors, and traps are
+    /// reported as an [`InstantiationError`], which the returned error can
+    /// be downcast into.
+    ///
+    /// Deferring the start function with [`InstanceOptions::defer_start`]
+    /// fails if the module has a start function that is not deferrable, see
+    /// [`Module::has_deferrable_start`].
+    pub fn new_with_options(
+        mut store: impl AsContextMut<Engine>,
         module: &Module,
         imports: &Imports<Engine>,
+        options: &InstanceOptions,
     ) -> anyhow::Result<Self> {
-        Python::with_gil(|py| {
+        let mut store: StoreContextMut<_> = store.as_context_mut();
+
+        // externs from other backends are ruled out by the type system, but
+        //  host funcs from other stores must be detected at runtime
+        for (module_name, name, import) in imports.iter() {
+            if let Extern::Func(func) = import {
+                func.check_store(store.proof_mut()).map_err(|reason| {
+                    Error::StoreMismatch(format!(
+                        "import `{module_name}`.`{name}` {reason}, but host funcs can only be \
+                         imported by instances in the store that they were created in"
+                    ))
+                })?;
+            }
+        }
+
+        let instance = Python::with_gil(|py| -> anyhow::Result<Self> {
             #[cfg(feature = "tracing")]
-            let _span = tracing::debug_span!("Instance::new").entered();
+            let _span = tracing::debug_span!("Instance::new", ?options).entered();
+
+            let imports_object = create_imports_object(py, imports, module, options)?;
+            if module.has_epoch_interruption() {
+                let check = store.epoch_state(py)?.getattr(intern!(py, "check"))?;
+                let checks = create_js_object(py)?;
+                checks.setattr(EPOCH_CHECK_NAME, check)?;
+                imports_object.setattr(EPOCH_CHECK_MODULE, checks)?;
+            }
+            let wasi_import_policy = store.engine().config().wasi_import_policy().clone();
+            let strict_exports = store.engine().config().strict_exports();
+            let require_type_reflection = store.engine().config().require_type_reflection();
+
+            if require_type_reflection && !Capabilities::detect(py)?.js_types() {
+                return Err(Error::EnvironmentUnavailable(String::from(
+                    "the WebAssembly type reflection (js-types) proposal is required by \
+                     EngineConfig::with_require_type_reflection but not supported",
+                ))
+                .into());
+            }
+
+            apply_wasi_import_policy(
+                py,
+                &mut store,
+                module,
+                imports,
+                &imports_object,
+                &wasi_import_policy,
+            )?;
+
+            check_imports(&store, module, imports, &imports_object)?;
 
-            let imports_object = create_imports_object(py, imports)?;
+            if options.defer_start && module.start() == StartFunction::Immediate {
+                return Err(Error::Link(String::from(
+                    "the start function of the module cannot be deferred since it was not \
+                     compiled with EngineConfig::with_deferrable_start",
+                ))
+                .into());
+            }
 
-            let instance =
-                web_assembly_instance_new(py)?.call1((module.module(py), imports_object))?;
+            // the constructor runs an immediate start function
+            let constructor = web_assembly_instance_new(py)
+                .map_err(|err| Error::EnvironmentUnavailable(err.to_string()))?;
+            let borrow = store.borrow_for_call();
+            let instance = constructor.call1((module.module(py), imports_object));
+            drop(borrow);
+
+            let instance = instance.map_err(|err| {
+                let err = js_exception_to_error(py, &err, Error::Link);
+                let context = match err.downcast_ref::<Error>() {
+                    Some(Error::Link(message)) => InstantiationError::Link(message.clone()),
+                    _ => InstantiationError::Trap(err.to_string()),
+                };
+                err.context(context)
+            })?;
 
             let exports = instance.getattr(intern!(py, "exports"))?;
-            let exports = process_exports(&exports, module)?;
+
+            let mut start = None;
+            if module.start() == StartFunction::Deferrable {
+                let func = exports.getattr(DEFERRED_START_EXPORT)?;
+
+                if options.defer_start {
+                    start = Some(func.unbind());
+                } else {
+                    call_start(&mut store, &func)?;
+                }
+            }
+
+            if (strict_exports || require_type_reflection) && !module.has_degraded_reflection() {
+                verify_exports(module, &exports)?;
+            }
+
+            let exports = LazyExports {
+                object: exports.unbind(),
+                module: module.clone(),
+                cache: Mutex::new(FxHashMap::default()),
+            };
 
             Ok(Self {
-                instance: instance.unbind(),
+                instance: Arc::new(instance.unbind()),
                 exports: Arc::new(exports),
+                start: Arc::new(Mutex::new(start)),
+            })
+        })?;
+
+        store.register_instance(&instance);
+
+        Ok(instance)
+    }
+}
+
+impl Instance {
+    /// Returns the export with the `name`, or [`None`] if the module has no
+    /// such export.
+    ///
+    /// Exports are only wrapped when they are first accessed. Unlike
+    /// [`Instance::get_export`], which can only report missing exports, this
+    /// method returns the error if an export cannot be wrapped, e.g. since
+    /// the JavaScript instance does not match the module, instead of only
+    /// logging it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JavaScript export does not match the type of
+    /// the export in the module.
+    ///
+    /// [`Instance::get_export`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Instance.html#method.get_export
+    pub fn try_get_export(
+        &self,
+        _store: impl AsContext<Engine>,
+        name: &str,
+    ) -> anyhow::Result<Option<Extern<Engine>>> {
+        let Some(ty) = self.exports.module.get_export(name) else {
+            return Ok(None);
+        };
+
+        self.exports.get_or_materialize(name, ty).map(Some)
+    }
+
+    /// Returns all exports of the instance.
+    ///
+    /// Unlike [`Instance::exports`], which skips exports that cannot be
+    /// wrapped, this method returns the first such error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any JavaScript export does not match the type of
+    /// the export in the module.
+    ///
+    /// [`Instance::exports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Instance.html#method.exports
+    pub fn try_exports(
+        &self,
+        _store: impl AsContext<Engine>,
+    ) -> anyhow::Result<Vec<Export<Engine>>> {
+        self.exports
+            .module
+            .exports()
+            .map(|ExportType { name, ty }| {
+                Ok(Export {
+                    name: String::from(name),
+                    value: self.exports.get_or_materialize(name, ty)?,
+                })
             })
+            .collect()
+    }
+
+    /// Returns the underlying [`WebAssembly.Instance`], e.g. to pass it to
+    /// hand-written JavaScript glue code.
+    ///
+    /// [`WebAssembly.Instance`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Instance
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.instance.clone_ref(py)
+    }
+
+    /// Returns a weak handle to this instance, which does not keep it alive
+    pub(crate) fn downgrade(&self) -> WeakInstance {
+        WeakInstance {
+            instance: Arc::downgrade(&self.instance),
+            exports: Arc::downgrade(&self.exports),
+            start: Arc::downgrade(&self.start),
+        }
+    }
+
+    /// Wraps the existing JavaScript `instance` of the `module`, e.g. one
+    /// that was instantiated by hand-written JavaScript glue code, and
+    /// registers it with the `store`.
+    ///
+    /// The exports of the instance are typed using the `module`. If the
+    /// module has a deferrable start function, it is assumed to have already
+    /// been run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `instance` is not a [`WebAssembly.Instance`] or
+    /// if it does not provide all exports of the `module`. Exports whose
+    /// kinds do not match are only detected when they are first accessed,
+    /// see [`Instance::try_get_export`].
+    ///
+    /// [`WebAssembly.Instance`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Instance
+    pub fn from_js(
+        mut store: impl AsContextMut<Engine>,
+        instance: &Bound<PyAny>,
+        module: &Module,
+    ) -> anyhow::Result<Self> {
+        let py = instance.py();
+
+        if !instanceof(instance, web_assembly_instance(py)?, "WebAssembly.Instance")? {
+            return Err(Error::Conversion(format!(
+                "expected WebAssembly.Instance but found {instance:?}"
+            ))
+            .into());
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(%instance, "Instance::from_js");
+
+        let exports = instance.getattr(intern!(py, "exports"))?;
+
+        for ExportType { name, .. } in module.exports() {
+            if !exports.hasattr(name)? {
+                return Err(Error::Conversion(format!(
+                    "the JavaScript instance does not provide the export `{name}` of the module"
+                ))
+                .into());
+            }
+        }
+
+        let exports = LazyExports {
+            object: exports.unbind(),
+            module: module.clone(),
+            cache: Mutex::new(FxHashMap::default()),
+        };
+
+        let instance = Self {
+            instance: Arc::new(instance.clone().unbind()),
+            exports: Arc::new(exports),
+            start: Arc::new(Mutex::new(None)),
+        };
+
+        let mut store: StoreContextMut<_> = store.as_context_mut();
+        store.register_instance(&instance);
+
+        Ok(instance)
+    }
+
+    /// Runs the start function of this instance if it was deferred using
+    /// [`InstanceOptions::defer_start`] and has not been run yet.
+    ///
+    /// Returns whether the start function was run. It is not run again, even
+    /// if it trapped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the start function traps, which is reported as an
+    /// [`InstantiationError::Trap`].
+    pub fn run_start(&self, mut ctx: impl AsContextMut<Engine>) -> anyhow::Result<bool> {
+        let Some(start) = self
+            .start
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return Ok(false);
+        };
+
+        Python::with_gil(|py| call_start(&mut ctx.as_context_mut(), start.bind(py)))?;
+
+        Ok(true)
+    }
+
+    /// Appends the JavaScript memories and tables that this instance exports
+    /// to the `memories` and `tables` lists, without materializing its other
+    /// exports
+    pub(crate) fn collect_js_memories_and_tables(
+        &self,
+        memories: &Bound<PyList>,
+        tables: &Bound<PyList>,
+    ) -> Result<(), PyErr> {
+        let exports = self.exports.object.bind(memories.py());
+
+        for ExportType { name, ty } in self.exports.module.exports() {
+            match ty {
+                ExternType::Memory(_) => memories.append(exports.getattr(name)?)?,
+                ExternType::Table(_) => tables.append(exports.getattr(name)?)?,
+                ExternType::Func(_) | ExternType::Global(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the function export with the `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no export with the `name` or if it is
+    /// not a function.
+    pub fn get_func(&self, ctx: impl AsContext<Engine>, name: &str) -> anyhow::Result<Func> {
+        match self.get_typed_export(ctx, name, "Func")? {
+            Extern::Func(func) => Ok(func),
+            export => Err(export_kind_mismatch(name, &export, "Func")),
+        }
+    }
+
+    /// Returns the global export with the `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no export with the `name` or if it is
+    /// not a global.
+    pub fn get_global(&self, ctx: impl AsContext<Engine>, name: &str) -> anyhow::Result<Global> {
+        match self.get_typed_export(ctx, name, "Global")? {
+            Extern::Global(global) => Ok(global),
+            export => Err(export_kind_mismatch(name, &export, "Global")),
+        }
+    }
+
+    /// Returns the memory export with the `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no export with the `name` or if it is
+    /// not a memory.
+    pub fn get_memory(&self, ctx: impl AsContext<Engine>, name: &str) -> anyhow::Result<Memory> {
+        match self.get_typed_export(ctx, name, "Memory")? {
+            Extern::Memory(memory) => Ok(memory),
+            export => Err(export_kind_mismatch(name, &export, "Memory")),
+        }
+    }
+
+    /// Returns the table export with the `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no export with the `name` or if it is
+    /// not a table.
+    pub fn get_table(&self, ctx: impl AsContext<Engine>, name: &str) -> anyhow::Result<Table> {
+        match self.get_typed_export(ctx, name, "Table")? {
+            Extern::Table(table) => Ok(table),
+            export => Err(export_kind_mismatch(name, &export, "Table")),
+        }
+    }
+
+    /// Returns the tag export with the `name`.
+    ///
+    /// Tag exports are not included in [`Instance::exports`] since the
+    /// [`wasm_runtime_layer`] API has no tag externs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no tag export with the `name`.
+    ///
+    /// [`Instance::exports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Instance.html#method.exports
+    /// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+    pub fn get_tag(&self, _ctx: impl AsContext<Engine>, name: &str) -> anyhow::Result<Tag> {
+        let params = self
+            .exports
+            .module
+            .get_tag_export(name)
+            .ok_or_else(|| anyhow::anyhow!("instance has no tag export named `{name}`"))?;
+
+        Python::with_gil(|py| {
+            Tag::from_exported_tag(self.exports.object.bind(py).getattr(name)?, params)
         })
     }
 
-    fn exports(&self, _store: impl AsContext<Engine>) -> Box<dyn Iterator<Item = Export<Engine>>> {
-        Box::new(
-            self.exports
-                .iter()
-                .map(|(name, value)| Export {
-                    name: name.into(),
-                    value: value.clone(),
+    /// Returns the export with the `name`, which is expected to be of the
+    /// `expected` kind
+    fn get_typed_export(
+        &self,
+        ctx: impl AsContext<Engine>,
+        name: &str,
+        expected: &str,
+    ) -> anyhow::Result<Extern<Engine>> {
+        self.get_export(ctx, name).ok_or_else(|| {
+            anyhow::anyhow!("instance has no export named `{name}`, expected {expected}")
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+/// Structured error that is returned when instantiating a [`Module`] fails.
+///
+/// The error is returned as an [`anyhow::Error`] that can be downcast into
+/// this type. Missing imports and import type mismatches are detected by
+/// cross-checking [`Module::imports`] against the provided imports before
+/// the module is handed to JavaScript. The categorised [`Error`], or the
+/// uncaught [`Exception`](crate::Exception), remains available as the source
+/// of the error.
+///
+/// [`Module::imports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Module.html#method.imports
+pub enum InstantiationError {
+    /// The import with the `name` from the `module` was not provided
+    MissingImport {
+        /// The module name of the import
+        module: String,
+        /// The name of the import
+        name: String,
+        /// The type that the module expects
+        expected: ExternType,
+    },
+    /// The import with the `name` from the `module` was provided with a type
+    /// that does not match the type expected by the module
+    ImportTypeMismatch {
+        /// The module name of the import
+        module: String,
+        /// The name of the import
+        name: String,
+        /// The type that the module expects
+        expected: ExternType,
+        /// The type of the provided import
+        found: ExternType,
+    },
+    /// The module could not be linked for another reason, i.e. a
+    /// `WebAssembly.LinkError`
+    Link(String),
+    /// The start function of the module trapped or threw an exception
+    Trap(String),
+}
+
+impl fmt::Display for InstantiationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingImport {
+                module,
+                name,
+                expected,
+            } => write!(
+                fmt,
+                "missing import `{module}`.`{name}`, expected {expected:?}"
+            ),
+            Self::ImportTypeMismatch {
+                module,
+                name,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "import `{module}`.`{name}` has the type {found:?}, expected {expected:?}"
+            ),
+            Self::Link(message) => write!(fmt, "failed to link the module: {message}"),
+            Self::Trap(message) => write!(fmt, "the start function failed: {message}"),
+        }
+    }
+}
+
+impl StdError for InstantiationError {}
+
+/// Cross-checks the imports of the `module` against the provided `imports`
+/// and the final JavaScript `imports_object`, which also contains tags and
+/// stubs
+///
+/// The types are only compared if they are known precisely, i.e. not for
+/// modules with degraded reflection or for imports that use `v128` values.
+fn check_imports<T>(
+    store: &StoreContextMut<T>,
+    module: &Module,
+    imports: &Imports<Engine>,
+    imports_object: &Bound<PyAny>,
+) -> anyhow::Result<()> {
+    for ImportType {
+        module: module_name,
+        name,
+        ty: expected,
+    } in module.imports()
+    {
+        let provided = imports_object
+            .getattr(module_name)
+            .ok()
+            .filter(|namespace| !namespace.is_none())
+            .map_or(Ok(false), |namespace| namespace.hasattr(name))?;
+
+        if !provided {
+            let err = InstantiationError::MissingImport {
+                module: String::from(module_name),
+                name: String::from(name),
+                expected,
+            };
+            return Err(anyhow::Error::new(Error::Link(err.to_string())).context(err));
+        }
+
+        if module.has_degraded_reflection() || module.is_v128_import(module_name, name) {
+            continue;
+        }
+
+        let Some(import) = imports.get_export(module_name, name) else {
+            continue;
+        };
+
+        // funcrefs of unknown type report the type `func()` until they have
+        //  learned their type, so they are left to the JavaScript linker
+        if let Extern::Func(func) = &import {
+            if !func.has_known_type() {
+                continue;
+            }
+        }
+
+        let found = import.ty(store.as_context());
+
+        if !is_import_type_compatible(&expected, &found) {
+            let err = InstantiationError::ImportTypeMismatch {
+                module: String::from(module_name),
+                name: String::from(name),
+                expected,
+                found,
+            };
+            return Err(anyhow::Error::new(Error::Link(err.to_string())).context(err));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks if an import of the `found` type can be provided where the module
+/// expects the `expected` type, following the import subtyping rules
+fn is_import_type_compatible(expected: &ExternType, found: &ExternType) -> bool {
+    let is_limit_compatible =
+        |expected_min: u32, expected_max: Option<u32>, found_min: u32, found_max: Option<u32>| {
+            found_min >= expected_min
+                && expected_max.map_or(true, |expected_max| {
+                    found_max.is_some_and(|found_max| found_max <= expected_max)
                 })
-                .collect::<Vec<_>>()
-                .into_iter(),
-        )
+        };
+
+    match (expected, found) {
+        (ExternType::Func(expected), ExternType::Func(found)) => expected == found,
+        (ExternType::Global(expected), ExternType::Global(found)) => expected == found,
+        (ExternType::Memory(expected), ExternType::Memory(found)) => is_limit_compatible(
+            expected.initial_pages(),
+            expected.maximum_pages(),
+            found.initial_pages(),
+            found.maximum_pages(),
+        ),
+        (ExternType::Table(expected), ExternType::Table(found)) => {
+            expected.element() == found.element()
+                && is_limit_compatible(
+                    expected.minimum(),
+                    expected.maximum(),
+                    found.minimum(),
+                    found.maximum(),
+                )
+        },
+        _ => false,
+    }
+}
+
+/// Creates the error for an `export` with the `name` that is not of the
+/// `expected` kind
+fn export_kind_mismatch(name: &str, export: &Extern<Engine>, expected: &str) -> anyhow::Error {
+    let found = match export {
+        Extern::Func(_) => "Func",
+        Extern::Global(_) => "Global",
+        Extern::Memory(_) => "Memory",
+        Extern::Table(_) => "Table",
+    };
+
+    anyhow::anyhow!("export `{name}` is a {found}, expected {expected}")
+}
+
+#[derive(Debug, Clone, Default)]
+/// Options to configure the instantiation of a [`Module`] with
+/// [`Instance::new_with_options`].
+pub struct InstanceOptions {
+    /// Only pass on the imports that the module requires
+    filter_imports: bool,
+    /// Provide trapping stubs for unused function imports that are missing
+    stub_unused_imports: bool,
+    /// Tags that are imported in addition to the externs
+    tags: Vec<(String, String, Tag)>,
+    /// Defer the start function until [`Instance::run_start`]
+    defer_start: bool,
+}
+
+impl InstanceOptions {
+    /// Creates the default instantiation options.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            filter_imports: false,
+            stub_unused_imports: false,
+            tags: Vec::new(),
+            defer_start: false,
+        }
+    }
+
+    /// Configures whether the provided imports are filtered down to exactly
+    /// those that the module requires.
+    ///
+    /// Filtering allows a generic bundle of imports to be reused across
+    /// several modules without converting and passing on unused imports.
+    #[must_use]
+    pub const fn filter_imports(mut self, filter_imports: bool) -> Self {
+        self.filter_imports = filter_imports;
+        self
     }
 
-    fn get_export(&self, _store: impl AsContext<Engine>, name: &str) -> Option<Extern<Engine>> {
-        self.exports.get(name).cloned()
+    /// Configures whether function imports that are missing from the
+    /// provided imports but never used by the module, see
+    /// [`Module::is_import_used`], are replaced by stubs that trap when
+    /// called.
+    ///
+    /// Stubbing allows hosts to skip constructing host functions for
+    /// imports that the module declares but never references.
+    #[must_use]
+    pub const fn stub_unused_imports(mut self, stub_unused_imports: bool) -> Self {
+        self.stub_unused_imports = stub_unused_imports;
+        self
+    }
+
+    /// Configures whether the start function of the module is deferred
+    /// until [`Instance::run_start`] is called, e.g. to instantiate a module
+    /// eagerly but delay the heavy work of its start function.
+    ///
+    /// Deferring requires the module to be compiled with
+    /// [`EngineConfig::with_deferrable_start`].
+    ///
+    /// [`EngineConfig::with_deferrable_start`]: crate::EngineConfig::with_deferrable_start
+    #[must_use]
+    pub const fn defer_start(mut self, defer_start: bool) -> Self {
+        self.defer_start = defer_start;
+        self
+    }
+
+    /// Provides the `tag` as the import with the `name` from the `module`.
+    ///
+    /// Tags cannot be passed as part of the [`Imports`] since the
+    /// [`wasm_runtime_layer`] API has no tag externs, see
+    /// [`Module::tag_imports`].
+    ///
+    /// [`Imports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Imports.html
+    /// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+    #[must_use]
+    pub fn tag_import(mut self, module: &str, name: &str, tag: Tag) -> Self {
+        self.tags
+            .push((String::from(module), String::from(name), tag));
+        self
     }
 }
 
+/// Calls the deferrable `start` function of an instance, reporting traps as
+/// an [`InstantiationError::Trap`]
+fn call_start<T>(store: &mut StoreContextMut<T>, start: &Bound<PyAny>) -> anyhow::Result<()> {
+    let py = start.py();
+
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("call_start").entered();
+
+    store.call_hook(CallHook::CallingWasm)?;
+    let borrow = store.borrow_for_call();
+
+    let result = start.call0().map(drop).map_err(|err| {
+        let err = js_exception_to_error(py, &err, Error::Trap);
+        let context = InstantiationError::Trap(err.to_string());
+        err.context(context)
+    });
+
+    drop(borrow);
+    let returned = store.call_hook(CallHook::ReturningFromWasm);
+
+    result.and(returned)
+}
+
 /// Creates the js import map
+///
+/// Depending on the `options`, the imports are filtered down to those that
+/// the `module` requires, and missing unused function imports are stubbed.
 fn create_imports_object<'py>(
     py: Python<'py>,
     imports: &Imports<Engine>,
+    module: &Module,
+    options: &InstanceOptions,
 ) -> Result<Bound<'py, PyAny>, PyErr> {
     #[cfg(feature = "tracing")]
     let _span = tracing::debug_span!("process_imports").entered();
 
-    let imports = imports
+    let mut imports = imports
         .iter()
+        .filter(|(module_name, name, _)| {
+            !options.filter_imports || module.requires_import(module_name, name)
+        })
         .map(|(module, name, import)| -> Result<_, PyErr> {
             #[cfg(feature = "tracing")]
             tracing::trace!(?module, ?name, ?import, "import");
 
-            let import = import.to_py(py);
+            let import = import.to_py(py).into_bound(py);
 
             #[cfg(feature = "tracing")]
             tracing::trace!(module, name, "export");
@@ -105,57 +814,212 @@ fn create_imports_object<'py>(
                 acc.entry(module).or_default().push(value);
                 Ok(acc)
             },
-        )?
-        .into_iter()
-        .try_fold(
-            create_js_object(py)?,
-            |acc, (module, imports)| -> Result<_, PyErr> {
-                let obj = create_js_object(py)?;
-                for (name, import) in imports {
-                    obj.setattr(name, import)?;
-                }
-                acc.setattr(module, obj)?;
-                Ok(acc)
-            },
         )?;
 
+    for (module_name, name, tag) in &options.tags {
+        if options.filter_imports
+            && !module.tag_imports().any(|(import_module, import_name, _)| {
+                import_module == module_name && import_name == name
+            })
+        {
+            continue;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(module_name, name, "tag import");
+
+        imports
+            .entry(module_name)
+            .or_default()
+            .push((name, tag.to_py(py).into_bound(py)));
+    }
+
+    if options.stub_unused_imports {
+        for ImportType {
+            module: module_name,
+            name,
+            ..
+        } in module.unused_imports()
+        {
+            let provided = imports
+                .get(module_name)
+                .is_some_and(|imports| imports.iter().any(|(import, _)| *import == name));
+
+            if !provided {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(module_name, name, "stubbing unused import");
+
+                let stub = js_unused_import_stub(py)?.call1((module_name, name))?;
+                imports.entry(module_name).or_default().push((name, stub));
+            }
+        }
+    }
+
+    let imports = imports.into_iter().try_fold(
+        create_js_object(py)?,
+        |acc, (module, imports)| -> Result<_, PyErr> {
+            let obj = create_js_object(py)?;
+            for (name, import) in imports {
+                obj.setattr(name, import)?;
+            }
+            acc.setattr(module, obj)?;
+            Ok(acc)
+        },
+    )?;
+
     Ok(imports)
 }
 
-/// Processes a wasm module's exports into a hashmap
-fn process_exports(
+#[derive(Debug)]
+/// A weak handle to an [`Instance`], which does not keep it alive
+pub struct WeakInstance {
+    /// The inner instance
+    instance: Weak<Py<PyAny>>,
+    /// The exports of the instance
+    exports: Weak<LazyExports>,
+    /// The deferred start function of the instance
+    start: Weak<Mutex<Option<Py<PyAny>>>>,
+}
+
+impl WeakInstance {
+    /// Returns the instance, unless it has already been dropped
+    pub(crate) fn upgrade(&self) -> Option<Instance> {
+        Some(Instance {
+            instance: self.instance.upgrade()?,
+            exports: self.exports.upgrade()?,
+            start: self.start.upgrade()?,
+        })
+    }
+
+    /// Returns `true` if the instance has not yet been dropped
+    pub(crate) fn is_alive(&self) -> bool {
+        self.instance.strong_count() > 0
+    }
+}
+
+#[derive(Debug)]
+/// The exports of an instance, which are only wrapped on first access
+struct LazyExports {
+    /// The JavaScript exports object of the instance
+    object: Py<PyAny>,
+    /// The instantiated module, containing the export signatures
+    module: Module,
+    /// The exports that have already been materialized
+    cache: Mutex<FxHashMap<String, Extern<Engine>>>,
+}
+
+impl LazyExports {
+    /// Returns the export with the `name` and type `ty`, wrapping it on first
+    /// access
+    ///
+    /// Failed materializations are not cached, such that they are reported
+    /// again on every access.
+    fn get_or_materialize(&self, name: &str, ty: ExternType) -> anyhow::Result<Extern<Engine>> {
+        if let Some(export) = self.lock_cache().get(name) {
+            return Ok(export.clone());
+        }
+
+        // materialize without holding the lock to avoid lock-order inversions
+        // with the GIL
+        let v128 = self.module.is_v128_export(name);
+        let export =
+            Python::with_gil(|py| materialize_export(self.object.bind(py), name, ty, v128))
+                .with_context(|| format!("failed to materialize the export `{name}`"))?;
+
+        Ok(self
+            .lock_cache()
+            .entry(String::from(name))
+            .or_insert(export)
+            .clone())
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, FxHashMap<String, Extern<Engine>>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Verifies the parsed types of the `module`'s exports against the
+/// JavaScript `exports` of its instance
+fn verify_exports(module: &Module, exports: &Bound<PyAny>) -> anyhow::Result<()> {
+    for ExportType { name, ty } in module.exports() {
+        let export = exports.getattr(name)?;
+
+        match ty {
+            ExternType::Func(ty) => {
+                Func::verify_exported_function(&export, &format!("exported function `{name}`"), &ty)
+            },
+            ExternType::Global(ty) => {
+                Global::verify_exported_global(&export, &format!("exported global `{name}`"), ty)
+            },
+            ExternType::Memory(ty) => {
+                Memory::verify_exported_memory(&export, &format!("exported memory `{name}`"), ty)
+            },
+            ExternType::Table(ty) => {
+                Table::verify_exported_table(&export, &format!("exported table `{name}`"), ty)
+            },
+        }?;
+    }
+
+    Ok(())
+}
+
+/// Wraps a single wasm module export, which uses `v128` values in its type if
+/// `v128` is set
+fn materialize_export(
     exports: &Bound<PyAny>,
-    module: &Module,
-) -> anyhow::Result<FxHashMap<String, Extern<Engine>>> {
+    name: &str,
+    ty: ExternType,
+    v128: bool,
+) -> anyhow::Result<Extern<Engine>> {
     #[cfg(feature = "tracing")]
-    let _span = tracing::debug_span!("process_exports").entered();
-
-    module
-        .exports()
-        .map(|ExportType { name, ty }| {
-            let export = match ty {
-                ExternType::Func(signature) => Extern::Func(Func::from_exported_function(
-                    exports.getattr(name)?,
-                    signature,
-                )?),
-                ExternType::Global(signature) => Extern::Global(Global::from_exported_global(
-                    exports.getattr(name)?,
-                    signature,
-                )?),
-                ExternType::Memory(ty) => {
-                    Extern::Memory(Memory::from_exported_memory(exports.getattr(name)?, ty)?)
-                },
-                ExternType::Table(ty) => {
-                    Extern::Table(Table::from_exported_table(exports.getattr(name)?, ty)?)
-                },
-            };
+    let _span = tracing::trace_span!("materialize_export", name).entered();
 
-            Ok((String::from(name), export))
-        })
-        .collect()
+    let export = match ty {
+        ExternType::Func(signature) => Extern::Func(Func::from_exported_function(
+            exports.getattr(name)?,
+            signature,
+            v128,
+        )?),
+        ExternType::Global(signature) => Extern::Global(Global::from_exported_global(
+            exports.getattr(name)?,
+            signature,
+        )?),
+        ExternType::Memory(ty) => {
+            Extern::Memory(Memory::from_exported_memory(exports.getattr(name)?, ty)?)
+        },
+        ExternType::Table(ty) => {
+            Extern::Table(Table::from_exported_table(exports.getattr(name)?, ty)?)
+        },
+    };
+
+    Ok(export)
+}
+
+fn web_assembly_instance(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_INSTANCE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_INSTANCE.import(py, "js.WebAssembly", "Instance")
 }
 
-fn web_assembly_instance_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+fn web_assembly_instance_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_INSTANCE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_INSTANCE.import(py, "js.WebAssembly.Instance", "new")
 }
+
+fn js_unused_import_stub(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_UNUSED_IMPORT_STUB: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_UNUSED_IMPORT_STUB
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((
+                    "function unusedImportStub(module, name){ return function(){ throw new \
+                     WebAssembly.RuntimeError(`unused import ${module}.${name} was called`); }; } \
+                     unusedImportStub",
+                ))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
@@ -0,0 +1,918 @@
+use std::ops::Range;
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell};
+use wasmparser::{ElementItems, ExternalKind, Operator, Payload, TypeRef};
+
+use crate::module::encode_leb128;
+
+/// The module name of the hidden import that checks for interruption, see
+/// [`EngineConfig::with_epoch_interruption`]
+///
+/// [`EngineConfig::with_epoch_interruption`]: crate::EngineConfig::with_epoch_interruption
+pub const EPOCH_CHECK_MODULE: &str = "__pyodide_webassembly_runtime_layer";
+
+/// The name of the hidden import that checks for interruption, see
+/// [`EngineConfig::with_epoch_interruption`]
+///
+/// [`EngineConfig::with_epoch_interruption`]: crate::EngineConfig::with_epoch_interruption
+pub const EPOCH_CHECK_NAME: &str = "epoch_check";
+
+#[derive(Debug)]
+/// A handle that interrupts the guest code that is running in a [`Store`],
+/// see [`Store::interrupt_handle`].
+///
+/// The handle sets a flag that the guest code checks whenever it enters a
+/// function or loop, which requires the modules to be compiled with
+/// [`EngineConfig::with_epoch_interruption`]. The running guest call then
+/// fails with a [`Trap`] of the kind [`TrapKind::Interrupt`], after which the
+/// flag is cleared again.
+///
+/// While the guest runs, the thread that runs it is blocked. Other code can
+/// therefore only interrupt the guest from a host function that the guest
+/// calls, or from another thread, e.g. a web worker, if the flag is backed by
+/// a `SharedArrayBuffer`, see [`InterruptHandle::as_js`].
+///
+/// [`Store`]: crate::Store
+/// [`Store::interrupt_handle`]: crate::Store::interrupt_handle
+/// [`EngineConfig::with_epoch_interruption`]: crate::EngineConfig::with_epoch_interruption
+/// [`Trap`]: crate::Trap
+/// [`TrapKind::Interrupt`]: crate::TrapKind::Interrupt
+pub struct InterruptHandle {
+    /// The JavaScript `Int32Array` with the interruption flag
+    flag: Py<PyAny>,
+}
+
+impl Clone for InterruptHandle {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| Self {
+            flag: self.flag.clone_ref(py),
+        })
+    }
+}
+
+impl InterruptHandle {
+    /// Creates a handle for the interruption flag of the epoch `state`
+    pub(crate) fn new(state: &Bound<PyAny>) -> Result<Self, PyErr> {
+        Ok(Self {
+            flag: state.getattr(intern!(state.py(), "flag"))?.unbind(),
+        })
+    }
+
+    /// Interrupts the guest code that is running in the store, or the next
+    /// guest code that runs in it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interruption flag cannot be set.
+    pub fn interrupt(&self) -> anyhow::Result<()> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("InterruptHandle::interrupt");
+
+            js_atomics(py)?.call_method1(intern!(py, "store"), (self.flag.bind(py), 0, 1))?;
+
+            Ok(())
+        })
+    }
+
+    /// Returns the JavaScript `Int32Array` with the interruption flag, which
+    /// interrupts the guest once its first element is set to a non-zero
+    /// value, e.g. with `Atomics.store(flag, 0, 1)`.
+    ///
+    /// If the page is cross-origin isolated, the array is backed by a
+    /// `SharedArrayBuffer` and can be posted to a web worker, which can then
+    /// interrupt the guest while the thread that runs it is blocked.
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.flag.clone_ref(py)
+    }
+}
+
+/// Creates the JavaScript state of the epoch interruption of a store
+pub fn create_epoch_state(py: Python) -> Result<Bound<PyAny>, PyErr> {
+    js_epoch_state(py)?.call0()
+}
+
+/// Instruments the module `bytes` for epoch interruption, such that every
+/// function entry and loop header calls the hidden
+/// [`EPOCH_CHECK_MODULE`].[`EPOCH_CHECK_NAME`] function import.
+///
+/// The import is appended to the function imports, which shifts the indices
+/// of all functions that the module defines. Since the `name` section would
+/// then refer to the wrong functions, it is removed.
+pub fn instrument_epoch_checks(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    /// The id of the custom sections
+    const CUSTOM_SECTION_ID: u8 = 0;
+    /// The id of the type section
+    const TYPE_SECTION_ID: u8 = 1;
+    /// The id of the import section
+    const IMPORT_SECTION_ID: u8 = 2;
+    /// The id of the code section
+    const CODE_SECTION_ID: u8 = 10;
+
+    let patches = EpochPatches::collect(bytes)?;
+
+    let mut instrumented = Vec::with_capacity(bytes.len() + bytes.len() / 16);
+    instrumented.extend_from_slice(&bytes[..8]);
+
+    let mut has_type_section = false;
+    let mut has_import_section = false;
+
+    let mut offset = 8;
+    while offset < bytes.len() {
+        let id = bytes[offset];
+        let (size, size_len) = decode_leb128(&bytes[(offset + 1)..])?;
+        let content = (offset + 1 + size_len)..(offset + 1 + size_len + size);
+        if content.end > bytes.len() {
+            anyhow::bail!("section at offset {offset} exceeds the module bytes");
+        }
+        offset = content.end;
+
+        if id != CUSTOM_SECTION_ID {
+            if !has_type_section && id != TYPE_SECTION_ID {
+                encode_section(&mut instrumented, TYPE_SECTION_ID, &[1, 0x60, 0, 0]);
+                has_type_section = true;
+            }
+            if !has_import_section && id != TYPE_SECTION_ID && id != IMPORT_SECTION_ID {
+                let mut section = vec![1];
+                section.extend_from_slice(&patches.import);
+                encode_section(&mut instrumented, IMPORT_SECTION_ID, &section);
+                has_import_section = true;
+            }
+        }
+
+        match id {
+            CUSTOM_SECTION_ID if is_name_section(&bytes[content.clone()]) => continue,
+            TYPE_SECTION_ID => has_type_section = true,
+            IMPORT_SECTION_ID => has_import_section = true,
+            _ => (),
+        }
+
+        let section = if id == CODE_SECTION_ID {
+            patches.splice_code_section(bytes, content)?
+        } else {
+            patches.splice(bytes, content)
+        };
+
+        encode_section(&mut instrumented, id, &section);
+    }
+
+    if !has_type_section {
+        encode_section(&mut instrumented, TYPE_SECTION_ID, &[1, 0x60, 0, 0]);
+    }
+    if !has_import_section {
+        let mut section = vec![1];
+        section.extend_from_slice(&patches.import);
+        encode_section(&mut instrumented, IMPORT_SECTION_ID, &section);
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        len = bytes.len(),
+        instrumented = instrumented.len(),
+        "instrumented the module for epoch interruption"
+    );
+
+    Ok(instrumented)
+}
+
+/// The byte patches that instrument a module for epoch interruption
+struct EpochPatches {
+    /// The replacements of byte ranges in the module, sorted by their start
+    patches: Vec<(Range<usize>, Vec<u8>)>,
+    /// The encoded import of the epoch check function
+    import: Vec<u8>,
+}
+
+impl EpochPatches {
+    /// Collects the patches that instrument the module `bytes`
+    fn collect(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut patches = Vec::new();
+        let mut types = 0_u32;
+        let mut func_imports = 0_u32;
+        let mut import = Vec::new();
+        let mut check = Vec::new();
+
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::Version { encoding, .. } if encoding != wasmparser::Encoding::Module => {
+                    anyhow::bail!("only core modules can be instrumented")
+                },
+                Payload::TypeSection(reader) => {
+                    for group in reader.clone() {
+                        types += u32::try_from(group?.types().len())?;
+                    }
+                    patches.push(replace_count(bytes, reader.range().start, reader.count())?);
+                    patches.push((reader.range().end..reader.range().end, vec![0x60, 0, 0]));
+                },
+                Payload::ImportSection(reader) => {
+                    for entry in reader.clone() {
+                        if let TypeRef::Func(_) = entry?.ty {
+                            func_imports += 1;
+                        }
+                    }
+                    patches.push(replace_count(bytes, reader.range().start, reader.count())?);
+                    import = encode_import(types);
+                    patches.push((reader.range().end..reader.range().end, import.clone()));
+                },
+                Payload::FunctionSection(_) => {
+                    if import.is_empty() {
+                        import = encode_import(types);
+                    }
+                    check = vec![0x10];
+                    encode_leb128(&mut check, func_imports as usize);
+                },
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        let global = global?;
+                        let mut ops = global.init_expr.get_operators_reader();
+                        remap_operators(bytes, &mut ops, func_imports, None, &mut patches)?;
+                    }
+                },
+                Payload::ExportSection(reader) => {
+                    for entry in reader.into_iter_with_offsets() {
+                        let (offset, export) = entry?;
+                        if export.kind != ExternalKind::Func {
+                            continue;
+                        }
+                        let (_, name_len) = decode_leb128(&bytes[offset..])?;
+                        let index = offset + name_len + export.name.len() + 1;
+                        patches.push(remap_func(bytes, index, func_imports)?);
+                    }
+                },
+                Payload::StartSection { range, .. } => {
+                    patches.push(remap_func(bytes, range.start, func_imports)?);
+                },
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        match element?.items {
+                            ElementItems::Functions(funcs) => {
+                                for func in funcs.into_iter_with_offsets() {
+                                    let (offset, _) = func?;
+                                    patches.push(remap_func(bytes, offset, func_imports)?);
+                                }
+                            },
+                            ElementItems::Expressions(_, exprs) => {
+                                for expr in exprs {
+                                    let mut ops = expr?.get_operators_reader();
+                                    remap_operators(
+                                        bytes,
+                                        &mut ops,
+                                        func_imports,
+                                        None,
+                                        &mut patches,
+                                    )?;
+                                }
+                            },
+                        }
+                    }
+                },
+                Payload::CodeSectionEntry(body) => {
+                    let mut ops = body.get_operators_reader()?;
+                    remap_operators(bytes, &mut ops, func_imports, Some(&check), &mut patches)?;
+                },
+                _ => (),
+            }
+        }
+
+        if import.is_empty() {
+            import = encode_import(types);
+        }
+
+        patches.sort_by_key(|(range, _)| (range.start, range.end));
+
+        Ok(Self { patches, import })
+    }
+
+    /// Applies the patches within the `range` of the module `bytes`
+    fn splice(&self, bytes: &[u8], range: Range<usize>) -> Vec<u8> {
+        let first = self
+            .patches
+            .partition_point(|(patch, _)| patch.start < range.start);
+
+        let mut spliced = Vec::with_capacity(range.len());
+        let mut offset = range.start;
+
+        for (patch, replacement) in &self.patches[first..] {
+            // insertions at the end of the range still belong to it
+            if patch.start > range.end || (patch.start == range.end && !patch.is_empty()) {
+                break;
+            }
+
+            spliced.extend_from_slice(&bytes[offset..patch.start]);
+            spliced.extend_from_slice(replacement);
+            offset = patch.end;
+        }
+
+        spliced.extend_from_slice(&bytes[offset..range.end]);
+        spliced
+    }
+
+    /// Applies the patches to the function bodies within the code section
+    /// `content` of the module `bytes`, re-encoding their sizes
+    fn splice_code_section(&self, bytes: &[u8], content: Range<usize>) -> anyhow::Result<Vec<u8>> {
+        let (count, count_len) = decode_leb128(&bytes[content.start..])?;
+
+        let mut section = Vec::with_capacity(content.len() + content.len() / 16);
+        section.extend_from_slice(&bytes[content.start..(content.start + count_len)]);
+
+        let mut offset = content.start + count_len;
+        for _ in 0..count {
+            let (size, size_len) = decode_leb128(&bytes[offset..])?;
+            let body = (offset + size_len)..(offset + size_len + size);
+
+            if body.end > content.end {
+                anyhow::bail!("function body at offset {offset} exceeds the code section");
+            }
+            offset = body.end;
+
+            let body = self.splice(bytes, body);
+            encode_leb128(&mut section, body.len());
+            section.extend_from_slice(&body);
+        }
+
+        Ok(section)
+    }
+}
+
+/// Collects the patches that remap the function indices in the operators of
+/// `ops` and, if a `check` call is provided, insert it at the start and at
+/// every loop header
+fn remap_operators(
+    bytes: &[u8],
+    ops: &mut wasmparser::OperatorsReader,
+    func_imports: u32,
+    check: Option<&[u8]>,
+    patches: &mut Vec<(Range<usize>, Vec<u8>)>,
+) -> anyhow::Result<()> {
+    let mut insert_check = check.is_some();
+
+    while !ops.eof() {
+        let (op, offset) = ops.read_with_offset()?;
+
+        if let (true, Some(check)) = (insert_check, check) {
+            patches.push((offset..offset, check.to_vec()));
+            insert_check = false;
+        }
+
+        match op {
+            Operator::Call { .. } | Operator::ReturnCall { .. } | Operator::RefFunc { .. } => {
+                patches.push(remap_func(bytes, offset + 1, func_imports)?);
+            },
+            Operator::Loop { .. } => insert_check = check.is_some(),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates the patch that remaps the LEB128-encoded function index at the
+/// `offset` of the module `bytes`, accounting for the epoch check import
+fn remap_func(
+    bytes: &[u8],
+    offset: usize,
+    func_imports: u32,
+) -> anyhow::Result<(Range<usize>, Vec<u8>)> {
+    let (func, len) = decode_leb128(&bytes[offset..])?;
+
+    let mut encoded = Vec::with_capacity(len + 1);
+    encode_leb128(
+        &mut encoded,
+        if func >= func_imports as usize {
+            func + 1
+        } else {
+            func
+        },
+    );
+
+    Ok((offset..(offset + len), encoded))
+}
+
+/// Creates the patch that increments the LEB128-encoded `count` of the
+/// section whose content starts at the `offset` of the module `bytes`
+fn replace_count(
+    bytes: &[u8],
+    offset: usize,
+    count: u32,
+) -> anyhow::Result<(Range<usize>, Vec<u8>)> {
+    let (_, len) = decode_leb128(&bytes[offset..])?;
+
+    let mut encoded = Vec::with_capacity(len + 1);
+    encode_leb128(&mut encoded, count as usize + 1);
+
+    Ok((offset..(offset + len), encoded))
+}
+
+/// Encodes the import of the epoch check function with the type index `ty`
+fn encode_import(ty: u32) -> Vec<u8> {
+    let mut import = Vec::new();
+    encode_leb128(&mut import, EPOCH_CHECK_MODULE.len());
+    import.extend_from_slice(EPOCH_CHECK_MODULE.as_bytes());
+    encode_leb128(&mut import, EPOCH_CHECK_NAME.len());
+    import.extend_from_slice(EPOCH_CHECK_NAME.as_bytes());
+    import.push(0x00);
+    encode_leb128(&mut import, ty as usize);
+    import
+}
+
+/// Appends the section with the `id` and `content` to the module `bytes`
+fn encode_section(bytes: &mut Vec<u8>, id: u8, content: &[u8]) {
+    bytes.push(id);
+    encode_leb128(bytes, content.len());
+    bytes.extend_from_slice(content);
+}
+
+/// Checks if the custom section `content` is the `name` section
+fn is_name_section(content: &[u8]) -> bool {
+    decode_leb128(content)
+        .is_ok_and(|(len, len_len)| content.get(len_len..(len_len + len)) == Some(&b"name"[..]))
+}
+
+/// Decodes the LEB128-encoded integer at the start of `bytes`, returning it
+/// and its encoded length
+fn decode_leb128(bytes: &[u8]) -> anyhow::Result<(usize, usize)> {
+    let mut value = 0_usize;
+
+    for (index, byte) in bytes.iter().enumerate().take(5) {
+        value |= usize::from(byte & 0x7F) << (7 * index);
+
+        if (byte & 0x80) == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+
+    anyhow::bail!("invalid LEB128-encoded integer")
+}
+
+fn js_epoch_state(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_EPOCH_STATE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_EPOCH_STATE
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function epochState() {
+    const shared = (typeof SharedArrayBuffer !== 'undefined') && globalThis.crossOriginIsolated;
+    const flag = new Int32Array(shared ? new SharedArrayBuffer(4) : new ArrayBuffer(4));
+
+    const state = {
+        ticks: 0,
+        deadline: Infinity,
+        flag,
+        setDeadline(ticks) {
+            state.deadline = state.ticks + Number(ticks);
+        },
+        check() {
+            state.ticks += 1;
+
+            if (Atomics.exchange(flag, 0, 0) !== 0) {
+                throw new WebAssembly.RuntimeError('guest execution was interrupted');
+            }
+
+            if (state.ticks > state.deadline) {
+                throw new WebAssembly.RuntimeError(
+                    'guest execution was interrupted since the epoch deadline was reached'
+                );
+            }
+        },
+    };
+
+    return state;
+}
+epochState
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+fn js_atomics(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_ATOMICS: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_ATOMICS.import(py, "js", "Atomics")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use wasm_encoder::{
+        BlockType, CodeSection, ConstExpr, CustomSection, ElementSection, Elements, EntityType,
+        ExportKind, ExportSection, Function, FunctionSection, GlobalSection, GlobalType,
+        ImportSection, Instruction, MemorySection, MemoryType, Module, RefType, StartSection,
+        TableSection, TableType, TypeSection, ValType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn empty_module() {
+        let instrumented = instrument(&Module::new());
+
+        assert_eq!(count_epoch_checks(&instrumented), 0);
+    }
+
+    #[test]
+    fn without_type_section() {
+        let mut module = Module::new();
+        module.section(&memory_section());
+
+        let instrumented = instrument(&module);
+
+        assert_eq!(count_epoch_checks(&instrumented), 0);
+    }
+
+    #[test]
+    fn without_import_section() {
+        let mut types = TypeSection::new();
+        types.ty().function([], []);
+        types.ty().function([ValType::I32], [ValType::I32]);
+
+        let mut funcs = FunctionSection::new();
+        funcs.function(0);
+        funcs.function(1);
+
+        let mut exports = ExportSection::new();
+        exports.export("run", ExportKind::Func, 0);
+        exports.export("double", ExportKind::Func, 1);
+
+        let mut run = Function::new([]);
+        run.instruction(&Instruction::I32Const(21));
+        run.instruction(&Instruction::Call(1));
+        run.instruction(&Instruction::Drop);
+        run.instruction(&Instruction::End);
+
+        let mut double = Function::new([]);
+        double.instruction(&Instruction::LocalGet(0));
+        double.instruction(&Instruction::LocalGet(0));
+        double.instruction(&Instruction::I32Add);
+        double.instruction(&Instruction::End);
+
+        let mut code = CodeSection::new();
+        code.function(&run);
+        code.function(&double);
+
+        let mut module = Module::new();
+        module.section(&types);
+        module.section(&funcs);
+        module.section(&memory_section());
+        module.section(&exports);
+        module.section(&StartSection { function_index: 0 });
+        module.section(&code);
+
+        let instrumented = instrument(&module);
+
+        assert_eq!(count_epoch_checks(&instrumented), 2);
+        assert_eq!(exported_func(&instrumented, "run"), 1);
+        assert_eq!(exported_func(&instrumented, "double"), 2);
+    }
+
+    #[test]
+    fn existing_func_imports() {
+        let mut types = TypeSection::new();
+        types.ty().function([], []);
+        types.ty().function([ValType::I32], []);
+        types.ty().function([], [ValType::I64]);
+
+        let mut imports = ImportSection::new();
+        imports.import("env", "log", EntityType::Function(1));
+        imports.import("env", "memory", EntityType::Memory(memory_type()));
+        imports.import("env", "now", EntityType::Function(2));
+
+        let mut funcs = FunctionSection::new();
+        funcs.function(0);
+        funcs.function(2);
+
+        let mut exports = ExportSection::new();
+        exports.export("run", ExportKind::Func, 2);
+        exports.export("now", ExportKind::Func, 1);
+        exports.export("later", ExportKind::Func, 3);
+
+        let mut run = Function::new([]);
+        run.instruction(&Instruction::I32Const(42));
+        run.instruction(&Instruction::Call(0));
+        run.instruction(&Instruction::Call(3));
+        run.instruction(&Instruction::Drop);
+        run.instruction(&Instruction::End);
+
+        let mut later = Function::new([]);
+        later.instruction(&Instruction::Call(1));
+        later.instruction(&Instruction::I64Const(1));
+        later.instruction(&Instruction::I64Add);
+        later.instruction(&Instruction::End);
+
+        let mut code = CodeSection::new();
+        code.function(&run);
+        code.function(&later);
+
+        let mut module = Module::new();
+        module.section(&types);
+        module.section(&imports);
+        module.section(&funcs);
+        module.section(&exports);
+        module.section(&StartSection { function_index: 2 });
+        module.section(&code);
+
+        let instrumented = instrument(&module);
+
+        assert_eq!(count_epoch_checks(&instrumented), 2);
+        assert_eq!(exported_func(&instrumented, "run"), 3);
+        assert_eq!(exported_func(&instrumented, "now"), 1);
+        assert_eq!(exported_func(&instrumented, "later"), 4);
+    }
+
+    #[test]
+    fn ref_func_in_globals_and_elements() {
+        let mut types = TypeSection::new();
+        types.ty().function([], []);
+        types.ty().function([], [ValType::I32]);
+
+        let mut imports = ImportSection::new();
+        imports.import("env", "tick", EntityType::Function(0));
+
+        let mut funcs = FunctionSection::new();
+        funcs.function(0);
+        funcs.function(1);
+
+        let mut tables = TableSection::new();
+        tables.table(TableType {
+            element_type: RefType::FUNCREF,
+            table64: false,
+            minimum: 3,
+            maximum: None,
+            shared: false,
+        });
+
+        let mut globals = GlobalSection::new();
+        globals.global(
+            GlobalType {
+                val_type: ValType::FUNCREF,
+                mutable: false,
+                shared: false,
+            },
+            &ConstExpr::ref_func(2),
+        );
+
+        let mut exports = ExportSection::new();
+        exports.export("first", ExportKind::Func, 1);
+
+        let mut elements = ElementSection::new();
+        elements.active(
+            Some(0),
+            &ConstExpr::i32_const(0),
+            Elements::Functions(Cow::Borrowed(&[0, 1])),
+        );
+        elements.active(
+            Some(0),
+            &ConstExpr::i32_const(2),
+            Elements::Expressions(RefType::FUNCREF, Cow::Owned(vec![ConstExpr::ref_func(2)])),
+        );
+
+        let mut first = Function::new([]);
+        first.instruction(&Instruction::RefFunc(2));
+        first.instruction(&Instruction::Drop);
+        first.instruction(&Instruction::End);
+
+        let mut second = Function::new([]);
+        second.instruction(&Instruction::I32Const(0));
+        second.instruction(&Instruction::CallIndirect {
+            type_index: 0,
+            table_index: 0,
+        });
+        second.instruction(&Instruction::I32Const(1));
+        second.instruction(&Instruction::End);
+
+        let mut code = CodeSection::new();
+        code.function(&first);
+        code.function(&second);
+
+        let mut module = Module::new();
+        module.section(&types);
+        module.section(&imports);
+        module.section(&funcs);
+        module.section(&tables);
+        module.section(&globals);
+        module.section(&exports);
+        module.section(&elements);
+        module.section(&code);
+
+        let instrumented = instrument(&module);
+
+        assert_eq!(count_epoch_checks(&instrumented), 2);
+        assert_eq!(exported_func(&instrumented, "first"), 2);
+
+        let mut refs = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&instrumented) {
+            match payload.expect("instrumented module should parse") {
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        let global = global.expect("global should parse");
+                        refs.extend(const_ref_funcs(&global.init_expr));
+                    }
+                },
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        match element.expect("element should parse").items {
+                            ElementItems::Functions(funcs) => {
+                                for func in funcs {
+                                    refs.push(func.expect("element func should parse"));
+                                }
+                            },
+                            ElementItems::Expressions(_, exprs) => {
+                                for expr in exprs {
+                                    refs.extend(const_ref_funcs(
+                                        &expr.expect("element expr should parse"),
+                                    ));
+                                }
+                            },
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        // the imported function keeps its index, defined functions shift
+        assert_eq!(refs, [3, 0, 2, 3]);
+    }
+
+    #[test]
+    fn loops() {
+        let mut types = TypeSection::new();
+        types.ty().function([ValType::I32], []);
+
+        let mut funcs = FunctionSection::new();
+        funcs.function(0);
+
+        let mut body = Function::new([]);
+        body.instruction(&Instruction::Loop(BlockType::Empty));
+        body.instruction(&Instruction::Block(BlockType::Empty));
+        body.instruction(&Instruction::Loop(BlockType::Empty));
+        body.instruction(&Instruction::LocalGet(0));
+        body.instruction(&Instruction::BrIf(0));
+        body.instruction(&Instruction::End);
+        body.instruction(&Instruction::End);
+        body.instruction(&Instruction::LocalGet(0));
+        body.instruction(&Instruction::BrIf(0));
+        body.instruction(&Instruction::End);
+        body.instruction(&Instruction::Loop(BlockType::Empty));
+        body.instruction(&Instruction::End);
+        body.instruction(&Instruction::End);
+
+        let mut code = CodeSection::new();
+        code.function(&body);
+
+        let mut module = Module::new();
+        module.section(&types);
+        module.section(&funcs);
+        module.section(&code);
+        module.section(&CustomSection {
+            name: Cow::Borrowed("name"),
+            data: Cow::Borrowed(&[]),
+        });
+
+        let instrumented = instrument(&module);
+
+        // one check at the function entry and one per loop header
+        assert_eq!(count_epoch_checks(&instrumented), 4);
+    }
+
+    fn memory_type() -> MemoryType {
+        MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        }
+    }
+
+    fn memory_section() -> MemorySection {
+        let mut memories = MemorySection::new();
+        memories.memory(memory_type());
+        memories
+    }
+
+    /// Instruments the `module`, checks that the instrumented module is valid
+    /// and imports the epoch check, and returns its bytes
+    fn instrument(module: &Module) -> Vec<u8> {
+        let bytes = module.clone().finish();
+        wasmparser::validate(&bytes).expect("the original module should be valid");
+
+        let instrumented =
+            instrument_epoch_checks(&bytes).expect("the module should be instrumentable");
+        wasmparser::validate(&instrumented).expect("the instrumented module should be valid");
+
+        let mut func_imports = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&instrumented) {
+            match payload.expect("instrumented module should parse") {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let import = import.expect("import should parse");
+                        if let TypeRef::Func(_) = import.ty {
+                            func_imports.push((import.module, import.name));
+                        }
+                    }
+                },
+                Payload::CustomSection(section) => {
+                    assert_ne!(section.name(), "name", "the name section should be removed");
+                },
+                _ => (),
+            }
+        }
+
+        assert_eq!(
+            func_imports.last(),
+            Some(&(EPOCH_CHECK_MODULE, EPOCH_CHECK_NAME)),
+            "the epoch check should be the last function import"
+        );
+
+        instrumented
+    }
+
+    /// Counts the calls to the epoch check in the `instrumented` module,
+    /// checking that every function body starts with one and that one
+    /// follows every loop header
+    fn count_epoch_checks(instrumented: &[u8]) -> usize {
+        let mut check = 0;
+        let mut count = 0;
+
+        for payload in wasmparser::Parser::new(0).parse_all(instrumented) {
+            match payload.expect("instrumented module should parse") {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if let TypeRef::Func(_) = import.expect("import should parse").ty {
+                            check += 1;
+                        }
+                    }
+                },
+                Payload::CodeSectionEntry(body) => {
+                    let mut ops = body
+                        .get_operators_reader()
+                        .expect("function body should parse");
+                    let mut expect_check = true;
+
+                    while !ops.eof() {
+                        let op = ops.read().expect("operator should parse");
+
+                        if expect_check {
+                            assert_eq!(
+                                op,
+                                Operator::Call {
+                                    function_index: check - 1
+                                },
+                                "expected an epoch check"
+                            );
+                        }
+
+                        if op
+                            == (Operator::Call {
+                                function_index: check - 1,
+                            })
+                        {
+                            count += 1;
+                        }
+
+                        expect_check = matches!(op, Operator::Loop { .. });
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        count
+    }
+
+    /// Returns the index of the function that the `instrumented` module
+    /// exports as `name`
+    fn exported_func(instrumented: &[u8], name: &str) -> u32 {
+        for payload in wasmparser::Parser::new(0).parse_all(instrumented) {
+            if let Payload::ExportSection(reader) =
+                payload.expect("instrumented module should parse")
+            {
+                for export in reader {
+                    let export = export.expect("export should parse");
+                    if export.name == name && export.kind == ExternalKind::Func {
+                        return export.index;
+                    }
+                }
+            }
+        }
+
+        panic!("the function {name} should be exported");
+    }
+
+    /// Returns the functions referenced by `ref.func` in the constant `expr`
+    fn const_ref_funcs(expr: &wasmparser::ConstExpr) -> Vec<u32> {
+        expr.get_operators_reader()
+            .into_iter()
+            .filter_map(|op| match op.expect("operator should parse") {
+                Operator::RefFunc { function_index } => Some(function_index),
+                _ => None,
+            })
+            .collect()
+    }
+}
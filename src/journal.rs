@@ -0,0 +1,154 @@
+use wasm_runtime_layer::backend::{AsContext, AsContextMut, WasmMemory};
+
+use crate::{memory::PAGE_SIZE, Engine, Memory};
+
+/// A journal of host-side writes to a [`Memory`], which are only applied
+/// once the journal is committed.
+///
+/// Transactional host functions can queue their writes in a journal and only
+/// commit it once they have succeeded, such that the guest never observes a
+/// partially applied set of writes if the host function fails midway.
+/// Dropping a journal without committing it discards all queued writes.
+///
+/// [`Memory::transaction`] wraps a fallible closure in a journal that is
+/// committed only if the closure succeeds.
+#[derive(Debug)]
+#[must_use]
+pub struct MemoryJournal {
+    /// The memory that the writes are applied to
+    memory: Memory,
+    /// The queued writes, in order, as offset and bytes
+    writes: Vec<(usize, Vec<u8>)>,
+}
+
+impl MemoryJournal {
+    /// Creates a new empty journal for the `memory`.
+    pub fn new(memory: &Memory) -> Self {
+        Self {
+            memory: memory.clone(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Queues a write of the `buffer` at the `offset`.
+    ///
+    /// The write is only bounds-checked and applied when the journal is
+    /// committed.
+    pub fn write(&mut self, offset: usize, buffer: &[u8]) {
+        self.writes.push((offset, buffer.to_vec()));
+    }
+
+    /// Reads from the memory at the `offset` into the `buffer`, as if all
+    /// queued writes had already been applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the read is out of bounds.
+    pub fn read(
+        &self,
+        ctx: impl AsContext<Engine>,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> anyhow::Result<()> {
+        self.memory.read(ctx, offset, buffer)?;
+
+        let end = offset + buffer.len();
+
+        for (write_offset, bytes) in &self.writes {
+            let write_end = write_offset.saturating_add(bytes.len());
+
+            let start = offset.max(*write_offset);
+            let stop = end.min(write_end);
+
+            if start < stop {
+                buffer[(start - offset)..(stop - offset)]
+                    .copy_from_slice(&bytes[(start - write_offset)..(stop - write_offset)]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of queued writes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns `true` if no writes have been queued.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Discards all queued writes.
+    pub fn clear(&mut self) {
+        self.writes.clear();
+    }
+
+    /// Applies all queued writes, in order.
+    ///
+    /// All writes are bounds-checked before any of them is applied, so that
+    /// either all or none of the writes are applied. Since no guest code can
+    /// run while the writes are applied, the guest never observes a partial
+    /// commit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, without applying any writes, if any write is out of
+    /// bounds.
+    pub fn commit(self, mut ctx: impl AsContextMut<Engine>) -> anyhow::Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("MemoryJournal::commit", writes = self.writes.len()).entered();
+
+        let byte_len = self
+            .memory
+            .current_pages_u64(ctx.as_context())?
+            .checked_mul(PAGE_SIZE)
+            .ok_or_else(|| anyhow::anyhow!("memory size exceeds the 64-bit range"))?;
+
+        for (offset, bytes) in &self.writes {
+            let end = offset
+                .checked_add(bytes.len())
+                .and_then(|end| u64::try_from(end).ok());
+
+            if !end.is_some_and(|end| end <= byte_len) {
+                anyhow::bail!(
+                    "out of bounds journalled memory write of {} bytes at offset {offset} in a \
+                     memory of {byte_len} bytes",
+                    bytes.len()
+                );
+            }
+        }
+
+        for (offset, bytes) in &self.writes {
+            self.memory.write(ctx.as_context_mut(), *offset, bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Memory {
+    /// Runs the fallible closure `f` with a [`MemoryJournal`] for this
+    /// memory and commits the journalled writes only if `f` succeeds.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error returned by `f`, in which case no writes are
+    /// applied, or the error of committing the journal.
+    pub fn transaction<R>(
+        &self,
+        ctx: impl AsContextMut<Engine>,
+        f: impl FnOnce(&mut MemoryJournal) -> anyhow::Result<R>,
+    ) -> anyhow::Result<R> {
+        let mut journal = MemoryJournal::new(self);
+
+        let result = f(&mut journal)?;
+
+        journal.commit(ctx)?;
+
+        Ok(result)
+    }
+}
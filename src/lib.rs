@@ -60,6 +60,55 @@
 //!   dropped or references to the [`Func`] are dropped, additional bookkeeping
 //!   data is required until both have been dropped.
 //!
+//! ## Blocking Calls
+//!
+//! Calls into the guest using [`WasmFunc::call`] hold the Python GIL until
+//! the guest returns. Releasing the GIL around the JavaScript call, e.g. with
+//! [`Python::allow_threads`], is not possible since [`Pyodide`] dispatches
+//! every JavaScript call through the Python interpreter, and host functions
+//! that the guest calls re-enter Python. Releasing it would also not help,
+//! since [`Pyodide`] runs all Python tasks on the same JavaScript thread,
+//! which remains blocked while the guest runs.
+//!
+//! Long-running guest computations should instead be started with
+//! [`Func::call_async`] and be awaited from Python, which yields to the event
+//! loop if the guest suspends, or be moved off the main thread using a
+//! [`WorkerBridge`].
+//!
+//! ## Re-entrancy
+//!
+//! Guest code may call a host function that calls back into the guest, even
+//! into the same exported [`Func`], with arbitrary nesting. Every host
+//! function receives a [`StoreContextMut`] that exclusively borrows the
+//! [`Store`] for the duration of its call, and can only call into the guest
+//! by lending this borrow to [`WasmFunc::call`]. Re-entrant host calls
+//! therefore receive stacked contexts, which guarantees that
+//!
+//! - a host function cannot hold references into the store's data across a call
+//!   into the guest, and observes all mutations of nested calls once the call
+//!   returns,
+//! - a context cannot outlive the host call that it was passed to, and the
+//!   store cannot be dropped while any call is active,
+//! - calls from the host into host functions of the same store bypass the
+//!   JavaScript trampoline, but are otherwise treated like calls from the
+//!   guest.
+//!
+//! Since a nested call may grow a memory or table, their sizes and contents
+//! should be re-read after each call into the guest. The nesting depth can
+//! be inspected with [`StoreContextMut::host_call_depth`] and is limited by
+//! [`EngineConfig::with_max_host_call_depth`], while the re-entrancy of a
+//! single function can be limited with [`Func::with_reentrancy_limit`].
+//!
+//! ## API Stability
+//!
+//! The public types of this crate implement the traits of, and use types from,
+//! a specific version of the [`wasm_runtime_layer`] crate, currently `0.4`.
+//! To avoid mismatches between the version used by this crate and by its
+//! dependents, the exact version is re-exported as [`api`]. Dependents can
+//! use `pyodide_webassembly_runtime_layer::api` instead of depending on
+//! [`wasm_runtime_layer`] directly, such that bumping this crate also bumps
+//! the [`wasm_runtime_layer`] API in lockstep.
+//!
 //! [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
 //! [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly
 //! [`Pyodide`]: https://pyodide.org/en/stable/
@@ -73,46 +122,109 @@
 //! [`wobbly`]: https://docs.rs/wobbly/0.1/
 //! [`Func`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Func.html
 //! [`Store`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Store.html
+//! [`WasmFunc::call`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/backend/trait.WasmFunc.html#tymethod.call
+//! [`StoreContextMut`]: crate::StoreContextMut
+//! [`StoreContextMut::host_call_depth`]: crate::StoreContextMut::host_call_depth
+//! [`EngineConfig::with_max_host_call_depth`]: crate::EngineConfig::with_max_host_call_depth
+//! [`Func::with_reentrancy_limit`]: crate::Func::with_reentrancy_limit
+//! [`Python::allow_threads`]: https://docs.rs/pyo3/0.23/pyo3/marker/struct.Python.html#method.allow_threads
 
-use wasm_runtime_layer::backend::WasmEngine;
-
+mod abi;
+mod animation;
+#[cfg(feature = "module-cache")]
+mod cache;
+mod canvas;
+mod capabilities;
+mod clock;
 mod conversion;
+mod custom;
+mod engine;
+mod error;
+mod event;
 mod externref;
 mod features;
 mod func;
 mod global;
+mod history;
+mod http;
 mod instance;
+mod interrupt;
+mod journal;
+mod limiter;
+mod linker;
+#[cfg(feature = "tracing")]
+mod log;
+mod manifest;
 mod memory;
 mod module;
+#[cfg(feature = "serde")]
+mod persist;
+mod signature;
+mod slots;
+mod stack;
 mod store;
 mod table;
+mod tag;
+mod trap;
+#[cfg(feature = "ed25519")]
+mod verify;
+mod wasi;
+mod websocket;
+#[cfg(feature = "serde")]
+mod wire;
+mod worker;
+mod wrap;
+
+/// The exact version of the [`wasm_runtime_layer`] API that this crate
+/// implements, see the [API Stability](crate#api-stability) section.
+///
+/// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+pub use wasm_runtime_layer as api;
 
+pub use animation::AnimationLoop;
+#[cfg(feature = "module-cache")]
+pub use cache::PendingModuleCache;
+pub use canvas::CanvasBlitter;
+pub use capabilities::Capabilities;
+pub use clock::{Clock, VirtualClock};
+pub use conversion::V128;
+pub use custom::CustomSectionWriter;
+pub use engine::{Engine, EngineConfig, PyodideVersion};
+pub use error::Error;
+pub use event::EventListener;
 pub use externref::ExternRef;
-pub use func::Func;
+pub use features::{ProbeResult, UnsupportedWasmFeatureExtensionError, WasmFeatureExtension};
+pub use func::{Func, PendingCall};
 pub use global::Global;
-pub use instance::Instance;
+pub use history::{Mutation, MutationKind};
+pub use http::{HttpImports, HttpPolicy};
+pub use instance::{Instance, InstanceOptions, InstantiationError};
+pub use interrupt::InterruptHandle;
+pub use journal::MemoryJournal;
+pub use limiter::{ResourceLimiter, StoreLimits};
+pub use linker::Linker;
+#[cfg(feature = "tracing")]
+pub use log::GuestLogger;
+pub use manifest::{ConstValue, ImportManifest, ImportSource, ImportsBuilder, ManifestImport};
 pub use memory::Memory;
-pub use module::Module;
-pub use store::{Store, StoreContext, StoreContextMut};
-pub use table::Table;
-
-#[derive(Default, Debug, Clone)]
-/// Runtime for [`WebAssembly`] web runtime.
-///
-/// [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly
-pub struct Engine {
-    _private: (),
-}
-
-impl WasmEngine for Engine {
-    type ExternRef = ExternRef;
-    type Func = Func;
-    type Global = Global;
-    type Instance = Instance;
-    type Memory = Memory;
-    type Module = Module;
-    type Store<T> = Store<T>;
-    type StoreContext<'a, T: 'a> = StoreContext<'a, T>;
-    type StoreContextMut<'a, T: 'a> = StoreContextMut<'a, T>;
-    type Table = Table;
-}
+pub use module::{Module, ModuleMetadata, ModuleStats, ModuleStream};
+pub use signature::{FuncSignature, FuncSignatureBuilder, SignatureError};
+pub use slots::TableSlotAllocator;
+pub use stack::{ShadowStack, StackGuard};
+pub use store::{
+    CallHook, HostFuncInfo, PythonScope, Store, StoreContext, StoreContextMut, StoreFootprint,
+};
+pub use table::{Table, TableMaximumPolicy};
+pub use tag::{Exception, Tag};
+pub use trap::{Trap, TrapKind};
+#[cfg(feature = "ed25519")]
+pub use verify::ModuleVerifier;
+pub use wasi::{
+    IndexedDbFs, MemoryFs, PendingIndexedDbFs, WasiFileType, WasiFs, WasiFsError,
+    WasiImportHandler, WasiImportPolicy, WasiMetadata, WasiShim, WasiShimBuilder,
+};
+pub use websocket::{WebSocketImports, WebSocketPolicy};
+#[cfg(feature = "serde")]
+pub use wire::{RefHandles, WireValue};
+pub use worker::{PendingTransfer, RemoteInstance, WorkerBridge};
+pub use wrap::{HostFuncResult, IntoFunc, WasmResults, WasmTy};
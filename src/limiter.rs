@@ -0,0 +1,122 @@
+/// A limiter for the resources that a [`Store`] may consume, which can veto
+/// the creation and growth of memories and tables, similar to wasmtime's
+/// [`ResourceLimiter`].
+///
+/// A limiter is installed on a store with [`Store::limiter`]. It is consulted
+/// when the host creates a memory or table and when [`Memory::grow`] or
+/// [`Table::grow`] are called from the host.
+///
+/// Note that the JavaScript API provides no hook into the `memory.grow` and
+/// `table.grow` instructions that are executed by the guest, which can
+/// therefore only be bounded by the maximum size of the memory or table. To
+/// bound an untrusted guest, it should import a memory that the host created
+/// with a maximum size, instead of defining its own memory.
+///
+/// [`Store`]: crate::Store
+/// [`Store::limiter`]: crate::Store::limiter
+/// [`ResourceLimiter`]: https://docs.rs/wasmtime/latest/wasmtime/trait.ResourceLimiter.html
+/// [`Memory::grow`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.grow
+/// [`Table::grow`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Table.html#method.grow
+pub trait ResourceLimiter {
+    /// Decides whether a memory may grow from `current` to `desired` bytes,
+    /// where `maximum` is the maximum size of the memory in bytes, if any.
+    ///
+    /// A new memory grows from zero bytes to its initial size.
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the growth with that error.
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool>;
+
+    /// Decides whether a table may grow from `current` to `desired`
+    /// elements, where `maximum` is the maximum number of elements of the
+    /// table, if any.
+    ///
+    /// A new table grows from zero elements to its initial size.
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the growth with that error.
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// A [`ResourceLimiter`] that limits the size of every memory and table of a
+/// store, similar to wasmtime's [`StoreLimits`].
+///
+/// [`StoreLimits`]: https://docs.rs/wasmtime/latest/wasmtime/struct.StoreLimits.html
+pub struct StoreLimits {
+    /// The maximum size of each memory in bytes, if limited
+    memory_size: Option<usize>,
+    /// The maximum number of elements of each table, if limited
+    table_elements: Option<usize>,
+}
+
+impl StoreLimits {
+    /// Creates new limits that do not limit any resources.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            memory_size: None,
+            table_elements: None,
+        }
+    }
+
+    /// Limits the size of each memory to `memory_size` bytes, or removes the
+    /// limit if `None`.
+    #[must_use]
+    pub const fn with_memory_size(mut self, memory_size: Option<usize>) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+
+    /// Returns the maximum size of each memory in bytes, if limited.
+    #[must_use]
+    pub const fn memory_size(&self) -> Option<usize> {
+        self.memory_size
+    }
+
+    /// Limits the number of elements of each table to `table_elements`, or
+    /// removes the limit if `None`.
+    #[must_use]
+    pub const fn with_table_elements(mut self, table_elements: Option<usize>) -> Self {
+        self.table_elements = table_elements;
+        self
+    }
+
+    /// Returns the maximum number of elements of each table, if limited.
+    #[must_use]
+    pub const fn table_elements(&self) -> Option<usize> {
+        self.table_elements
+    }
+}
+
+impl ResourceLimiter for StoreLimits {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        Ok(self.memory_size.map_or(true, |limit| desired <= limit))
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        Ok(self.table_elements.map_or(true, |limit| desired <= limit))
+    }
+}
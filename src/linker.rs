@@ -0,0 +1,355 @@
+use std::collections::BTreeSet;
+
+use fxhash::{FxHashMap, FxHashSet};
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Extern, Imports, WasmInstance, WasmModule},
+    ExternType, ImportType,
+};
+
+use crate::{wrap::IntoFunc, Engine, Error, Func, Instance, InstanceOptions, Module};
+
+#[derive(Debug, Clone, Default)]
+/// A linker that resolves the imports of [`Module`]s by their names, similar
+/// to wasmtime's [`Linker`].
+///
+/// Items are defined once under a module and name, either individually using
+/// [`Linker::define`] and [`Linker::func_wrap`] or for all exports of an
+/// [`Instance`] using [`Linker::instance`], and are then passed on to every
+/// module that is instantiated with [`Linker::instantiate`] and imports them.
+///
+/// [`Linker`]: https://docs.rs/wasmtime/latest/wasmtime/struct.Linker.html
+pub struct Linker {
+    /// The defined items
+    imports: Imports<Engine>,
+    /// Whether items may be redefined
+    allow_shadowing: bool,
+}
+
+impl Linker {
+    /// Creates a new linker without any defined items.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures whether defining an item under a module and name that
+    /// already have an item replaces the previous item instead of failing.
+    pub fn allow_shadowing(&mut self, allow_shadowing: bool) -> &mut Self {
+        self.allow_shadowing = allow_shadowing;
+        self
+    }
+
+    /// Defines the `item` under the `name` in the `module`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an item is already defined under the `name` in
+    /// the `module` and shadowing is not allowed, see
+    /// [`Linker::allow_shadowing`].
+    pub fn define(
+        &mut self,
+        module: &str,
+        name: &str,
+        item: Extern<Engine>,
+    ) -> anyhow::Result<&mut Self> {
+        if !self.allow_shadowing && self.imports.exists(module, name) {
+            return Err(Error::Link(format!(
+                "import `{module}`.`{name}` is already defined in the linker"
+            ))
+            .into());
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(module, name, "Linker::define");
+
+        self.imports.define(module, name, item);
+
+        Ok(self)
+    }
+
+    /// Defines a host function, created from the Rust closure `func` using
+    /// [`Func::wrap`], under the `name` in the `module`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an item is already defined under the `name` in
+    /// the `module` and shadowing is not allowed, see
+    /// [`Linker::allow_shadowing`].
+    pub fn func_wrap<T, Params, Results>(
+        &mut self,
+        ctx: impl AsContextMut<Engine, UserState = T>,
+        module: &str,
+        name: &str,
+        func: impl IntoFunc<T, Params, Results>,
+    ) -> anyhow::Result<&mut Self> {
+        self.define(module, name, Extern::Func(Func::wrap(ctx, func)))
+    }
+
+    /// Defines all exports of the `instance` under their names in the
+    /// `module`, e.g. to link a module against another instantiated module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an item is already defined under the name of an
+    /// export in the `module` and shadowing is not allowed, see
+    /// [`Linker::allow_shadowing`].
+    pub fn instance(
+        &mut self,
+        ctx: impl AsContext<Engine>,
+        module: &str,
+        instance: &Instance,
+    ) -> anyhow::Result<&mut Self> {
+        for export in WasmInstance::exports(instance, ctx) {
+            self.define(module, &export.name, export.value)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Defines host functions that trap when called for all function imports
+    /// of the `module` that are not yet defined in this linker.
+    ///
+    /// This allows instantiating modules that declare imports which they
+    /// never call in practice. Unlike
+    /// [`InstanceOptions::stub_unused_imports`], the stubs are defined even
+    /// for imports that the module may use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the module's function imports cannot be
+    /// determined.
+    pub fn define_unknown_imports_as_traps(
+        &mut self,
+        mut ctx: impl AsContextMut<Engine>,
+        module: &Module,
+    ) -> anyhow::Result<&mut Self> {
+        for ImportType {
+            module: module_name,
+            name,
+            ty,
+        } in WasmModule::imports(module)
+        {
+            let ExternType::Func(ty) = ty else {
+                continue;
+            };
+
+            if self.imports.exists(module_name, name) {
+                continue;
+            }
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(module_name, name, "defining unknown import as a trap");
+
+            let message = format!("unknown import `{module_name}`.`{name}` was called");
+            let trap = Func::new_with_signature(
+                ctx.as_context_mut(),
+                ty.into(),
+                move |_ctx, _args, _results| Err(anyhow::anyhow!("{message}")),
+            );
+
+            self.imports.define(module_name, name, Extern::Func(trap));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the item that is defined under the `name` in the `module`, if
+    /// any.
+    #[must_use]
+    pub fn get(&self, module: &str, name: &str) -> Option<Extern<Engine>> {
+        self.imports.get_export(module, name)
+    }
+
+    /// Returns the items that are defined in this linker.
+    #[must_use]
+    pub const fn imports(&self) -> &Imports<Engine> {
+        &self.imports
+    }
+
+    /// Returns the items that are defined in this linker mutably, e.g. to
+    /// define import sets such as [`HttpImports`] that extend an
+    /// [`Imports`] object.
+    ///
+    /// Items that are defined through the returned [`Imports`] silently
+    /// replace existing items, regardless of [`Linker::allow_shadowing`].
+    ///
+    /// [`HttpImports`]: crate::HttpImports
+    /// [`Imports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Imports.html
+    pub fn imports_mut(&mut self) -> &mut Imports<Engine> {
+        &mut self.imports
+    }
+
+    /// Instantiates the `module` in the `store`, resolving its imports by
+    /// their names from the items that are defined in this linker.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instantiation fails, e.g. because an import
+    /// is not defined in this linker, see [`Instance::new_with_options`].
+    pub fn instantiate(
+        &self,
+        store: impl AsContextMut<Engine>,
+        module: &Module,
+    ) -> anyhow::Result<Instance> {
+        self.instantiate_with_options(store, module, &InstanceOptions::new())
+    }
+
+    /// Instantiates the `module` in the `store`, like
+    /// [`Linker::instantiate`], using the provided instantiation `options`.
+    ///
+    /// Only the items that the `module` imports are passed on, see
+    /// [`InstanceOptions::filter_imports`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instantiation fails, e.g. because an import
+    /// is not defined in this linker, see [`Instance::new_with_options`].
+    pub fn instantiate_with_options(
+        &self,
+        store: impl AsContextMut<Engine>,
+        module: &Module,
+        options: &InstanceOptions,
+    ) -> anyhow::Result<Instance> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Linker::instantiate").entered();
+
+        Instance::new_with_options(
+            store,
+            module,
+            &self.imports,
+            &options.clone().filter_imports(true),
+        )
+    }
+
+    /// Computes an order in which the named `modules` can be instantiated,
+    /// such that every module is instantiated after the modules whose
+    /// exports it imports.
+    ///
+    /// A module depends on another module in the set if it imports an item
+    /// from the other module's name. Imports from names outside of the set
+    /// are expected to be defined in the linker instead. Modules without
+    /// dependencies between them keep their relative order.
+    ///
+    /// Returns the indices of the `modules` in instantiation order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if two modules have the same name, if a module
+    /// imports an item that its dependency does not export, or if the
+    /// dependencies are cyclic.
+    pub fn instantiation_order(modules: &[(&str, &Module)]) -> anyhow::Result<Vec<usize>> {
+        let mut indices = FxHashMap::default();
+        for (index, (name, _)) in modules.iter().enumerate() {
+            if indices.insert(*name, index).is_some() {
+                return Err(
+                    Error::Link(format!("module `{name}` is defined more than once")).into(),
+                );
+            }
+        }
+
+        let mut dependents = vec![Vec::new(); modules.len()];
+        let mut dependencies = vec![0_usize; modules.len()];
+
+        for (index, (name, module)) in modules.iter().enumerate() {
+            let mut seen = FxHashSet::default();
+
+            for ImportType {
+                module: module_name,
+                name: import_name,
+                ..
+            } in WasmModule::imports(*module)
+            {
+                let Some(&dependency) = indices.get(module_name) else {
+                    continue;
+                };
+
+                let (_, dependency_module) = modules[dependency];
+                if WasmModule::get_export(dependency_module, import_name).is_none() {
+                    return Err(Error::Link(format!(
+                        "module `{name}` imports `{module_name}`.`{import_name}`, which module \
+                         `{module_name}` does not export"
+                    ))
+                    .into());
+                }
+
+                if seen.insert(dependency) {
+                    dependents[dependency].push(index);
+                    dependencies[index] += 1;
+                }
+            }
+        }
+
+        // Kahn's algorithm, always picking the first ready module
+        let mut ready = dependencies
+            .iter()
+            .enumerate()
+            .filter(|(_, dependencies)| **dependencies == 0)
+            .map(|(index, _)| index)
+            .collect::<BTreeSet<_>>();
+        let mut order = Vec::with_capacity(modules.len());
+
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+
+            for &dependent in &dependents[index] {
+                dependencies[dependent] -= 1;
+                if dependencies[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+
+        if order.len() < modules.len() {
+            let cyclic = modules
+                .iter()
+                .zip(&dependencies)
+                .filter(|(_, dependencies)| **dependencies > 0)
+                .map(|((name, _), _)| format!("`{name}`"))
+                .collect::<Vec<_>>();
+
+            return Err(Error::Link(format!(
+                "the modules {} have cyclic dependencies",
+                cyclic.join(", ")
+            ))
+            .into());
+        }
+
+        Ok(order)
+    }
+
+    /// Instantiates the named `modules` in the `store` in their
+    /// [`Linker::instantiation_order`], defining the exports of every
+    /// instance under its name using [`Linker::instance`] such that the
+    /// modules that depend on it can import them.
+    ///
+    /// Returns the instances in the order of the `modules`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no instantiation order exists or if any
+    /// instantiation fails, in which case the exports of the modules that
+    /// were already instantiated remain defined.
+    pub fn instantiate_all(
+        &mut self,
+        mut store: impl AsContextMut<Engine>,
+        modules: &[(&str, &Module)],
+    ) -> anyhow::Result<Vec<Instance>> {
+        let order = Self::instantiation_order(modules)?;
+
+        let mut instances = vec![None; modules.len()];
+
+        for index in order {
+            let (name, module) = modules[index];
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(name, "Linker::instantiate_all");
+
+            let instance = self.instantiate(store.as_context_mut(), module)?;
+            self.instance(store.as_context(), name, &instance)?;
+
+            instances[index] = Some(instance);
+        }
+
+        Ok(instances.into_iter().flatten().collect())
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Extern, Imports, Value, WasmMemory},
+    ValueType,
+};
+
+use crate::{Engine, Func, FuncSignature, Instance, Memory};
+
+/// A standard import set that lets guests emit [`tracing`] events.
+///
+/// The import set provides a single function import,
+/// `host.log(level: i32, ptr: i32, len: i32)`, which reads the UTF-8 message
+/// at `ptr..ptr+len` from the guest's memory and emits it as a [`tracing`]
+/// event with the `guest` target. The `level` is `0` for trace, `1` for
+/// debug, `2` for info, `3` for warn, and `4` (or higher) for error.
+///
+/// Since the guest's memory only exists after instantiation, it must be
+/// provided using [`GuestLogger::bind_memory`] or
+/// [`GuestLogger::bind_instance`] before the guest starts logging.
+///
+/// [`tracing`]: https://docs.rs/tracing/0.1/tracing/
+#[derive(Debug, Clone, Default)]
+pub struct GuestLogger {
+    /// The guest memory from which log messages are read
+    memory: Arc<Mutex<Option<Memory>>>,
+}
+
+impl GuestLogger {
+    /// The import name of the logging function
+    pub const LOG: &'static str = "log";
+    /// The import module name of the logging import set
+    pub const MODULE: &'static str = "host";
+
+    /// Creates a new logger that is not yet bound to a guest memory.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines the `host.log` import in the `imports`.
+    pub fn define<T: 'static>(
+        &self,
+        ctx: impl AsContextMut<Engine, UserState = T>,
+        imports: &mut Imports<Engine>,
+    ) {
+        let memory = Arc::clone(&self.memory);
+
+        let log = Func::new_with_signature(
+            ctx,
+            FuncSignature::builder()
+                .name(Self::LOG)
+                .param("level", ValueType::I32)
+                .param("ptr", ValueType::I32)
+                .param("len", ValueType::I32)
+                .build(),
+            move |ctx, args, _results| {
+                let [Value::I32(level), Value::I32(ptr), Value::I32(len)] = args else {
+                    anyhow::bail!("host.log called with invalid arguments {args:?}");
+                };
+
+                let Some(memory) = memory
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+                else {
+                    anyhow::bail!("host.log called before the guest memory was bound");
+                };
+
+                #[allow(clippy::cast_sign_loss)]
+                let (ptr, len) = (*ptr as u32 as usize, *len as u32 as usize);
+
+                let mut message = vec![0; len];
+                memory.read(ctx, ptr, &mut message)?;
+                let message = String::from_utf8_lossy(&message);
+
+                match level {
+                    ..=0 => tracing::trace!(target: "guest", "{message}"),
+                    1 => tracing::debug!(target: "guest", "{message}"),
+                    2 => tracing::info!(target: "guest", "{message}"),
+                    3 => tracing::warn!(target: "guest", "{message}"),
+                    _ => tracing::error!(target: "guest", "{message}"),
+                }
+
+                Ok(())
+            },
+        );
+
+        imports.define(Self::MODULE, Self::LOG, Extern::Func(log));
+    }
+
+    /// Binds the logger to the guest `memory` from which messages are read.
+    pub fn bind_memory(&self, memory: &Memory) {
+        *self.memory.lock().unwrap_or_else(PoisonError::into_inner) = Some(memory.clone());
+    }
+
+    /// Binds the logger to the memory that the `instance` exports under the
+    /// `name`, e.g. `"memory"`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `instance` does not export a memory with the
+    /// `name`.
+    pub fn bind_instance(
+        &self,
+        ctx: impl AsContext<Engine>,
+        instance: &Instance,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let memory = instance.get_memory(ctx, name)?;
+
+        self.bind_memory(&memory);
+
+        Ok(())
+    }
+}
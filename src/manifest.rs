@@ -0,0 +1,298 @@
+use fxhash::FxHashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use wasm_runtime_layer::{
+    backend::{AsContextMut, Extern, Imports, Value, WasmGlobal, WasmMemory, WasmModule},
+    ExternType, ImportType, MemoryType,
+};
+
+use crate::{wrap::IntoFunc, Engine, Error, Func, Global, Memory, Module};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A declarative manifest of the imports that a plugin requires and of how
+/// the host satisfies them, e.g. to configure a plugin host from data instead
+/// of code.
+///
+/// Each import is satisfied either by a host function, which is referred to
+/// by an id and registered with an [`ImportsBuilder`], by a new memory, or by
+/// a new global with a constant value. The manifest is materialized into
+/// [`Imports`] using [`ImportsBuilder::build`].
+///
+/// With the `serde` feature, the manifest can be deserialized from any
+/// format, e.g. from JSON using `ImportManifest::from_json`.
+///
+/// [`Imports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Imports.html
+pub struct ImportManifest {
+    /// The declared imports
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub imports: Vec<ManifestImport>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// An import that is declared in an [`ImportManifest`].
+pub struct ManifestImport {
+    /// The module name of the import
+    pub module: String,
+    /// The name of the import
+    pub name: String,
+    /// How the import is satisfied
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub source: ImportSource,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "lowercase"))]
+/// How an import of an [`ImportManifest`] is satisfied.
+pub enum ImportSource {
+    /// A host function that is registered under the `id` with
+    /// [`ImportsBuilder::func`]
+    Func {
+        /// The id of the host function
+        id: String,
+    },
+    /// A new memory with the `initial` and `maximum` number of pages
+    Memory {
+        /// The initial number of pages
+        initial: u32,
+        /// The maximum number of pages, if limited
+        #[cfg_attr(feature = "serde", serde(default))]
+        maximum: Option<u32>,
+        /// Whether the memory is shared, see [`Memory::new_shared`]
+        #[cfg_attr(feature = "serde", serde(default))]
+        shared: bool,
+    },
+    /// A new global with a constant initial `value`
+    Global {
+        /// The initial value
+        value: ConstValue,
+        /// Whether the global is mutable
+        #[cfg_attr(feature = "serde", serde(default))]
+        mutable: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "lowercase")
+)]
+/// A constant numeric value of a global in an [`ImportManifest`].
+pub enum ConstValue {
+    /// A 32-bit integer value
+    I32(i32),
+    /// A 64-bit integer value
+    I64(i64),
+    /// A 32-bit floating point value
+    F32(f32),
+    /// A 64-bit floating point value
+    F64(f64),
+}
+
+impl From<ConstValue> for Value<Engine> {
+    fn from(value: ConstValue) -> Self {
+        match value {
+            ConstValue::I32(v) => Self::I32(v),
+            ConstValue::I64(v) => Self::I64(v),
+            ConstValue::F32(v) => Self::F32(v),
+            ConstValue::F64(v) => Self::F64(v),
+        }
+    }
+}
+
+impl ImportManifest {
+    /// Creates a new manifest without any declared imports.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            imports: Vec::new(),
+        }
+    }
+
+    /// Declares that the import `module`.`name` is satisfied by the
+    /// `source`.
+    #[must_use]
+    pub fn with_import(
+        mut self,
+        module: impl Into<String>,
+        name: impl Into<String>,
+        source: ImportSource,
+    ) -> Self {
+        self.imports.push(ManifestImport {
+            module: module.into(),
+            name: name.into(),
+            source,
+        });
+        self
+    }
+
+    /// Parses a manifest from its JSON representation, e.g.
+    ///
+    /// ```json
+    /// { "imports": [
+    ///     { "module": "env", "name": "log", "kind": "func", "id": "log" },
+    ///     { "module": "env", "name": "memory", "kind": "memory", "initial": 1 },
+    ///     { "module": "env", "name": "seed", "kind": "global",
+    ///       "value": { "type": "i64", "value": 42 } }
+    /// ] }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Conversion`] if the `json` is not a valid
+    /// manifest.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json)
+            .map_err(|err| Error::Conversion(format!("invalid import manifest: {err}")))
+    }
+
+    /// Checks that the manifest declares every import of the `module` with
+    /// a source of the matching kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Link`] that names the first import of the
+    /// `module` that is not declared or whose source has a different kind.
+    pub fn verify(&self, module: &Module) -> anyhow::Result<()> {
+        for ImportType {
+            module: module_name,
+            name,
+            ty,
+        } in WasmModule::imports(module)
+        {
+            let Some(import) = self
+                .imports
+                .iter()
+                .find(|import| import.module == module_name && import.name == name)
+            else {
+                return Err(Error::Link(format!(
+                    "import `{module_name}`.`{name}` is not declared in the manifest"
+                ))
+                .into());
+            };
+
+            let matches = matches!(
+                (&ty, &import.source),
+                (ExternType::Func(_), ImportSource::Func { .. })
+                    | (ExternType::Memory(_), ImportSource::Memory { .. })
+                    | (ExternType::Global(_), ImportSource::Global { .. })
+            );
+
+            if !matches {
+                return Err(Error::Link(format!(
+                    "import `{module_name}`.`{name}` is expected as {ty:?} but the manifest \
+                     declares {:?}",
+                    import.source
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A builder that materializes the [`Imports`] that are declared in an
+/// [`ImportManifest`], using the host functions that are registered under
+/// their ids.
+///
+/// [`Imports`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Imports.html
+pub struct ImportsBuilder {
+    /// The registered host functions by their ids
+    funcs: FxHashMap<String, Func>,
+}
+
+impl ImportsBuilder {
+    /// Creates a new builder without any registered host functions.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the host function `func` under the `id`, replacing any
+    /// function that was previously registered under the same `id`.
+    pub fn func(&mut self, id: impl Into<String>, func: Func) -> &mut Self {
+        self.funcs.insert(id.into(), func);
+        self
+    }
+
+    /// Registers a host function, created from the Rust closure `func`
+    /// using [`Func::wrap`], under the `id`.
+    pub fn func_wrap<T, Params, Results>(
+        &mut self,
+        ctx: impl AsContextMut<Engine, UserState = T>,
+        id: impl Into<String>,
+        func: impl IntoFunc<T, Params, Results>,
+    ) -> &mut Self {
+        self.func(id, Func::wrap(ctx, func))
+    }
+
+    /// Materializes the imports that are declared in the `manifest`, creating
+    /// new memories and globals in the store of the `ctx`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Link`] if the manifest refers to a host function
+    /// id that is not registered, or an error if a memory cannot be created.
+    pub fn build(
+        &self,
+        mut ctx: impl AsContextMut<Engine>,
+        manifest: &ImportManifest,
+    ) -> anyhow::Result<Imports<Engine>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("ImportsBuilder::build").entered();
+
+        let mut imports = Imports::new();
+
+        for ManifestImport {
+            module,
+            name,
+            source,
+        } in &manifest.imports
+        {
+            let item = match source {
+                ImportSource::Func { id } => {
+                    let Some(func) = self.funcs.get(id) else {
+                        return Err(Error::Link(format!(
+                            "import `{module}`.`{name}` refers to the unregistered host function \
+                             `{id}`"
+                        ))
+                        .into());
+                    };
+
+                    Extern::Func(func.clone())
+                },
+                ImportSource::Memory {
+                    initial,
+                    maximum,
+                    shared,
+                } => {
+                    let ty = MemoryType::new(*initial, *maximum);
+
+                    Extern::Memory(if *shared {
+                        Memory::new_shared(ctx.as_context_mut(), ty)?
+                    } else {
+                        Memory::new(ctx.as_context_mut(), ty)?
+                    })
+                },
+                ImportSource::Global { value, mutable } => Extern::Global(Global::new(
+                    ctx.as_context_mut(),
+                    Value::from(*value),
+                    *mutable,
+                )),
+            };
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(module, name, "materialized manifest import");
+
+            imports.define(module, name, item);
+        }
+
+        Ok(imports)
+    }
+}
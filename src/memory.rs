@@ -1,25 +1,64 @@
-use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyBytes};
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell};
 use wasm_runtime_layer::{
     backend::{AsContext, AsContextMut, WasmMemory},
     MemoryType,
 };
 
 use crate::{
-    conversion::{create_js_object, instanceof, js_uint8_array_new, ToPy},
-    Engine,
+    capabilities::check_cross_origin_isolation,
+    conversion::{create_js_object, instanceof, js_uint8_array_new, ToPy, V128},
+    history::MutationKind,
+    Engine, Error,
 };
 
+/// Size of a WebAssembly memory page in bytes
+pub const PAGE_SIZE: u64 = 1 << 16;
+
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 /// A WASM memory.
 ///
 /// This type wraps a [`WebAssembly.Memory`] from the JavaScript API.
 ///
+/// # Atomicity
+///
+/// [`Memory::read`] and [`Memory::write`] copy all bytes within a single
+/// JavaScript call, during which no guest code can run. A read or write is
+/// therefore never interleaved with other accesses from the same thread.
+///
+/// Growing a memory detaches its previous `ArrayBuffer`. Every access looks up
+/// the memory's current buffer and, should it find the buffer detached, e.g.
+/// because a re-entrant guest call grew the memory, retries the access on the
+/// fresh buffer.
+///
+/// # Shared memories
+///
+/// Shared memories, which are backed by a `SharedArrayBuffer`, can be
+/// created using [`Memory::new_shared`] and are accepted when exported by a
+/// threaded module. Since other threads may access a shared memory
+/// concurrently, [`Memory::read`] first copies the bytes into a private
+/// snapshot before converting them.
+///
 /// [`WebAssembly.Memory`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Memory
+/// [`Memory::read`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.read
+/// [`Memory::write`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.write
 pub struct Memory {
     /// The memory value
     memory: Py<PyAny>,
     /// The memory type
     ty: MemoryType,
+    /// The observed memory usage, shared between clones
+    usage: Arc<MemoryUsage>,
+    /// Whether the memory is shared, i.e. backed by a `SharedArrayBuffer`
+    shared: bool,
 }
 
 impl Clone for Memory {
@@ -27,12 +66,16 @@ impl Clone for Memory {
         Python::with_gil(|py| Self {
             memory: self.memory.clone_ref(py),
             ty: self.ty,
+            usage: Arc::clone(&self.usage),
+            shared: self.shared,
         })
     }
 }
 
 impl WasmMemory<Engine> for Memory {
-    fn new(_ctx: impl AsContextMut<Engine>, ty: MemoryType) -> anyhow::Result<Self> {
+    fn new(mut ctx: impl AsContextMut<Engine>, ty: MemoryType) -> anyhow::Result<Self> {
+        limit_new_memory(ctx.as_context_mut(), ty)?;
+
         Python::with_gil(|py| {
             #[cfg(feature = "tracing")]
             tracing::debug!(?ty, "Memory::new");
@@ -45,9 +88,14 @@ impl WasmMemory<Engine> for Memory {
 
             let memory = web_assembly_memory_new(py)?.call1((desc,))?;
 
+            let usage = Arc::new(MemoryUsage::default());
+            usage.observe(byte_length(&memory)?);
+
             Ok(Self {
                 memory: memory.unbind(),
                 ty,
+                usage,
+                shared: false,
             })
         })
     }
@@ -56,39 +104,35 @@ impl WasmMemory<Engine> for Memory {
         self.ty
     }
 
-    fn grow(&self, _ctx: impl AsContextMut<Engine>, additional: u32) -> anyhow::Result<u32> {
+    fn grow(&self, mut ctx: impl AsContextMut<Engine>, additional: u32) -> anyhow::Result<u32> {
         Python::with_gil(|py| {
             let memory = self.memory.bind(py);
 
             #[cfg(feature = "tracing")]
             tracing::debug!(memory = %memory, ?self.ty, additional, "Memory::grow");
 
+            let current = byte_length(memory)?;
+            ctx.as_context_mut().limit_memory_growth(
+                bytes_to_usize(current),
+                bytes_to_usize(current.saturating_add(u64::from(additional) * PAGE_SIZE)),
+                self.ty
+                    .maximum_pages()
+                    .map(|maximum| bytes_to_usize(u64::from(maximum) * PAGE_SIZE)),
+            )?;
+
             let old_pages = memory
                 .call_method1(intern!(py, "grow"), (additional,))?
                 .extract()?;
 
-            Ok(old_pages)
+            self.usage.observe(byte_length(memory)?);
+
+            pages_to_u32(old_pages)
         })
     }
 
-    fn current_pages(&self, _ctx: impl AsContext<Engine>) -> u32 {
-        const PAGE_SIZE: u64 = 1 << 16;
-
-        Python::with_gil(|py| -> Result<u32, PyErr> {
-            let memory = self.memory.bind(py);
-
-            #[cfg(feature = "tracing")]
-            tracing::debug!(memory = %memory, ?self.ty, "Memory::current_pages");
-
-            let byte_len: u64 = memory
-                .getattr(intern!(py, "buffer"))?
-                .getattr(intern!(py, "byteLength"))?
-                .extract()?;
-
-            let pages = u32::try_from(byte_len / PAGE_SIZE)?;
-            Ok(pages)
-        })
-        .expect("Memory::current_pages should not fail")
+    fn current_pages(&self, ctx: impl AsContext<Engine>) -> u32 {
+        self.try_current_pages(ctx)
+            .expect("Memory::current_pages should not fail")
     }
 
     fn read(
@@ -97,41 +141,24 @@ impl WasmMemory<Engine> for Memory {
         offset: usize,
         buffer: &mut [u8],
     ) -> anyhow::Result<()> {
-        Python::with_gil(|py| {
-            let memory = self.memory.bind(py);
-
-            #[cfg(feature = "tracing")]
-            tracing::debug!(memory = %memory, ?self.ty, offset, len = buffer.len(), "Memory::read");
-
-            let memory = memory.getattr(intern!(py, "buffer"))?;
-            let memory = js_uint8_array_new(py)?.call1((memory, offset, buffer.len()))?;
-
-            let bytes: Bound<PyBytes> = memory.call_method0(intern!(py, "to_bytes"))?.extract()?;
-            buffer.copy_from_slice(bytes.as_bytes());
-
-            Ok(())
-        })
+        Python::with_gil(|py| self.read_with_gil(py, offset, buffer))
     }
 
     fn write(
         &self,
-        _ctx: impl AsContextMut<Engine>,
+        mut ctx: impl AsContextMut<Engine>,
         offset: usize,
         buffer: &[u8],
     ) -> anyhow::Result<()> {
-        Python::with_gil(|py| {
-            let memory = self.memory.bind(py);
+        Python::with_gil(|py| self.write_with_gil(py, offset, buffer))?;
 
-            #[cfg(feature = "tracing")]
-            tracing::debug!(memory = %memory, ?self.ty, offset, len = buffer.len(), "Memory::write");
-
-            let memory = memory.getattr(intern!(py, "buffer"))?;
-            let memory = js_uint8_array_new(py)?.call1((memory, offset, buffer.len()))?;
+        ctx.as_context_mut()
+            .record_mutation(|| MutationKind::MemoryWrite {
+                offset,
+                len: buffer.len(),
+            });
 
-            memory.call_method1(intern!(py, "assign"), (buffer,))?;
-
-            Ok(())
-        })
+        Ok(())
     }
 }
 
@@ -145,31 +172,791 @@ impl ToPy for Memory {
 }
 
 impl Memory {
+    /// Returns the type of this memory.
+    ///
+    /// Unlike [`Memory::ty`], this method does not require a store context,
+    /// e.g. so that bindings generators or loggers can inspect the type.
+    ///
+    /// [`Memory::ty`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.ty
+    #[must_use]
+    pub const fn ty_ref(&self) -> &MemoryType {
+        &self.ty
+    }
+
+    /// Returns the underlying [`WebAssembly.Memory`], e.g. to pass it to
+    /// hand-written JavaScript glue code.
+    ///
+    /// [`WebAssembly.Memory`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Memory
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.memory.clone_ref(py)
+    }
+
+    /// Wraps the existing JavaScript `memory`, e.g. one that was created by
+    /// hand-written JavaScript glue code, with the type `ty`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `memory` is not a [`WebAssembly.Memory`] or if it
+    /// is smaller than the initial size of `ty`.
+    ///
+    /// [`WebAssembly.Memory`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Memory
+    pub fn from_js(memory: &Bound<PyAny>, ty: MemoryType) -> anyhow::Result<Self> {
+        if !instanceof(
+            memory,
+            web_assembly_memory(memory.py())?,
+            "WebAssembly.Memory",
+        )? {
+            return Err(Error::Conversion(format!(
+                "expected WebAssembly.Memory but found {memory:?}"
+            ))
+            .into());
+        }
+
+        let pages = byte_length(memory)? / PAGE_SIZE;
+        if pages < u64::from(ty.initial_pages()) {
+            return Err(Error::Conversion(format!(
+                "the JavaScript memory has {pages} pages but {ty:?} requires at least {}",
+                ty.initial_pages()
+            ))
+            .into());
+        }
+
+        Self::from_exported_memory(memory.clone(), ty)
+    }
+
+    /// Verifies that the JavaScript `memory`, described by `what` in errors,
+    /// has the type `ty` if its type can be reflected using the type
+    /// reflection (js-types) proposal
+    ///
+    /// Since the reflected minimum is the current size of the memory, it only
+    /// needs to be at least the minimum of `ty`.
+    pub(crate) fn verify_exported_memory(
+        memory: &Bound<PyAny>,
+        what: &str,
+        ty: MemoryType,
+    ) -> anyhow::Result<()> {
+        let py = memory.py();
+
+        let Ok(reflected) = memory.call_method0(intern!(py, "type")) else {
+            return Ok(());
+        };
+
+        let minimum: u32 = reflected.getattr(intern!(py, "minimum"))?.extract()?;
+        let maximum: Option<u32> = reflected
+            .getattr(intern!(py, "maximum"))
+            .ok()
+            .and_then(|maximum| maximum.extract().ok());
+
+        if minimum < ty.initial_pages() || maximum != ty.maximum_pages() {
+            anyhow::bail!(
+                "{what} is expected as {ty:?} but has the JavaScript type {{ minimum: {minimum}, \
+                 maximum: {maximum:?} }}"
+            );
+        }
+
+        Ok(())
+    }
+
     /// Construct a memory from an exported memory object
     pub(crate) fn from_exported_memory(
         memory: Bound<PyAny>,
         ty: MemoryType,
     ) -> anyhow::Result<Self> {
-        if !instanceof(&memory, web_assembly_memory(memory.py())?)? {
+        if !instanceof(
+            &memory,
+            web_assembly_memory(memory.py())?,
+            "WebAssembly.Memory",
+        )? {
             anyhow::bail!("expected WebAssembly.Memory but found {memory}");
         }
 
         #[cfg(feature = "tracing")]
         tracing::debug!(memory = %memory, ?ty, "Memory::from_exported_memory");
 
+        // SharedArrayBuffer is only defined if the page is cross-origin isolated
+        let buffer = memory.getattr(intern!(memory.py(), "buffer"))?;
+        let shared = match js_shared_array_buffer(memory.py()) {
+            Ok(shared_array_buffer) => {
+                instanceof(&buffer, shared_array_buffer, "SharedArrayBuffer")?
+            },
+            Err(_) => false,
+        };
+
+        let usage = Arc::new(MemoryUsage::default());
+        usage.observe(byte_length(&memory)?);
+
         Ok(Self {
             memory: memory.unbind(),
             ty,
+            usage,
+            shared,
+        })
+    }
+
+    /// Creates a new shared memory of type `ty`, which is backed by a
+    /// `SharedArrayBuffer`, e.g. to be imported by a threaded module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ty` has no maximum size, which shared memories
+    /// require, if the page is not cross-origin isolated, or if the memory
+    /// cannot be created.
+    pub fn new_shared(mut ctx: impl AsContextMut<Engine>, ty: MemoryType) -> anyhow::Result<Self> {
+        let Some(maximum) = ty.maximum_pages() else {
+            anyhow::bail!("shared memory of type {ty:?} must have a maximum size");
+        };
+
+        limit_new_memory(ctx.as_context_mut(), ty)?;
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?ty, "Memory::new_shared");
+
+            check_cross_origin_isolation(py, "creating a shared memory")?;
+
+            let desc = create_js_object(py)?;
+            desc.setattr(intern!(py, "initial"), ty.initial_pages())?;
+            desc.setattr(intern!(py, "maximum"), maximum)?;
+            desc.setattr(intern!(py, "shared"), true)?;
+
+            let memory = web_assembly_memory_new(py)?.call1((desc,))?;
+
+            let usage = Arc::new(MemoryUsage::default());
+            usage.observe(byte_length(&memory)?);
+
+            Ok(Self {
+                memory: memory.unbind(),
+                ty,
+                usage,
+                shared: true,
+            })
+        })
+    }
+
+    /// Returns `true` if this memory is shared, i.e. backed by a
+    /// `SharedArrayBuffer`.
+    #[must_use]
+    pub const fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Returns the current size of this memory in pages.
+    ///
+    /// Unlike [`Memory::current_pages`], this method supports page counts
+    /// that exceed the range of the 32-bit API, e.g. for memory64.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size of the memory cannot be queried.
+    ///
+    /// [`Memory::current_pages`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.current_pages
+    pub fn current_pages_u64(&self, _ctx: impl AsContext<Engine>) -> anyhow::Result<u64> {
+        Python::with_gil(|py| {
+            let memory = self.memory.bind(py);
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(memory = %memory, ?self.ty, "Memory::current_pages");
+
+            let byte_len = byte_length(memory)?;
+            self.usage.observe(byte_len);
+
+            Ok(byte_len / PAGE_SIZE)
+        })
+    }
+
+    /// Returns the current size of this memory in pages.
+    ///
+    /// Unlike [`Memory::current_pages`], this method returns an error
+    /// instead of panicking if the page count exceeds the range of the
+    /// 32-bit API.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size of the memory cannot be queried or if it
+    /// does not fit into a `u32`.
+    ///
+    /// [`Memory::current_pages`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.current_pages
+    pub fn try_current_pages(&self, ctx: impl AsContext<Engine>) -> anyhow::Result<u32> {
+        pages_to_u32(self.current_pages_u64(ctx)?)
+    }
+
+    /// Creates a new memory with the same limits and identical contents as
+    /// this memory, e.g. to fork the guest state for speculative execution.
+    ///
+    /// The new memory starts with the current size of this memory and its
+    /// contents are copied with a single bulk copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new memory cannot be created, e.g. since the
+    /// resource limiter of the store denied its creation.
+    pub fn duplicate(&self, mut ctx: impl AsContextMut<Engine>) -> anyhow::Result<Self> {
+        Python::with_gil(|py| {
+            let memory = self.memory.bind(py);
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(memory = %memory, ?self.ty, "Memory::duplicate");
+
+            let buffer = memory.getattr(intern!(py, "buffer"))?;
+            let byte_len: u64 = buffer.getattr(intern!(py, "byteLength"))?.extract()?;
+            let pages = pages_to_u32(byte_len / PAGE_SIZE)?;
+            let ty = MemoryType::new(pages, self.ty.maximum_pages());
+
+            limit_new_memory(ctx.as_context_mut(), ty)?;
+
+            let desc = create_js_object(py)?;
+            desc.setattr(intern!(py, "initial"), pages)?;
+            if let Some(maximum) = self.ty.maximum_pages() {
+                desc.setattr(intern!(py, "maximum"), maximum)?;
+            }
+            if self.shared {
+                desc.setattr(intern!(py, "shared"), true)?;
+            }
+
+            let duplicate = web_assembly_memory_new(py)?.call1((desc,))?;
+
+            js_uint8_array_new(py)?
+                .call1((duplicate.getattr(intern!(py, "buffer"))?,))?
+                .call_method1(
+                    intern!(py, "set"),
+                    (js_uint8_array_new(py)?.call1((buffer,))?,),
+                )?;
+
+            let usage = Arc::new(MemoryUsage::default());
+            usage.observe(byte_len);
+
+            Ok(Self {
+                memory: duplicate.unbind(),
+                ty,
+                usage,
+                shared: self.shared,
+            })
+        })
+    }
+
+    /// Returns the maximum size of this memory in bytes that has been
+    /// observed so far.
+    ///
+    /// The size is observed when the memory is created or grown from the
+    /// host, when its current size is queried, and periodically during host
+    /// reads and writes, which also catches growth from within the guest.
+    /// Calling this method also observes the current size of the memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current size of the memory cannot be queried.
+    #[must_use]
+    pub fn high_water_mark(&self, _ctx: impl AsContext<Engine>) -> u64 {
+        Python::with_gil(|py| -> Result<u64, PyErr> {
+            let memory = self.memory.bind(py);
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(memory = %memory, ?self.ty, "Memory::high_water_mark");
+
+            self.usage.observe(byte_length(memory)?);
+
+            Ok(self.usage.high_water_mark.load(Ordering::Relaxed))
+        })
+        .expect("Memory::high_water_mark should not fail")
+    }
+
+    /// Reads many disjoint ranges of this memory within a single acquisition
+    /// of the GIL, where each range starts at its offset and is as long as
+    /// its buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any range is out of bounds of this memory, in which
+    /// case the preceding ranges have already been read.
+    pub fn read_many(
+        &self,
+        _ctx: impl AsContext<Engine>,
+        ranges: &mut [(usize, &mut [u8])],
+    ) -> anyhow::Result<()> {
+        Python::with_gil(|py| {
+            ranges
+                .iter_mut()
+                .try_for_each(|(offset, buffer)| self.read_with_gil(py, *offset, buffer))
+        })
+    }
+
+    /// Writes many disjoint ranges of this memory within a single acquisition
+    /// of the GIL, where each buffer is written at its offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any range is out of bounds of this memory, in which
+    /// case the preceding ranges have already been written.
+    pub fn write_many(
+        &self,
+        mut ctx: impl AsContextMut<Engine>,
+        ranges: &[(usize, &[u8])],
+    ) -> anyhow::Result<()> {
+        let mut store = ctx.as_context_mut();
+
+        Python::with_gil(|py| {
+            ranges.iter().try_for_each(|(offset, buffer)| {
+                self.write_with_gil(py, *offset, buffer)?;
+
+                store.record_mutation(|| MutationKind::MemoryWrite {
+                    offset: *offset,
+                    len: buffer.len(),
+                });
+
+                Ok(())
+            })
+        })
+    }
+
+    /// Calls `f` with the `len` bytes at `offset` in this memory, e.g. such
+    /// that a serialization library can deserialize straight from them.
+    ///
+    /// Since the memory lives in a JavaScript `ArrayBuffer`, the bytes are
+    /// copied once into a scratch buffer that is reused across calls, but
+    /// without any intermediate allocations.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is out of bounds of this memory.
+    pub fn with_bytes<R>(
+        &self,
+        _ctx: impl AsContext<Engine>,
+        offset: usize,
+        len: usize,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> anyhow::Result<R> {
+        with_scratch(len, |scratch| {
+            Python::with_gil(|py| self.read_with_gil(py, offset, scratch))?;
+
+            Ok(f(scratch))
         })
     }
+
+    /// Calls `f` with the `len` bytes at `offset` in this memory, which `f`
+    /// may modify, e.g. such that a serialization library can serialize
+    /// straight into them.
+    ///
+    /// Like [`Memory::with_bytes`], the bytes are copied into a reused scratch
+    /// buffer, and copied back into the memory once `f` returns. Concurrent
+    /// writes to the range of a shared memory by other threads while `f`
+    /// runs are therefore overwritten.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is out of bounds of this memory.
+    pub fn with_bytes_mut<R>(
+        &self,
+        mut ctx: impl AsContextMut<Engine>,
+        offset: usize,
+        len: usize,
+        f: impl FnOnce(&mut [u8]) -> R,
+    ) -> anyhow::Result<R> {
+        let result = with_scratch(len, |scratch| {
+            Python::with_gil(|py| self.read_with_gil(py, offset, scratch))?;
+
+            let result = f(scratch);
+
+            Python::with_gil(|py| self.write_with_gil(py, offset, scratch))?;
+
+            Ok(result)
+        })?;
+
+        ctx.as_context_mut()
+            .record_mutation(|| MutationKind::MemoryWrite { offset, len });
+
+        Ok(result)
+    }
+
+    /// Copies the bytes at `offset` in this memory into the `buffer`
+    ///
+    /// The bytes are copied by JavaScript directly into a `memoryview` that
+    /// borrows the `buffer`, without an intermediate Python bytes object.
+    fn read_with_gil(&self, py: Python, offset: usize, buffer: &mut [u8]) -> anyhow::Result<()> {
+        let memory = self.memory.bind(py);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(memory = %memory, ?self.ty, offset, len = buffer.len(), "Memory::read");
+
+        self.usage.sample(memory)?;
+
+        with_uint8_array_view(memory, offset, buffer.len(), |view| {
+            // snapshot shared memories, which other threads may write to
+            let view = if self.shared {
+                js_uint8_array_new(py)?.call1((view,))?
+            } else {
+                view.clone()
+            };
+
+            with_borrowed_memoryview_mut(py, buffer, |buffer| {
+                view.call_method1(intern!(py, "assign_to"), (buffer,))?;
+                Ok(())
+            })
+        })
+    }
+
+    /// Copies the `buffer` to `offset` in this memory
+    ///
+    /// The bytes are copied by JavaScript directly from a `memoryview` that
+    /// borrows the `buffer`, without an intermediate Python bytes object.
+    fn write_with_gil(&self, py: Python, offset: usize, buffer: &[u8]) -> anyhow::Result<()> {
+        let memory = self.memory.bind(py);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(memory = %memory, ?self.ty, offset, len = buffer.len(), "Memory::write");
+
+        self.usage.sample(memory)?;
+
+        with_uint8_array_view(memory, offset, buffer.len(), |view| {
+            with_borrowed_memoryview(py, buffer, |buffer| {
+                view.call_method1(intern!(py, "assign"), (buffer,))?;
+                Ok(())
+            })
+        })
+    }
+
+    /// Reads the `v128` SIMD vector at `offset` in this memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vector is out of bounds of this memory.
+    pub fn read_v128(&self, ctx: impl AsContext<Engine>, offset: usize) -> anyhow::Result<V128> {
+        let mut bytes = [0; 16];
+        self.read(ctx, offset, &mut bytes)?;
+        Ok(V128::from_le_bytes(bytes))
+    }
+
+    /// Writes the `v128` SIMD vector `value` at `offset` in this memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vector is out of bounds of this memory.
+    pub fn write_v128(
+        &self,
+        ctx: impl AsContextMut<Engine>,
+        offset: usize,
+        value: &V128,
+    ) -> anyhow::Result<()> {
+        self.write(ctx, offset, &value.to_le_bytes())
+    }
+
+    /// Copies `len` bytes at `offset` in this memory into the [`GPUBuffer`]
+    /// `gpu_buffer` at `buffer_offset`, e.g. so that WebGPU pipelines can
+    /// consume guest-generated data.
+    ///
+    /// The bytes are copied from a view into this memory within JavaScript,
+    /// without an intermediate copy into Python. If the `gpu_buffer` is
+    /// currently mapped, the bytes are written directly into its mapped
+    /// range. Otherwise, they are scheduled for upload using the
+    /// [`GPUQueue`] `queue`'s [`writeBuffer`].
+    ///
+    /// Like [`Memory::read`], the copy is retried on the fresh buffer if the
+    /// memory's buffer was detached by growth.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `buffer_offset` or `len` are not multiples of
+    /// four bytes, if the bytes are out of bounds of this memory, or if
+    /// WebGPU rejects the upload.
+    ///
+    /// [`GPUBuffer`]: https://developer.mozilla.org/en-US/docs/Web/API/GPUBuffer
+    /// [`GPUQueue`]: https://developer.mozilla.org/en-US/docs/Web/API/GPUQueue
+    /// [`writeBuffer`]: https://developer.mozilla.org/en-US/docs/Web/API/GPUQueue/writeBuffer
+    /// [`Memory::read`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Memory.html#method.read
+    pub fn write_to_gpu_buffer(
+        &self,
+        _ctx: impl AsContext<Engine>,
+        offset: usize,
+        len: usize,
+        queue: &Bound<PyAny>,
+        gpu_buffer: &Bound<PyAny>,
+        buffer_offset: u64,
+    ) -> anyhow::Result<()> {
+        if (buffer_offset % 4 != 0) || (len % 4 != 0) {
+            anyhow::bail!(
+                "GPU buffer upload of {len} bytes at buffer offset {buffer_offset} must be \
+                 aligned to four bytes"
+            );
+        }
+
+        let py = queue.py();
+        let memory = self.memory.bind(py);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(memory = %memory, ?self.ty, offset, len, buffer_offset, "Memory::write_to_gpu_buffer");
+
+        self.usage.sample(memory)?;
+
+        with_uint8_array_view(memory, offset, len, |view| {
+            js_gpu_upload(py)?.call1((queue, gpu_buffer, buffer_offset, view))?;
+
+            Ok(())
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+/// Observed usage statistics of a memory
+struct MemoryUsage {
+    /// The maximum observed size of the memory in bytes
+    high_water_mark: AtomicU64,
+    /// The number of host reads and writes
+    accesses: AtomicU64,
+}
+
+impl MemoryUsage {
+    /// Number of host accesses after which the memory size is sampled
+    const SAMPLE_INTERVAL: u64 = 256;
+
+    fn observe(&self, byte_len: u64) {
+        self.high_water_mark.fetch_max(byte_len, Ordering::Relaxed);
+    }
+
+    fn sample(&self, memory: &Bound<PyAny>) -> Result<(), PyErr> {
+        if self.accesses.fetch_add(1, Ordering::Relaxed) % Self::SAMPLE_INTERVAL == 0 {
+            self.observe(byte_length(memory)?);
+        }
+
+        Ok(())
+    }
+}
+
+/// Calls `f` with a `Uint8Array` view of `len` bytes at `offset` into the
+/// current buffer of the `memory`
+///
+/// If the buffer is detached while the view is created or used, `f` is
+/// retried once with a view into the fresh buffer.
+fn with_uint8_array_view<R>(
+    memory: &Bound<PyAny>,
+    offset: usize,
+    len: usize,
+    mut f: impl FnMut(&Bound<PyAny>) -> Result<R, PyErr>,
+) -> anyhow::Result<R> {
+    const MAX_ATTEMPTS: usize = 2;
+
+    let py = memory.py();
+
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let buffer = memory.getattr(intern!(py, "buffer"))?;
+        let byte_len: u64 = buffer.getattr(intern!(py, "byteLength"))?.extract()?;
+
+        let end = offset
+            .checked_add(len)
+            .and_then(|end| u64::try_from(end).ok());
+        if !end.is_some_and(|end| end <= byte_len) {
+            if (attempt < MAX_ATTEMPTS) && is_detached(&buffer)? {
+                continue;
+            }
+
+            anyhow::bail!(
+                "out of bounds memory access of {len} bytes at offset {offset} in a memory of \
+                 {byte_len} bytes"
+            );
+        }
+
+        let result = js_uint8_array_new(py)?
+            .call1((&buffer, offset, len))
+            .and_then(|view| f(&view));
+
+        match result {
+            Ok(result) => return Ok(result),
+            Err(err) if (attempt < MAX_ATTEMPTS) && is_detached(&buffer)? => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(%err, "retrying memory access on a detached buffer");
+                #[cfg(not(feature = "tracing"))]
+                let _ = err;
+            },
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// The maximum capacity of the scratch buffer that is kept between calls to
+/// [`with_scratch`], one wasm page of [`PAGE_SIZE`] bytes
+const MAX_SCRATCH_CAPACITY: usize = 1 << 16;
+
+/// Calls `f` with a zeroed scratch buffer of `len` bytes
+///
+/// The scratch buffer of the current thread is reused across calls to avoid
+/// allocations. Re-entrant calls, e.g. from inside `f`, allocate a fresh
+/// buffer instead. At most [`MAX_SCRATCH_CAPACITY`] bytes are kept between
+/// calls, such that one large access does not pin its buffer forever.
+fn with_scratch<R>(
+    len: usize,
+    f: impl FnOnce(&mut [u8]) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    thread_local! {
+        static SCRATCH: Cell<Vec<u8>> = const { Cell::new(Vec::new()) };
+    }
+
+    let mut scratch = SCRATCH.with(Cell::take);
+    scratch.clear();
+    scratch.resize(len, 0);
+
+    let result = f(&mut scratch);
+
+    scratch.clear();
+    scratch.shrink_to(MAX_SCRATCH_CAPACITY);
+    SCRATCH.with(|cell| cell.set(scratch));
+
+    result
+}
+
+/// Calls `f` with a read-only Python `memoryview` that borrows the `bytes`
+/// without copying them
+///
+/// The `memoryview` is released before returning, such that it cannot be
+/// used after the borrow ends.
+fn with_borrowed_memoryview<R>(
+    py: Python,
+    bytes: &[u8],
+    f: impl FnOnce(&Bound<PyAny>) -> Result<R, PyErr>,
+) -> Result<R, PyErr> {
+    // Safety: the memoryview is read-only and released before the borrow of
+    //         the bytes ends
+    unsafe { with_raw_memoryview(py, bytes.as_ptr().cast_mut(), bytes.len(), PYBUF_READ, f) }
+}
+
+/// Calls `f` with a writable Python `memoryview` that borrows the `bytes`
+/// without copying them
+///
+/// The `memoryview` is released before returning, such that it cannot be
+/// used after the borrow ends.
+fn with_borrowed_memoryview_mut<R>(
+    py: Python,
+    bytes: &mut [u8],
+    f: impl FnOnce(&Bound<PyAny>) -> Result<R, PyErr>,
+) -> Result<R, PyErr> {
+    // Safety: the memoryview is released before the mutable borrow of the
+    //         bytes ends
+    unsafe { with_raw_memoryview(py, bytes.as_mut_ptr(), bytes.len(), PYBUF_WRITE, f) }
+}
+
+/// Flag for a read-only `memoryview`, see `PyBUF_READ`
+const PYBUF_READ: std::os::raw::c_int = 0x100;
+/// Flag for a writable `memoryview`, see `PyBUF_WRITE`
+const PYBUF_WRITE: std::os::raw::c_int = 0x200;
+
+/// Calls `f` with a Python `memoryview` of the `len` bytes at `ptr`
+///
+/// # Safety
+///
+/// The bytes must be valid for reads, and for writes if `flags` is
+/// `PYBUF_WRITE`, until this function returns.
+unsafe fn with_raw_memoryview<R>(
+    py: Python,
+    ptr: *mut u8,
+    len: usize,
+    flags: std::os::raw::c_int,
+    f: impl FnOnce(&Bound<PyAny>) -> Result<R, PyErr>,
+) -> Result<R, PyErr> {
+    let len = pyo3::ffi::Py_ssize_t::try_from(len)
+        .map_err(|err| pyo3::exceptions::PyOverflowError::new_err(err.to_string()))?;
+
+    let view = Bound::from_owned_ptr_or_err(
+        py,
+        pyo3::ffi::PyMemoryView_FromMemory(ptr.cast(), len, flags),
+    )?;
+
+    let result = f(&view);
+
+    // release the view such that it cannot outlive the borrow, even if a
+    //  reference to it was leaked
+    view.call_method0(intern!(py, "release"))?;
+
+    result
+}
+
+/// Checks if the `ArrayBuffer` has been detached, which resets its length to
+/// zero
+fn is_detached(buffer: &Bound<PyAny>) -> Result<bool, PyErr> {
+    let py = buffer.py();
+
+    if let Ok(detached) = buffer.getattr(intern!(py, "detached")) {
+        if let Ok(detached) = detached.extract() {
+            return Ok(detached);
+        }
+    }
+
+    let byte_len: u64 = buffer.getattr(intern!(py, "byteLength"))?.extract()?;
+    Ok(byte_len == 0)
 }
 
-fn web_assembly_memory(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+/// Converts a page count into a `u32`, with an explicit error if it exceeds
+/// the range of the 32-bit API
+fn pages_to_u32(pages: u64) -> anyhow::Result<u32> {
+    u32::try_from(pages).map_err(|_| {
+        anyhow::anyhow!(
+            "memory size of {pages} pages exceeds the range of the 32-bit page count API, use \
+             Memory::current_pages_u64 instead"
+        )
+    })
+}
+
+/// Asks the resource limiter of the store, if installed, whether a new memory
+/// of the type `ty` may be created
+fn limit_new_memory(mut ctx: impl AsContextMut<Engine>, ty: MemoryType) -> anyhow::Result<()> {
+    ctx.as_context_mut().limit_memory_growth(
+        0,
+        bytes_to_usize(u64::from(ty.initial_pages()) * PAGE_SIZE),
+        ty.maximum_pages()
+            .map(|maximum| bytes_to_usize(u64::from(maximum) * PAGE_SIZE)),
+    )
+}
+
+/// Converts a number of bytes into a `usize`, saturating on overflow
+fn bytes_to_usize(bytes: u64) -> usize {
+    usize::try_from(bytes).unwrap_or(usize::MAX)
+}
+
+fn byte_length(memory: &Bound<PyAny>) -> Result<u64, PyErr> {
+    let py = memory.py();
+
+    memory
+        .getattr(intern!(py, "buffer"))?
+        .getattr(intern!(py, "byteLength"))?
+        .extract()
+}
+
+fn web_assembly_memory(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_MEMORY: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_MEMORY.import(py, "js.WebAssembly", "Memory")
 }
 
-fn web_assembly_memory_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+fn js_shared_array_buffer(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_SHARED_ARRAY_BUFFER: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_SHARED_ARRAY_BUFFER.import(py, "js", "SharedArrayBuffer")
+}
+
+fn web_assembly_memory_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_MEMORY_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_MEMORY_NEW.import(py, "js.WebAssembly.Memory", "new")
 }
+
+fn js_gpu_upload(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_GPU_UPLOAD: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_GPU_UPLOAD
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function gpuUpload(queue, buffer, bufferOffset, view) {
+    if (buffer.mapState === 'mapped') {
+        new Uint8Array(buffer.getMappedRange(bufferOffset, view.length)).set(view);
+    } else {
+        queue.writeBuffer(buffer, bufferOffset, view);
+    }
+}
+gpuUpload
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
@@ -1,17 +1,38 @@
-use std::sync::Arc;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt,
+    hash::Hasher,
+    ops::Range,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use anyhow::Context;
-use fxhash::FxHashMap;
-use pyo3::{prelude::*, sync::GILOnceCell};
+use fxhash::{FxHashMap, FxHashSet};
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyString};
 use wasm_runtime_layer::{
     backend::WasmModule, ExportType, ExternType, FuncType, GlobalType, ImportType, MemoryType,
     TableType, ValueType,
 };
 
+#[cfg(feature = "ed25519")]
+use crate::ModuleVerifier;
 use crate::{
-    conversion::js_uint8_array_new, features::UnsupportedWasmFeatureExtensionError, Engine,
+    capabilities::check_cross_origin_isolation,
+    conversion::{instanceof, js_uint8_array_new},
+    engine::PyodideVersion,
+    features::{UnsupportedWasmFeatureExtensionError, WasmFeatureExtension},
+    interrupt::{instrument_epoch_checks, EPOCH_CHECK_MODULE, EPOCH_CHECK_NAME},
+    memory::PAGE_SIZE,
+    Engine, Error, Table, TableMaximumPolicy,
 };
 
+/// The name of the hidden export under which the start function of a module
+/// with a deferrable start is exported, see
+/// [`EngineConfig::with_deferrable_start`]
+///
+/// [`EngineConfig::with_deferrable_start`]: crate::EngineConfig::with_deferrable_start
+pub const DEFERRED_START_EXPORT: &str = "__pyodide_webassembly_runtime_layer_start";
+
 #[derive(Debug)]
 /// A WASM module.
 ///
@@ -35,40 +56,25 @@ impl Clone for Module {
 }
 
 impl WasmModule<Engine> for Module {
-    fn new(_engine: &Engine, mut stream: impl std::io::Read) -> anyhow::Result<Self> {
-        Python::with_gil(|py| {
-            #[cfg(feature = "tracing")]
-            let _span = tracing::debug_span!("Module::new").entered();
-
-            let mut bytes = Vec::new();
-            stream
-                .read_to_end(&mut bytes)
-                .context("Failed to read module bytes")?;
-
-            let parsed = ParsedModule::parse(&bytes)?;
-
-            let buffer = js_uint8_array_new(py)?.call1((bytes.as_slice(),))?;
-
-            let module = match web_assembly_module_new(py)?.call1((buffer,)) {
-                Ok(module) => module,
-                // check if the error comes from missing feature support
-                // - if so, report the more informative unsupported feature error instead
-                // - if not, bubble up the error that made module instantiation fail
-                Err(err) => match Python::with_gil(|py| {
-                    UnsupportedWasmFeatureExtensionError::check_support(py, &bytes)
-                })? {
-                    Ok(()) => anyhow::bail!(err),
-                    Err(unsupported) => anyhow::bail!(unsupported),
-                },
+    fn new(engine: &Engine, mut stream: impl std::io::Read) -> anyhow::Result<Self> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Module::new").entered();
+
+        let mut module = ModuleStream::new(engine)?;
+
+        let mut chunk = vec![0; ModuleStream::CHUNK_SIZE];
+        loop {
+            let len = match stream.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err).context("Failed to read module bytes"),
             };
 
-            let parsed = Arc::new(parsed);
+            module.write(&chunk[..len])?;
+        }
 
-            Ok(Self {
-                module: module.unbind(),
-                parsed,
-            })
-        })
+        module.finish()
     }
 
     fn exports(&self) -> Box<dyn '_ + Iterator<Item = ExportType<'_>>> {
@@ -97,256 +103,2272 @@ impl WasmModule<Engine> for Module {
 }
 
 impl Module {
+    /// Compiles the module bytes in the JavaScript `Uint8Array` `buffer` into
+    /// a `WebAssembly.Module`, describing unsupported features with the
+    /// `formatter` if provided
+    fn compile<'py>(
+        py: Python<'py>,
+        buffer: &Bound<'py, PyAny>,
+        formatter: Option<fn(&UnsupportedWasmFeatureExtensionError) -> String>,
+    ) -> anyhow::Result<Bound<'py, PyAny>> {
+        match web_assembly_module_new(py)
+            .map_err(|err| Error::EnvironmentUnavailable(err.to_string()))?
+            .call1((buffer,))
+        {
+            Ok(module) => Ok(module),
+            // check if the error comes from missing feature support
+            // - if so, report the more informative unsupported feature error instead
+            // - if not, bubble up the error that made module instantiation fail
+            Err(err) => {
+                // only copy the bytes back from JavaScript in the failure case
+                let bytes: Vec<u8> = buffer.call_method0(intern!(py, "to_bytes"))?.extract()?;
+
+                match UnsupportedWasmFeatureExtensionError::check_support(py, &bytes)? {
+                    Ok(()) => anyhow::bail!(Error::from_js_exception(py, &err, Error::Compile)),
+                    Err(unsupported) => {
+                        // threads are often only unavailable because the page
+                        // is not cross-origin isolated
+                        if unsupported.is_missing(WasmFeatureExtension::Threads) {
+                            check_cross_origin_isolation(py, "the threads feature")?;
+                        }
+
+                        let message = formatter.map_or_else(
+                            || unsupported.to_string(),
+                            |formatter| formatter(&unsupported),
+                        );
+
+                        Err(anyhow::Error::new(unsupported)
+                            .context(Error::UnsupportedFeature(message)))
+                    },
+                }
+            },
+        }
+    }
+
+    /// Reconstructs a module from a precompiled [`WebAssembly.Module`] and
+    /// its [`ModuleMetadata`], without parsing or compiling the module bytes.
+    ///
+    /// Both parts can be cached, e.g. in `IndexedDB`, after obtaining them
+    /// using [`Module::as_js`] and [`Module::metadata`]. The metadata must
+    /// originate from the same module, which is only checked by comparing
+    /// the names of its imports and exports.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `js_module` is not a [`WebAssembly.Module`] or if
+    /// its imports and exports do not match the `metadata`.
+    ///
+    /// [`WebAssembly.Module`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Module
+    pub fn from_parts(js_module: &Bound<PyAny>, metadata: &ModuleMetadata) -> anyhow::Result<Self> {
+        let py = js_module.py();
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Module::from_parts").entered();
+
+        if !instanceof(js_module, web_assembly_module(py)?, "WebAssembly.Module")? {
+            anyhow::bail!("expected WebAssembly.Module but found {js_module:?}");
+        }
+
+        let mut imports = Vec::new();
+        for import in web_assembly_module(py)?
+            .call_method1(intern!(py, "imports"), (js_module,))?
+            .try_iter()?
+        {
+            let import = import?;
+            let import = (
+                import.getattr(intern!(py, "module"))?.extract::<String>()?,
+                import.getattr(intern!(py, "name"))?.extract::<String>()?,
+            );
+
+            if metadata.parsed.epoch_interruption
+                && import.0 == EPOCH_CHECK_MODULE
+                && import.1 == EPOCH_CHECK_NAME
+            {
+                continue;
+            }
+
+            imports.push(import);
+        }
+
+        let mut exports = Vec::new();
+        for export in web_assembly_module(py)?
+            .call_method1(intern!(py, "exports"), (js_module,))?
+            .try_iter()?
+        {
+            let name = export?.getattr(intern!(py, "name"))?.extract::<String>()?;
+
+            if metadata.parsed.start == StartFunction::Deferrable && name == DEFERRED_START_EXPORT {
+                continue;
+            }
+
+            exports.push(name);
+        }
+
+        let parsed = &metadata.parsed;
+
+        if imports.len() != parsed.imports.len() + parsed.tag_imports.len()
+            || imports.iter().any(|import| {
+                !parsed.imports.contains_key(import) && !parsed.tag_imports.contains_key(import)
+            })
+        {
+            anyhow::bail!("the module's imports do not match its metadata");
+        }
+
+        if exports.len() != parsed.exports.len() + parsed.tag_exports.len()
+            || exports.iter().any(|export| {
+                !parsed.exports.contains_key(export) && !parsed.tag_exports.contains_key(export)
+            })
+        {
+            anyhow::bail!("the module's exports do not match its metadata");
+        }
+
+        Ok(Self {
+            module: js_module.clone().unbind(),
+            parsed: Arc::clone(parsed),
+        })
+    }
+
+    /// Returns the underlying [`WebAssembly.Module`], which can be cached
+    /// or sent to other contexts since it is structured-cloneable.
+    ///
+    /// The module can be wrapped again using [`Module::from_parts`], which
+    /// requires its [`Module::metadata`] since the JavaScript API does not
+    /// reflect the types of its imports and exports.
+    ///
+    /// [`WebAssembly.Module`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Module
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.module.clone_ref(py)
+    }
+
+    /// Returns the metadata of this module, i.e. its import and export
+    /// signatures, which can be used to reconstruct the module using
+    /// [`Module::from_parts`].
+    #[must_use]
+    pub fn metadata(&self) -> ModuleMetadata {
+        ModuleMetadata {
+            parsed: Arc::clone(&self.parsed),
+        }
+    }
+
     pub(crate) fn module(&self, py: Python) -> Py<PyAny> {
         self.module.clone_ref(py)
     }
+
+    /// Checks if the module imports an item with the `name` from the
+    /// `module`
+    pub(crate) fn requires_import(&self, module: &str, name: &str) -> bool {
+        self.parsed
+            .imports
+            .contains_key(&(String::from(module), String::from(name)))
+    }
+
+    /// Checks if the item with the `name` that the module imports from the
+    /// `module` may be used by the module.
+    ///
+    /// A function import is unused if it is never called, referenced by a
+    /// `ref.func` instruction, placed into a table, exported, or used as the
+    /// start function. All other imports are conservatively assumed to be
+    /// used. Returns `false` if the module does not have this import.
+    ///
+    /// Hosts can use this analysis to avoid constructing expensive host
+    /// functions for unused imports, which can then be stubbed out using
+    /// [`InstanceOptions::stub_unused_imports`].
+    ///
+    /// [`InstanceOptions::stub_unused_imports`]: crate::InstanceOptions::stub_unused_imports
+    #[must_use]
+    pub fn is_import_used(&self, module: &str, name: &str) -> bool {
+        let key = (String::from(module), String::from(name));
+
+        self.parsed.imports.contains_key(&key) && !self.parsed.unused_imports.contains(&key)
+    }
+
+    /// Returns the statistics of this module, which were computed when the
+    /// module was parsed.
+    ///
+    /// To inspect the statistics of module bytes before committing to their
+    /// compilation, use [`ModuleStats::from_bytes`].
+    #[must_use]
+    pub fn stats(&self) -> ModuleStats {
+        self.parsed.stats
+    }
+
+    /// Returns `true` if the module contains constructs that cannot be
+    /// modelled by its parser, e.g. from a cutting-edge proposal, such that
+    /// its import and export signatures were instead reflected using the
+    /// JavaScript `WebAssembly.Module.imports` and `WebAssembly.Module.exports`
+    /// functions.
+    ///
+    /// With degraded reflection, the types of the imports and exports are only
+    /// known if the browser supports the type reflection (js-types) proposal.
+    /// Otherwise, placeholder types are reported, e.g. function types without
+    /// parameters and results. All imports are assumed to be used, and the
+    /// module's [`ModuleStats`] only cover the parsed prefix of the module.
+    #[must_use]
+    pub fn has_degraded_reflection(&self) -> bool {
+        self.parsed.degraded
+    }
+
+    /// Checks if the module has a start function that can be deferred using
+    /// [`InstanceOptions::defer_start`], which requires the module to be
+    /// compiled with [`EngineConfig::with_deferrable_start`].
+    ///
+    /// [`InstanceOptions::defer_start`]: crate::InstanceOptions::defer_start
+    /// [`EngineConfig::with_deferrable_start`]: crate::EngineConfig::with_deferrable_start
+    #[must_use]
+    pub fn has_deferrable_start(&self) -> bool {
+        self.parsed.start == StartFunction::Deferrable
+    }
+
+    /// Checks if the module was instrumented for epoch interruption, which
+    /// requires it to be compiled with
+    /// [`EngineConfig::with_epoch_interruption`].
+    ///
+    /// [`EngineConfig::with_epoch_interruption`]: crate::EngineConfig::with_epoch_interruption
+    #[must_use]
+    pub fn has_epoch_interruption(&self) -> bool {
+        self.parsed.epoch_interruption
+    }
+
+    /// Returns how the start function of the module is run
+    pub(crate) fn start(&self) -> StartFunction {
+        self.parsed.start
+    }
+
+    /// Returns the deduplicated function types of the module, in the order
+    /// of their first occurrence in the type section.
+    ///
+    /// Identical function types are interned when the module is parsed, so
+    /// that the signatures of all imports and exports share their type
+    /// table entry.
+    #[must_use]
+    pub fn types(&self) -> &[FuncType] {
+        &self.parsed.types
+    }
+
+    /// Returns an iterator over the tag imports of the module, i.e. the
+    /// `module`, `name`, and payload types of each imported [`Tag`].
+    ///
+    /// Tag imports are not included in the module's imports since the
+    /// [`wasm_runtime_layer`] API has no tag externs. They are provided using
+    /// [`InstanceOptions::tag_import`] instead.
+    ///
+    /// [`Tag`]: crate::Tag
+    /// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+    /// [`InstanceOptions::tag_import`]: crate::InstanceOptions::tag_import
+    pub fn tag_imports(&self) -> impl Iterator<Item = (&str, &str, &[ValueType])> {
+        self.parsed
+            .tag_imports
+            .iter()
+            .map(|((module, name), params)| (module.as_str(), name.as_str(), &**params))
+    }
+
+    /// Returns an iterator over the tag exports of the module, i.e. the
+    /// `name` and payload types of each exported [`Tag`], which can be
+    /// obtained from an instance using [`Instance::get_tag`].
+    ///
+    /// [`Tag`]: crate::Tag
+    /// [`Instance::get_tag`]: crate::Instance::get_tag
+    pub fn tag_exports(&self) -> impl Iterator<Item = (&str, &[ValueType])> {
+        self.parsed
+            .tag_exports
+            .iter()
+            .map(|(name, params)| (name.as_str(), &**params))
+    }
+
+    /// Checks if the function import with the `name` from the `module` uses
+    /// `v128` values, which are reported as `externref`s in its type, see
+    /// [`V128`].
+    ///
+    /// [`V128`]: crate::V128
+    #[must_use]
+    pub fn is_v128_import(&self, module: &str, name: &str) -> bool {
+        self.parsed
+            .v128_imports
+            .contains(&(String::from(module), String::from(name)))
+    }
+
+    /// Checks if the function export with the `name` uses `v128` values,
+    /// which are reported as `externref`s in its type, see [`V128`].
+    ///
+    /// [`V128`]: crate::V128
+    #[must_use]
+    pub fn is_v128_export(&self, name: &str) -> bool {
+        self.parsed.v128_exports.contains(name)
+    }
+
+    /// Returns the payload types of the tag export with the `name`
+    pub(crate) fn get_tag_export(&self, name: &str) -> Option<Arc<[ValueType]>> {
+        self.parsed.tag_exports.get(name).cloned()
+    }
+
+    /// Returns an iterator over the function imports that are never used by
+    /// the module, see [`Module::is_import_used`].
+    pub fn unused_imports(&self) -> impl Iterator<Item = ImportType<'_>> {
+        self.parsed.unused_imports.iter().filter_map(|key| {
+            let (module, name) = key;
+            self.parsed.imports.get(key).map(|ty| ImportType {
+                module,
+                name,
+                ty: ty.clone(),
+            })
+        })
+    }
 }
 
-#[derive(Debug)]
-/// A parsed core module with imports and exports
-struct ParsedModule {
-    /// Import signatures
-    imports: FxHashMap<(String, String), ExternType>,
-    /// Export signatures
-    exports: FxHashMap<String, ExternType>,
+/// A [`Module`] whose bytes are streamed in chunks, e.g. while they are
+/// being downloaded.
+///
+/// Each chunk is parsed as soon as it arrives and copied directly into a
+/// JavaScript buffer, from which the module is compiled once the stream is
+/// finished. Unlike buffering all bytes before calling [`Module::new`], the
+/// module bytes are therefore usually not held in full by Rust or Python.
+///
+/// There are two exceptions, in which Rust holds a full copy of the module
+/// bytes:
+///
+/// - With a module verifier, which requires the `ed25519` feature, the stream
+///   buffers all bytes until [`ModuleStream::finish`], since the signature
+///   covers the whole module.
+/// - With epoch interruption, see [`EngineConfig::with_epoch_interruption`],
+///   [`ModuleStream::finish`] copies the finished module out of JavaScript to
+///   instrument it.
+///
+/// The stream also implements [`std::io::Write`], e.g. for use with
+/// [`std::io::copy`].
+///
+/// [`Module::new`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Module.html#method.new
+/// [`EngineConfig::with_epoch_interruption`]: crate::EngineConfig::with_epoch_interruption
+pub struct ModuleStream {
+    /// The incremental parser of the module bytes
+    parser: wasmparser::Parser,
+    /// The extracted import and export signatures
+    module: ModuleParser,
+    /// The bytes that have been written but not yet parsed
+    pending: Vec<u8>,
+    /// The JavaScript buffer that receives the module bytes
+    buffer: Py<PyAny>,
+    /// Whether the end of the module has been parsed
+    done: bool,
+    /// The error that stopped the parser from modelling the module, in which
+    /// case its signatures are reflected from JavaScript instead
+    unmodelled: Option<anyhow::Error>,
+    /// The formatter for the message of unsupported features, if configured
+    formatter: Option<fn(&UnsupportedWasmFeatureExtensionError) -> String>,
+    /// The offset of the pending bytes in the module
+    offset: usize,
+    /// Whether the start function is moved into a hidden export
+    deferrable_start: bool,
+    /// The export section, if it is needed to defer the start function
+    export_section: Option<ExportSection>,
+    /// The range of the start section and the index of the start function
+    start_section: Option<(Range<usize>, u32)>,
+    /// The policy for table maximums that exceed the JavaScript API limit
+    table_maximum_policy: TableMaximumPolicy,
+    /// The number of imported and defined tables that have been parsed
+    tables: u32,
+    /// The offsets and re-encoded bytes of table maximums that are clamped
+    table_maximum_patches: Vec<(usize, Vec<u8>)>,
+    /// Whether the module is instrumented for epoch interruption
+    epoch_interruption: bool,
+    /// The shared cache of compiled modules and the hasher of the module
+    /// bytes, if the engine caches modules
+    cache: Option<(Arc<ModuleCache>, ModuleHasher)>,
+    /// The verifier of the module signature and the buffered module bytes,
+    /// if the engine verifies modules
+    #[cfg(feature = "ed25519")]
+    signed: Option<(Arc<ModuleVerifier>, Vec<u8>)>,
 }
 
-impl ParsedModule {
-    #[allow(clippy::too_many_lines)]
-    /// Parses a module from bytes and extracts import and export signatures
-    fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
-        let parser = wasmparser::Parser::new(0);
+/// The export section of a module, which is extended with the hidden start
+/// function export to defer the start function
+struct ExportSection {
+    /// The range of the section in the module, including its header
+    range: Range<usize>,
+    /// The number of exports
+    count: u32,
+    /// The encoded exports, without their count
+    entries: Vec<u8>,
+}
 
-        let mut imports = FxHashMap::default();
-        let mut exports = FxHashMap::default();
+impl ModuleStream {
+    /// The size of the chunks in which [`Module::new`] reads its stream
+    ///
+    /// [`Module::new`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Module.html#method.new
+    const CHUNK_SIZE: usize = 64 * 1024;
 
-        let mut types = Vec::new();
-
-        let mut functions = Vec::new();
-        let mut memories = Vec::new();
-        let mut tables = Vec::new();
-        let mut globals = Vec::new();
-
-        parser.parse_all(bytes).try_for_each(|payload| {
-            match payload? {
-                wasmparser::Payload::TypeSection(section) => {
-                    for ty in section {
-                        let ty = ty?;
-
-                        let mut subtypes = ty.types();
-                        let subtype = subtypes.next();
-
-                        let ty = match (subtype, subtypes.next()) {
-                            (Some(subtype), None) => match &subtype.composite_type.inner {
-                                wasmparser::CompositeInnerType::Func(func_type) => FuncType::new(
-                                    func_type
-                                        .params()
-                                        .iter()
-                                        .copied()
-                                        .map(ValueType::from_value),
-                                    func_type
-                                        .results()
-                                        .iter()
-                                        .copied()
-                                        .map(ValueType::from_value),
-                                ),
-                                _ => unreachable!(),
-                            },
-                            _ => unimplemented!(),
-                        };
+    /// Starts streaming a new module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the Pyodide version is not supported or if the
+    /// JavaScript buffer cannot be created.
+    pub fn new(engine: &Engine) -> anyhow::Result<Self> {
+        Self::with_capacity(engine, Self::CHUNK_SIZE)
+    }
 
-                        types.push(ty);
-                    }
-                },
-                wasmparser::Payload::FunctionSection(section) => {
-                    for type_index in section {
-                        let type_index = type_index?;
+    /// Starts streaming a new module whose length is expected to be
+    /// `capacity` bytes, e.g. from a `Content-Length` header, such that the
+    /// JavaScript buffer does not need to grow.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the Pyodide version is not supported or if the
+    /// JavaScript buffer cannot be created.
+    pub fn with_capacity(engine: &Engine, capacity: usize) -> anyhow::Result<Self> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(capacity, "ModuleStream::with_capacity");
 
-                        let ty = &types[type_index as usize];
+            // fail fast with a clear error on unsupported Pyodide versions
+            PyodideVersion::detect(py)?;
 
-                        functions.push(ty.clone());
-                    }
-                },
-                wasmparser::Payload::TableSection(section) => {
-                    for table in section {
-                        let table = table?;
-                        tables.push(TableType::from_parsed(&table.ty)?);
-                    }
-                },
-                wasmparser::Payload::MemorySection(section) => {
-                    for memory in section {
-                        let memory = memory?;
-                        memories.push(MemoryType::from_parsed(&memory)?);
-                    }
-                },
-                wasmparser::Payload::GlobalSection(section) => {
-                    for global in section {
-                        let global = global?;
-                        globals.push(GlobalType::from_parsed(global.ty));
-                    }
+            let buffer = js_module_buffer(py)?.call1((capacity,))?;
+
+            Ok(Self {
+                parser: wasmparser::Parser::new(0),
+                module: ModuleParser::default(),
+                pending: Vec::new(),
+                buffer: buffer.unbind(),
+                done: false,
+                unmodelled: None,
+                formatter: engine.config().unsupported_feature_formatter(),
+                offset: 0,
+                deferrable_start: engine.config().deferrable_start(),
+                export_section: None,
+                start_section: None,
+                table_maximum_policy: engine.config().table_maximum_policy(),
+                tables: 0,
+                table_maximum_patches: Vec::new(),
+                epoch_interruption: engine.config().epoch_interruption(),
+                cache: engine
+                    .module_cache()
+                    .map(|cache| (Arc::clone(cache), ModuleHasher::new())),
+                #[cfg(feature = "ed25519")]
+                signed: engine
+                    .config()
+                    .module_verifier()
+                    .map(|verifier| (Arc::clone(verifier), Vec::with_capacity(capacity))),
+            })
+        })
+    }
+
+    /// Writes the next `chunk` of module bytes, which is parsed as far as
+    /// possible.
+    ///
+    /// If the parser cannot model the module, the remaining bytes are only
+    /// buffered and the module's signatures are reflected from JavaScript
+    /// once it has been compiled, see [`Module::has_degraded_reflection`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the module has trailing bytes after its end.
+    pub fn write(&mut self, chunk: &[u8]) -> anyhow::Result<()> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        Python::with_gil(|py| -> anyhow::Result<()> {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(len = chunk.len(), "ModuleStream::write");
+
+            self.buffer
+                .bind(py)
+                .call_method1(intern!(py, "reserve"), (chunk.len(),))?
+                .call_method1(intern!(py, "assign"), (chunk,))?;
+
+            Ok(())
+        })?;
+
+        if let Some((_, hasher)) = &mut self.cache {
+            hasher.write(chunk);
+        }
+
+        #[cfg(feature = "ed25519")]
+        if let Some((_, bytes)) = &mut self.signed {
+            bytes.extend_from_slice(chunk);
+        }
+
+        if self.done {
+            if self.unmodelled.is_some() {
+                return Ok(());
+            }
+
+            anyhow::bail!("trailing bytes after the end of the module");
+        }
+
+        self.pending.extend_from_slice(chunk);
+        self.parse(false)
+    }
+
+    /// Finishes the stream and compiles the module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the module bytes are incomplete or invalid, or if
+    /// the module fails to compile.
+    pub fn finish(mut self) -> anyhow::Result<Module> {
+        self.parse(true)?;
+
+        if !self.done {
+            anyhow::bail!("module bytes ended before the end of the module");
+        }
+
+        #[cfg(feature = "ed25519")]
+        if let Some((verifier, bytes)) = self.signed.take() {
+            verifier.verify(&bytes)?;
+        }
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("ModuleStream::finish").entered();
+
+            let cache = self
+                .cache
+                .take()
+                .map(|(cache, hasher)| (cache, hasher.finish()));
+
+            if let Some(module) = cache.as_ref().and_then(|(cache, hash)| cache.get(hash)) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("reusing module from the shared module cache");
+
+                return Ok(module);
+            }
+
+            let mut buffer = self.buffer.bind(py).call_method0(intern!(py, "finish"))?;
+
+            for (offset, patch) in &self.table_maximum_patches {
+                let array = js_uint8_array_new(py)?.call1((patch.len(),))?;
+                array.call_method1(intern!(py, "assign"), (patch.as_slice(),))?;
+                buffer.call_method1(intern!(py, "set"), (array, *offset))?;
+            }
+
+            let mut start = StartFunction::None;
+            if let (Some((range, func)), None) = (&self.start_section, &self.unmodelled) {
+                start = StartFunction::Immediate;
+
+                if self.deferrable_start {
+                    buffer = defer_start(&buffer, self.export_section.as_ref(), range, *func)?;
+                    start = StartFunction::Deferrable;
+                }
+            }
+
+            let epoch_interruption = self.epoch_interruption && self.unmodelled.is_none();
+            if epoch_interruption {
+                let bytes: Vec<u8> = buffer.call_method0(intern!(py, "to_bytes"))?.extract()?;
+                let bytes = instrument_epoch_checks(&bytes).map_err(|err| {
+                    Error::Compile(format!(
+                        "failed to instrument the module for epoch interruption: {err}"
+                    ))
+                })?;
+
+                buffer = js_uint8_array_new(py)?.call1((bytes.len(),))?;
+                buffer.call_method1(intern!(py, "assign"), (bytes.as_slice(),))?;
+            }
+
+            let module = Module::compile(py, &buffer, self.formatter)?;
+
+            let mut parsed = match self.unmodelled {
+                None => self.module.finish(),
+                Some(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(%err, "falling back to degraded module reflection");
+                    #[cfg(not(feature = "tracing"))]
+                    let _ = err;
+
+                    ParsedModule::reflect(&module, self.module.stats)?
                 },
-                wasmparser::Payload::TagSection(section) => {
-                    for tag in section {
-                        let tag = tag?;
-
-                        #[cfg(feature = "tracing")]
-                        tracing::trace!(?tag, "tag");
-                        #[cfg(not(feature = "tracing"))]
-                        let _ = tag;
-                    }
+            };
+            parsed.start = start;
+            parsed.epoch_interruption = epoch_interruption;
+
+            let module = Module {
+                module: module.unbind(),
+                parsed: Arc::new(parsed),
+            };
+
+            if let Some((cache, hash)) = cache {
+                cache.insert(hash, module.clone());
+            }
+
+            Ok(module)
+        })
+    }
+
+    /// Parses the pending bytes as far as possible, where `eof` signals that
+    /// no more bytes will follow
+    fn parse(&mut self, eof: bool) -> anyhow::Result<()> {
+        let mut offset = 0;
+
+        while !self.done {
+            let chunk = match self.parser.parse(&self.pending[offset..], eof) {
+                Ok(chunk) => chunk,
+                // the module cannot even be split into sections, so stop
+                // parsing and leave the verdict to the JavaScript compiler
+                Err(err) => {
+                    self.unmodelled.get_or_insert_with(|| err.into());
+                    self.done = true;
+                    break;
                 },
-                wasmparser::Payload::ImportSection(section) => {
-                    for import in section {
-                        let import = import?;
-                        let ty = match import.ty {
-                            wasmparser::TypeRef::Func(index) => {
-                                let sig = types[index as usize].clone().with_name(import.name);
-                                functions.push(sig.clone());
-                                ExternType::Func(sig)
-                            },
-                            wasmparser::TypeRef::Table(ty) => {
-                                tables.push(TableType::from_parsed(&ty)?);
-                                ExternType::Table(TableType::from_parsed(&ty)?)
-                            },
-                            wasmparser::TypeRef::Memory(ty) => {
-                                memories.push(MemoryType::from_parsed(&ty)?);
-                                ExternType::Memory(MemoryType::from_parsed(&ty)?)
-                            },
-                            wasmparser::TypeRef::Global(ty) => {
-                                globals.push(GlobalType::from_parsed(ty));
-                                ExternType::Global(GlobalType::from_parsed(ty))
+            };
+
+            match chunk {
+                wasmparser::Chunk::NeedMoreData(_) => break,
+                wasmparser::Chunk::Parsed { consumed, payload } => {
+                    let range = (self.offset + offset)..(self.offset + offset + consumed);
+                    offset += consumed;
+                    self.done = matches!(payload, wasmparser::Payload::End(_));
+
+                    if self.unmodelled.is_none() {
+                        match &payload {
+                            wasmparser::Payload::ExportSection(reader) if self.deferrable_start => {
+                                let contents = &self.pending[(reader.range().start - self.offset)
+                                    ..(reader.range().end - self.offset)];
+                                // skip the LEB128-encoded count
+                                let count_len = contents
+                                    .iter()
+                                    .position(|byte| (byte & 0x80) == 0)
+                                    .map_or(contents.len(), |len| len + 1);
+
+                                self.export_section = Some(ExportSection {
+                                    range,
+                                    count: reader.count(),
+                                    entries: contents[count_len..].to_vec(),
+                                });
                             },
-                            wasmparser::TypeRef::Tag(_) => {
-                                unimplemented!("WebAssembly.Tag is not yet supported")
+                            wasmparser::Payload::StartSection { func, .. } => {
+                                self.start_section = Some((range, *func));
                             },
-                        };
+                            wasmparser::Payload::ImportSection(reader) => {
+                                for import in reader.clone().into_iter_with_offsets() {
+                                    let Ok((import_offset, import)) = import else {
+                                        break;
+                                    };
+                                    let wasmparser::TypeRef::Table(ty) = import.ty else {
+                                        continue;
+                                    };
 
-                        imports.insert((import.module.to_string(), import.name.to_string()), ty);
-                    }
-                },
-                wasmparser::Payload::ExportSection(section) => {
-                    for export in section {
-                        let export = export?;
-                        let index = export.index as usize;
-                        let ty = match export.kind {
-                            wasmparser::ExternalKind::Func => {
-                                ExternType::Func(functions[index].clone().with_name(export.name))
+                                    let table = format!(
+                                        "table {} imported as `{}`.`{}`",
+                                        self.tables, import.module, import.name
+                                    );
+                                    if let Some((patch_offset, patch)) = check_table_maximum(
+                                        &self.pending[(import_offset - self.offset)..],
+                                        &ty,
+                                        &table,
+                                        true,
+                                        self.table_maximum_policy,
+                                    )? {
+                                        self.table_maximum_patches
+                                            .push((import_offset + patch_offset, patch));
+                                    }
+                                    self.tables += 1;
+                                }
                             },
-                            wasmparser::ExternalKind::Table => ExternType::Table(tables[index]),
-                            wasmparser::ExternalKind::Memory => ExternType::Memory(memories[index]),
-                            wasmparser::ExternalKind::Global => ExternType::Global(globals[index]),
-                            wasmparser::ExternalKind::Tag => {
-                                unimplemented!("WebAssembly.Tag is not yet supported")
+                            wasmparser::Payload::TableSection(reader) => {
+                                for table in reader.clone().into_iter_with_offsets() {
+                                    let Ok((table_offset, table)) = table else {
+                                        break;
+                                    };
+
+                                    if let Some((patch_offset, patch)) = check_table_maximum(
+                                        &self.pending[(table_offset - self.offset)..],
+                                        &table.ty,
+                                        &format!("table {}", self.tables),
+                                        false,
+                                        self.table_maximum_policy,
+                                    )? {
+                                        self.table_maximum_patches
+                                            .push((table_offset + patch_offset, patch));
+                                    }
+                                    self.tables += 1;
+                                }
                             },
-                        };
+                            _ => (),
+                        }
 
-                        exports.insert(export.name.to_string(), ty);
-                    }
-                },
-                wasmparser::Payload::ElementSection(section) => {
-                    for element in section {
-                        let element = element?;
-
-                        #[cfg(feature = "tracing")]
-                        match element.kind {
-                            wasmparser::ElementKind::Passive => tracing::debug!("passive"),
-                            wasmparser::ElementKind::Active { .. } => tracing::debug!("active"),
-                            wasmparser::ElementKind::Declared => tracing::debug!("declared"),
+                        if let Err(err) = self.module.payload(payload) {
+                            self.unmodelled = Some(err);
                         }
-                        #[cfg(not(feature = "tracing"))]
-                        let _ = element;
                     }
                 },
-                _ => (),
             }
+        }
 
-            anyhow::Ok(())
-        })?;
+        if self.unmodelled.is_some() && self.done {
+            self.pending = Vec::new();
+            return Ok(());
+        }
+
+        self.pending.drain(..offset);
+        self.offset += offset;
+
+        if self.done && !self.pending.is_empty() {
+            anyhow::bail!("trailing bytes after the end of the module");
+        }
 
-        Ok(Self { imports, exports })
+        Ok(())
     }
 }
 
-trait ValueTypeFrom {
-    fn from_value(value: wasmparser::ValType) -> Self;
-    fn from_ref(ty: wasmparser::RefType) -> Self;
-}
+impl std::io::Write for ModuleStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Self::write(self, buf).map_err(std::io::Error::other)?;
+        Ok(buf.len())
+    }
 
-impl ValueTypeFrom for ValueType {
-    fn from_value(value: wasmparser::ValType) -> Self {
-        match value {
-            wasmparser::ValType::I32 => Self::I32,
-            wasmparser::ValType::I64 => Self::I64,
-            wasmparser::ValType::F32 => Self::F32,
-            wasmparser::ValType::F64 => Self::F64,
-            wasmparser::ValType::V128 => unimplemented!("v128 is not supported"),
-            wasmparser::ValType::Ref(ty) => Self::from_ref(ty),
-        }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
+}
 
-    fn from_ref(ty: wasmparser::RefType) -> Self {
-        if ty.is_func_ref() {
-            Self::FuncRef
-        } else if ty.is_extern_ref() {
-            Self::ExternRef
-        } else {
-            unimplemented!("unsupported reference type {ty:?}")
-        }
+impl fmt::Debug for ModuleStream {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ModuleStream")
+            .field("pending", &self.pending.len())
+            .field("done", &self.done)
+            .finish_non_exhaustive()
     }
 }
 
-trait TableTypeFrom: Sized {
-    fn from_parsed(value: &wasmparser::TableType) -> anyhow::Result<Self>;
+#[derive(Debug, Default)]
+/// A cache of compiled modules, keyed by the hash of their bytes, which is
+/// shared by all clones of an [`Engine`]
+pub struct ModuleCache {
+    /// The cached modules
+    modules: Mutex<FxHashMap<ModuleHash, Module>>,
 }
 
-impl TableTypeFrom for TableType {
-    fn from_parsed(value: &wasmparser::TableType) -> anyhow::Result<Self> {
-        Ok(Self::new(
-            ValueType::from_ref(value.element_type),
-            value.initial.try_into()?,
-            match value.maximum {
-                None => None,
-                Some(maximum) => Some(maximum.try_into()?),
-            },
-        ))
+impl ModuleCache {
+    /// Returns the cached module with the `hash`, if any
+    fn get(&self, hash: &ModuleHash) -> Option<Module> {
+        self.modules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(hash)
+            .cloned()
+    }
+
+    /// Caches the `module` under its `hash`
+    fn insert(&self, hash: ModuleHash, module: Module) {
+        self.modules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(hash, module);
+    }
+
+    /// Returns the number of cached modules
+    pub fn len(&self) -> usize {
+        self.modules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Removes all cached modules
+    pub fn clear(&self) {
+        self.modules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
     }
 }
 
-trait MemoryTypeFrom: Sized {
-    fn from_parsed(value: &wasmparser::MemoryType) -> anyhow::Result<Self>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The hash of the bytes of a module, which combines two independently
+/// seeded 64-bit hashes with the length of the bytes
+struct ModuleHash {
+    /// The number of module bytes
+    len: usize,
+    /// The first hash of the module bytes
+    primary: u64,
+    /// The second, differently seeded, hash of the module bytes
+    secondary: u64,
 }
 
-impl MemoryTypeFrom for MemoryType {
-    fn from_parsed(value: &wasmparser::MemoryType) -> anyhow::Result<Self> {
-        if value.memory64 {
-            anyhow::bail!("memory64 is not yet supported");
-        }
+/// The incremental hasher of the bytes of a module, whose result does not
+/// depend on how the bytes are split into chunks
+struct ModuleHasher {
+    /// The number of module bytes hashed so far
+    len: usize,
+    /// The first hasher
+    primary: DefaultHasher,
+    /// The second, differently seeded, hasher
+    secondary: DefaultHasher,
+}
 
-        if value.shared {
-            anyhow::bail!("shared memory is not yet supported");
+impl ModuleHasher {
+    /// The seed that distinguishes the second hasher from the first
+    const SECONDARY_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    /// Creates a new hasher for the bytes of a module
+    fn new() -> Self {
+        let mut secondary = DefaultHasher::new();
+        secondary.write_u64(Self::SECONDARY_SEED);
+
+        Self {
+            len: 0,
+            primary: DefaultHasher::new(),
+            secondary,
         }
+    }
 
-        Ok(Self::new(
-            value.initial.try_into()?,
-            match value.maximum {
-                None => None,
-                Some(maximum) => Some(maximum.try_into()?),
-            },
-        ))
+    /// Hashes the next `chunk` of module bytes
+    fn write(&mut self, chunk: &[u8]) {
+        self.len += chunk.len();
+        self.primary.write(chunk);
+        self.secondary.write(chunk);
     }
-}
 
-trait GlobalTypeFrom {
-    fn from_parsed(value: wasmparser::GlobalType) -> Self;
+    /// Finishes hashing the module bytes
+    fn finish(self) -> ModuleHash {
+        ModuleHash {
+            len: self.len,
+            primary: self.primary.finish(),
+            secondary: self.secondary.finish(),
+        }
+    }
 }
 
-impl GlobalTypeFrom for GlobalType {
-    fn from_parsed(value: wasmparser::GlobalType) -> Self {
-        Self::new(ValueType::from_value(value.content_type), value.mutable)
-    }
+#[derive(Debug, Clone)]
+/// The import and export signatures of a [`Module`].
+///
+/// The metadata can be serialized into a compact binary format using
+/// [`ModuleMetadata::to_bytes`] and deserialized again using
+/// [`ModuleMetadata::from_bytes`].
+pub struct ModuleMetadata {
+    /// The parsed module, containing import and export signatures
+    parsed: Arc<ParsedModule>,
 }
 
-fn web_assembly_module_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
-    static WEB_ASSEMBLY_MODULE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
-    WEB_ASSEMBLY_MODULE.import(py, "js.WebAssembly.Module", "new")
+impl ModuleMetadata {
+    /// Magic prefix of the serialized metadata
+    const MAGIC: &'static [u8; 8] = b"pwrlmeta";
+    /// Version of the serialized metadata format
+    const VERSION: u8 = 9;
+
+    /// Serializes the metadata into a compact binary format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(&Self::MAGIC[..]);
+        bytes.push(Self::VERSION);
+
+        // sort the entries to produce a deterministic encoding
+        let mut imports = self.parsed.imports.iter().collect::<Vec<_>>();
+        imports.sort_unstable_by_key(|(key, _)| *key);
+
+        let mut exports = self.parsed.exports.iter().collect::<Vec<_>>();
+        exports.sort_unstable_by_key(|(key, _)| *key);
+
+        // function signatures refer to the type table by index
+        let mut types = FuncTypeInterner::default();
+        for ty in &self.parsed.types {
+            types.intern(ty);
+        }
+        for ty in imports
+            .iter()
+            .map(|(_, ty)| *ty)
+            .chain(exports.iter().map(|(_, ty)| *ty))
+        {
+            if let ExternType::Func(ty) = ty {
+                types.intern(ty);
+            }
+        }
+
+        encode_len(&mut bytes, types.types.len());
+        for ty in &types.types {
+            encode_value_types(&mut bytes, ty.params());
+            encode_value_types(&mut bytes, ty.results());
+        }
+
+        encode_len(&mut bytes, imports.len());
+        for ((module, name), ty) in imports {
+            encode_str(&mut bytes, module);
+            encode_str(&mut bytes, name);
+            encode_extern_type(&mut bytes, ty, &types);
+        }
+
+        encode_len(&mut bytes, exports.len());
+        for (name, ty) in exports {
+            encode_str(&mut bytes, name);
+            encode_extern_type(&mut bytes, ty, &types);
+        }
+
+        let mut unused_imports = self.parsed.unused_imports.iter().collect::<Vec<_>>();
+        unused_imports.sort_unstable();
+
+        encode_len(&mut bytes, unused_imports.len());
+        for (module, name) in unused_imports {
+            encode_str(&mut bytes, module);
+            encode_str(&mut bytes, name);
+        }
+
+        let mut tag_imports = self.parsed.tag_imports.iter().collect::<Vec<_>>();
+        tag_imports.sort_unstable_by_key(|(key, _)| *key);
+
+        encode_len(&mut bytes, tag_imports.len());
+        for ((module, name), params) in tag_imports {
+            encode_str(&mut bytes, module);
+            encode_str(&mut bytes, name);
+            encode_value_types(&mut bytes, params);
+        }
+
+        let mut tag_exports = self.parsed.tag_exports.iter().collect::<Vec<_>>();
+        tag_exports.sort_unstable_by_key(|(key, _)| *key);
+
+        encode_len(&mut bytes, tag_exports.len());
+        for (name, params) in tag_exports {
+            encode_str(&mut bytes, name);
+            encode_value_types(&mut bytes, params);
+        }
+
+        let mut v128_imports = self.parsed.v128_imports.iter().collect::<Vec<_>>();
+        v128_imports.sort_unstable();
+
+        encode_len(&mut bytes, v128_imports.len());
+        for (module, name) in v128_imports {
+            encode_str(&mut bytes, module);
+            encode_str(&mut bytes, name);
+        }
+
+        let mut v128_exports = self.parsed.v128_exports.iter().collect::<Vec<_>>();
+        v128_exports.sort_unstable();
+
+        encode_len(&mut bytes, v128_exports.len());
+        for name in v128_exports {
+            encode_str(&mut bytes, name);
+        }
+
+        bytes.push(u8::from(self.parsed.degraded));
+        bytes.push(match self.parsed.start {
+            StartFunction::None => 0,
+            StartFunction::Immediate => 1,
+            StartFunction::Deferrable => 2,
+        });
+        bytes.push(u8::from(self.parsed.epoch_interruption));
+
+        let stats = &self.parsed.stats;
+        for count in [stats.types, stats.imports, stats.exports, stats.functions] {
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+        for size in [stats.code_size, stats.data_size, stats.initial_memory_size] {
+            bytes.extend_from_slice(&size.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Deserializes metadata that was serialized using
+    /// [`ModuleMetadata::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `bytes` are not valid serialized metadata.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut decoder = Decoder {
+            bytes,
+            types: Vec::new(),
+        };
+
+        if decoder.take(Self::MAGIC.len())? != Self::MAGIC {
+            anyhow::bail!("invalid module metadata magic");
+        }
+
+        let version = decoder.u8()?;
+        if version != Self::VERSION {
+            anyhow::bail!("unsupported module metadata version {version}");
+        }
+
+        for _ in 0..decoder.u32()? {
+            let params = decoder.value_types()?;
+            let results = decoder.value_types()?;
+            decoder.types.push(FuncType::new(params, results));
+        }
+
+        let mut imports = FxHashMap::default();
+        for _ in 0..decoder.u32()? {
+            let module = decoder.str()?;
+            let name = decoder.str()?;
+            let ty = decoder.extern_type(&name)?;
+            imports.insert((module, name), ty);
+        }
+
+        let mut exports = FxHashMap::default();
+        for _ in 0..decoder.u32()? {
+            let name = decoder.str()?;
+            let ty = decoder.extern_type(&name)?;
+            exports.insert(name, ty);
+        }
+
+        let mut unused_imports = FxHashSet::default();
+        for _ in 0..decoder.u32()? {
+            let module = decoder.str()?;
+            let name = decoder.str()?;
+            unused_imports.insert((module, name));
+        }
+
+        let mut tag_imports = FxHashMap::default();
+        for _ in 0..decoder.u32()? {
+            let module = decoder.str()?;
+            let name = decoder.str()?;
+            let params = decoder.value_types()?;
+            tag_imports.insert((module, name), Arc::from(params));
+        }
+
+        let mut tag_exports = FxHashMap::default();
+        for _ in 0..decoder.u32()? {
+            let name = decoder.str()?;
+            let params = decoder.value_types()?;
+            tag_exports.insert(name, Arc::from(params));
+        }
+
+        let mut v128_imports = FxHashSet::default();
+        for _ in 0..decoder.u32()? {
+            let module = decoder.str()?;
+            let name = decoder.str()?;
+            v128_imports.insert((module, name));
+        }
+
+        let mut v128_exports = FxHashSet::default();
+        for _ in 0..decoder.u32()? {
+            v128_exports.insert(decoder.str()?);
+        }
+
+        let degraded = decoder.u8()? != 0;
+        let start = match decoder.u8()? {
+            0 => StartFunction::None,
+            1 => StartFunction::Immediate,
+            2 => StartFunction::Deferrable,
+            start => anyhow::bail!("invalid module metadata start function {start}"),
+        };
+        let epoch_interruption = decoder.u8()? != 0;
+
+        let stats = ModuleStats {
+            types: decoder.u32()?,
+            imports: decoder.u32()?,
+            exports: decoder.u32()?,
+            functions: decoder.u32()?,
+            code_size: decoder.u64()?,
+            data_size: decoder.u64()?,
+            initial_memory_size: decoder.u64()?,
+        };
+
+        if !decoder.bytes.is_empty() {
+            anyhow::bail!("trailing bytes after module metadata");
+        }
+
+        Ok(Self {
+            parsed: Arc::new(ParsedModule {
+                types: decoder.types,
+                imports,
+                exports,
+                unused_imports,
+                tag_imports,
+                tag_exports,
+                v128_imports,
+                v128_exports,
+                degraded,
+                start,
+                epoch_interruption,
+                stats,
+            }),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Lightweight statistics of a [`Module`], e.g. to choose between
+/// synchronous and asynchronous compilation for large modules.
+///
+/// The statistics are computed when a module is parsed, see
+/// [`Module::stats`], or from the module bytes alone using
+/// [`ModuleStats::from_bytes`].
+pub struct ModuleStats {
+    /// The number of function types
+    types: u32,
+    /// The number of imports
+    imports: u32,
+    /// The number of exports
+    exports: u32,
+    /// The number of functions defined by the module
+    functions: u32,
+    /// The size of the code section in bytes
+    code_size: u64,
+    /// The total size of the data segments in bytes
+    data_size: u64,
+    /// The total initial size of the defined and imported memories in bytes
+    initial_memory_size: u64,
+}
+
+impl ModuleStats {
+    /// Computes the statistics of the module `bytes` without validating or
+    /// compiling them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `bytes` cannot be parsed as a module.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut stats = Self::default();
+
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            stats.observe(&payload?)?;
+        }
+
+        Ok(stats)
+    }
+
+    /// Returns the number of function types.
+    #[must_use]
+    pub const fn types(&self) -> u32 {
+        self.types
+    }
+
+    /// Returns the number of imports.
+    #[must_use]
+    pub const fn imports(&self) -> u32 {
+        self.imports
+    }
+
+    /// Returns the number of exports.
+    #[must_use]
+    pub const fn exports(&self) -> u32 {
+        self.exports
+    }
+
+    /// Returns the number of functions that are defined, i.e. not imported,
+    /// by the module.
+    #[must_use]
+    pub const fn functions(&self) -> u32 {
+        self.functions
+    }
+
+    /// Returns the size of the code section in bytes.
+    #[must_use]
+    pub const fn code_size(&self) -> u64 {
+        self.code_size
+    }
+
+    /// Returns the total size of the data segments in bytes.
+    #[must_use]
+    pub const fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the estimated memory that instantiating the module requires
+    /// in bytes, i.e. the total initial size of its defined and imported
+    /// memories.
+    #[must_use]
+    pub const fn initial_memory_size(&self) -> u64 {
+        self.initial_memory_size
+    }
+
+    /// Updates the statistics with the parsed `payload`
+    fn observe(&mut self, payload: &wasmparser::Payload) -> anyhow::Result<()> {
+        match payload {
+            wasmparser::Payload::TypeSection(section) => self.types = section.count(),
+            wasmparser::Payload::ImportSection(section) => {
+                self.imports = section.count();
+
+                for import in section.clone() {
+                    if let wasmparser::TypeRef::Memory(ty) = import?.ty {
+                        self.initial_memory_size = self
+                            .initial_memory_size
+                            .saturating_add(ty.initial.saturating_mul(PAGE_SIZE));
+                    }
+                }
+            },
+            wasmparser::Payload::FunctionSection(section) => self.functions = section.count(),
+            wasmparser::Payload::MemorySection(section) => {
+                for memory in section.clone() {
+                    self.initial_memory_size = self
+                        .initial_memory_size
+                        .saturating_add(memory?.initial.saturating_mul(PAGE_SIZE));
+                }
+            },
+            wasmparser::Payload::ExportSection(section) => self.exports = section.count(),
+            wasmparser::Payload::CodeSectionStart { size, .. } => {
+                self.code_size = u64::from(*size);
+            },
+            wasmparser::Payload::DataSection(section) => {
+                for data in section.clone() {
+                    self.data_size = self.data_size.saturating_add(data?.data.len() as u64);
+                }
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_len(bytes: &mut Vec<u8>, len: usize) {
+    #[allow(clippy::cast_possible_truncation)]
+    bytes.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+/// Checks if the maximum of the `table` with type `ty`, whose encoding
+/// starts at the beginning of `bytes` as an import entry if `import` and as a
+/// table section entry otherwise, exceeds the limit of the JavaScript API
+///
+/// Depending on the `policy`, an error is returned or the offset and bytes of
+/// the clamped re-encoded maximum are returned, which keep the length of the
+/// original encoding such that no section sizes change.
+fn check_table_maximum(
+    bytes: &[u8],
+    ty: &wasmparser::TableType,
+    table: &str,
+    import: bool,
+    policy: TableMaximumPolicy,
+) -> anyhow::Result<Option<(usize, Vec<u8>)>> {
+    let Some(maximum) = ty.maximum else {
+        return Ok(None);
+    };
+
+    if maximum <= u64::from(Table::MAX_SIZE) {
+        return Ok(None);
+    }
+
+    if policy == TableMaximumPolicy::Error {
+        return Err(Error::Compile(format!(
+            "{table} declares a maximum of {maximum} elements, which exceeds the JavaScript API \
+             limit of {} elements, see EngineConfig::with_table_maximum_policy",
+            Table::MAX_SIZE
+        ))
+        .into());
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        table,
+        maximum,
+        clamped = Table::MAX_SIZE,
+        "clamping the table maximum to the JavaScript API limit"
+    );
+
+    let Some(range) = table_maximum_range(bytes, import) else {
+        return Ok(None);
+    };
+
+    // the maximum exceeds 2^21 and is thus encoded in at least four bytes,
+    //  which can hold the clamped maximum
+    let mut patch = Vec::with_capacity(range.len());
+    let mut value = Table::MAX_SIZE;
+    for i in 0..range.len() {
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        patch.push(if i + 1 < range.len() {
+            byte | 0x80
+        } else {
+            byte
+        });
+    }
+
+    Ok(Some((range.start, patch)))
+}
+
+/// Locates the LEB128-encoded maximum of the table whose encoding starts at
+/// the beginning of `bytes`, as an import entry if `import` and as a table
+/// section entry otherwise
+fn table_maximum_range(bytes: &[u8], import: bool) -> Option<Range<usize>> {
+    fn skip_leb128(bytes: &[u8], pos: usize) -> Option<usize> {
+        let len = bytes
+            .get(pos..)?
+            .iter()
+            .position(|byte| (byte & 0x80) == 0)?;
+        Some(pos + len + 1)
+    }
+
+    fn read_leb128(bytes: &[u8], pos: usize) -> Option<(usize, usize)> {
+        let end = skip_leb128(bytes, pos)?;
+        let value = bytes[pos..end].iter().rev().fold(0_usize, |value, byte| {
+            (value << 7) | usize::from(byte & 0x7F)
+        });
+        Some((value, end))
+    }
+
+    /// The kind of a table import
+    const TABLE_IMPORT_KIND: u8 = 0x01;
+    /// The prefix of a table with an explicit initializer expression
+    const TABLE_INIT_PREFIX: u8 = 0x40;
+    /// The prefixes of non-abbreviated reference types with a heap type
+    const REF_TYPE_PREFIXES: [u8; 2] = [0x63, 0x64];
+
+    let mut pos = 0;
+
+    if import {
+        // skip the module and field names
+        for _ in 0..2 {
+            let (len, start) = read_leb128(bytes, pos)?;
+            pos = start + len;
+        }
+
+        if *bytes.get(pos)? != TABLE_IMPORT_KIND {
+            return None;
+        }
+        pos += 1;
+    } else if *bytes.first()? == TABLE_INIT_PREFIX {
+        pos += 2;
+    }
+
+    if REF_TYPE_PREFIXES.contains(bytes.get(pos)?) {
+        pos = skip_leb128(bytes, pos + 1)?;
+    } else {
+        pos += 1;
+    }
+
+    let flags = *bytes.get(pos)?;
+    if (flags & 0x01) == 0 {
+        return None;
+    }
+
+    let start = skip_leb128(bytes, pos + 1)?;
+    let end = skip_leb128(bytes, start)?;
+
+    Some(start..end)
+}
+
+pub fn encode_leb128(bytes: &mut Vec<u8>, value: usize) {
+    let mut value = value;
+
+    loop {
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn encode_str(bytes: &mut Vec<u8>, s: &str) {
+    encode_len(bytes, s.len());
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+fn encode_limits(bytes: &mut Vec<u8>, min: u32, max: Option<u32>) {
+    bytes.extend_from_slice(&min.to_le_bytes());
+    match max {
+        None => bytes.push(0),
+        Some(max) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&max.to_le_bytes());
+        },
+    }
+}
+
+const fn encode_value_type(ty: ValueType) -> u8 {
+    match ty {
+        ValueType::I32 => 0,
+        ValueType::I64 => 1,
+        ValueType::F32 => 2,
+        ValueType::F64 => 3,
+        ValueType::FuncRef => 4,
+        ValueType::ExternRef => 5,
+    }
+}
+
+fn encode_value_types(bytes: &mut Vec<u8>, tys: &[ValueType]) {
+    encode_len(bytes, tys.len());
+    bytes.extend(tys.iter().copied().map(encode_value_type));
+}
+
+fn encode_extern_type(bytes: &mut Vec<u8>, ty: &ExternType, types: &FuncTypeInterner) {
+    match ty {
+        ExternType::Func(ty) => {
+            bytes.push(0);
+            encode_len(
+                bytes,
+                types
+                    .index_of(ty)
+                    .expect("function types are interned before they are encoded"),
+            );
+        },
+        ExternType::Table(ty) => {
+            bytes.push(1);
+            bytes.push(encode_value_type(ty.element()));
+            encode_limits(bytes, ty.minimum(), ty.maximum());
+        },
+        ExternType::Memory(ty) => {
+            bytes.push(2);
+            encode_limits(bytes, ty.initial_pages(), ty.maximum_pages());
+        },
+        ExternType::Global(ty) => {
+            bytes.push(3);
+            bytes.push(encode_value_type(ty.content()));
+            bytes.push(u8::from(ty.mutable()));
+        },
+    }
+}
+
+/// Decoder for the serialized [`ModuleMetadata`]
+struct Decoder<'a> {
+    /// The remaining bytes
+    bytes: &'a [u8],
+    /// The decoded function type table
+    types: Vec<FuncType>,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            anyhow::bail!("unexpected end of module metadata");
+        }
+
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn str(&mut self) -> anyhow::Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from(std::str::from_utf8(self.take(len)?)?))
+    }
+
+    fn limits(&mut self) -> anyhow::Result<(u32, Option<u32>)> {
+        let min = self.u32()?;
+        let max = match self.u8()? {
+            0 => None,
+            1 => Some(self.u32()?),
+            tag => anyhow::bail!("invalid module metadata limits tag {tag}"),
+        };
+        Ok((min, max))
+    }
+
+    fn value_type(&mut self) -> anyhow::Result<ValueType> {
+        match self.u8()? {
+            0 => Ok(ValueType::I32),
+            1 => Ok(ValueType::I64),
+            2 => Ok(ValueType::F32),
+            3 => Ok(ValueType::F64),
+            4 => Ok(ValueType::FuncRef),
+            5 => Ok(ValueType::ExternRef),
+            tag => anyhow::bail!("invalid module metadata value type tag {tag}"),
+        }
+    }
+
+    fn value_types(&mut self) -> anyhow::Result<Vec<ValueType>> {
+        (0..self.u32()?).map(|_| self.value_type()).collect()
+    }
+
+    fn extern_type(&mut self, name: &str) -> anyhow::Result<ExternType> {
+        match self.u8()? {
+            0 => {
+                let index = self.u32()?;
+                let ty = self.types.get(index as usize).ok_or_else(|| {
+                    anyhow::anyhow!("invalid module metadata function type index {index}")
+                })?;
+                Ok(ExternType::Func(ty.clone().with_name(name)))
+            },
+            1 => {
+                let element = self.value_type()?;
+                let (min, max) = self.limits()?;
+                Ok(ExternType::Table(TableType::new(element, min, max)))
+            },
+            2 => {
+                let (initial, maximum) = self.limits()?;
+                Ok(ExternType::Memory(MemoryType::new(initial, maximum)))
+            },
+            3 => {
+                let content = self.value_type()?;
+                let mutable = self.u8()? != 0;
+                Ok(ExternType::Global(GlobalType::new(content, mutable)))
+            },
+            tag => anyhow::bail!("invalid module metadata extern type tag {tag}"),
+        }
+    }
+}
+
+#[derive(Default)]
+/// Interner that deduplicates identical function types, which then share
+/// their parameter and result types
+struct FuncTypeInterner {
+    /// The unique function types, in the order of their first occurrence
+    types: Vec<FuncType>,
+    /// The indices of the unique function types, keyed by their encoding
+    indices: FxHashMap<Vec<u8>, usize>,
+}
+
+impl FuncTypeInterner {
+    /// Returns the interned, unnamed, copy of the function type `ty`
+    fn intern(&mut self, ty: &FuncType) -> FuncType {
+        let index = *self.indices.entry(Self::key(ty)).or_insert_with(|| {
+            self.types.push(FuncType::new(
+                ty.params().iter().copied(),
+                ty.results().iter().copied(),
+            ));
+            self.types.len() - 1
+        });
+
+        self.types[index].clone()
+    }
+
+    /// Returns the index of the function type `ty`, if it has been interned
+    fn index_of(&self, ty: &FuncType) -> Option<usize> {
+        self.indices.get(&Self::key(ty)).copied()
+    }
+
+    /// Encodes the parameter and result types of `ty` into the interning key
+    fn key(ty: &FuncType) -> Vec<u8> {
+        let mut key = Vec::with_capacity(ty.params().len() + ty.results().len() + 8);
+        encode_value_types(&mut key, ty.params());
+        encode_value_types(&mut key, ty.results());
+        key
+    }
+}
+
+#[derive(Debug)]
+/// A parsed core module with imports and exports
+struct ParsedModule {
+    /// Deduplicated function types
+    types: Vec<FuncType>,
+    /// Import signatures
+    imports: FxHashMap<(String, String), ExternType>,
+    /// Export signatures
+    exports: FxHashMap<String, ExternType>,
+    /// Function imports that are never referenced by the module
+    unused_imports: FxHashSet<(String, String)>,
+    /// Payload types of the tag imports
+    tag_imports: FxHashMap<(String, String), Arc<[ValueType]>>,
+    /// Payload types of the tag exports
+    tag_exports: FxHashMap<String, Arc<[ValueType]>>,
+    /// Function imports that use `v128` values
+    v128_imports: FxHashSet<(String, String)>,
+    /// Function exports that use `v128` values
+    v128_exports: FxHashSet<String>,
+    /// Whether the signatures were reflected from JavaScript since the module
+    /// could not be modelled by the parser
+    degraded: bool,
+    /// How the start function of the module is run
+    start: StartFunction,
+    /// Whether the module is instrumented for epoch interruption
+    epoch_interruption: bool,
+    /// Lightweight statistics of the module
+    stats: ModuleStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How the start function of a module is run
+pub enum StartFunction {
+    /// The module has no start function, or it is not known to have one
+    /// since its signatures were reflected from JavaScript
+    None,
+    /// The start function is run while the module is instantiated
+    Immediate,
+    /// The start function was moved into the hidden
+    /// [`DEFERRED_START_EXPORT`] and must be called explicitly
+    Deferrable,
+}
+
+impl ParsedModule {
+    /// Reflects the import and export signatures of the compiled JavaScript
+    /// `module`, for which only the `stats` of its parsed prefix are known
+    fn reflect(module: &Bound<PyAny>, stats: ModuleStats) -> anyhow::Result<Self> {
+        let py = module.py();
+
+        let mut interner = FuncTypeInterner::default();
+
+        let mut imports = FxHashMap::default();
+        let mut tag_imports = FxHashMap::default();
+        for import in web_assembly_module(py)?
+            .call_method1(intern!(py, "imports"), (module,))?
+            .try_iter()?
+        {
+            let import = import?;
+            let key = (
+                import.getattr(intern!(py, "module"))?.extract::<String>()?,
+                import.getattr(intern!(py, "name"))?.extract::<String>()?,
+            );
+
+            let kind = import.getattr(intern!(py, "kind"))?.extract::<String>()?;
+
+            if kind == "tag" {
+                tag_imports.insert(key, Arc::from([]));
+            } else if let Some(ty) = reflect_extern_type(&import, &kind, &key.1, &mut interner)? {
+                imports.insert(key, ty);
+            }
+        }
+
+        let mut exports = FxHashMap::default();
+        let mut tag_exports = FxHashMap::default();
+        for export in web_assembly_module(py)?
+            .call_method1(intern!(py, "exports"), (module,))?
+            .try_iter()?
+        {
+            let export = export?;
+            let name = export.getattr(intern!(py, "name"))?.extract::<String>()?;
+            let kind = export.getattr(intern!(py, "kind"))?.extract::<String>()?;
+
+            if kind == "tag" {
+                tag_exports.insert(name, Arc::from([]));
+            } else if let Some(ty) = reflect_extern_type(&export, &kind, &name, &mut interner)? {
+                exports.insert(name, ty);
+            }
+        }
+
+        Ok(Self {
+            types: interner.types,
+            imports,
+            exports,
+            unused_imports: FxHashSet::default(),
+            tag_imports,
+            tag_exports,
+            v128_imports: FxHashSet::default(),
+            v128_exports: FxHashSet::default(),
+            degraded: true,
+            start: StartFunction::None,
+            epoch_interruption: false,
+            stats,
+        })
+    }
+}
+
+/// Reflects the type of the JavaScript import or export `descriptor` of the
+/// `kind` with the `name`, using the type reflection (js-types) proposal if it
+/// is supported and placeholder types otherwise
+///
+/// Returns `None` for kinds that cannot be represented as an [`ExternType`],
+/// e.g. tags.
+fn reflect_extern_type(
+    descriptor: &Bound<PyAny>,
+    kind: &str,
+    name: &str,
+    interner: &mut FuncTypeInterner,
+) -> anyhow::Result<Option<ExternType>> {
+    let py = descriptor.py();
+
+    let ty = descriptor
+        .getattr(intern!(py, "type"))
+        .ok()
+        .filter(|ty| !ty.is_none());
+
+    let value_types =
+        |ty: &Bound<PyAny>, attr: &Bound<PyString>| -> anyhow::Result<Vec<ValueType>> {
+            ty.getattr(attr)?
+                .try_iter()?
+                .map(|value| Ok(reflect_value_type(&value?.extract::<String>()?)))
+                .collect()
+        };
+    let limits = |ty: &Bound<PyAny>| -> anyhow::Result<(u32, Option<u32>)> {
+        let minimum = ty.getattr(intern!(py, "minimum"))?.extract()?;
+        let maximum = ty
+            .getattr(intern!(py, "maximum"))
+            .ok()
+            .and_then(|maximum| maximum.extract().ok());
+        Ok((minimum, maximum))
+    };
+
+    let ty = match (kind, ty) {
+        ("function", Some(ty)) => ExternType::Func(
+            interner
+                .intern(&FuncType::new(
+                    value_types(&ty, intern!(py, "parameters"))?,
+                    value_types(&ty, intern!(py, "results"))?,
+                ))
+                .with_name(name),
+        ),
+        ("function", None) => ExternType::Func(FuncType::new([], []).with_name(name)),
+        ("table", Some(ty)) => {
+            let element =
+                reflect_value_type(&ty.getattr(intern!(py, "element"))?.extract::<String>()?);
+            let (minimum, maximum) = limits(&ty)?;
+            ExternType::Table(TableType::new(element, minimum, maximum))
+        },
+        ("table", None) => ExternType::Table(TableType::new(ValueType::FuncRef, 0, None)),
+        ("memory", Some(ty)) => {
+            let (minimum, maximum) = limits(&ty)?;
+            ExternType::Memory(MemoryType::new(minimum, maximum))
+        },
+        ("memory", None) => ExternType::Memory(MemoryType::new(0, None)),
+        ("global", Some(ty)) => ExternType::Global(GlobalType::new(
+            reflect_value_type(&ty.getattr(intern!(py, "value"))?.extract::<String>()?),
+            ty.getattr(intern!(py, "mutable"))?.extract()?,
+        )),
+        ("global", None) => ExternType::Global(GlobalType::new(ValueType::I32, false)),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(ty))
+}
+
+/// Reflects the JavaScript value type `descriptor`, reporting unknown types,
+/// e.g. `v128`, as opaque `externref`s
+fn reflect_value_type(descriptor: &str) -> ValueType {
+    match descriptor {
+        "i32" => ValueType::I32,
+        "i64" => ValueType::I64,
+        "f32" => ValueType::F32,
+        "f64" => ValueType::F64,
+        "anyfunc" | "funcref" => ValueType::FuncRef,
+        _ => ValueType::ExternRef,
+    }
+}
+
+#[derive(Default)]
+/// Incremental parser that extracts the import and export signatures of a
+/// module from its payloads
+struct ModuleParser {
+    /// Import signatures
+    imports: FxHashMap<(String, String), ExternType>,
+    /// Export signatures
+    exports: FxHashMap<String, ExternType>,
+    /// Function types by type index, which are `None` for non-function types
+    types: Vec<Option<FuncType>>,
+    /// Whether the type at each type index uses `v128` values
+    v128_types: Vec<bool>,
+    /// Interner for identical function types
+    interner: FuncTypeInterner,
+    /// Function types by function index
+    functions: Vec<FuncType>,
+    /// Whether the function at each function index uses `v128` values
+    v128_functions: Vec<bool>,
+    /// Function imports that use `v128` values
+    v128_imports: FxHashSet<(String, String)>,
+    /// Function exports that use `v128` values
+    v128_exports: FxHashSet<String>,
+    /// Function imports by function index
+    function_imports: Vec<(String, String)>,
+    /// Indices of the functions that are referenced by the module
+    used_functions: FxHashSet<u32>,
+    /// Memory types by memory index
+    memories: Vec<MemoryType>,
+    /// Table types by table index
+    tables: Vec<TableType>,
+    /// Global types by global index
+    globals: Vec<GlobalType>,
+    /// Tag payload types by tag index
+    tags: Vec<Arc<[ValueType]>>,
+    /// Payload types of the tag imports
+    tag_imports: FxHashMap<(String, String), Arc<[ValueType]>>,
+    /// Payload types of the tag exports
+    tag_exports: FxHashMap<String, Arc<[ValueType]>>,
+    /// Lightweight statistics of the module
+    stats: ModuleStats,
+}
+
+impl ModuleParser {
+    #[allow(clippy::too_many_lines)]
+    /// Processes the next parsed `payload` of the module
+    fn payload(&mut self, payload: wasmparser::Payload) -> anyhow::Result<()> {
+        let Self {
+            imports,
+            exports,
+            types,
+            v128_types,
+            interner,
+            functions,
+            v128_functions,
+            v128_imports,
+            v128_exports,
+            function_imports,
+            used_functions,
+            memories,
+            tables,
+            globals,
+            tags,
+            tag_imports,
+            tag_exports,
+            stats,
+        } = self;
+
+        stats.observe(&payload)?;
+
+        match payload {
+            wasmparser::Payload::TypeSection(section) => {
+                for rec_group in section {
+                    // every subtype of a recursion group has its own type index
+                    for subtype in rec_group?.into_types() {
+                        // struct, array, and continuation types cannot be used
+                        //  as function signatures and are skipped
+                        let wasmparser::CompositeInnerType::Func(func_type) =
+                            &subtype.composite_type.inner
+                        else {
+                            #[cfg(feature = "tracing")]
+                            tracing::trace!(%subtype, "non-function type");
+
+                            types.push(None);
+                            v128_types.push(false);
+                            continue;
+                        };
+
+                        v128_types.push(
+                            func_type
+                                .params()
+                                .iter()
+                                .chain(func_type.results())
+                                .any(|ty| *ty == wasmparser::ValType::V128),
+                        );
+
+                        let ty = FuncType::new(
+                            func_type
+                                .params()
+                                .iter()
+                                .copied()
+                                .map(ValueType::from_value)
+                                .collect::<Result<Vec<_>, _>>()?,
+                            func_type
+                                .results()
+                                .iter()
+                                .copied()
+                                .map(ValueType::from_value)
+                                .collect::<Result<Vec<_>, _>>()?,
+                        );
+
+                        types.push(Some(interner.intern(&ty)));
+                    }
+                }
+            },
+            wasmparser::Payload::FunctionSection(section) => {
+                for type_index in section {
+                    let type_index = type_index?;
+
+                    let ty = func_type(types, type_index)?;
+
+                    functions.push(ty.clone());
+                    v128_functions.push(v128_types[type_index as usize]);
+                }
+            },
+            wasmparser::Payload::TableSection(section) => {
+                for table in section {
+                    let table = table?;
+                    tables.push(TableType::from_parsed(&table.ty)?);
+                }
+            },
+            wasmparser::Payload::MemorySection(section) => {
+                for memory in section {
+                    let memory = memory?;
+                    memories.push(MemoryType::from_parsed(&memory)?);
+                }
+            },
+            wasmparser::Payload::GlobalSection(section) => {
+                for global in section {
+                    let global = global?;
+                    globals.push(GlobalType::from_parsed(global.ty)?);
+                    collect_referenced_functions(
+                        global.init_expr.get_operators_reader(),
+                        used_functions,
+                    )?;
+                }
+            },
+            wasmparser::Payload::TagSection(section) => {
+                for tag in section {
+                    let tag = tag?;
+
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(?tag, "tag");
+
+                    tags.push(Arc::from(func_type(types, tag.func_type_idx)?.params()));
+                }
+            },
+            wasmparser::Payload::ImportSection(section) => {
+                for import in section {
+                    let import = import?;
+                    let ty = match import.ty {
+                        wasmparser::TypeRef::Func(index) => {
+                            let sig = func_type(types, index)?.clone().with_name(import.name);
+                            functions.push(sig.clone());
+                            v128_functions.push(v128_types[index as usize]);
+                            if v128_types[index as usize] {
+                                v128_imports
+                                    .insert((import.module.to_string(), import.name.to_string()));
+                            }
+                            function_imports
+                                .push((import.module.to_string(), import.name.to_string()));
+                            ExternType::Func(sig)
+                        },
+                        wasmparser::TypeRef::Table(ty) => {
+                            tables.push(TableType::from_parsed(&ty)?);
+                            ExternType::Table(TableType::from_parsed(&ty)?)
+                        },
+                        wasmparser::TypeRef::Memory(ty) => {
+                            memories.push(MemoryType::from_parsed(&ty)?);
+                            ExternType::Memory(MemoryType::from_parsed(&ty)?)
+                        },
+                        wasmparser::TypeRef::Global(ty) => {
+                            globals.push(GlobalType::from_parsed(ty)?);
+                            ExternType::Global(GlobalType::from_parsed(ty)?)
+                        },
+                        wasmparser::TypeRef::Tag(tag) => {
+                            let params = Arc::<[ValueType]>::from(
+                                func_type(types, tag.func_type_idx)?.params(),
+                            );
+                            tags.push(Arc::clone(&params));
+                            tag_imports.insert(
+                                (import.module.to_string(), import.name.to_string()),
+                                params,
+                            );
+                            continue;
+                        },
+                    };
+
+                    imports.insert((import.module.to_string(), import.name.to_string()), ty);
+                }
+            },
+            wasmparser::Payload::ExportSection(section) => {
+                for export in section {
+                    let export = export?;
+                    let index = export.index as usize;
+                    let ty = match export.kind {
+                        wasmparser::ExternalKind::Func => {
+                            used_functions.insert(export.index);
+                            if v128_functions[index] {
+                                v128_exports.insert(export.name.to_string());
+                            }
+                            ExternType::Func(functions[index].clone().with_name(export.name))
+                        },
+                        wasmparser::ExternalKind::Table => ExternType::Table(tables[index]),
+                        wasmparser::ExternalKind::Memory => ExternType::Memory(memories[index]),
+                        wasmparser::ExternalKind::Global => ExternType::Global(globals[index]),
+                        wasmparser::ExternalKind::Tag => {
+                            tag_exports.insert(export.name.to_string(), Arc::clone(&tags[index]));
+                            continue;
+                        },
+                    };
+
+                    exports.insert(export.name.to_string(), ty);
+                }
+            },
+            wasmparser::Payload::ElementSection(section) => {
+                for element in section {
+                    let element = element?;
+
+                    #[cfg(feature = "tracing")]
+                    match element.kind {
+                        wasmparser::ElementKind::Passive => tracing::debug!("passive"),
+                        wasmparser::ElementKind::Active { .. } => tracing::debug!("active"),
+                        wasmparser::ElementKind::Declared => tracing::debug!("declared"),
+                    }
+
+                    match element.items {
+                        wasmparser::ElementItems::Functions(indices) => {
+                            for index in indices {
+                                used_functions.insert(index?);
+                            }
+                        },
+                        wasmparser::ElementItems::Expressions(_, exprs) => {
+                            for expr in exprs {
+                                collect_referenced_functions(
+                                    expr?.get_operators_reader(),
+                                    used_functions,
+                                )?;
+                            }
+                        },
+                    }
+                }
+            },
+            wasmparser::Payload::StartSection { func, .. } => {
+                used_functions.insert(func);
+            },
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                collect_referenced_functions(body.get_operators_reader()?, used_functions)?;
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Finishes parsing the module
+    fn finish(self) -> ParsedModule {
+        let unused_imports = self
+            .function_imports
+            .into_iter()
+            .zip(0_u32..)
+            .filter(|(_, index)| !self.used_functions.contains(index))
+            .map(|(import, _)| import)
+            .collect();
+
+        ParsedModule {
+            types: self.interner.types,
+            imports: self.imports,
+            exports: self.exports,
+            unused_imports,
+            tag_imports: self.tag_imports,
+            tag_exports: self.tag_exports,
+            v128_imports: self.v128_imports,
+            v128_exports: self.v128_exports,
+            degraded: false,
+            start: StartFunction::None,
+            epoch_interruption: false,
+            stats: self.stats,
+        }
+    }
+}
+
+/// Collects the indices of all functions that are called or referenced by
+/// the `operators`
+fn collect_referenced_functions(
+    operators: wasmparser::OperatorsReader,
+    functions: &mut FxHashSet<u32>,
+) -> anyhow::Result<()> {
+    for operator in operators {
+        match operator? {
+            wasmparser::Operator::Call { function_index }
+            | wasmparser::Operator::ReturnCall { function_index }
+            | wasmparser::Operator::RefFunc { function_index } => {
+                functions.insert(function_index);
+            },
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the function type at `index` in the type section
+fn func_type(types: &[Option<FuncType>], index: u32) -> anyhow::Result<&FuncType> {
+    match types.get(index as usize) {
+        Some(Some(ty)) => Ok(ty),
+        Some(None) => anyhow::bail!("type {index} is not a function type"),
+        None => anyhow::bail!("type index {index} is out of bounds"),
+    }
+}
+
+trait ValueTypeFrom: Sized {
+    fn from_value(value: wasmparser::ValType) -> anyhow::Result<Self>;
+    fn from_ref(ty: wasmparser::RefType) -> anyhow::Result<Self>;
+}
+
+impl ValueTypeFrom for ValueType {
+    fn from_value(value: wasmparser::ValType) -> anyhow::Result<Self> {
+        match value {
+            wasmparser::ValType::I32 => Ok(Self::I32),
+            wasmparser::ValType::I64 => Ok(Self::I64),
+            wasmparser::ValType::F32 => Ok(Self::F32),
+            wasmparser::ValType::F64 => Ok(Self::F64),
+            // v128 values cannot cross the JavaScript API, so they are reported
+            //  as opaque externrefs and rejected at the call boundary
+            wasmparser::ValType::V128 => Ok(Self::ExternRef),
+            wasmparser::ValType::Ref(ty) => Self::from_ref(ty),
+        }
+    }
+
+    fn from_ref(ty: wasmparser::RefType) -> anyhow::Result<Self> {
+        if ty.is_func_ref() {
+            Ok(Self::FuncRef)
+        } else if ty.is_extern_ref() {
+            Ok(Self::ExternRef)
+        } else {
+            anyhow::bail!("reference type {ty} is not yet supported")
+        }
+    }
+}
+
+trait TableTypeFrom: Sized {
+    fn from_parsed(value: &wasmparser::TableType) -> anyhow::Result<Self>;
+}
+
+impl TableTypeFrom for TableType {
+    fn from_parsed(value: &wasmparser::TableType) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            ValueType::from_ref(value.element_type)?,
+            value.initial.try_into()?,
+            // maximums beyond the JavaScript API limit have already been
+            //  rejected or clamped in the module bytes, see
+            //  `TableMaximumPolicy`
+            match value.maximum {
+                None => None,
+                Some(maximum) => Some(u32::try_from(maximum)?.min(Table::MAX_SIZE)),
+            },
+        ))
+    }
+}
+
+trait MemoryTypeFrom: Sized {
+    fn from_parsed(value: &wasmparser::MemoryType) -> anyhow::Result<Self>;
+}
+
+impl MemoryTypeFrom for MemoryType {
+    fn from_parsed(value: &wasmparser::MemoryType) -> anyhow::Result<Self> {
+        if value.memory64 {
+            anyhow::bail!("memory64 is not yet supported");
+        }
+
+        // shared memories are represented by their limits, the instantiated
+        //  memory knows whether it is shared, see Memory::is_shared
+        if value.shared && value.maximum.is_none() {
+            anyhow::bail!("shared memory must have a maximum size");
+        }
+
+        Ok(Self::new(
+            value.initial.try_into()?,
+            match value.maximum {
+                None => None,
+                Some(maximum) => Some(maximum.try_into()?),
+            },
+        ))
+    }
+}
+
+trait GlobalTypeFrom: Sized {
+    fn from_parsed(value: wasmparser::GlobalType) -> anyhow::Result<Self>;
+}
+
+impl GlobalTypeFrom for GlobalType {
+    fn from_parsed(value: wasmparser::GlobalType) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            ValueType::from_value(value.content_type)?,
+            value.mutable,
+        ))
+    }
+}
+
+fn js_module_buffer(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_MODULE_BUFFER: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_MODULE_BUFFER
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function moduleBuffer(capacity) {
+    let bytes = new Uint8Array(capacity);
+    let len = 0;
+
+    return {
+        reserve(additional) {
+            if ((len + additional) > bytes.length) {
+                const grown = new Uint8Array(Math.max(len + additional, bytes.length * 2));
+                grown.set(bytes.subarray(0, len));
+                bytes = grown;
+            }
+
+            const view = bytes.subarray(len, len + additional);
+            len += additional;
+            return view;
+        },
+        finish() {
+            return bytes.subarray(0, len);
+        },
+    };
+}
+moduleBuffer
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+/// Moves the start function `func` of the module `bytes` from its start
+/// section at `start_range` into the hidden [`DEFERRED_START_EXPORT`],
+/// extending the `exports` section or inserting a new one
+fn defer_start<'py>(
+    bytes: &Bound<'py, PyAny>,
+    exports: Option<&ExportSection>,
+    start_range: &Range<usize>,
+    func: u32,
+) -> Result<Bound<'py, PyAny>, PyErr> {
+    /// The id of the export section
+    const EXPORT_SECTION_ID: u8 = 7;
+    /// The kind of a function export
+    const FUNC_EXPORT_KIND: u8 = 0x00;
+
+    let py = bytes.py();
+
+    let (range, count, entries) = exports.map_or(
+        (start_range.start..start_range.start, 0, &[][..]),
+        |exports| {
+            (
+                exports.range.clone(),
+                exports.count,
+                exports.entries.as_slice(),
+            )
+        },
+    );
+
+    let mut contents = Vec::with_capacity(entries.len() + DEFERRED_START_EXPORT.len() + 16);
+    encode_leb128(&mut contents, count as usize + 1);
+    contents.extend_from_slice(entries);
+    encode_leb128(&mut contents, DEFERRED_START_EXPORT.len());
+    contents.extend_from_slice(DEFERRED_START_EXPORT.as_bytes());
+    contents.push(FUNC_EXPORT_KIND);
+    encode_leb128(&mut contents, func as usize);
+
+    let mut section = vec![EXPORT_SECTION_ID];
+    encode_leb128(&mut section, contents.len());
+    section.extend_from_slice(&contents);
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(func, "deferring the start function");
+
+    let array = js_uint8_array_new(py)?.call1((section.len(),))?;
+    array.call_method1(intern!(py, "assign"), (section.as_slice(),))?;
+
+    js_splice_module(py)?.call1((
+        bytes,
+        range.start,
+        range.end,
+        array,
+        start_range.start,
+        start_range.end,
+    ))
+}
+
+fn js_splice_module(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_SPLICE_MODULE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_SPLICE_MODULE
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function spliceModule(bytes, exportStart, exportEnd, exportSection, startStart, startEnd) {
+    const spliced = new Uint8Array(
+        bytes.length - (exportEnd - exportStart) + exportSection.length - (startEnd - startStart)
+    );
+
+    let len = 0;
+    for (const part of [
+        bytes.subarray(0, exportStart),
+        exportSection,
+        bytes.subarray(exportEnd, startStart),
+        bytes.subarray(startEnd),
+    ]) {
+        spliced.set(part, len);
+        len += part.length;
+    }
+
+    return spliced;
+}
+spliceModule
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+fn web_assembly_module_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_MODULE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_MODULE.import(py, "js.WebAssembly.Module", "new")
+}
+
+fn web_assembly_module(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_MODULE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_MODULE.import(py, "js.WebAssembly", "Module")
 }
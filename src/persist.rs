@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use pyo3::{intern, prelude::*, types::PyBytes};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use wasm_runtime_layer::{
+    backend::{AsContext, Extern, Value, WasmGlobal, WasmInstance, WasmStore},
+    ValueType,
+};
+
+use crate::{
+    conversion::{js_uint8_array_new, ToPy, ValueExt},
+    memory::PAGE_SIZE,
+    Engine, Instance, Store,
+};
+
+impl<T> Store<T> {
+    /// Serializes the user data together with the state of all mutable
+    /// globals and memories that are exported by the live instances of this
+    /// store. Instances that have already been dropped are not persisted.
+    ///
+    /// Memory contents are stored without their trailing zero bytes to keep
+    /// the format compact. Reference-typed globals are not persisted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state cannot be read from the instances or if
+    /// the `serializer` fails.
+    pub fn persist<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Store::persist").entered();
+
+        let instances = Python::with_gil(|py| {
+            self.instances()
+                .iter()
+                .map(|instance| InstanceSnapshot::capture(py, self, instance))
+                .collect::<Result<Vec<_>, PyErr>>()
+        })
+        .map_err(serde::ser::Error::custom)?;
+
+        StoreSnapshot {
+            data: self.data(),
+            instances,
+        }
+        .serialize(serializer)
+    }
+
+    /// Restores the user data and the state of all instances from a snapshot
+    /// that was created with [`Store::persist`].
+    ///
+    /// The live instances are matched up with the snapshot by their order of
+    /// instantiation, i.e. the same modules must have been instantiated in
+    /// the same order in this store before restoring the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `deserializer` fails or if the snapshot does
+    /// not match the instances of this store.
+    pub fn restore<'de, D: Deserializer<'de>>(&mut self, deserializer: D) -> Result<(), D::Error>
+    where
+        T: Deserialize<'de>,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Store::restore").entered();
+
+        let snapshot = StoreSnapshot::<T, Vec<InstanceSnapshot>>::deserialize(deserializer)?;
+
+        let instances = self.instances();
+
+        if snapshot.instances.len() != instances.len() {
+            return Err(serde::de::Error::custom(format!(
+                "snapshot contains {} instances but the store has {}",
+                snapshot.instances.len(),
+                instances.len()
+            )));
+        }
+
+        Python::with_gil(|py| {
+            for (instance, snapshot) in instances.iter().zip(&snapshot.instances) {
+                snapshot.apply(py, self, instance)?;
+            }
+
+            anyhow::Ok(())
+        })
+        .map_err(serde::de::Error::custom)?;
+
+        *self.data_mut() = snapshot.data;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+/// Persisted state of a store
+struct StoreSnapshot<T, I> {
+    /// The user data
+    data: T,
+    /// The persisted state of each instance
+    instances: I,
+}
+
+#[derive(Serialize, Deserialize)]
+/// Persisted state of the exported globals and memories of an instance
+struct InstanceSnapshot {
+    /// Values of the mutable globals, by export name
+    globals: BTreeMap<String, PersistedValue>,
+    /// Contents of the memories, by export name
+    memories: BTreeMap<String, MemorySnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+/// A persisted numeric global value
+enum PersistedValue {
+    /// A 32-bit integer value
+    I32(i32),
+    /// A 64-bit integer value
+    I64(i64),
+    /// A 32-bit floating point value
+    F32(f32),
+    /// A 64-bit floating point value
+    F64(f64),
+}
+
+#[derive(Serialize, Deserialize)]
+/// Persisted contents of a memory
+struct MemorySnapshot {
+    /// The number of pages of the memory
+    pages: u64,
+    /// The contents of the memory, without trailing zero bytes
+    bytes: Vec<u8>,
+}
+
+impl InstanceSnapshot {
+    fn capture<T>(py: Python, store: &Store<T>, instance: &Instance) -> Result<Self, PyErr> {
+        let mut globals = BTreeMap::new();
+        let mut memories = BTreeMap::new();
+
+        for export in WasmInstance::exports(instance, store.as_context()) {
+            match export.value {
+                Extern::Global(global) => {
+                    let ty = WasmGlobal::ty(&global, store.as_context());
+
+                    if !ty.mutable() {
+                        continue;
+                    }
+
+                    let value = global.to_py(py).bind(py).getattr(intern!(py, "value"))?;
+
+                    let value = match Value::from_py_typed(value, ty.content())? {
+                        Value::I32(v) => PersistedValue::I32(v),
+                        Value::I64(v) => PersistedValue::I64(v),
+                        Value::F32(v) => PersistedValue::F32(v),
+                        Value::F64(v) => PersistedValue::F64(v),
+                        Value::FuncRef(_) | Value::ExternRef(_) => continue,
+                    };
+
+                    globals.insert(export.name, value);
+                },
+                Extern::Memory(memory) => {
+                    let buffer = memory.to_py(py).bind(py).getattr(intern!(py, "buffer"))?;
+                    let byte_len: u64 = buffer.getattr(intern!(py, "byteLength"))?.extract()?;
+
+                    let bytes: Bound<PyBytes> = js_uint8_array_new(py)?
+                        .call1((buffer,))?
+                        .call_method0(intern!(py, "to_bytes"))?
+                        .extract()?;
+                    let bytes = bytes.as_bytes();
+                    let len = bytes
+                        .iter()
+                        .rposition(|byte| *byte != 0)
+                        .map_or(0, |last| last + 1);
+
+                    memories.insert(
+                        export.name,
+                        MemorySnapshot {
+                            pages: byte_len / PAGE_SIZE,
+                            bytes: bytes[..len].to_vec(),
+                        },
+                    );
+                },
+                Extern::Table(_) | Extern::Func(_) => (),
+            }
+        }
+
+        Ok(Self { globals, memories })
+    }
+
+    fn apply<T>(&self, py: Python, store: &Store<T>, instance: &Instance) -> anyhow::Result<()> {
+        for (name, value) in &self.globals {
+            let Some(Extern::Global(global)) = instance.get_export(store.as_context(), name) else {
+                anyhow::bail!("snapshot contains global `{name}` that is not exported");
+            };
+
+            let ty = WasmGlobal::ty(&global, store.as_context());
+
+            let value = match (value, ty.content()) {
+                (PersistedValue::I32(v), ValueType::I32) => Value::<Engine>::I32(*v),
+                (PersistedValue::I64(v), ValueType::I64) => Value::I64(*v),
+                (PersistedValue::F32(v), ValueType::F32) => Value::F32(*v),
+                (PersistedValue::F64(v), ValueType::F64) => Value::F64(*v),
+                _ => anyhow::bail!("snapshot value of global `{name}` does not match its type"),
+            };
+
+            global
+                .to_py(py)
+                .bind(py)
+                .setattr(intern!(py, "value"), value.to_py(py))?;
+        }
+
+        for (name, snapshot) in &self.memories {
+            let Some(Extern::Memory(memory)) = instance.get_export(store.as_context(), name) else {
+                anyhow::bail!("snapshot contains memory `{name}` that is not exported");
+            };
+
+            let memory = memory.to_py(py);
+            let memory = memory.bind(py);
+
+            let byte_len: u64 = memory
+                .getattr(intern!(py, "buffer"))?
+                .getattr(intern!(py, "byteLength"))?
+                .extract()?;
+            let pages = byte_len / PAGE_SIZE;
+
+            if pages < snapshot.pages {
+                memory.call_method1(intern!(py, "grow"), (snapshot.pages - pages,))?;
+            }
+
+            let buffer = memory.getattr(intern!(py, "buffer"))?;
+
+            js_uint8_array_new(py)?
+                .call1((&buffer,))?
+                .call_method1(intern!(py, "fill"), (0,))?;
+            js_uint8_array_new(py)?
+                .call1((buffer, 0, snapshot.bytes.len()))?
+                .call_method1(intern!(py, "assign"), (snapshot.bytes.as_slice(),))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,443 @@
+use std::{error::Error as StdError, fmt, sync::Arc};
+
+use wasm_runtime_layer::{backend::Value, FuncType, ValueType};
+
+use crate::{conversion::ValueExt, Capabilities, Engine};
+
+/// A [`FuncType`] whose parameters and results can be named.
+///
+/// The names are only used for diagnostics, i.e. in error messages and
+/// [`tracing`] events, such that a failing conversion reports
+/// "param \`width\` (i32)" instead of "param 2". Two signatures with the same
+/// [`FuncType`] are interchangeable, regardless of their names.
+///
+/// A signature is created with a [`FuncSignatureBuilder`], or converted from
+/// an unnamed [`FuncType`].
+///
+/// [`tracing`]: https://docs.rs/tracing/0.1/tracing/
+#[derive(Debug, Clone)]
+pub struct FuncSignature {
+    /// The function type
+    ty: FuncType,
+    /// The optional names of the parameters
+    params: Arc<[Option<Arc<str>>]>,
+    /// The optional names of the results
+    results: Arc<[Option<Arc<str>>]>,
+    /// Whether the function uses `v128` values, which are reported as
+    /// `externref`s in the function type
+    v128: bool,
+}
+
+impl FuncSignature {
+    /// The maximum number of parameters of a function in the JavaScript API
+    pub const MAX_PARAMS: usize = 1000;
+    /// The maximum number of results of a function in the JavaScript API
+    pub const MAX_RESULTS: usize = 1000;
+
+    /// Creates a new builder for a function signature.
+    #[must_use]
+    pub fn builder() -> FuncSignatureBuilder {
+        FuncSignatureBuilder::default()
+    }
+
+    /// Returns the function type of this signature.
+    #[must_use]
+    pub const fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+
+    /// Returns `true` if the function has `v128` parameters or results,
+    /// which are reported as `externref`s in its [`FuncType`] and cannot be
+    /// passed through the JavaScript API, see [`V128`].
+    ///
+    /// [`V128`]: crate::V128
+    #[must_use]
+    pub const fn uses_v128(&self) -> bool {
+        self.v128
+    }
+
+    /// Marks the function as using `v128` values
+    pub(crate) const fn with_v128(mut self, v128: bool) -> Self {
+        self.v128 = v128;
+        self
+    }
+
+    /// Returns the name of the parameter at `index`, if it is named.
+    #[must_use]
+    pub fn param_name(&self, index: usize) -> Option<&str> {
+        self.params.get(index)?.as_deref()
+    }
+
+    /// Returns the name of the result at `index`, if it is named.
+    #[must_use]
+    pub fn result_name(&self, index: usize) -> Option<&str> {
+        self.results.get(index)?.as_deref()
+    }
+
+    /// Checks that the `args` match the parameters of this signature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of `args` or the type of any argument
+    /// does not match the parameters, or if the function uses `v128` values,
+    /// which cannot be passed through the JavaScript API.
+    pub fn validate_args(&self, args: &[Value<Engine>]) -> Result<(), SignatureError> {
+        if self.v128 {
+            return Err(SignatureError::Unrepresentable {
+                signature: self.to_string(),
+                reason: String::from(
+                    "it uses v128 values, which are shown as externref, but v128 values cannot be \
+                     passed to or from WebAssembly functions, exchange them through memory instead",
+                ),
+            });
+        }
+
+        if self.ty.params().len() != args.len() {
+            return Err(SignatureError::ArgumentCount {
+                signature: self.to_string(),
+                expected: self.ty.params().len(),
+                found: args.len(),
+            });
+        }
+
+        for (index, (ty, arg)) in self.ty.params().iter().zip(args).enumerate() {
+            if *ty != ValueExt::ty(arg) {
+                return Err(SignatureError::ArgumentType {
+                    signature: self.to_string(),
+                    param: self.describe_param(index).to_string(),
+                    found: ValueExt::ty(arg),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the `results` buffer matches the results of this
+    /// signature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of `results` does not match.
+    pub fn validate_results(&self, results: &[Value<Engine>]) -> Result<(), SignatureError> {
+        if self.ty.results().len() != results.len() {
+            return Err(SignatureError::ResultCount {
+                signature: self.to_string(),
+                expected: self.ty.results().len(),
+                found: results.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that this signature can be represented in the browser's
+    /// JavaScript API with the `capabilities`, see [`Engine::capabilities`].
+    ///
+    /// Host functions that are created with [`Func::try_new_with_signature`]
+    /// are checked when they are created, such that an unrepresentable
+    /// signature fails fast instead of on the first call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signature exceeds the [implementation limits]
+    /// of the JavaScript API, if it has multiple results but the multi-value
+    /// feature extension is not supported, or if it uses `i64` values but
+    /// JavaScript `BigInt`s are not supported.
+    ///
+    /// [`Engine::capabilities`]: crate::Engine::capabilities
+    /// [`Func::try_new_with_signature`]: crate::Func::try_new_with_signature
+    /// [implementation limits]: https://webassembly.github.io/spec/js-api/#limits
+    pub fn check_representable(&self, capabilities: &Capabilities) -> Result<(), SignatureError> {
+        let unrepresentable = |reason: String| SignatureError::Unrepresentable {
+            signature: self.to_string(),
+            reason,
+        };
+
+        if self.ty.params().len() > Self::MAX_PARAMS {
+            return Err(unrepresentable(format!(
+                "it has {} params but at most {} are supported",
+                self.ty.params().len(),
+                Self::MAX_PARAMS
+            )));
+        }
+
+        if self.ty.results().len() > Self::MAX_RESULTS {
+            return Err(unrepresentable(format!(
+                "it has {} results but at most {} are supported",
+                self.ty.results().len(),
+                Self::MAX_RESULTS
+            )));
+        }
+
+        if (self.ty.results().len() > 1) && !capabilities.supports_wasm_feature("multi-value") {
+            return Err(unrepresentable(format!(
+                "it has {} results but the multi-value feature extension is not supported",
+                self.ty.results().len()
+            )));
+        }
+
+        if !capabilities.bigint() {
+            let i64_param = self
+                .ty
+                .params()
+                .iter()
+                .position(|ty| *ty == ValueType::I64)
+                .map(|index| self.describe_param(index));
+            let i64_result = self
+                .ty
+                .results()
+                .iter()
+                .position(|ty| *ty == ValueType::I64)
+                .map(|index| self.describe_result(index));
+
+            if let Some(slot) = i64_param.or(i64_result) {
+                return Err(unrepresentable(format!(
+                    "{slot} requires JavaScript BigInts, which are not supported"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describes the parameter at `index` for diagnostics
+    pub(crate) fn describe_param(&self, index: usize) -> SlotDescription<'_> {
+        SlotDescription {
+            kind: "param",
+            index,
+            name: self.param_name(index),
+            ty: self.ty.params().get(index).copied(),
+        }
+    }
+
+    /// Describes the result at `index` for diagnostics
+    pub(crate) fn describe_result(&self, index: usize) -> SlotDescription<'_> {
+        SlotDescription {
+            kind: "result",
+            index,
+            name: self.result_name(index),
+            ty: self.ty.results().get(index).copied(),
+        }
+    }
+}
+
+impl From<FuncType> for FuncSignature {
+    fn from(ty: FuncType) -> Self {
+        Self {
+            params: vec![None; ty.params().len()].into(),
+            results: vec![None; ty.results().len()].into(),
+            ty,
+            v128: false,
+        }
+    }
+}
+
+impl From<FuncSignature> for FuncType {
+    fn from(signature: FuncSignature) -> Self {
+        signature.ty
+    }
+}
+
+impl fmt::Display for FuncSignature {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fn write_slots(
+            fmt: &mut fmt::Formatter,
+            names: &[Option<Arc<str>>],
+            tys: &[ValueType],
+        ) -> fmt::Result {
+            for (i, (name, ty)) in names.iter().zip(tys).enumerate() {
+                if i > 0 {
+                    fmt.write_str(", ")?;
+                }
+                if let Some(name) = name {
+                    write!(fmt, "{name}: ")?;
+                }
+                write!(fmt, "{ty}")?;
+            }
+            Ok(())
+        }
+
+        fmt.write_str("func(")?;
+        write_slots(fmt, &self.params, self.ty.params())?;
+        fmt.write_str(")")?;
+
+        if !self.ty.results().is_empty() {
+            fmt.write_str(" -> (")?;
+            write_slots(fmt, &self.results, self.ty.results())?;
+            fmt.write_str(")")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// Error that is returned when the arguments or results of a call do not
+/// match the [`FuncSignature`] of the called function.
+pub enum SignatureError {
+    /// The wrong number of arguments was provided
+    ArgumentCount {
+        /// The signature of the called function
+        signature: String,
+        /// The number of parameters of the function
+        expected: usize,
+        /// The number of provided arguments
+        found: usize,
+    },
+    /// An argument of the wrong type was provided
+    ArgumentType {
+        /// The signature of the called function
+        signature: String,
+        /// The description of the mismatched parameter
+        param: String,
+        /// The type of the provided argument
+        found: ValueType,
+    },
+    /// A results buffer of the wrong length was provided
+    ResultCount {
+        /// The signature of the called function
+        signature: String,
+        /// The number of results of the function
+        expected: usize,
+        /// The length of the provided results buffer
+        found: usize,
+    },
+    /// The signature cannot be represented in the JavaScript API, see
+    /// [`FuncSignature::check_representable`]
+    Unrepresentable {
+        /// The unrepresentable signature
+        signature: String,
+        /// The reason why the signature is unrepresentable
+        reason: String,
+    },
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ArgumentCount {
+                signature,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "{signature} expects {expected} arguments but {found} were provided"
+            ),
+            Self::ArgumentType {
+                signature,
+                param,
+                found,
+            } => write!(
+                fmt,
+                "{param} of {signature} was provided a {found} argument"
+            ),
+            Self::ResultCount {
+                signature,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "{signature} returns {expected} results but {found} were requested"
+            ),
+            Self::Unrepresentable { signature, reason } => write!(
+                fmt,
+                "{signature} cannot be represented in the JavaScript API since {reason}"
+            ),
+        }
+    }
+}
+
+impl StdError for SignatureError {}
+
+#[derive(Debug, Clone, Default)]
+/// A builder for a [`FuncSignature`].
+pub struct FuncSignatureBuilder {
+    /// The debug name of the function
+    name: Option<Arc<str>>,
+    /// The optionally named parameters
+    params: Vec<(Option<Arc<str>>, ValueType)>,
+    /// The optionally named results
+    results: Vec<(Option<Arc<str>>, ValueType)>,
+}
+
+impl FuncSignatureBuilder {
+    /// Sets the debug name of the function, see [`FuncType::with_name`].
+    #[must_use]
+    pub fn name(mut self, name: impl Into<Arc<str>>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Appends a parameter with the `name` and type `ty`.
+    #[must_use]
+    pub fn param(mut self, name: impl Into<Arc<str>>, ty: ValueType) -> Self {
+        self.params.push((Some(name.into()), ty));
+        self
+    }
+
+    /// Appends an unnamed parameter of type `ty`.
+    #[must_use]
+    pub fn unnamed_param(mut self, ty: ValueType) -> Self {
+        self.params.push((None, ty));
+        self
+    }
+
+    /// Appends a result with the `name` and type `ty`.
+    #[must_use]
+    pub fn result(mut self, name: impl Into<Arc<str>>, ty: ValueType) -> Self {
+        self.results.push((Some(name.into()), ty));
+        self
+    }
+
+    /// Appends an unnamed result of type `ty`.
+    #[must_use]
+    pub fn unnamed_result(mut self, ty: ValueType) -> Self {
+        self.results.push((None, ty));
+        self
+    }
+
+    /// Builds the function signature.
+    #[must_use]
+    pub fn build(self) -> FuncSignature {
+        let (params, param_tys): (Vec<_>, Vec<_>) = self.params.into_iter().unzip();
+        let (results, result_tys): (Vec<_>, Vec<_>) = self.results.into_iter().unzip();
+
+        let mut ty = FuncType::new(param_tys, result_tys);
+        if let Some(name) = self.name {
+            ty = ty.with_name(name);
+        }
+
+        FuncSignature {
+            ty,
+            params: params.into(),
+            results: results.into(),
+            v128: false,
+        }
+    }
+}
+
+/// A parameter or result of a [`FuncSignature`], which is displayed as
+/// "param \`name\` (ty)" or "param index (ty)"
+pub struct SlotDescription<'a> {
+    /// Whether this is a parameter or result
+    kind: &'static str,
+    /// The index of the slot
+    index: usize,
+    /// The optional name of the slot
+    name: Option<&'a str>,
+    /// The type of the slot, if the index is in bounds
+    ty: Option<ValueType>,
+}
+
+impl fmt::Display for SlotDescription<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(fmt, "{} `{name}`", self.kind)?,
+            None => write!(fmt, "{} {}", self.kind, self.index)?,
+        }
+
+        self.ty.map_or(Ok(()), |ty| write!(fmt, " ({ty})"))
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+
+use wasm_runtime_layer::{
+    backend::{AsContextMut, Value, WasmTable},
+    ValueType,
+};
+
+use crate::{Engine, Error, Func, Table};
+
+/// An allocator for the slots of a `funcref` [`Table`], e.g. to implement
+/// `dlopen`-like dynamic linking where the functions of loaded modules are
+/// added to and removed from the indirect function table.
+///
+/// The allocator only manages the slots that it has appended to the table
+/// itself, such that slots which are used by the guest are never handed out.
+/// Freed slots are cleared and reused before the table is grown, always
+/// starting with the lowest free index, which keeps the table compact.
+#[derive(Debug)]
+pub struct TableSlotAllocator {
+    /// The table whose slots are allocated
+    table: Table,
+    /// The slots that are currently allocated
+    allocated: BTreeSet<u32>,
+    /// The slots that were allocated before and have been freed
+    free: BTreeSet<u32>,
+}
+
+impl TableSlotAllocator {
+    /// Creates a new allocator for the slots of the `table`, which starts
+    /// without any allocated or free slots.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `table` is not a `funcref` table.
+    pub fn new(table: &Table) -> anyhow::Result<Self> {
+        if table.ty_ref().element() != ValueType::FuncRef {
+            return Err(Error::Conversion(format!(
+                "slots can only be allocated in funcref tables, but the table has the element \
+                 type {}",
+                table.ty_ref().element()
+            ))
+            .into());
+        }
+
+        Ok(Self {
+            table: table.clone(),
+            allocated: BTreeSet::new(),
+            free: BTreeSet::new(),
+        })
+    }
+
+    /// Returns the table whose slots are allocated.
+    #[must_use]
+    pub const fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// Stores the `func` in a free slot of the table, growing it by one slot
+    /// if no slot is free, and returns the index of the slot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `func` cannot be stored in the table, see
+    /// [`Table::set`], or if the table cannot be grown, e.g. because it has
+    /// reached its maximum size.
+    ///
+    /// [`Table::set`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Table.html#method.set
+    pub fn alloc_slot(
+        &mut self,
+        mut ctx: impl AsContextMut<Engine>,
+        func: Func,
+    ) -> anyhow::Result<u32> {
+        let index = if let Some(&index) = self.free.first() {
+            self.table
+                .set(ctx.as_context_mut(), index, Value::FuncRef(Some(func)))?;
+            self.free.remove(&index);
+            index
+        } else {
+            self.table
+                .grow(ctx.as_context_mut(), 1, Value::FuncRef(Some(func)))?
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(index, "TableSlotAllocator::alloc_slot");
+
+        self.allocated.insert(index);
+
+        Ok(index)
+    }
+
+    /// Frees the slot at the `index`, which must have been allocated with
+    /// [`TableSlotAllocator::alloc_slot`], and clears it such that the
+    /// function it held can no longer be called through the table.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the slot is not allocated, e.g. because it was
+    /// already freed, or if it cannot be cleared.
+    pub fn free_slot(
+        &mut self,
+        mut ctx: impl AsContextMut<Engine>,
+        index: u32,
+    ) -> anyhow::Result<()> {
+        if !self.allocated.contains(&index) {
+            anyhow::bail!("table slot {index} is not allocated");
+        }
+
+        self.table
+            .set(ctx.as_context_mut(), index, Value::FuncRef(None))?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(index, "TableSlotAllocator::free_slot");
+
+        self.allocated.remove(&index);
+        self.free.insert(index);
+
+        Ok(())
+    }
+
+    /// Checks if the slot at the `index` is currently allocated.
+    #[must_use]
+    pub fn is_allocated(&self, index: u32) -> bool {
+        self.allocated.contains(&index)
+    }
+
+    /// Returns the indices of the allocated slots in ascending order.
+    pub fn allocated_slots(&self) -> impl Iterator<Item = u32> + '_ {
+        self.allocated.iter().copied()
+    }
+
+    /// Returns the number of freed slots that can be reused without growing
+    /// the table.
+    #[must_use]
+    pub fn free_slots(&self) -> usize {
+        self.free.len()
+    }
+}
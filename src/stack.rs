@@ -0,0 +1,253 @@
+use std::sync::{Arc, OnceLock};
+
+use wasm_runtime_layer::{
+    backend::{AsContextMut, Extern, Imports, Value, WasmFunc, WasmGlobal, WasmInstance},
+    ValueType,
+};
+
+use crate::{store::StoreContextMut, Engine, Error, Func, Global, Instance};
+
+/// The shadow stack of a C-family guest, which follows the LLVM convention of
+/// keeping its stack pointer in the mutable `i32`
+/// [`ShadowStack::STACK_POINTER`] global.
+///
+/// The shadow stack lives inside linear memory and grows downwards, so a
+/// stack overflow silently corrupts the data below it instead of trapping.
+/// The remaining headroom can be inspected with [`ShadowStack::headroom`],
+/// and a [`StackGuard`] checks it whenever the guest calls into the host.
+///
+/// The guest must export its stack pointer, e.g. by linking with
+/// `--export=__stack_pointer`.
+#[derive(Debug, Clone)]
+pub struct ShadowStack {
+    /// The stack pointer global
+    stack_pointer: Global,
+    /// The lowest address of the stack
+    stack_low: u32,
+}
+
+impl ShadowStack {
+    /// The conventional names of the globals that hold the lowest address of
+    /// the stack, in order of preference
+    pub const STACK_LOW: [&'static str; 2] = ["__stack_low", "__data_end"];
+    /// The conventional name of the stack pointer global
+    pub const STACK_POINTER: &'static str = "__stack_pointer";
+
+    /// Locates the shadow stack of the `instance` through its exported
+    /// [`ShadowStack::STACK_POINTER`] global.
+    ///
+    /// The lowest address of the stack is read from the first exported
+    /// [`ShadowStack::STACK_LOW`] global, or assumed to be zero otherwise,
+    /// which can be overridden with [`ShadowStack::with_stack_low`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instance does not export its stack pointer as
+    /// a mutable `i32` global.
+    pub fn new(mut ctx: impl AsContextMut<Engine>, instance: &Instance) -> anyhow::Result<Self> {
+        let stack_pointer = match instance.get_export(ctx.as_context(), Self::STACK_POINTER) {
+            Some(Extern::Global(global))
+                if global.ty_ref().content() == ValueType::I32 && global.ty_ref().mutable() =>
+            {
+                global
+            },
+            _ => {
+                return Err(Error::Conversion(format!(
+                    "the instance does not export its stack pointer as the mutable i32 global `{}`",
+                    Self::STACK_POINTER
+                ))
+                .into())
+            },
+        };
+
+        let mut stack_low = 0;
+        for name in Self::STACK_LOW {
+            if let Some(Extern::Global(global)) = instance.get_export(ctx.as_context(), name) {
+                if let Value::I32(address) = global.get(ctx.as_context_mut()) {
+                    #[allow(clippy::cast_sign_loss)]
+                    let address = address as u32;
+                    stack_low = address;
+                    break;
+                }
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(stack_low, "ShadowStack::new");
+
+        Ok(Self {
+            stack_pointer,
+            stack_low,
+        })
+    }
+
+    /// Overrides the lowest address of the stack, e.g. if the guest does not
+    /// export any of the [`ShadowStack::STACK_LOW`] globals.
+    #[must_use]
+    pub const fn with_stack_low(mut self, stack_low: u32) -> Self {
+        self.stack_low = stack_low;
+        self
+    }
+
+    /// Returns the lowest address of the stack.
+    #[must_use]
+    pub const fn stack_low(&self) -> u32 {
+        self.stack_low
+    }
+
+    /// Returns the current value of the stack pointer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stack pointer global does not hold an `i32`.
+    pub fn stack_pointer(&self, ctx: impl AsContextMut<Engine>) -> anyhow::Result<u32> {
+        match self.stack_pointer.get(ctx) {
+            #[allow(clippy::cast_sign_loss)]
+            Value::I32(address) => Ok(address as u32),
+            value => Err(Error::Conversion(format!(
+                "the stack pointer holds {value:?} instead of an i32"
+            ))
+            .into()),
+        }
+    }
+
+    /// Returns the remaining headroom of the stack in bytes, i.e. the
+    /// distance between the stack pointer and the lowest address of the
+    /// stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stack pointer cannot be read.
+    pub fn headroom(&self, ctx: impl AsContextMut<Engine>) -> anyhow::Result<u32> {
+        Ok(self.stack_pointer(ctx)?.saturating_sub(self.stack_low))
+    }
+
+    /// Checks that the remaining headroom of the stack is at least
+    /// `threshold` bytes, and returns the headroom.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Trap`] if the headroom is below the `threshold`,
+    /// or an error if the stack pointer cannot be read.
+    pub fn check_headroom(
+        &self,
+        ctx: impl AsContextMut<Engine>,
+        threshold: u32,
+    ) -> anyhow::Result<u32> {
+        let headroom = self.headroom(ctx)?;
+
+        if headroom < threshold {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(headroom, threshold, "shadow stack headroom is exhausted");
+
+            return Err(Error::Trap(format!(
+                "the shadow stack headroom of {headroom} bytes is below the threshold of \
+                 {threshold} bytes"
+            ))
+            .into());
+        }
+
+        Ok(headroom)
+    }
+}
+
+/// A guard that traps when the headroom of a guest's [`ShadowStack`] drops
+/// below a threshold, which is checked whenever the guest calls a host
+/// function import.
+///
+/// Since the shadow stack is only known once the guest has been instantiated,
+/// the imports are first instrumented with [`StackGuard::instrument`], and
+/// the guard is then attached to the instance with [`StackGuard::attach`].
+/// Calls before the guard is attached, e.g. from the start function, are not
+/// checked.
+#[derive(Debug, Clone)]
+pub struct StackGuard {
+    /// The shadow stack, once attached
+    stack: Arc<OnceLock<ShadowStack>>,
+    /// The minimum headroom in bytes
+    threshold: u32,
+}
+
+impl StackGuard {
+    /// Creates a new guard that traps when the stack headroom drops below
+    /// `threshold` bytes.
+    #[must_use]
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            stack: Arc::new(OnceLock::new()),
+            threshold,
+        }
+    }
+
+    /// Returns the minimum headroom in bytes.
+    #[must_use]
+    pub const fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Returns the guarded shadow stack, if the guard has been attached.
+    #[must_use]
+    pub fn shadow_stack(&self) -> Option<&ShadowStack> {
+        self.stack.get()
+    }
+
+    /// Returns a copy of the `imports` in which every function import is
+    /// wrapped in a host function that checks the stack headroom before
+    /// forwarding the call.
+    pub fn instrument<T>(
+        &self,
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        imports: &Imports<Engine>,
+    ) -> Imports<Engine> {
+        let mut instrumented = Imports::new();
+
+        for (module, name, import) in imports.iter() {
+            let import = match import {
+                Extern::Func(func) => {
+                    let guard = self.clone();
+                    let func = func.clone();
+
+                    Extern::Func(Func::new_with_signature(
+                        ctx.as_context_mut(),
+                        func.signature().clone(),
+                        move |mut store: StoreContextMut<T>, args, results| {
+                            if let Some(stack) = guard.stack.get() {
+                                stack.check_headroom(store.as_context_mut(), guard.threshold)?;
+                            }
+
+                            func.call::<T>(store, args, results)
+                        },
+                    ))
+                },
+                import => import.clone(),
+            };
+
+            instrumented.define(module, name, import);
+        }
+
+        instrumented
+    }
+
+    /// Attaches the guard to the shadow stack of the `instance`, which must
+    /// have been instantiated with the instrumented imports, see
+    /// [`StackGuard::instrument`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the guard has already been attached or if the
+    /// shadow stack of the `instance` cannot be located, see
+    /// [`ShadowStack::new`].
+    pub fn attach(
+        &self,
+        ctx: impl AsContextMut<Engine>,
+        instance: &Instance,
+    ) -> anyhow::Result<()> {
+        let stack = ShadowStack::new(ctx, instance)?;
+
+        if self.stack.set(stack).is_err() {
+            anyhow::bail!("the stack guard has already been attached to an instance");
+        }
+
+        Ok(())
+    }
+}
@@ -1,18 +1,46 @@
 use std::{
     fmt,
+    hash::{Hash, Hasher},
     marker::PhantomData,
-    sync::{Arc, Weak},
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
 };
 
-use wasm_runtime_layer::backend::{
-    AsContext, AsContextMut, WasmStore, WasmStoreContext, WasmStoreContextMut,
+use fxhash::FxHashMap;
+
+use pyo3::{
+    intern,
+    prelude::*,
+    sync::GILOnceCell,
+    types::{PyDict, PyList},
+};
+use wasm_runtime_layer::{
+    backend::{
+        AsContext, AsContextMut, Value, WasmInstance, WasmStore, WasmStoreContext,
+        WasmStoreContextMut,
+    },
+    FuncType,
 };
 use wobbly::sync::Wobbly;
 
-use crate::{func::PyHostFuncFn, Engine};
+use crate::{
+    conversion::ToPy,
+    func::{DirectHostFuncFn, PyHostFuncFn},
+    history::{Mutation, MutationHistory, MutationKind},
+    instance::WeakInstance,
+    interrupt::{create_epoch_state, InterruptHandle},
+    Engine, Error, Func, Instance, ResourceLimiter,
+};
 
 /// A store for the [`Engine`], which stores host-defined data `T` and internal
 /// state.
+///
+/// The store only keeps weak handles to the [`Instance`]s that are
+/// instantiated in it, such that dropped instances are released before the
+/// store is dropped.
 pub struct Store<T> {
     /// The internal store is kept behind a pointer.
     ///
@@ -63,11 +91,35 @@ struct StoreInner<T> {
     engine: Engine,
     /// The user data
     data: T,
-    /// The user host functions, which must live in Rust and not JS to avoid a
-    /// cross-language reference cycle
-    host_funcs: Vec<Wobbly<PyHostFuncFn>>,
+    /// The user host functions and their types, which must live in Rust and
+    /// not JS to avoid a cross-language reference cycle
+    host_funcs: Vec<(Wobbly<PyHostFuncFn>, FuncType)>,
+    /// The Rust host functions that can be called directly from the host
+    direct_host_funcs: Vec<Wobbly<DirectHostFuncFn>>,
+    /// The instances that have been instantiated in this store, which are
+    /// not kept alive by the store
+    instances: Vec<WeakInstance>,
+    /// The current nesting depth of host calls
+    host_call_depth: usize,
+    /// The recent mutations, if enabled in the engine config
+    mutation_history: MutationHistory,
+    /// The cached JavaScript descriptors of function types, which are used
+    /// to construct typed host functions
+    func_type_descriptors: FxHashMap<FuncTypeKey, Py<PyAny>>,
+    /// The accessor of the resource limiter in the user data, if installed
+    limiter: Option<Box<LimiterFn<T>>>,
+    /// The JavaScript state of the epoch interruption, once created
+    epoch_state: Option<Py<PyAny>>,
+    /// The hook that is called on host and guest transitions, if installed
+    call_hook: Option<Box<CallHookFn<T>>>,
 }
 
+/// An accessor of the [`ResourceLimiter`] in the user data of a store
+type LimiterFn<T> = dyn 'static + Send + Sync + FnMut(&mut T) -> &mut dyn ResourceLimiter;
+
+/// A hook that is called with the user data of a store on every [`CallHook`]
+type CallHookFn<T> = dyn 'static + Send + Sync + FnMut(&mut T, CallHook) -> anyhow::Result<()>;
+
 impl<T> WasmStore<T, Engine> for Store<T> {
     fn new(engine: &Engine, data: T) -> Self {
         #[cfg(feature = "tracing")]
@@ -78,6 +130,14 @@ impl<T> WasmStore<T, Engine> for Store<T> {
                 engine: engine.clone(),
                 data,
                 host_funcs: Vec::new(),
+                direct_host_funcs: Vec::new(),
+                instances: Vec::new(),
+                host_call_depth: 0,
+                mutation_history: MutationHistory::default(),
+                func_type_descriptors: FxHashMap::default(),
+                limiter: None,
+                epoch_state: None,
+                call_hook: None,
             })))),
             _marker: PhantomData::<T>,
         }
@@ -180,6 +240,184 @@ impl<T> Store<T> {
         unsafe { &*self.inner.as_ptr() }
     }
 
+    /// Returns the live instances that have been instantiated in this store,
+    /// in the order of their instantiation
+    pub(crate) fn instances(&self) -> Vec<Instance> {
+        self.as_inner()
+            .instances
+            .iter()
+            .filter_map(WeakInstance::upgrade)
+            .collect()
+    }
+
+    /// Returns a Python view of all live instances in this store, intended
+    /// for interactive debugging, e.g. from a Pyodide REPL.
+    ///
+    /// The view is a list with one dictionary per instance, in the order of
+    /// their instantiation, which maps the export names to the exported
+    /// JavaScript objects. Exported functions can be called directly from
+    /// Python, while globals, memories, and tables expose the JavaScript
+    /// [`WebAssembly`] API, e.g. `view[0]["counter"].value`.
+    ///
+    /// Note that calling exported functions through the view bypasses the
+    /// store, so host functions called by the guest reborrow the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if creating the Python objects fails.
+    ///
+    /// [`WebAssembly`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface
+    pub fn debug_py_view<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyList>, PyErr> {
+        let view = PyList::empty(py);
+
+        for instance in &self.instances() {
+            let exports = PyDict::new(py);
+
+            for export in WasmInstance::exports(instance, self.as_context()) {
+                exports.set_item(export.name, export.value.to_py(py))?;
+            }
+
+            view.append(exports)?;
+        }
+
+        Ok(view)
+    }
+
+    /// Acquires the GIL once and calls `f` with a [`PythonScope`], which can
+    /// be used as the store context for many backend operations, e.g. calls,
+    /// global accesses, or table accesses, in a tight interop loop.
+    ///
+    /// Every backend operation needs the GIL. Inside the scope, the GIL is
+    /// already held, so the operations only check for it instead of acquiring
+    /// it again.
+    pub fn with_python_scope<R>(&mut self, f: impl FnOnce(PythonScope<'_, T>) -> R) -> R {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("Store::with_python_scope").entered();
+
+        Python::with_gil(|py| {
+            f(PythonScope {
+                py,
+                store: self.as_context_mut(),
+            })
+        })
+    }
+
+    /// Returns the recent mutations of globals, tables, and memories in this
+    /// store, oldest first, if enabled with
+    /// [`EngineConfig::with_mutation_history`].
+    ///
+    /// [`EngineConfig::with_mutation_history`]: crate::EngineConfig::with_mutation_history
+    pub fn mutation_history(&self) -> impl Iterator<Item = &Mutation> {
+        self.as_inner().mutation_history.iter()
+    }
+
+    /// Formats the recent mutations of this store, one per line, e.g. to
+    /// dump them after a trap.
+    #[must_use]
+    pub fn dump_mutation_history(&self) -> String {
+        self.as_inner().mutation_history.to_string()
+    }
+
+    /// Removes all recorded mutations from this store.
+    pub fn clear_mutation_history(&mut self) {
+        self.as_inner_mut().mutation_history.clear();
+    }
+
+    /// Installs a [`ResourceLimiter`], which `limiter` obtains from the user
+    /// data of this store, that can veto the creation and growth of memories
+    /// and tables, replacing any previously installed limiter.
+    ///
+    /// See [`ResourceLimiter`] for which growth the limiter can observe.
+    pub fn limiter(
+        &mut self,
+        limiter: impl 'static + Send + Sync + FnMut(&mut T) -> &mut dyn ResourceLimiter,
+    ) {
+        self.as_inner_mut().limiter = Some(Box::new(limiter));
+    }
+
+    /// Installs a hook that is called with the user data of this store on
+    /// every transition between host and guest code, e.g. to measure the
+    /// time spent in guest calls or to enforce re-entrancy policies,
+    /// replacing any previously installed hook.
+    ///
+    /// The hook observes calls into the guest with [`WasmFunc::call`] and
+    /// deferred start functions, and calls of host functions from the guest
+    /// or the host, see [`CallHook`]. Calls with [`Func::call_async`] and
+    /// start functions that run during instantiation are not observed.
+    ///
+    /// If the hook returns an error when entering a call, the call fails
+    /// with that error without running. If it returns an error when
+    /// returning from a call, the call fails with that error unless it
+    /// already failed.
+    ///
+    /// [`WasmFunc::call`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/backend/trait.WasmFunc.html#tymethod.call
+    pub fn call_hook(
+        &mut self,
+        hook: impl 'static + Send + Sync + FnMut(&mut T, CallHook) -> anyhow::Result<()>,
+    ) {
+        self.as_inner_mut().call_hook = Some(Box::new(hook));
+    }
+
+    /// Sets the epoch deadline of this store to `ticks` epochs after the
+    /// current epoch, after which guest calls fail with a [`Trap`] of the
+    /// kind [`TrapKind::Interrupt`] until a new deadline is set.
+    ///
+    /// The epoch advances whenever guest code that was compiled with
+    /// [`EngineConfig::with_epoch_interruption`] enters a function or loop
+    /// iteration, see [`Store::epoch`]. Other guest code is never
+    /// interrupted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epoch state cannot be created or updated.
+    ///
+    /// [`Trap`]: crate::Trap
+    /// [`TrapKind::Interrupt`]: crate::TrapKind::Interrupt
+    /// [`EngineConfig::with_epoch_interruption`]: crate::EngineConfig::with_epoch_interruption
+    pub fn set_epoch_deadline(&mut self, ticks: u64) -> anyhow::Result<()> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(ticks, "Store::set_epoch_deadline");
+
+            self.as_context_mut()
+                .epoch_state(py)?
+                .call_method1(intern!(py, "setDeadline"), (ticks,))?;
+
+            Ok(())
+        })
+    }
+
+    /// Returns the current epoch of this store, i.e. the number of function
+    /// entries and loop iterations of instrumented guest code, see
+    /// [`Store::set_epoch_deadline`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epoch state cannot be created or read.
+    pub fn epoch(&mut self) -> anyhow::Result<u64> {
+        Python::with_gil(|py| {
+            Ok(self
+                .as_context_mut()
+                .epoch_state(py)?
+                .getattr(intern!(py, "ticks"))?
+                .extract()?)
+        })
+    }
+
+    /// Returns a handle that interrupts the guest code that is running in
+    /// this store, see [`InterruptHandle`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epoch state cannot be created.
+    pub fn interrupt_handle(&mut self) -> anyhow::Result<InterruptHandle> {
+        Python::with_gil(|py| {
+            Ok(InterruptHandle::new(
+                &self.as_context_mut().epoch_state(py)?,
+            )?)
+        })
+    }
+
     fn as_inner_mut(&mut self) -> &mut StoreInner<T> {
         // Safety:
         //
@@ -234,11 +472,321 @@ impl<'a, T: 'a> StoreContextMut<'a, T> {
         }
     }
 
-    pub(crate) fn register_host_func(&mut self, func: Arc<PyHostFuncFn>) -> Wobbly<PyHostFuncFn> {
+    #[allow(clippy::needless_pass_by_ref_mut)]
+    /// Marks the store as borrowed by a call until the returned guard is
+    /// dropped, see [`StoreProof::is_borrowed`]
+    pub(crate) fn borrow_for_call(&mut self) -> StoreBorrow {
+        self.proof.borrows.fetch_add(1, Ordering::AcqRel);
+
+        StoreBorrow {
+            proof: Arc::clone(self.proof),
+        }
+    }
+
+    pub(crate) fn register_host_func(
+        &mut self,
+        func: Arc<PyHostFuncFn>,
+        ty: &FuncType,
+    ) -> Wobbly<PyHostFuncFn> {
         let func = Wobbly::new(func);
-        self.store.host_funcs.push(func.clone());
+        self.store.host_funcs.push((func.clone(), ty.clone()));
         func
     }
+
+    pub(crate) fn register_direct_host_func(
+        &mut self,
+        func: Arc<DirectHostFuncFn>,
+    ) -> Wobbly<DirectHostFuncFn> {
+        let func = Wobbly::new(func);
+        self.store.direct_host_funcs.push(func.clone());
+        func
+    }
+
+    /// Returns the current nesting depth of host calls in this store, which
+    /// is `1` inside a host function that the guest called from a host call
+    /// and grows with every re-entrant guest → host call, see the
+    /// [Re-entrancy](crate#re-entrancy) section.
+    #[must_use]
+    pub fn host_call_depth(&self) -> usize {
+        self.store.host_call_depth
+    }
+
+    /// Creates a temporary host function of the type `ty`, which is only
+    /// registered with this store while the `scope` runs, e.g. for the
+    /// imports of a single instantiation.
+    ///
+    /// Once the `scope` returns, the host function is unregistered from the
+    /// store, dropping its closure, and its JavaScript proxy is destroyed,
+    /// such that transient host functions do not accumulate in
+    /// [`StoreContext::host_funcs`]. Calling the host function afterwards,
+    /// e.g. from an instance that imported it, fails.
+    pub fn with_temp_host_func<R>(
+        &mut self,
+        ty: FuncType,
+        func: impl 'static
+            + Send
+            + Sync
+            + Fn(StoreContextMut<T>, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>,
+        scope: impl FnOnce(StoreContextMut<T>, &Func) -> R,
+    ) -> R {
+        let host_func_index = self.store.host_funcs.len();
+        let direct_host_func_index = self.store.direct_host_funcs.len();
+
+        let func = Func::new_with_signature(self.as_context_mut(), ty.into(), func);
+
+        // the guard unregisters exactly the host functions that were just
+        //  registered, even if the scope registers or unregisters others or
+        //  panics
+        let temp = TempHostFunc::<T> {
+            proof: Arc::clone(self.proof),
+            host_funcs: self.store.host_funcs[host_func_index..]
+                .iter()
+                .map(|(func, _)| func.downgrade())
+                .collect(),
+            direct_host_funcs: self.store.direct_host_funcs[direct_host_func_index..]
+                .iter()
+                .map(Wobbly::downgrade)
+                .collect(),
+            func,
+            _marker: PhantomData::<fn() -> T>,
+        };
+
+        scope(self.as_context_mut(), &temp.func)
+    }
+
+    /// Returns the cached JavaScript descriptor of the function type `ty`,
+    /// which is created using `create` if it is not yet cached
+    pub(crate) fn func_type_descriptor<'py>(
+        &mut self,
+        py: Python<'py>,
+        ty: &FuncType,
+        create: impl FnOnce(Python<'py>, &FuncType) -> Result<Bound<'py, PyAny>, PyErr>,
+    ) -> Result<Bound<'py, PyAny>, PyErr> {
+        let key = FuncTypeKey(ty.clone());
+
+        if let Some(descriptor) = self.store.func_type_descriptors.get(&key) {
+            return Ok(descriptor.bind(py).clone());
+        }
+
+        let descriptor = create(py, ty)?;
+        self.store
+            .func_type_descriptors
+            .insert(key, descriptor.clone().unbind());
+
+        Ok(descriptor)
+    }
+
+    /// Returns the strong proof of having a mutable borrow of the inner store
+    pub(crate) fn proof_mut(&mut self) -> &mut Arc<StoreProof> {
+        self.proof
+    }
+
+    /// Registers the `instance` with the store, without keeping it alive,
+    /// and forgets all instances that have been dropped since
+    pub(crate) fn register_instance(&mut self, instance: &Instance) {
+        self.store.instances.retain(WeakInstance::is_alive);
+        self.store.instances.push(instance.downgrade());
+    }
+
+    /// Enters a host call, unless the maximum host call depth of the engine
+    /// has already been reached
+    pub(crate) fn enter_host_call(&mut self) -> Result<(), Error> {
+        if let Some(limit) = self.store.engine.config().max_host_call_depth() {
+            if self.store.host_call_depth >= limit {
+                return Err(Error::CallDepthExceeded { limit });
+            }
+        }
+
+        self.store.host_call_depth += 1;
+
+        Ok(())
+    }
+
+    /// Records the mutation produced by `kind` in the mutation history, if
+    /// enabled in the engine config
+    pub(crate) fn record_mutation(&mut self, kind: impl FnOnce() -> MutationKind) {
+        let Some(capacity) = self.store.engine.config().mutation_history() else {
+            return;
+        };
+
+        let mutation = Mutation {
+            timestamp: self.store.engine.config().clock().now(),
+            host_call_depth: self.store.host_call_depth,
+            kind: kind(),
+        };
+
+        self.store.mutation_history.record(mutation, capacity);
+    }
+
+    #[cfg(feature = "tracing")]
+    /// Dumps the mutation history as a trace event after a trap, if it is
+    /// enabled and not empty
+    pub(crate) fn dump_mutation_history_on_trap(&self) {
+        if self.store.mutation_history.iter().next().is_some() {
+            tracing::error!(
+                history = %self.store.mutation_history,
+                "mutation history before trap"
+            );
+        }
+    }
+
+    /// Calls the call hook, if installed, with the transition `kind`
+    pub(crate) fn call_hook(&mut self, kind: CallHook) -> anyhow::Result<()> {
+        let Some(hook) = &mut self.store.call_hook else {
+            return Ok(());
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?kind, "call hook");
+
+        hook(&mut self.store.data, kind)
+    }
+
+    /// Returns the JavaScript state of the epoch interruption of this store,
+    /// which is created on first use
+    pub(crate) fn epoch_state<'py>(&mut self, py: Python<'py>) -> Result<Bound<'py, PyAny>, PyErr> {
+        if let Some(state) = &self.store.epoch_state {
+            return Ok(state.bind(py).clone());
+        }
+
+        let state = create_epoch_state(py)?;
+        self.store.epoch_state = Some(state.clone().unbind());
+
+        Ok(state)
+    }
+
+    /// Asks the resource limiter, if installed, whether a memory may grow from
+    /// `current` to `desired` bytes, and fails if it is denied
+    pub(crate) fn limit_memory_growth(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let Some(limiter) = &mut self.store.limiter else {
+            return Ok(());
+        };
+
+        if !limiter(&mut self.store.data).memory_growing(current, desired, maximum)? {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(current, desired, "resource limiter denied memory growth");
+
+            anyhow::bail!(
+                "growing the memory from {current} to {desired} bytes was denied by the resource \
+                 limiter"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Asks the resource limiter, if installed, whether a table may grow from
+    /// `current` to `desired` elements, and fails if it is denied
+    pub(crate) fn limit_table_growth(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let Some(limiter) = &mut self.store.limiter else {
+            return Ok(());
+        };
+
+        if !limiter(&mut self.store.data).table_growing(current, desired, maximum)? {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(current, desired, "resource limiter denied table growth");
+
+            anyhow::bail!(
+                "growing the table from {current} to {desired} elements was denied by the \
+                 resource limiter"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Exits a host call that was entered with [`Self::enter_host_call`]
+    pub(crate) fn exit_host_call(&mut self) {
+        self.store.host_call_depth = self.store.host_call_depth.saturating_sub(1);
+    }
+}
+
+impl<'a, T: 'a> StoreContext<'a, T> {
+    /// Returns the host functions that have been registered with this store,
+    /// in the order of their registration, e.g. to display which host
+    /// bindings a long-lived store holds.
+    ///
+    /// Host functions that are no longer referenced from JavaScript are
+    /// eventually collected and then reported as no longer alive, while
+    /// their entries remain in the store. Many entries that stay alive may
+    /// therefore indicate a leak.
+    pub fn host_funcs(&self) -> impl Iterator<Item = HostFuncInfo> + 'a {
+        self.store
+            .host_funcs
+            .iter()
+            .enumerate()
+            .map(|(index, (func, ty))| HostFuncInfo {
+                index,
+                ty: ty.clone(),
+                alive: func.strong_count() > 0,
+            })
+    }
+
+    /// Returns the current nesting depth of host calls in this store, see
+    /// [`StoreContextMut::host_call_depth`].
+    #[must_use]
+    pub const fn host_call_depth(&self) -> usize {
+        self.store.host_call_depth
+    }
+
+    /// Estimates the JavaScript-side footprint that is attributable to this
+    /// store, e.g. to evict idle guest sandboxes under memory pressure.
+    ///
+    /// The estimate covers the memories and tables that are exported by the
+    /// store's live instances, each counted once even if it is exported by
+    /// several instances, and the host function proxies that are still
+    /// alive. Memories and tables that are neither exported nor reachable
+    /// through an export are not covered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size of a memory or table cannot be queried.
+    pub fn footprint(&self) -> anyhow::Result<StoreFootprint> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("StoreContext::footprint").entered();
+
+            let memories = PyList::empty(py);
+            let tables = PyList::empty(py);
+
+            let instances = self
+                .store
+                .instances
+                .iter()
+                .filter_map(WeakInstance::upgrade)
+                .collect::<Vec<_>>();
+
+            for instance in &instances {
+                instance.collect_js_memories_and_tables(&memories, &tables)?;
+            }
+
+            let footprint = js_store_footprint(py)?.call1((memories, tables))?;
+
+            Ok(StoreFootprint {
+                instances: instances.len(),
+                memories: footprint.getattr(intern!(py, "memories"))?.extract()?,
+                memory_bytes: footprint.getattr(intern!(py, "memoryBytes"))?.extract()?,
+                tables: footprint.getattr(intern!(py, "tables"))?.extract()?,
+                table_elements: footprint.getattr(intern!(py, "tableElements"))?.extract()?,
+                host_funcs: self
+                    .store
+                    .host_funcs
+                    .iter()
+                    .filter(|(func, _)| func.strong_count() > 0)
+                    .count(),
+            })
+        })
+    }
 }
 
 impl<'a, T: 'a> WasmStoreContext<'a, T, Engine> for StoreContext<'a, T> {
@@ -298,19 +846,371 @@ impl<'a, T: 'a> AsContextMut<Engine> for StoreContextMut<'a, T> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A transition between host and guest code, which is reported to the hook
+/// that is installed with [`Store::call_hook`].
+pub enum CallHook {
+    /// The host is about to call into the guest
+    CallingWasm,
+    /// The guest has returned to the host
+    ReturningFromWasm,
+    /// A host function is about to be called, by the guest or the host
+    CallingHost,
+    /// A host function has returned to its caller
+    ReturningFromHost,
+}
+
+impl CallHook {
+    /// Returns `true` if the transition enters host code, i.e. for
+    /// [`CallHook::CallingHost`] and [`CallHook::ReturningFromWasm`].
+    #[must_use]
+    pub const fn entering_host(self) -> bool {
+        matches!(self, Self::CallingHost | Self::ReturningFromWasm)
+    }
+
+    /// Returns `true` if the transition exits host code, i.e. for
+    /// [`CallHook::CallingWasm`] and [`CallHook::ReturningFromHost`].
+    #[must_use]
+    pub const fn exiting_host(self) -> bool {
+        matches!(self, Self::CallingWasm | Self::ReturningFromHost)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Information about a host function that has been registered with a store,
+/// see [`StoreContext::host_funcs`].
+pub struct HostFuncInfo {
+    /// The index of the registration in the store
+    index: usize,
+    /// The type of the host function
+    ty: FuncType,
+    /// Whether the host function has not yet been collected
+    alive: bool,
+}
+
+impl HostFuncInfo {
+    /// Returns the index of the registration in the store.
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the type of the host function.
+    #[must_use]
+    pub const fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+
+    /// Returns whether the host function is still alive, i.e. has not yet
+    /// been collected since it may still be referenced from JavaScript.
+    #[must_use]
+    pub const fn is_alive(&self) -> bool {
+        self.alive
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// An estimate of the JavaScript-side footprint of a store, see
+/// [`StoreContext::footprint`].
+pub struct StoreFootprint {
+    /// The number of live instances
+    instances: usize,
+    /// The number of distinct exported memories
+    memories: usize,
+    /// The total byte length of the exported memories
+    memory_bytes: u64,
+    /// The number of distinct exported tables
+    tables: usize,
+    /// The total number of elements of the exported tables
+    table_elements: u64,
+    /// The number of host function proxies that are still alive
+    host_funcs: usize,
+}
+
+impl StoreFootprint {
+    /// Returns the number of live instances in the store.
+    #[must_use]
+    pub const fn instances(&self) -> usize {
+        self.instances
+    }
+
+    /// Returns the number of distinct memories that the instances export.
+    #[must_use]
+    pub const fn memories(&self) -> usize {
+        self.memories
+    }
+
+    /// Returns the total byte length of the exported memories.
+    #[must_use]
+    pub const fn memory_bytes(&self) -> u64 {
+        self.memory_bytes
+    }
+
+    /// Returns the number of distinct tables that the instances export.
+    #[must_use]
+    pub const fn tables(&self) -> usize {
+        self.tables
+    }
+
+    /// Returns the total number of elements of the exported tables.
+    #[must_use]
+    pub const fn table_elements(&self) -> u64 {
+        self.table_elements
+    }
+
+    /// Returns the number of host function proxies that are still alive,
+    /// see [`HostFuncInfo::is_alive`].
+    #[must_use]
+    pub const fn host_funcs(&self) -> usize {
+        self.host_funcs
+    }
+}
+
+/// A mutable context to the store during which the GIL is held, see
+/// [`Store::with_python_scope`].
+pub struct PythonScope<'a, T: 'a> {
+    /// The GIL token
+    py: Python<'a>,
+    /// The store
+    store: StoreContextMut<'a, T>,
+}
+
+impl<'a, T: 'a> PythonScope<'a, T> {
+    /// Returns the GIL token of this scope, e.g. to convert values to and
+    /// from Python without acquiring the GIL again.
+    #[must_use]
+    pub const fn py(&self) -> Python<'a> {
+        self.py
+    }
+}
+
+impl<'a, T: 'a> WasmStoreContext<'a, T, Engine> for PythonScope<'a, T> {
+    fn engine(&self) -> &Engine {
+        self.store.engine()
+    }
+
+    fn data(&self) -> &T {
+        self.store.data()
+    }
+}
+
+impl<'a, T: 'a> WasmStoreContextMut<'a, T, Engine> for PythonScope<'a, T> {
+    fn data_mut(&mut self) -> &mut T {
+        self.store.data_mut()
+    }
+}
+
+impl<'a, T: 'a> AsContext<Engine> for PythonScope<'a, T> {
+    type UserState = T;
+
+    fn as_context(&self) -> StoreContext<'_, T> {
+        self.store.as_context()
+    }
+}
+
+impl<'a, T: 'a> AsContextMut<Engine> for PythonScope<'a, T> {
+    fn as_context_mut(&mut self) -> StoreContextMut<'_, T> {
+        self.store.as_context_mut()
+    }
+}
+
+/// A guard for a host function that was created by
+/// [`StoreContextMut::with_temp_host_func`], which unregisters the host
+/// function from its store and destroys its JavaScript proxy when dropped
+struct TempHostFunc<T> {
+    /// The proof of the store that the host function is registered with
+    proof: Arc<StoreProof>,
+    /// The registered Python host functions, identified by their allocation
+    host_funcs: Vec<Weak<PyHostFuncFn>>,
+    /// The registered direct host functions, identified by their allocation
+    direct_host_funcs: Vec<Weak<DirectHostFuncFn>>,
+    /// The temporary host function
+    func: Func,
+    /// The type of the user data of the store
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Drop for TempHostFunc<T> {
+    fn drop(&mut self) {
+        // Safety:
+        //
+        // - The proof is cloned from a mutable store context with the same generic type
+        //   T
+        // - The guard is only dropped once the scope, which reborrowed that store
+        //   context, has returned or unwound
+        let store = unsafe { StoreContextMut::<T>::from_proof_unchecked(&mut self.proof) };
+
+        store.store.host_funcs.retain(|(func, _)| {
+            let func = func.downgrade();
+            !self.host_funcs.iter().any(|temp| Weak::ptr_eq(temp, &func))
+        });
+        store.store.direct_host_funcs.retain(|func| {
+            let func = func.downgrade();
+            !self
+                .direct_host_funcs
+                .iter()
+                .any(|temp| Weak::ptr_eq(temp, &func))
+        });
+
+        self.func.destroy_host_proxy();
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 /// Helper type to transfer an opaque pointer to a [`StoreInner`]
-pub struct StoreProof(*mut ());
+pub struct StoreProof {
+    /// The opaque pointer to the [`StoreInner`]
+    ptr: *mut (),
+    /// The number of calls that currently hold a mutable borrow of the store
+    borrows: AtomicUsize,
+}
 
 unsafe impl Send for StoreProof {}
 unsafe impl Sync for StoreProof {}
 
 impl StoreProof {
     const fn from_ptr<T>(ptr: *mut StoreInner<T>) -> Self {
-        Self(ptr.cast())
+        Self {
+            ptr: ptr.cast(),
+            borrows: AtomicUsize::new(0),
+        }
     }
 
     const fn as_ptr<T>(&self) -> *mut StoreInner<T> {
-        self.0.cast()
+        self.ptr.cast()
+    }
+
+    /// Returns `true` if a call that holds a mutable borrow of the store is
+    /// in progress, e.g. since JavaScript called back into Rust
+    /// synchronously from within a guest or host function
+    pub(crate) fn is_borrowed(&self) -> bool {
+        self.borrows.load(Ordering::Acquire) > 0
+    }
+}
+
+/// A guard that marks its store as borrowed by a call until it is dropped,
+/// see [`StoreProof::is_borrowed`]
+pub struct StoreBorrow {
+    /// The proof of the borrowed store
+    proof: Arc<StoreProof>,
+}
+
+impl Drop for StoreBorrow {
+    fn drop(&mut self) {
+        self.proof.borrows.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A [`FuncType`] that is hashed and compared by its parameter and result
+/// types, ignoring its debug name
+struct FuncTypeKey(FuncType);
+
+impl PartialEq for FuncTypeKey {
+    fn eq(&self, other: &Self) -> bool {
+        (self.0.params() == other.0.params()) && (self.0.results() == other.0.results())
+    }
+}
+
+impl Eq for FuncTypeKey {}
+
+impl Hash for FuncTypeKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.params().len().hash(state);
+
+        for ty in self.0.params().iter().chain(self.0.results()) {
+            mem::discriminant(ty).hash(state);
+        }
+    }
+}
+
+fn js_store_footprint(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_STORE_FOOTPRINT: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_STORE_FOOTPRINT
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function storeFootprint(memories, tables) {
+    // memories and tables may be exported by several instances
+    const uniqueMemories = new Set(memories);
+    const uniqueTables = new Set(tables);
+
+    let memoryBytes = 0;
+    for (const memory of uniqueMemories) {
+        memoryBytes += memory.buffer.byteLength;
+    }
+
+    let tableElements = 0;
+    for (const table of uniqueTables) {
+        tableElements += table.length;
+    }
+
+    return {
+        memories: uniqueMemories.size,
+        memoryBytes,
+        tables: uniqueTables.size,
+        tableElements,
+    };
+}
+storeFootprint
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EngineConfig;
+
+    #[test]
+    fn host_call_depth() {
+        let engine = Engine::new(EngineConfig::new().with_max_host_call_depth(Some(2)));
+        let mut store = Store::new(&engine, ());
+        let mut ctx = store.as_context_mut();
+
+        assert_eq!(ctx.store.host_call_depth, 0);
+
+        ctx.enter_host_call()
+            .expect("the first host call should enter");
+        ctx.enter_host_call()
+            .expect("the second host call should enter");
+        assert_eq!(ctx.store.host_call_depth, 2);
+
+        assert!(matches!(
+            ctx.enter_host_call(),
+            Err(Error::CallDepthExceeded { limit: 2 })
+        ));
+        assert_eq!(ctx.store.host_call_depth, 2);
+
+        ctx.exit_host_call();
+        assert_eq!(ctx.store.host_call_depth, 1);
+        ctx.enter_host_call()
+            .expect("a host call should enter again below the limit");
+
+        ctx.exit_host_call();
+        ctx.exit_host_call();
+        assert_eq!(ctx.store.host_call_depth, 0);
+    }
+
+    #[test]
+    fn unlimited_host_call_depth() {
+        let engine = Engine::new(EngineConfig::new().with_max_host_call_depth(None));
+        let mut store = Store::new(&engine, ());
+        let mut ctx = store.as_context_mut();
+
+        for _ in 0..(EngineConfig::DEFAULT_MAX_HOST_CALL_DEPTH * 2) {
+            ctx.enter_host_call()
+                .expect("host calls should not be limited");
+        }
+
+        assert_eq!(
+            ctx.store.host_call_depth,
+            EngineConfig::DEFAULT_MAX_HOST_CALL_DEPTH * 2
+        );
     }
 }
@@ -1,18 +1,20 @@
 use pyo3::{intern, prelude::*, sync::GILOnceCell};
 use wasm_runtime_layer::{
-    backend::{AsContext, AsContextMut, Value, WasmTable},
+    backend::{AsContext, AsContextMut, Value, WasmStoreContext, WasmTable},
     TableType, ValueType,
 };
 
 use crate::{
-    conversion::{create_js_object, instanceof, ToPy, ValueExt, ValueTypeExt},
-    Engine,
+    conversion::{create_js_object, instanceof, to_py_for_ref_slot, ToPy, ValueExt, ValueTypeExt},
+    history::MutationKind,
+    Engine, Error, Trap, TrapKind,
 };
 
 #[derive(Debug)]
 /// A WASM table.
 ///
-/// This type wraps a [`WebAssembly.Table`] from the JavaScript API.
+/// This type wraps a [`WebAssembly.Table`] from the JavaScript API. Tables
+/// can hold either `funcref` or `externref` elements.
 ///
 /// [`WebAssembly.Table`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Table
 pub struct Table {
@@ -33,22 +35,55 @@ impl Clone for Table {
 
 impl WasmTable<Engine> for Table {
     fn new(
-        _ctx: impl AsContextMut<Engine>,
+        mut ctx: impl AsContextMut<Engine>,
         ty: TableType,
         init: Value<Engine>,
     ) -> anyhow::Result<Self> {
+        let policy = ctx.as_context().engine().config().table_maximum_policy();
+
         Python::with_gil(|py| -> anyhow::Result<Self> {
             #[cfg(feature = "tracing")]
             tracing::debug!(?ty, ?init, "Table::new");
 
+            check_element_type(ty.element())?;
+            check_element(&ty, &init)?;
+
+            let ty = match (ty.maximum(), policy) {
+                (Some(maximum), TableMaximumPolicy::Error) if maximum > Self::MAX_SIZE => {
+                    return Err(Error::Conversion(format!(
+                        "the table maximum of {maximum} elements exceeds the JavaScript API limit \
+                         of {} elements, see EngineConfig::with_table_maximum_policy",
+                        Self::MAX_SIZE
+                    ))
+                    .into());
+                },
+                (Some(maximum), TableMaximumPolicy::Clamp) if maximum > Self::MAX_SIZE => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        maximum,
+                        clamped = Self::MAX_SIZE,
+                        "clamping the table maximum to the JavaScript API limit"
+                    );
+
+                    TableType::new(ty.element(), ty.minimum(), Some(Self::MAX_SIZE))
+                },
+                _ => ty,
+            };
+
+            ctx.as_context_mut().limit_table_growth(
+                0,
+                ty.minimum() as usize,
+                ty.maximum().map(|maximum| maximum as usize),
+            )?;
+
             let desc = create_js_object(py)?;
-            desc.setattr(intern!(py, "element"), ty.element().as_js_descriptor())?;
+            desc.setattr(intern!(py, "element"), ty.element().as_js_descriptor(py))?;
             desc.setattr(intern!(py, "initial"), ty.minimum())?;
             if let Some(max) = ty.maximum() {
                 desc.setattr(intern!(py, "maximum"), max)?;
             }
 
-            let init = init.to_py(py);
+            let init = to_py_for_ref_slot(py, &init)?;
 
             let table = web_assembly_table_new(py)?.call1((desc, init))?;
 
@@ -80,7 +115,7 @@ impl WasmTable<Engine> for Table {
     /// Grows the table by the given amount of elements.
     fn grow(
         &self,
-        _ctx: impl AsContextMut<Engine>,
+        mut ctx: impl AsContextMut<Engine>,
         delta: u32,
         init: Value<Engine>,
     ) -> anyhow::Result<u32> {
@@ -90,7 +125,16 @@ impl WasmTable<Engine> for Table {
             #[cfg(feature = "tracing")]
             tracing::debug!(table = %table, ?self.ty, delta, ?init, "Table::grow");
 
-            let init = init.to_py(py);
+            check_element(&self.ty, &init)?;
+
+            let current: usize = table.getattr(intern!(py, "length"))?.extract()?;
+            ctx.as_context_mut().limit_table_growth(
+                current,
+                current.saturating_add(delta as usize),
+                self.ty.maximum().map(|maximum| maximum as usize),
+            )?;
+
+            let init = to_py_for_ref_slot(py, &init)?;
 
             let old_len = table
                 .call_method1(intern!(py, "grow"), (delta, init))?
@@ -100,41 +144,64 @@ impl WasmTable<Engine> for Table {
         })
     }
 
-    /// Returns the table element value at `index`.
-    fn get(&self, _ctx: impl AsContextMut<Engine>, index: u32) -> Option<Value<Engine>> {
-        Python::with_gil(|py| {
-            let table = self.table.bind(py);
-
-            #[cfg(feature = "tracing")]
-            tracing::debug!(table = %table, ?self.ty, index, "Table::get");
-
-            let value = table.call_method1(intern!(py, "get"), (index,)).ok()?;
-
-            Some(
-                Value::from_py_typed(value, self.ty.element()).expect("Table::get should not fail"),
-            )
-        })
+    /// Returns the table element value at `index`, or [`None`] if the index
+    /// is out of bounds.
+    ///
+    /// If the element cannot be read for any other reason, the error is
+    /// logged and [`None`] is returned as well. Use [`Table::try_get`] to
+    /// distinguish these cases.
+    fn get(&self, ctx: impl AsContextMut<Engine>, index: u32) -> Option<Value<Engine>> {
+        match self.try_get(ctx, index) {
+            Ok(value) => value,
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::error!(index, "Table::get failed: {err:?}");
+                #[cfg(not(feature = "tracing"))]
+                let _ = err;
+
+                None
+            },
+        }
     }
 
     /// Sets the value of this table at `index`.
     fn set(
         &self,
-        _ctx: impl AsContextMut<Engine>,
+        mut ctx: impl AsContextMut<Engine>,
         index: u32,
         value: Value<Engine>,
     ) -> anyhow::Result<()> {
-        Python::with_gil(|py| {
+        Python::with_gil(|py| -> anyhow::Result<()> {
             let table = self.table.bind(py);
 
             #[cfg(feature = "tracing")]
             tracing::debug!(table = %table, ?self.ty, index, ?value, "Table::set");
 
-            let value = value.to_py(py);
+            check_element(&self.ty, &value)?;
+
+            let value = to_py_for_ref_slot(py, &value)?;
 
-            table.call_method1(intern!(py, "set"), (index, value))?;
+            table
+                .call_method1(intern!(py, "set"), (index, value))
+                .map_err(|err| {
+                    if is_range_error(py, &err) {
+                        out_of_bounds(py, &err, index)
+                    } else {
+                        err.into()
+                    }
+                })?;
 
             Ok(())
-        })
+        })?;
+
+        ctx.as_context_mut()
+            .record_mutation(|| MutationKind::TableSet {
+                ty: self.ty,
+                index,
+                value,
+            });
+
+        Ok(())
     }
 }
 
@@ -148,19 +215,181 @@ impl ToPy for Table {
 }
 
 impl Table {
+    /// The maximum number of elements of a table in the JavaScript API, which
+    /// also limits the declared maximum size of a table
+    ///
+    /// See: <https://webassembly.github.io/spec/js-api/#limits>
+    pub const MAX_SIZE: u32 = 10_000_000;
+
+    /// Returns the type of this table.
+    ///
+    /// Unlike [`Table::ty`], this method does not require a store context,
+    /// e.g. so that bindings generators or loggers can inspect the type.
+    ///
+    /// [`Table::ty`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Table.html#method.ty
+    #[must_use]
+    pub const fn ty_ref(&self) -> &TableType {
+        &self.ty
+    }
+
+    /// Returns the table element value at `index`, or [`None`] if the index
+    /// is out of bounds.
+    ///
+    /// Unlike [`Table::get`], which can only report out-of-bounds accesses,
+    /// this method returns any other error that the JavaScript table raises,
+    /// e.g. from a proxy, instead of only logging it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JavaScript table raises an exception other
+    /// than a `RangeError`, or if the element cannot be converted.
+    ///
+    /// [`Table::get`]: https://docs.rs/wasm_runtime_layer/0.4/wasm_runtime_layer/struct.Table.html#method.get
+    pub fn try_get(
+        &self,
+        _ctx: impl AsContextMut<Engine>,
+        index: u32,
+    ) -> anyhow::Result<Option<Value<Engine>>> {
+        Python::with_gil(|py| {
+            let table = self.table.bind(py);
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(table = %table, ?self.ty, index, "Table::get");
+
+            let value = match table.call_method1(intern!(py, "get"), (index,)) {
+                Ok(value) => value,
+                Err(err) if is_range_error(py, &err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+
+            let value = Value::from_py_typed(value, self.ty.element()).map_err(|err| {
+                Error::Conversion(format!("invalid table element at index {index}: {err}"))
+            })?;
+
+            Ok(Some(value))
+        })
+    }
+
+    /// Returns the underlying [`WebAssembly.Table`], e.g. to pass it to
+    /// hand-written JavaScript glue code.
+    ///
+    /// [`WebAssembly.Table`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Table
+    #[must_use]
+    pub fn as_js(&self, py: Python) -> Py<PyAny> {
+        self.table.clone_ref(py)
+    }
+
+    /// Wraps the existing JavaScript `table`, e.g. one that was created by
+    /// hand-written JavaScript glue code, with the type `ty`.
+    ///
+    /// The element type of the table is only checked if the browser supports
+    /// the WebAssembly type reflection (js-types) proposal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `table` is not a [`WebAssembly.Table`], if it is
+    /// smaller than the minimum size of `ty`, or if its element type does not
+    /// match.
+    ///
+    /// [`WebAssembly.Table`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Table
+    pub fn from_js(table: &Bound<PyAny>, ty: TableType) -> anyhow::Result<Self> {
+        let py = table.py();
+
+        if !instanceof(table, web_assembly_table(py)?, "WebAssembly.Table")? {
+            return Err(Error::Conversion(format!(
+                "expected WebAssembly.Table but found {table:?}"
+            ))
+            .into());
+        }
+
+        let length: u32 = table.getattr(intern!(py, "length"))?.extract()?;
+        if length < ty.minimum() {
+            return Err(Error::Conversion(format!(
+                "the JavaScript table has {length} elements but {ty:?} requires at least {}",
+                ty.minimum()
+            ))
+            .into());
+        }
+
+        if let Ok(reflected) = table.call_method0(intern!(py, "type")) {
+            let element: String = reflected.getattr(intern!(py, "element"))?.extract()?;
+
+            let element_matches = element == ty.element().as_js_descriptor(py).to_str()?
+                || (ty.element() == ValueType::FuncRef && element == "funcref");
+
+            if !element_matches {
+                return Err(Error::Conversion(format!(
+                    "the JavaScript table has the element type {element} but {ty:?} is expected"
+                ))
+                .into());
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(table = %table, ?ty, "Table::from_js");
+
+        Ok(Self {
+            table: table.clone().unbind(),
+            ty,
+        })
+    }
+
+    /// Verifies that the JavaScript `table`, described by `what` in errors,
+    /// has the type `ty` if its type can be reflected using the type
+    /// reflection (js-types) proposal
+    ///
+    /// Since the reflected minimum is the current size of the table, it only
+    /// needs to be at least the minimum of `ty`.
+    pub(crate) fn verify_exported_table(
+        table: &Bound<PyAny>,
+        what: &str,
+        ty: TableType,
+    ) -> anyhow::Result<()> {
+        let py = table.py();
+
+        let Ok(reflected) = table.call_method0(intern!(py, "type")) else {
+            return Ok(());
+        };
+
+        let element: String = reflected.getattr(intern!(py, "element"))?.extract()?;
+        let minimum: u32 = reflected.getattr(intern!(py, "minimum"))?.extract()?;
+        let maximum: Option<u32> = reflected
+            .getattr(intern!(py, "maximum"))
+            .ok()
+            .and_then(|maximum| maximum.extract().ok());
+
+        let element_matches = element == ty.element().as_js_descriptor(py).to_str()?
+            || (ty.element() == ValueType::FuncRef && element == "funcref");
+
+        if !element_matches || minimum < ty.minimum() || maximum != ty.maximum() {
+            anyhow::bail!(
+                "{what} is expected as {ty:?} but has the JavaScript type {{ element: {element}, \
+                 minimum: {minimum}, maximum: {maximum:?} }}"
+            );
+        }
+
+        Ok(())
+    }
+
     /// Creates a new table from a Python value
     pub(crate) fn from_exported_table(table: Bound<PyAny>, ty: TableType) -> anyhow::Result<Self> {
-        if !instanceof(&table, web_assembly_table(table.py())?)? {
+        if !instanceof(&table, web_assembly_table(table.py())?, "WebAssembly.Table")? {
             anyhow::bail!("expected WebAssembly.Table but found {table}");
         }
 
         #[cfg(feature = "tracing")]
         tracing::debug!(table = %table, ?ty, "Table::from_exported_table");
 
+        check_element_type(ty.element())?;
+
         let table_length: u32 = table.getattr(intern!(table.py(), "length"))?.extract()?;
 
-        assert!(table_length >= ty.minimum());
-        assert_eq!(ty.element(), ValueType::FuncRef);
+        if table_length < ty.minimum() {
+            anyhow::bail!(
+                "exported table has {table_length} elements but {ty:?} requires at least {}",
+                ty.minimum()
+            );
+        }
 
         Ok(Self {
             table: table.unbind(),
@@ -169,12 +398,88 @@ impl Table {
     }
 }
 
-fn web_assembly_table(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The policy for table maximums that exceed the limit of the JavaScript API,
+/// see [`EngineConfig::with_table_maximum_policy`].
+///
+/// [`EngineConfig::with_table_maximum_policy`]: crate::EngineConfig::with_table_maximum_policy
+pub enum TableMaximumPolicy {
+    /// Tables whose maximum exceeds the limit fail with an error that
+    /// identifies the offending table
+    #[default]
+    Error,
+    /// Table maximums that exceed the limit are clamped to
+    /// [`Table::MAX_SIZE`], which emits a warning
+    Clamp,
+}
+
+/// Checks that tables can hold elements of the `element` type, i.e. that it is
+/// a reference type
+fn check_element_type(element: ValueType) -> Result<(), Error> {
+    match element {
+        ValueType::FuncRef | ValueType::ExternRef => Ok(()),
+        element => Err(Error::Conversion(format!(
+            "tables can only hold funcref or externref elements, not {element}"
+        ))),
+    }
+}
+
+/// Checks that the `value` can be stored in a table of type `ty`
+fn check_element(ty: &TableType, value: &Value<Engine>) -> Result<(), Error> {
+    if ValueExt::ty(value) == ty.element() {
+        return Ok(());
+    }
+
+    Err(Error::Conversion(format!(
+        "a {} value cannot be stored in a table of {} elements",
+        ValueExt::ty(value),
+        ty.element()
+    )))
+}
+
+/// Checks if a JavaScript exception, raised by a table access, is a
+/// `RangeError`, which browsers throw for out-of-bounds indices
+fn is_range_error(py: Python, err: &PyErr) -> bool {
+    let value = err.value(py);
+
+    // Pyodide may wrap JavaScript errors
+    let error = value
+        .getattr(intern!(py, "js_error"))
+        .unwrap_or_else(|_| value.clone().into_any());
+
+    error
+        .getattr(intern!(py, "name"))
+        .and_then(|name| name.extract::<String>())
+        .is_ok_and(|name| name == "RangeError")
+}
+
+/// Converts the `RangeError` of an out-of-bounds table access at `index` into
+/// an [`Error::Trap`] with a [`TrapKind::TableOutOfBounds`] [`Trap`] source
+fn out_of_bounds(py: Python, err: &PyErr, index: u32) -> anyhow::Error {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(index, %err, "out of bounds table access");
+
+    let message = format!("table index {index} is out of bounds");
+    let stack = err
+        .value(py)
+        .getattr(intern!(py, "stack"))
+        .and_then(|stack| stack.extract::<String>())
+        .ok();
+
+    anyhow::Error::new(Trap::new(
+        TrapKind::TableOutOfBounds,
+        message.clone(),
+        stack,
+    ))
+    .context(Error::Trap(message))
+}
+
+fn web_assembly_table(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_TABLE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_TABLE.import(py, "js.WebAssembly", "Table")
 }
 
-fn web_assembly_table_new(py: Python) -> Result<&Bound<PyAny>, PyErr> {
+fn web_assembly_table_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
     static WEB_ASSEMBLY_TABLE_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
     WEB_ASSEMBLY_TABLE_NEW.import(py, "js.WebAssembly.Table", "new")
 }
@@ -0,0 +1,286 @@
+use std::{error::Error as StdError, fmt, sync::Arc};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyList};
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Value},
+    ValueType,
+};
+
+use crate::{
+    conversion::{instanceof, ToPy, ValueExt, ValueTypeExt},
+    func::host_panic_from_py,
+    Engine, Error, Trap,
+};
+
+#[derive(Debug)]
+/// A WASM exception tag, which identifies the exceptions that are thrown
+/// and caught using the exception handling proposal.
+///
+/// This type wraps a [`WebAssembly.Tag`] from the JavaScript API. Since the
+/// [`wasm_runtime_layer`] API has no tag externs, tags are imported using
+/// [`InstanceOptions::tag_import`] and exported tags are obtained using
+/// [`Instance::get_tag`].
+///
+/// [`WebAssembly.Tag`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Tag
+/// [`wasm_runtime_layer`]: https://docs.rs/wasm_runtime_layer/0.4/
+/// [`InstanceOptions::tag_import`]: crate::InstanceOptions::tag_import
+/// [`Instance::get_tag`]: crate::Instance::get_tag
+pub struct Tag {
+    /// The inner tag
+    tag: Py<PyAny>,
+    /// The types of the exception payload
+    params: Arc<[ValueType]>,
+}
+
+impl Clone for Tag {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| Self {
+            tag: self.tag.clone_ref(py),
+            params: Arc::clone(&self.params),
+        })
+    }
+}
+
+impl Tag {
+    /// Creates a new tag for exceptions whose payload has the `params`
+    /// types.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the browser does not support `WebAssembly.Tag`.
+    pub fn new(
+        _ctx: impl AsContextMut<Engine>,
+        params: impl IntoIterator<Item = ValueType>,
+    ) -> anyhow::Result<Self> {
+        let params = params.into_iter().collect::<Arc<[_]>>();
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?params, "Tag::new");
+
+            let parameters = PyList::new(py, params.iter().map(|ty| ty.as_js_descriptor(py)))?;
+
+            let tag = web_assembly_tag_new(py)
+                .map_err(|err| {
+                    Error::EnvironmentUnavailable(format!(
+                        "WebAssembly.Tag is not supported: {err}"
+                    ))
+                })?
+                .call1((js_tag_descriptor(py)?.call1((parameters,))?,))?;
+
+            Ok(Self {
+                tag: tag.unbind(),
+                params,
+            })
+        })
+    }
+
+    /// Returns the types of the payload of exceptions with this tag.
+    #[must_use]
+    pub fn params(&self, _ctx: impl AsContext<Engine>) -> &[ValueType] {
+        &self.params
+    }
+
+    /// Creates a tag wrapper from a JavaScript tag that was exported by an
+    /// instance
+    pub(crate) fn from_exported_tag(
+        tag: Bound<PyAny>,
+        params: Arc<[ValueType]>,
+    ) -> anyhow::Result<Self> {
+        if !instanceof(&tag, web_assembly_tag(tag.py())?, "WebAssembly.Tag")? {
+            anyhow::bail!("expected WebAssembly.Tag but found {tag}");
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(tag = %tag, ?params, "Tag::from_exported_tag");
+
+        Ok(Self {
+            tag: tag.unbind(),
+            params,
+        })
+    }
+}
+
+impl ToPy for Tag {
+    fn to_py(&self, py: Python) -> Py<PyAny> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(tag = %self.tag, ?self.params, "Tag::to_py");
+
+        self.tag.clone_ref(py)
+    }
+}
+
+/// A WASM exception that was thrown by the guest and not caught before it
+/// reached the host.
+///
+/// This type wraps a [`WebAssembly.Exception`] from the JavaScript API.
+/// Calls into the guest return it as an [`anyhow::Error`] that can be
+/// downcast into this type, which can then be matched against known
+/// [`Tag`]s to extract its payload.
+///
+/// [`WebAssembly.Exception`]: https://developer.mozilla.org/en-US/docs/WebAssembly/JavaScript_interface/Exception
+pub struct Exception {
+    /// The inner exception
+    exception: Py<PyAny>,
+    /// The message of the exception
+    message: String,
+}
+
+impl Clone for Exception {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| Self {
+            exception: self.exception.clone_ref(py),
+            message: self.message.clone(),
+        })
+    }
+}
+
+impl Exception {
+    /// Checks if the exception was thrown with the `tag`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the exception cannot be compared with the `tag`.
+    pub fn is(&self, _ctx: impl AsContext<Engine>, tag: &Tag) -> anyhow::Result<bool> {
+        Python::with_gil(|py| {
+            Ok(self
+                .exception
+                .bind(py)
+                .call_method1(intern!(py, "is"), (tag.tag.bind(py),))?
+                .extract()?)
+        })
+    }
+
+    /// Returns the payload of the exception, which must have been thrown with
+    /// the `tag`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the exception was not thrown with the `tag` or if
+    /// its payload cannot be converted, e.g. because it contains a non-null
+    /// `funcref`.
+    pub fn payload(
+        &self,
+        ctx: impl AsContext<Engine>,
+        tag: &Tag,
+    ) -> anyhow::Result<Vec<Value<Engine>>> {
+        if !self.is(ctx, tag)? {
+            anyhow::bail!("exception was not thrown with the tag");
+        }
+
+        Python::with_gil(|py| {
+            let exception = self.exception.bind(py);
+
+            tag.params
+                .iter()
+                .enumerate()
+                .map(|(index, ty)| {
+                    let arg =
+                        exception.call_method1(intern!(py, "getArg"), (tag.tag.bind(py), index))?;
+                    Value::from_py_typed(arg, *ty).map_err(|err| {
+                        Error::Conversion(format!("invalid exception payload: {err}")).into()
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Extracts the exception from a JavaScript exception, if it was thrown
+    /// as a `WebAssembly.Exception`
+    pub(crate) fn from_js_exception(py: Python, err: &PyErr) -> Option<Self> {
+        let value = err.value(py);
+
+        // Pyodide may wrap thrown values that are not JavaScript errors
+        let exception = value
+            .getattr(intern!(py, "js_error"))
+            .unwrap_or_else(|_| value.clone().into_any());
+
+        let is_exception = web_assembly_exception(py)
+            .and_then(|constructor| instanceof(&exception, constructor, "WebAssembly.Exception"))
+            .unwrap_or(false);
+
+        if !is_exception {
+            return None;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(exception = %exception, "uncaught WebAssembly.Exception");
+
+        Some(Self {
+            exception: exception.unbind(),
+            message: err.to_string(),
+        })
+    }
+}
+
+impl fmt::Debug for Exception {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Exception")
+            .field("message", &self.message)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "uncaught WebAssembly exception: {}", self.message)
+    }
+}
+
+impl StdError for Exception {}
+
+/// Converts a JavaScript exception, raised while calling into the guest, into
+/// an [`Exception`] if it was thrown as a `WebAssembly.Exception`, into an
+/// [`Error::HostPanic`] if a host function panicked, into an [`Error::Trap`]
+/// with a [`Trap`] source if the guest trapped, and into a categorised
+/// [`Error`] using `fallback` otherwise.
+pub fn js_exception_to_error(
+    py: Python,
+    err: &PyErr,
+    fallback: fn(String) -> Error,
+) -> anyhow::Error {
+    if let Some(exception) = Exception::from_js_exception(py, err) {
+        return exception.into();
+    }
+
+    if let Some(panic) = host_panic_from_py(py, err) {
+        return panic.into();
+    }
+
+    if let Some(trap) = Trap::from_js_exception(py, err) {
+        return anyhow::Error::new(trap).context(Error::Trap(err.to_string()));
+    }
+
+    Error::from_js_exception(py, err, fallback).into()
+}
+
+fn js_tag_descriptor(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_TAG_DESCRIPTOR: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_TAG_DESCRIPTOR
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1(("function tagDescriptor(parameters){ return { parameters: \
+                         Array.from(parameters) }; } tagDescriptor",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
+
+fn web_assembly_tag(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_TAG: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_TAG.import(py, "js.WebAssembly", "Tag")
+}
+
+fn web_assembly_tag_new(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_TAG_NEW: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_TAG_NEW.import(py, "js.WebAssembly.Tag", "new")
+}
+
+fn web_assembly_exception(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static WEB_ASSEMBLY_EXCEPTION: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    WEB_ASSEMBLY_EXCEPTION.import(py, "js.WebAssembly", "Exception")
+}
@@ -0,0 +1,204 @@
+use std::{error::Error as StdError, fmt};
+
+use pyo3::{intern, prelude::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+/// The kind of a [`Trap`], which is classified from the message of the
+/// JavaScript error since browsers do not expose it otherwise.
+pub enum TrapKind {
+    /// An `unreachable` instruction was executed
+    Unreachable,
+    /// A memory access was out of bounds
+    MemoryOutOfBounds,
+    /// A table access was out of bounds
+    TableOutOfBounds,
+    /// An indirect call went to a null table entry
+    IndirectCallToNull,
+    /// An indirect call had a mismatching signature
+    BadSignature,
+    /// A null reference was dereferenced
+    NullReference,
+    /// An integer was divided by zero
+    IntegerDivisionByZero,
+    /// An integer operation overflowed
+    IntegerOverflow,
+    /// A float could not be converted to an integer
+    BadConversionToInteger,
+    /// An atomic memory access was unaligned
+    UnalignedAtomic,
+    /// The call stack was exhausted, e.g. by unbounded recursion
+    StackOverflow,
+    /// The guest was interrupted, see [`InterruptHandle`] and
+    /// [`Store::set_epoch_deadline`]
+    ///
+    /// [`InterruptHandle`]: crate::InterruptHandle
+    /// [`Store::set_epoch_deadline`]: crate::Store::set_epoch_deadline
+    Interrupt,
+    /// The trap could not be classified
+    Unknown,
+}
+
+impl TrapKind {
+    /// Classifies the JavaScript error `message` of a trap, covering the
+    /// messages of the major browser engines
+    fn classify(message: &str) -> Self {
+        const PATTERNS: &[(&str, TrapKind)] = &[
+            ("guest execution was interrupted", TrapKind::Interrupt),
+            ("unreachable", TrapKind::Unreachable),
+            ("memory access out of bounds", TrapKind::MemoryOutOfBounds),
+            ("table index is out of bounds", TrapKind::TableOutOfBounds),
+            ("table access out of bounds", TrapKind::TableOutOfBounds),
+            ("index out of bounds", TrapKind::MemoryOutOfBounds),
+            ("out of bounds memory access", TrapKind::MemoryOutOfBounds),
+            ("null function", TrapKind::IndirectCallToNull),
+            ("indirect call to null", TrapKind::IndirectCallToNull),
+            ("signature mismatch", TrapKind::BadSignature),
+            ("indirect call type mismatch", TrapKind::BadSignature),
+            ("null", TrapKind::NullReference),
+            ("divide by zero", TrapKind::IntegerDivisionByZero),
+            ("division by zero", TrapKind::IntegerDivisionByZero),
+            ("remainder by zero", TrapKind::IntegerDivisionByZero),
+            ("divide result unrepresentable", TrapKind::IntegerOverflow),
+            ("integer overflow", TrapKind::IntegerOverflow),
+            ("float unrepresentable", TrapKind::BadConversionToInteger),
+            (
+                "invalid conversion to integer",
+                TrapKind::BadConversionToInteger,
+            ),
+            ("unaligned", TrapKind::UnalignedAtomic),
+            ("call stack", TrapKind::StackOverflow),
+            ("too much recursion", TrapKind::StackOverflow),
+            ("stack overflow", TrapKind::StackOverflow),
+        ];
+
+        let message = message.to_lowercase();
+
+        PATTERNS
+            .iter()
+            .find(|(pattern, _)| message.contains(pattern))
+            .map_or(Self::Unknown, |(_, kind)| *kind)
+    }
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            Self::Unreachable => "unreachable code executed",
+            Self::MemoryOutOfBounds => "out of bounds memory access",
+            Self::TableOutOfBounds => "out of bounds table access",
+            Self::IndirectCallToNull => "indirect call to null",
+            Self::BadSignature => "indirect call signature mismatch",
+            Self::NullReference => "null reference",
+            Self::IntegerDivisionByZero => "integer division by zero",
+            Self::IntegerOverflow => "integer overflow",
+            Self::BadConversionToInteger => "invalid conversion to integer",
+            Self::UnalignedAtomic => "unaligned atomic access",
+            Self::StackOverflow => "call stack exhausted",
+            Self::Interrupt => "interrupted",
+            Self::Unknown => "unknown trap",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A trap of the guest, i.e. a `WebAssembly.RuntimeError` or an exhausted
+/// call stack.
+///
+/// Calls into the guest return an [`Error::Trap`] whose source is this type,
+/// such that it can be downcast from the [`anyhow::Error`] to distinguish
+/// traps from errors that were raised by host functions.
+///
+/// [`Error::Trap`]: crate::Error::Trap
+pub struct Trap {
+    /// The classified kind of the trap
+    kind: TrapKind,
+    /// The message of the JavaScript error
+    message: String,
+    /// The JavaScript stack trace, if available
+    stack: Option<String>,
+}
+
+impl Trap {
+    /// Returns the kind of the trap.
+    #[must_use]
+    pub const fn kind(&self) -> TrapKind {
+        self.kind
+    }
+
+    /// Returns the message of the JavaScript error.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the JavaScript stack trace of the trap, if available, which
+    /// includes the WebAssembly frames in the browser's format.
+    #[must_use]
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// Creates a trap of the `kind` from a JavaScript error
+    pub(crate) const fn new(kind: TrapKind, message: String, stack: Option<String>) -> Self {
+        Self {
+            kind,
+            message,
+            stack,
+        }
+    }
+
+    /// Extracts the trap from a JavaScript exception, if it was raised as a
+    /// `WebAssembly.RuntimeError` or because the call stack was exhausted
+    pub(crate) fn from_js_exception(py: Python, err: &PyErr) -> Option<Self> {
+        let value = err.value(py);
+
+        // Pyodide may wrap JavaScript errors
+        let error = value
+            .getattr(intern!(py, "js_error"))
+            .unwrap_or_else(|_| value.clone().into_any());
+
+        let name = error
+            .getattr(intern!(py, "name"))
+            .and_then(|name| name.extract::<String>())
+            .ok()?;
+        let message = error
+            .getattr(intern!(py, "message"))
+            .and_then(|message| message.extract::<String>())
+            .unwrap_or_else(|_| err.to_string());
+
+        let kind = match name.as_str() {
+            "RuntimeError" => TrapKind::classify(&message),
+            // V8 reports exhausted stacks as a RangeError, Firefox as an
+            //  InternalError
+            "RangeError" | "InternalError"
+                if TrapKind::classify(&message) == TrapKind::StackOverflow =>
+            {
+                TrapKind::StackOverflow
+            },
+            _ => return None,
+        };
+
+        let stack = error
+            .getattr(intern!(py, "stack"))
+            .and_then(|stack| stack.extract::<String>())
+            .ok();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?kind, message, ?stack, "guest trap");
+
+        Some(Self {
+            kind,
+            message,
+            stack,
+        })
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl StdError for Trap {}
@@ -0,0 +1,127 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use crate::{custom::retain_custom_sections, Error};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A verifier of the Ed25519 signatures of modules, which is enforced before
+/// modules are compiled when it is configured with
+/// [`EngineConfig::with_module_verifier`].
+///
+/// A signed module carries its 64-byte signature in the
+/// [`ModuleVerifier::SIGNATURE_SECTION`] custom section. The signature covers
+/// the [`ModuleVerifier::unsigned_bytes`] of the module, i.e. the module
+/// without its signature section. A module is therefore signed by signing
+/// its unsigned bytes and then appending the signature to them with a
+/// [`CustomSectionWriter`]:
+///
+/// ```rust,ignore
+/// let unsigned = ModuleVerifier::unsigned_bytes(&bytes)?;
+/// let signature = signing_key.sign(&unsigned);
+/// let signed = CustomSectionWriter::new()
+///     .with_section(ModuleVerifier::SIGNATURE_SECTION, signature.to_bytes())
+///     .write(&unsigned)?;
+/// ```
+///
+/// A module is authentic if its signature verifies under any of the trusted
+/// keys, so a verifier without trusted keys rejects all modules.
+///
+/// [`EngineConfig::with_module_verifier`]: crate::EngineConfig::with_module_verifier
+/// [`CustomSectionWriter`]: crate::CustomSectionWriter
+pub struct ModuleVerifier {
+    /// The trusted public keys
+    keys: Vec<VerifyingKey>,
+}
+
+impl ModuleVerifier {
+    /// The name of the custom section that holds the signature of a module
+    pub const SIGNATURE_SECTION: &'static str = "ed25519-signature";
+
+    /// Creates a new verifier without any trusted keys.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { keys: Vec::new() }
+    }
+
+    /// Trusts the Ed25519 `public_key` to sign modules.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Signature`] if the `public_key` is not a valid
+    /// Ed25519 public key.
+    pub fn with_trusted_key(mut self, public_key: &[u8; 32]) -> Result<Self, Error> {
+        let key = VerifyingKey::from_bytes(public_key)
+            .map_err(|err| Error::Signature(format!("invalid public key: {err}")))?;
+
+        self.keys.push(key);
+
+        Ok(self)
+    }
+
+    /// Returns the trusted public keys.
+    pub fn trusted_keys(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.keys.iter().map(VerifyingKey::as_bytes)
+    }
+
+    /// Returns the module `bytes` without their signature section, which are
+    /// covered by the signature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Compile`] if the `bytes` cannot be parsed as a
+    /// module.
+    pub fn unsigned_bytes(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let module = retain_custom_sections(bytes, |name, _| name != Self::SIGNATURE_SECTION)?;
+
+        Ok(module.finish())
+    }
+
+    /// Verifies that the module `bytes` are signed by any of the trusted
+    /// keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Signature`] if the module is not signed exactly
+    /// once, or if its signature does not verify under any trusted key, or
+    /// an [`Error::Compile`] if the `bytes` cannot be parsed as a module.
+    pub fn verify(&self, bytes: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("ModuleVerifier::verify").entered();
+
+        let mut signatures = Vec::new();
+        let unsigned = retain_custom_sections(bytes, |name, data| {
+            if name == Self::SIGNATURE_SECTION {
+                signatures.push(data);
+                return false;
+            }
+
+            true
+        })?
+        .finish();
+
+        let signature = match signatures.as_slice() {
+            [signature] => Signature::from_slice(signature)
+                .map_err(|err| Error::Signature(format!("malformed signature: {err}")))?,
+            [] => return Err(Error::Signature(String::from("the module is not signed"))),
+            _ => {
+                return Err(Error::Signature(String::from(
+                    "the module has more than one signature section",
+                )))
+            },
+        };
+
+        if !self
+            .keys
+            .iter()
+            .any(|key| key.verify_strict(&unsigned, &signature).is_ok())
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("rejected a module whose signature does not verify");
+
+            return Err(Error::Signature(String::from(
+                "the module signature does not verify under any trusted key",
+            )));
+        }
+
+        Ok(())
+    }
+}
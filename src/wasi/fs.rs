@@ -0,0 +1,597 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyBytes};
+
+use crate::{conversion::js_uint8_array_new, func::pyodide_run_sync, Error};
+
+/// A pluggable filesystem that backs the files of a [`WasiShim`].
+///
+/// All paths are absolute and normalized, i.e. they start with a `/`, are
+/// separated by single `/`s, and contain no `.` or `..` components. The
+/// [`WasiShim`] resolves the guest's paths against its preopened directories
+/// before passing them to the filesystem, such that guests cannot escape
+/// their preopens.
+///
+/// Files are read and written as a whole. The [`WasiShim`] buffers the
+/// contents of open files and writes them back when they are synced or
+/// closed.
+///
+/// [`WasiShim`]: crate::WasiShim
+pub trait WasiFs: Send + Sync {
+    /// Returns the metadata of the file or directory at the `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WasiFsError::NotFound`] if there is no file or directory at
+    /// the `path`.
+    fn metadata(&self, path: &str) -> Result<WasiMetadata, WasiFsError>;
+
+    /// Reads the contents of the file at the `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no file at the `path`.
+    fn read_file(&self, path: &str) -> Result<Vec<u8>, WasiFsError>;
+
+    /// Creates or replaces the file at the `path` with the `contents`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the parent directory does not exist or if the
+    /// `path` refers to a directory.
+    fn write_file(&self, path: &str, contents: &[u8]) -> Result<(), WasiFsError>;
+
+    /// Removes the file at the `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no file at the `path`.
+    fn remove_file(&self, path: &str) -> Result<(), WasiFsError>;
+
+    /// Creates a new directory at the `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the parent directory does not exist or if the
+    /// `path` already exists.
+    fn create_dir(&self, path: &str) -> Result<(), WasiFsError>;
+
+    /// Removes the empty directory at the `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no directory at the `path` or if it is
+    /// not empty.
+    fn remove_dir(&self, path: &str) -> Result<(), WasiFsError>;
+
+    /// Returns the names and metadata of the entries in the directory at the
+    /// `path`, sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no directory at the `path`.
+    fn read_dir(&self, path: &str) -> Result<Vec<(String, WasiMetadata)>, WasiFsError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The kind of an entry in a [`WasiFs`]
+pub enum WasiFileType {
+    /// A regular file
+    File,
+    /// A directory
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The metadata of an entry in a [`WasiFs`]
+pub struct WasiMetadata {
+    /// The kind of entry
+    pub file_type: WasiFileType,
+    /// The length of a file in bytes, or `0` for a directory
+    pub len: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// An error returned by a [`WasiFs`], which the [`WasiShim`] translates into
+/// the corresponding WASI errno.
+///
+/// [`WasiShim`]: crate::WasiShim
+pub enum WasiFsError {
+    /// The file or directory does not exist
+    NotFound,
+    /// The file or directory already exists
+    AlreadyExists,
+    /// A directory was expected but a file was found
+    NotADirectory,
+    /// A file was expected but a directory was found
+    IsADirectory,
+    /// The directory is not empty
+    DirectoryNotEmpty,
+    /// Any other failure of the filesystem
+    Io(String),
+}
+
+impl fmt::Display for WasiFsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound => fmt.write_str("no such file or directory"),
+            Self::AlreadyExists => fmt.write_str("file or directory already exists"),
+            Self::NotADirectory => fmt.write_str("not a directory"),
+            Self::IsADirectory => fmt.write_str("is a directory"),
+            Self::DirectoryNotEmpty => fmt.write_str("directory not empty"),
+            Self::Io(msg) => write!(fmt, "filesystem error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for WasiFsError {}
+
+#[derive(Debug, Clone)]
+/// An entry of a [`MemoryFs`]
+enum MemoryEntry {
+    /// A file with its contents
+    File(Vec<u8>),
+    /// A directory
+    Directory,
+}
+
+impl MemoryEntry {
+    fn metadata(&self) -> WasiMetadata {
+        match self {
+            Self::File(contents) => WasiMetadata {
+                file_type: WasiFileType::File,
+                len: contents.len() as u64,
+            },
+            Self::Directory => WasiMetadata {
+                file_type: WasiFileType::Directory,
+                len: 0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// An in-memory [`WasiFs`], whose contents are lost once it is dropped.
+pub struct MemoryFs {
+    /// The entries by their path, excluding the root directory
+    entries: Mutex<BTreeMap<String, MemoryEntry>>,
+}
+
+impl MemoryFs {
+    /// Creates a new empty in-memory filesystem.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the paths and contents of all files, where directories have
+    /// no contents, sorted by path.
+    #[must_use]
+    pub fn entries(&self) -> Vec<(String, Option<Vec<u8>>)> {
+        self.lock()
+            .iter()
+            .map(|(path, entry)| match entry {
+                MemoryEntry::File(contents) => (path.clone(), Some(contents.clone())),
+                MemoryEntry::Directory => (path.clone(), None),
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, MemoryEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_entries<R>(&self, f: impl FnOnce(&mut BTreeMap<String, MemoryEntry>) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Checks that the parent directory of the `path` exists
+    fn check_parent(
+        entries: &BTreeMap<String, MemoryEntry>,
+        path: &str,
+    ) -> Result<(), WasiFsError> {
+        match parent(path) {
+            None | Some("/") => Ok(()),
+            Some(parent) => match entries.get(parent) {
+                Some(MemoryEntry::Directory) => Ok(()),
+                Some(MemoryEntry::File(_)) => Err(WasiFsError::NotADirectory),
+                None => Err(WasiFsError::NotFound),
+            },
+        }
+    }
+}
+
+impl WasiFs for MemoryFs {
+    fn metadata(&self, path: &str) -> Result<WasiMetadata, WasiFsError> {
+        if path == "/" {
+            return Ok(MemoryEntry::Directory.metadata());
+        }
+
+        self.lock()
+            .get(path)
+            .map(MemoryEntry::metadata)
+            .ok_or(WasiFsError::NotFound)
+    }
+
+    fn read_file(&self, path: &str) -> Result<Vec<u8>, WasiFsError> {
+        match self.lock().get(path) {
+            Some(MemoryEntry::File(contents)) => Ok(contents.clone()),
+            Some(MemoryEntry::Directory) => Err(WasiFsError::IsADirectory),
+            None if path == "/" => Err(WasiFsError::IsADirectory),
+            None => Err(WasiFsError::NotFound),
+        }
+    }
+
+    fn write_file(&self, path: &str, contents: &[u8]) -> Result<(), WasiFsError> {
+        self.with_entries(|entries| {
+            Self::check_parent(entries, path)?;
+
+            match entries.get_mut(path) {
+                Some(MemoryEntry::Directory) => Err(WasiFsError::IsADirectory),
+                None if path == "/" => Err(WasiFsError::IsADirectory),
+                Some(MemoryEntry::File(old)) => {
+                    contents.clone_into(old);
+                    Ok(())
+                },
+                None => {
+                    entries.insert(String::from(path), MemoryEntry::File(contents.to_vec()));
+                    Ok(())
+                },
+            }
+        })
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), WasiFsError> {
+        self.with_entries(|entries| match entries.get(path) {
+            Some(MemoryEntry::File(_)) => {
+                entries.remove(path);
+                Ok(())
+            },
+            Some(MemoryEntry::Directory) => Err(WasiFsError::IsADirectory),
+            None if path == "/" => Err(WasiFsError::IsADirectory),
+            None => Err(WasiFsError::NotFound),
+        })
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), WasiFsError> {
+        self.with_entries(|entries| {
+            if path == "/" || entries.contains_key(path) {
+                return Err(WasiFsError::AlreadyExists);
+            }
+
+            Self::check_parent(entries, path)?;
+            entries.insert(String::from(path), MemoryEntry::Directory);
+
+            Ok(())
+        })
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), WasiFsError> {
+        self.with_entries(|entries| {
+            match entries.get(path) {
+                Some(MemoryEntry::Directory) => (),
+                Some(MemoryEntry::File(_)) => return Err(WasiFsError::NotADirectory),
+                None if path == "/" => {
+                    return Err(WasiFsError::Io(String::from(
+                        "the root directory cannot be removed",
+                    )))
+                },
+                None => return Err(WasiFsError::NotFound),
+            }
+
+            if children(entries, path).next().is_some() {
+                return Err(WasiFsError::DirectoryNotEmpty);
+            }
+
+            entries.remove(path);
+
+            Ok(())
+        })
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<(String, WasiMetadata)>, WasiFsError> {
+        self.with_entries(|entries| {
+            match entries.get(path) {
+                Some(MemoryEntry::Directory) => (),
+                None if path == "/" => (),
+                Some(MemoryEntry::File(_)) => return Err(WasiFsError::NotADirectory),
+                None => return Err(WasiFsError::NotFound),
+            }
+
+            Ok(children(entries, path)
+                .map(|(name, entry)| (String::from(name), entry.metadata()))
+                .collect())
+        })
+    }
+}
+
+/// Returns the parent of the normalized `path`, or `None` for the root
+fn parent(path: &str) -> Option<&str> {
+    match path.rfind('/') {
+        Some(0) if path.len() > 1 => Some("/"),
+        Some(0) | None => None,
+        Some(index) => Some(&path[..index]),
+    }
+}
+
+/// Returns the names and entries of the direct children of the directory at
+/// the normalized `path`
+fn children<'a>(
+    entries: &'a BTreeMap<String, MemoryEntry>,
+    path: &str,
+) -> impl Iterator<Item = (&'a str, &'a MemoryEntry)> {
+    let prefix = if path == "/" {
+        String::from("/")
+    } else {
+        format!("{path}/")
+    };
+
+    let len = prefix.len();
+
+    entries
+        .range(prefix.clone()..)
+        .take_while(move |(child, _)| child.starts_with(&prefix))
+        .filter_map(move |(child, entry)| {
+            let name = &child[len..];
+            (!name.contains('/')).then_some((name, entry))
+        })
+}
+
+/// A [`WasiFs`] that is persisted in the browser's `IndexedDB`, such that files
+/// survive across sessions.
+///
+/// All entries are loaded into an in-memory cache when the filesystem is
+/// opened. Reads are then served from the cache, while every change is
+/// applied to the cache and written through to `IndexedDB` in the background.
+/// Use [`IndexedDbFs::flush`] to wait until all changes have been persisted.
+pub struct IndexedDbFs {
+    /// The cached entries of the filesystem
+    cache: MemoryFs,
+    /// The JavaScript handle of the opened database
+    db: Py<PyAny>,
+}
+
+impl fmt::Debug for IndexedDbFs {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("IndexedDbFs")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IndexedDbFs {
+    /// Starts opening the `IndexedDB` database with the `name`, which is
+    /// created if it does not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `IndexedDB` is unavailable.
+    pub fn open(name: &str) -> anyhow::Result<PendingIndexedDbFs> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("IndexedDbFs::open", name).entered();
+
+            let promise = js_indexed_db_fs(py)?
+                .call1((name,))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::EnvironmentUnavailable))?;
+
+            Ok(PendingIndexedDbFs {
+                promise: promise.unbind(),
+            })
+        })
+    }
+
+    /// Returns an awaitable that resolves once all changes so far have been
+    /// persisted to `IndexedDB`, or rejects if persisting any of them failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database handle cannot be accessed.
+    pub fn flush(&self, py: Python) -> Result<Py<PyAny>, PyErr> {
+        self.db
+            .bind(py)
+            .call_method0(intern!(py, "flush"))
+            .map(Bound::unbind)
+    }
+
+    /// Writes the `path` with the `contents`, or a directory if `None`,
+    /// through to `IndexedDB`
+    fn put(&self, path: &str, contents: Option<&[u8]>) -> Result<(), WasiFsError> {
+        Python::with_gil(|py| -> Result<(), PyErr> {
+            let value = match contents {
+                None => py.None().into_bound(py),
+                Some(contents) => {
+                    let array = js_uint8_array_new(py)?.call1((contents.len(),))?;
+                    array.call_method1(intern!(py, "assign"), (contents,))?;
+                    array
+                },
+            };
+
+            self.db
+                .bind(py)
+                .call_method1(intern!(py, "put"), (path, value))?;
+
+            Ok(())
+        })
+        .map_err(|err| WasiFsError::Io(err.to_string()))
+    }
+
+    /// Deletes the `path` from `IndexedDB`
+    fn delete(&self, path: &str) -> Result<(), WasiFsError> {
+        Python::with_gil(|py| {
+            self.db
+                .bind(py)
+                .call_method1(intern!(py, "delete"), (path,))
+                .map(drop)
+        })
+        .map_err(|err| WasiFsError::Io(err.to_string()))
+    }
+}
+
+impl WasiFs for IndexedDbFs {
+    fn metadata(&self, path: &str) -> Result<WasiMetadata, WasiFsError> {
+        self.cache.metadata(path)
+    }
+
+    fn read_file(&self, path: &str) -> Result<Vec<u8>, WasiFsError> {
+        self.cache.read_file(path)
+    }
+
+    fn write_file(&self, path: &str, contents: &[u8]) -> Result<(), WasiFsError> {
+        self.cache.write_file(path, contents)?;
+        self.put(path, Some(contents))
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), WasiFsError> {
+        self.cache.remove_file(path)?;
+        self.delete(path)
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), WasiFsError> {
+        self.cache.create_dir(path)?;
+        self.put(path, None)
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), WasiFsError> {
+        self.cache.remove_dir(path)?;
+        self.delete(path)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<(String, WasiMetadata)>, WasiFsError> {
+        self.cache.read_dir(path)
+    }
+}
+
+#[derive(Debug)]
+/// An [`IndexedDbFs`] that is still being opened, see [`IndexedDbFs::open`].
+pub struct PendingIndexedDbFs {
+    /// The promise that resolves to the opened database
+    promise: Py<PyAny>,
+}
+
+impl PendingIndexedDbFs {
+    /// Returns the JavaScript promise that resolves once the database has
+    /// been opened and its entries have been loaded, which can be awaited
+    /// from async Python code.
+    #[must_use]
+    pub fn as_awaitable(&self, py: Python) -> Py<PyAny> {
+        self.promise.clone_ref(py)
+    }
+
+    /// Creates the filesystem from the `resolved` value of the awaited
+    /// promise, see [`PendingIndexedDbFs::as_awaitable`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the loaded entries cannot be converted.
+    pub fn finish(&self, resolved: &Bound<PyAny>) -> anyhow::Result<IndexedDbFs> {
+        let py = resolved.py();
+
+        let mut entries = BTreeMap::new();
+
+        for entry in resolved.getattr(intern!(py, "entries"))?.try_iter()? {
+            let entry = entry?;
+            let path: String = entry.get_item(0)?.extract()?;
+            let contents = entry.get_item(1)?;
+
+            let entry = if contents.is_none() {
+                MemoryEntry::Directory
+            } else {
+                let contents: Bound<PyBytes> =
+                    contents.call_method0(intern!(py, "to_bytes"))?.extract()?;
+                MemoryEntry::File(contents.as_bytes().to_vec())
+            };
+
+            entries.insert(path, entry);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(entries = entries.len(), "loaded IndexedDbFs");
+
+        Ok(IndexedDbFs {
+            cache: MemoryFs {
+                entries: Mutex::new(entries),
+            },
+            db: resolved.clone().unbind(),
+        })
+    }
+
+    /// Blocks until the database has been opened and creates the filesystem.
+    ///
+    /// Blocking requires `pyodide.ffi.run_sync`, see [`PendingCall::wait`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if blocking is not supported or if opening the
+    /// database fails.
+    ///
+    /// [`PendingCall::wait`]: crate::PendingCall::wait
+    pub fn wait(self) -> anyhow::Result<Arc<IndexedDbFs>> {
+        Python::with_gil(|py| {
+            let run_sync = pyodide_run_sync(py).map_err(|err| {
+                Error::EnvironmentUnavailable(format!(
+                    "blocking on opening IndexedDB requires pyodide.ffi.run_sync: {err}"
+                ))
+            })?;
+
+            let resolved = run_sync
+                .call1((self.promise.bind(py),))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::EnvironmentUnavailable))?;
+
+            self.finish(&resolved).map(Arc::new)
+        })
+    }
+}
+
+fn js_indexed_db_fs(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_INDEXED_DB_FS: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_INDEXED_DB_FS
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r#"
+function openIndexedDbFs(name) {
+    function done(request) {
+        return new Promise((resolve, reject) => {
+            request.onsuccess = () => resolve(request.result);
+            request.onerror = () => reject(request.error);
+        });
+    }
+
+    const open = indexedDB.open(name, 1);
+    open.onupgradeneeded = () => open.result.createObjectStore("entries");
+
+    return done(open).then(async (db) => {
+        const store = db.transaction("entries", "readonly").objectStore("entries");
+        const [keys, values] = await Promise.all([
+            done(store.getAllKeys()), done(store.getAll()),
+        ]);
+
+        let pending = Promise.resolve();
+        function write(apply) {
+            const request = apply(db.transaction("entries", "readwrite").objectStore("entries"));
+            pending = Promise.all([pending, done(request)]);
+        }
+
+        return {
+            entries: keys.map((key, i) => [key, values[i]]),
+            put(path, value) { write((store) => store.put(value, path)); },
+            delete(path) { write((store) => store.delete(path)); },
+            flush() { return pending.then(() => undefined); },
+        };
+    });
+}
+openIndexedDbFs
+"#,))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
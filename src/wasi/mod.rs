@@ -0,0 +1,202 @@
+use std::{fmt, sync::Arc};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyTuple};
+use pyo3_error::PyErrChain;
+use wasm_runtime_layer::{
+    backend::{Imports, Value, WasmModule},
+    ExternType, FuncType, ImportType, ValueType,
+};
+
+mod fs;
+mod shim;
+
+pub use fs::{
+    IndexedDbFs, MemoryFs, PendingIndexedDbFs, WasiFileType, WasiFs, WasiFsError, WasiMetadata,
+};
+pub use shim::{WasiShim, WasiShimBuilder};
+
+use crate::{
+    conversion::{create_js_object, ToPy, ValueExt},
+    func::{js_host_callable, PyHostFuncFn},
+    store::StoreContextMut,
+    Engine, Module,
+};
+
+/// A custom handler for unimplemented WASI imports, which is called with the
+/// module and name of the import, its arguments, and its results
+pub type WasiImportHandler = dyn 'static
+    + Send
+    + Sync
+    + Fn(&str, &str, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>;
+
+#[derive(Clone, Default)]
+/// The policy for WASI function imports that a module declares but that are
+/// not provided when it is instantiated, see
+/// [`EngineConfig::with_wasi_import_policy`].
+///
+/// The policy applies to all function imports from the
+/// `wasi_snapshot_preview1` and `wasi_unstable` modules, such that modules can
+/// be linked against a WASI shim that only implements some of the syscalls.
+///
+/// [`EngineConfig::with_wasi_import_policy`]: crate::EngineConfig::with_wasi_import_policy
+pub enum WasiImportPolicy {
+    /// Unimplemented WASI imports remain missing, so instantiation fails
+    /// with a link error
+    #[default]
+    Missing,
+    /// Unimplemented WASI imports are provided by stubs that trap when called
+    Trap,
+    /// Unimplemented WASI imports are provided by stubs that return the
+    /// [`WasiImportPolicy::ENOSYS`] errno, or trap if the import does not
+    /// return an errno
+    Enosys,
+    /// Unimplemented WASI imports are provided by the custom handler
+    Custom(Arc<WasiImportHandler>),
+}
+
+impl WasiImportPolicy {
+    /// The WASI errno for "function not supported"
+    pub const ENOSYS: i32 = 52;
+    /// The import module names of the WASI snapshots
+    pub const MODULES: &'static [&'static str] = &["wasi_snapshot_preview1", "wasi_unstable"];
+
+    /// Creates a policy that provides unimplemented WASI imports using the
+    /// custom `handler`.
+    #[must_use]
+    pub fn custom(
+        handler: impl 'static
+            + Send
+            + Sync
+            + Fn(&str, &str, &[Value<Engine>], &mut [Value<Engine>]) -> anyhow::Result<()>,
+    ) -> Self {
+        Self::Custom(Arc::new(handler))
+    }
+}
+
+impl fmt::Debug for WasiImportPolicy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => fmt.write_str("Missing"),
+            Self::Trap => fmt.write_str("Trap"),
+            Self::Enosys => fmt.write_str("Enosys"),
+            Self::Custom(_) => fmt.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Adds stubs for the unimplemented WASI function imports of the `module`,
+/// which are missing from the `imports`, to the js `imports_object`,
+/// according to the `policy`
+pub fn apply_wasi_import_policy<T>(
+    py: Python,
+    store: &mut StoreContextMut<T>,
+    module: &Module,
+    imports: &Imports<Engine>,
+    imports_object: &Bound<PyAny>,
+    policy: &WasiImportPolicy,
+) -> Result<(), PyErr> {
+    if matches!(policy, WasiImportPolicy::Missing) {
+        return Ok(());
+    }
+
+    for ImportType {
+        module: module_name,
+        name,
+        ty,
+    } in module.imports()
+    {
+        let ExternType::Func(ty) = ty else {
+            continue;
+        };
+
+        if !WasiImportPolicy::MODULES.contains(&module_name) || imports.exists(module_name, name) {
+            continue;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            module_name,
+            name,
+            ?policy,
+            "stubbing unimplemented WASI import"
+        );
+
+        let stub = match policy {
+            WasiImportPolicy::Missing => continue,
+            WasiImportPolicy::Trap => js_wasi_stub(py)?.call1((module_name, name, py.None()))?,
+            WasiImportPolicy::Enosys => {
+                let errno = (ty.results() == [ValueType::I32]).then_some(WasiImportPolicy::ENOSYS);
+                js_wasi_stub(py)?.call1((module_name, name, errno))?
+            },
+            WasiImportPolicy::Custom(handler) => {
+                let func = custom_host_func(module_name, name, ty.clone(), Arc::clone(handler));
+                js_host_callable(py, store, func, &ty)?
+            },
+        };
+
+        if !imports_object.hasattr(module_name)? {
+            imports_object.setattr(module_name, create_js_object(py)?)?;
+        }
+        imports_object.getattr(module_name)?.setattr(name, stub)?;
+    }
+
+    Ok(())
+}
+
+/// Wraps the custom `handler` for the WASI import `module`.`name` with the
+/// signature `ty` into a host function
+fn custom_host_func(
+    module: &str,
+    name: &str,
+    ty: FuncType,
+    handler: Arc<WasiImportHandler>,
+) -> Arc<PyHostFuncFn> {
+    let (module, name) = (String::from(module), String::from(name));
+
+    Arc::new(move |args: Bound<PyTuple>| -> Result<Py<PyAny>, PyErr> {
+        let py = args.py();
+
+        let args = ty
+            .params()
+            .iter()
+            .zip(args.iter())
+            .map(|(ty, arg)| Value::from_py_typed(arg, *ty))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut results = vec![Value::I32(0); ty.results().len()];
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("call_wasi_stub", module, name, ?args).entered();
+
+        handler(&module, &name, &args, &mut results)
+            .map_err(|err| PyErrChain::pyerr_from_err(py, err))?;
+
+        let results = match results.as_slice() {
+            [] => py.None(),
+            [res] => res.to_py(py),
+            results => PyTuple::new(py, results.iter().map(|res| res.to_py(py)))?
+                .into_any()
+                .unbind(),
+        };
+
+        Ok(results)
+    })
+}
+
+fn js_wasi_stub(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_WASI_STUB: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_WASI_STUB
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((
+                    "function wasiStub(module, name, errno){ return function(){ if (errno != \
+                     null) { return errno; } throw new WebAssembly.RuntimeError(`unimplemented \
+                     WASI import ${module}.${name} was called`); }; } wasiStub",
+                ))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
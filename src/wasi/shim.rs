@@ -0,0 +1,1358 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+
+use pyo3::{prelude::*, types::PyBytes};
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Extern, Imports, Value, WasmMemory},
+    ValueType,
+};
+
+use crate::{
+    memory::PAGE_SIZE,
+    wasi::fs::{MemoryFs, WasiFileType, WasiFs, WasiFsError, WasiMetadata},
+    Clock, Engine, Func, FuncSignature, Instance, Memory, StoreContextMut,
+};
+
+/// A WASI shim that provides the filesystem, argument, environment, and clock
+/// `wasi_snapshot_preview1` imports for `wasm32-wasip1` guests, backed by a
+/// pluggable [`WasiFs`].
+///
+/// Guests can only access the directories that are preopened with
+/// [`WasiShimBuilder::preopen_dir`]. Their arguments, environment variables,
+/// standard input, and [`Clock`] are configured on the [`WasiShimBuilder`],
+/// while their standard output and error streams are captured and can be
+/// taken with [`WasiShim::take_stdout`] and [`WasiShim::take_stderr`]. Any
+/// other WASI imports are not provided and are handled by the
+/// [`WasiImportPolicy`].
+///
+/// Since the guest's memory only exists after instantiation, it must be
+/// provided using [`WasiShim::bind_memory`] or [`WasiShim::bind_instance`]
+/// before the guest makes any WASI calls.
+///
+/// [`WasiImportPolicy`]: crate::WasiImportPolicy
+#[derive(Clone)]
+pub struct WasiShim {
+    /// The state of the shim, which is shared with its imports
+    state: Arc<Mutex<WasiState>>,
+}
+
+impl fmt::Debug for WasiShim {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        fmt.debug_struct("WasiShim")
+            .field("fds", &state.fds)
+            .field("memory", &state.memory)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasiShim {
+    /// The import module name of the WASI imports
+    pub const MODULE: &'static str = "wasi_snapshot_preview1";
+
+    /// Creates a new builder for a WASI shim.
+    #[must_use]
+    pub fn builder() -> WasiShimBuilder {
+        WasiShimBuilder {
+            fs: None,
+            preopens: Vec::new(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            stdin: Vec::new(),
+            clock: Clock::System,
+        }
+    }
+
+    /// Defines the WASI imports of this shim in the `imports`.
+    pub fn define<T: 'static>(
+        &self,
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        imports: &mut Imports<Engine>,
+    ) {
+        for (name, params, syscall) in syscalls::<T>() {
+            let signature = params
+                .iter()
+                .fold(
+                    FuncSignature::builder().name(name),
+                    |builder, (param, ty)| builder.param(*param, *ty),
+                )
+                .result("errno", ValueType::I32)
+                .build();
+
+            let state = Arc::clone(&self.state);
+
+            let func = Func::new_with_signature(
+                ctx.as_context_mut(),
+                signature,
+                move |ctx, args, results| {
+                    let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+
+                    let Some(memory) = state.memory.clone() else {
+                        anyhow::bail!("{name} called before the guest memory was bound");
+                    };
+
+                    let errno = match syscall(&mut state, &mut GuestMemory { ctx, memory }, args) {
+                        Ok(()) => Errno::SUCCESS,
+                        Err(errno) => errno,
+                    };
+                    drop(state);
+
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(name, errno = errno.0, "WASI syscall");
+
+                    results[0] = Value::I32(errno.0);
+
+                    Ok(())
+                },
+            );
+
+            imports.define(Self::MODULE, name, Extern::Func(func));
+        }
+    }
+
+    /// Binds the shim to the guest `memory` through which the WASI calls
+    /// exchange data.
+    pub fn bind_memory(&self, memory: &Memory) {
+        self.lock().memory = Some(memory.clone());
+    }
+
+    /// Binds the shim to the memory that the `instance` exports under the
+    /// `name`, e.g. `"memory"`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `instance` does not export a memory with the
+    /// `name`.
+    pub fn bind_instance(
+        &self,
+        ctx: impl AsContext<Engine>,
+        instance: &Instance,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let memory = instance.get_memory(ctx, name)?;
+
+        self.bind_memory(&memory);
+
+        Ok(())
+    }
+
+    /// Takes the bytes that the guest has written to its standard output
+    /// since the last call.
+    #[must_use]
+    pub fn take_stdout(&self) -> Vec<u8> {
+        std::mem::take(&mut self.lock().stdout)
+    }
+
+    /// Takes the bytes that the guest has written to its standard error
+    /// since the last call.
+    #[must_use]
+    pub fn take_stderr(&self) -> Vec<u8> {
+        std::mem::take(&mut self.lock().stderr)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, WasiState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A builder for a [`WasiShim`], see [`WasiShim::builder`].
+pub struct WasiShimBuilder {
+    /// The filesystem, an in-memory one by default
+    fs: Option<Arc<dyn WasiFs>>,
+    /// The preopened directories by guest path and filesystem path
+    preopens: Vec<(String, String)>,
+    /// The command-line arguments, including the program name
+    args: Vec<String>,
+    /// The environment variables by name and value
+    envs: Vec<(String, String)>,
+    /// The contents of the standard input
+    stdin: Vec<u8>,
+    /// The source of time
+    clock: Clock,
+}
+
+impl fmt::Debug for WasiShimBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WasiShimBuilder")
+            .field("preopens", &self.preopens)
+            .field("args", &self.args)
+            .field("envs", &self.envs)
+            .field("stdin", &self.stdin.len())
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasiShimBuilder {
+    /// Uses the `fs` to back the files of the shim, e.g. a [`MemoryFs`] or
+    /// an [`IndexedDbFs`].
+    ///
+    /// By default, the shim uses a new [`MemoryFs`].
+    ///
+    /// [`IndexedDbFs`]: crate::IndexedDbFs
+    #[must_use]
+    pub fn fs(mut self, fs: Arc<dyn WasiFs>) -> Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    /// Preopens the directory at the `fs_path` in the filesystem, such that
+    /// the guest can access it under the `guest_path`, e.g. `"/"` or `"."`.
+    ///
+    /// The directory is created when the shim is built if it does not exist
+    /// yet. Preopens are assigned file descriptors in the order in which
+    /// they are added, starting at `3`.
+    #[must_use]
+    pub fn preopen_dir(mut self, guest_path: impl Into<String>, fs_path: &str) -> Self {
+        self.preopens.push((guest_path.into(), normalize(fs_path)));
+        self
+    }
+
+    /// Appends the `arg` to the command-line arguments of the guest.
+    ///
+    /// By convention, the first argument is the name of the program.
+    #[must_use]
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Appends the `args` to the command-line arguments of the guest.
+    #[must_use]
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the environment variable with the `name` to the `value`,
+    /// replacing any previous value.
+    #[must_use]
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let (name, value) = (name.into(), value.into());
+
+        match self.envs.iter_mut().find(|(env, _)| *env == name) {
+            Some((_, old)) => *old = value,
+            None => self.envs.push((name, value)),
+        }
+
+        self
+    }
+
+    /// Sets the environment variables from the `envs` name-value pairs.
+    #[must_use]
+    pub fn envs(
+        self,
+        envs: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        envs.into_iter()
+            .fold(self, |builder, (name, value)| builder.env(name, value))
+    }
+
+    /// Provides the `stdin` bytes as the contents of the guest's standard
+    /// input, after which the guest reads an end-of-file.
+    #[must_use]
+    pub fn stdin(mut self, stdin: &[u8]) -> Self {
+        stdin.clone_into(&mut self.stdin);
+        self
+    }
+
+    /// Provides the Python `stdin` bytes as the contents of the guest's
+    /// standard input, see [`WasiShimBuilder::stdin`].
+    #[must_use]
+    pub fn stdin_py(self, stdin: &Bound<PyBytes>) -> Self {
+        self.stdin(stdin.as_bytes())
+    }
+
+    /// Uses the `clock` as the source of time for the guest's realtime and
+    /// monotonic clocks.
+    ///
+    /// By default, the [`Clock::System`] time is used. With a
+    /// [`Clock::Virtual`] clock, the guest only observes time advancing when
+    /// the host advances the clock, e.g. for deterministic simulations.
+    #[must_use]
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Builds the WASI shim.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a preopened directory cannot be created.
+    pub fn build(self) -> Result<WasiShim, WasiFsError> {
+        let fs = self.fs.unwrap_or_else(|| Arc::new(MemoryFs::new()));
+
+        let mut fds = BTreeMap::from([
+            (0, FileDescriptor::Stdin),
+            (1, FileDescriptor::Stdout),
+            (2, FileDescriptor::Stderr),
+        ]);
+
+        for (fd, (guest_path, path)) in (3..).zip(self.preopens) {
+            create_dir_all(&*fs, &path)?;
+
+            fds.insert(
+                fd,
+                FileDescriptor::Directory {
+                    path,
+                    preopen: Some(guest_path),
+                },
+            );
+        }
+
+        Ok(WasiShim {
+            state: Arc::new(Mutex::new(WasiState {
+                fs,
+                fds,
+                memory: None,
+                args: self.args,
+                envs: self
+                    .envs
+                    .into_iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect(),
+                stdin: self.stdin,
+                stdin_position: 0,
+                clock: self.clock,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            })),
+        })
+    }
+}
+
+/// The shared state of a [`WasiShim`]
+struct WasiState {
+    /// The filesystem
+    fs: Arc<dyn WasiFs>,
+    /// The open file descriptors
+    fds: BTreeMap<u32, FileDescriptor>,
+    /// The guest memory through which data is exchanged
+    memory: Option<Memory>,
+    /// The command-line arguments
+    args: Vec<String>,
+    /// The environment variables as `name=value` strings
+    envs: Vec<String>,
+    /// The contents of the standard input
+    stdin: Vec<u8>,
+    /// The number of standard input bytes that have been read
+    stdin_position: usize,
+    /// The source of time
+    clock: Clock,
+    /// The captured standard output
+    stdout: Vec<u8>,
+    /// The captured standard error
+    stderr: Vec<u8>,
+}
+
+impl WasiState {
+    /// Resolves the `path` relative to the directory `fd`, without escaping
+    /// it
+    fn resolve(&self, fd: u32, path: &str) -> Result<String, Errno> {
+        let base = match self.fds.get(&fd) {
+            Some(FileDescriptor::Directory { path, .. }) => path,
+            Some(_) => return Err(Errno::NOTDIR),
+            None => return Err(Errno::BADF),
+        };
+
+        let mut components = Vec::new();
+
+        for component in path.split('/') {
+            match component {
+                "" | "." => (),
+                ".." => {
+                    if components.pop().is_none() {
+                        return Err(Errno::NOTCAPABLE);
+                    }
+                },
+                component => components.push(component),
+            }
+        }
+
+        Ok(components
+            .into_iter()
+            .fold(base.clone(), |path, component| join(&path, component)))
+    }
+
+    /// Returns the open file `fd`
+    fn file(&mut self, fd: u32) -> Result<&mut OpenFile, Errno> {
+        match self.fds.get_mut(&fd) {
+            Some(FileDescriptor::File(file)) => Ok(file),
+            Some(FileDescriptor::Directory { .. }) => Err(Errno::ISDIR),
+            Some(_) => Err(Errno::SPIPE),
+            None => Err(Errno::BADF),
+        }
+    }
+
+    /// Inserts the `descriptor` under the lowest unused file descriptor
+    fn insert(&mut self, descriptor: FileDescriptor) -> u32 {
+        let fd = (0..=u32::MAX)
+            .find(|fd| !self.fds.contains_key(fd))
+            .unwrap_or(u32::MAX);
+        self.fds.insert(fd, descriptor);
+        fd
+    }
+}
+
+#[derive(Debug)]
+/// An open file descriptor of a [`WasiShim`]
+enum FileDescriptor {
+    /// The standard input
+    Stdin,
+    /// The standard output
+    Stdout,
+    /// The standard error
+    Stderr,
+    /// An open directory
+    Directory {
+        /// The path of the directory in the filesystem
+        path: String,
+        /// The guest path if the directory is preopened
+        preopen: Option<String>,
+    },
+    /// An open file
+    File(OpenFile),
+}
+
+/// The maximum size of an open file in bytes, which bounds the positions
+/// that a guest can seek to and write at, since open files are buffered in
+/// host memory
+const MAX_FILE_SIZE: usize = 1 << 30;
+
+#[derive(Debug)]
+/// An open file, whose contents are buffered until it is synced or closed
+struct OpenFile {
+    /// The path of the file in the filesystem
+    path: String,
+    /// The buffered contents of the file
+    contents: Vec<u8>,
+    /// The current read and write position
+    position: usize,
+    /// Whether all writes append to the end of the file
+    append: bool,
+    /// Whether the contents have changed since the last sync
+    dirty: bool,
+}
+
+impl OpenFile {
+    /// Writes the buffered contents back to the `fs` if they have changed
+    fn sync(&mut self, fs: &dyn WasiFs) -> Result<(), Errno> {
+        if self.dirty {
+            fs.write_file(&self.path, &self.contents)?;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A WASI errno
+struct Errno(i32);
+
+impl Errno {
+    const BADF: Self = Self(8);
+    const EXIST: Self = Self(20);
+    const FAULT: Self = Self(21);
+    const FBIG: Self = Self(22);
+    const ILSEQ: Self = Self(25);
+    const INVAL: Self = Self(28);
+    const IO: Self = Self(29);
+    const ISDIR: Self = Self(31);
+    const NOENT: Self = Self(44);
+    const NOTCAPABLE: Self = Self(76);
+    const NOTDIR: Self = Self(54);
+    const NOTEMPTY: Self = Self(55);
+    const SPIPE: Self = Self(70);
+    const SUCCESS: Self = Self(0);
+}
+
+impl From<WasiFsError> for Errno {
+    fn from(err: WasiFsError) -> Self {
+        match err {
+            WasiFsError::NotFound => Self::NOENT,
+            WasiFsError::AlreadyExists => Self::EXIST,
+            WasiFsError::NotADirectory => Self::NOTDIR,
+            WasiFsError::IsADirectory => Self::ISDIR,
+            WasiFsError::DirectoryNotEmpty => Self::NOTEMPTY,
+            WasiFsError::Io(_) => Self::IO,
+        }
+    }
+}
+
+/// The guest memory during a WASI call
+struct GuestMemory<'a, T> {
+    /// The store context of the call
+    ctx: StoreContextMut<'a, T>,
+    /// The guest memory
+    memory: Memory,
+}
+
+impl<T> GuestMemory<'_, T> {
+    /// Checks that the `len` bytes at `ptr` are inside the guest memory,
+    /// before a buffer of a guest-controlled length is allocated
+    fn check_bounds(&self, ptr: u32, len: u64) -> Result<(), Errno> {
+        let size = self
+            .memory
+            .current_pages_u64(self.ctx.as_context())
+            .map_err(|_| Errno::FAULT)?
+            .saturating_mul(PAGE_SIZE);
+
+        if u64::from(ptr).saturating_add(len) > size {
+            return Err(Errno::FAULT);
+        }
+
+        Ok(())
+    }
+
+    fn read(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+        self.check_bounds(ptr, u64::from(len))?;
+
+        let mut bytes = vec![0; len as usize];
+        self.memory
+            .read(self.ctx.as_context(), ptr as usize, &mut bytes)
+            .map_err(|_| Errno::FAULT)?;
+        Ok(bytes)
+    }
+
+    fn read_u32(&self, ptr: u32) -> Result<u32, Errno> {
+        let mut bytes = [0; 4];
+        self.memory
+            .read(self.ctx.as_context(), ptr as usize, &mut bytes)
+            .map_err(|_| Errno::FAULT)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_str(&self, ptr: u32, len: u32) -> Result<String, Errno> {
+        String::from_utf8(self.read(ptr, len)?).map_err(|_| Errno::ILSEQ)
+    }
+
+    /// Reads the `(buf, buf_len)` pairs of the `iovs_len` iovecs at `iovs`
+    fn read_iovs(&self, iovs: u32, iovs_len: u32) -> Result<Vec<(u32, u32)>, Errno> {
+        self.check_bounds(iovs, u64::from(iovs_len) * 8)?;
+
+        (0..iovs_len)
+            .map(|i| {
+                let iov = iovs.wrapping_add(i.wrapping_mul(8));
+                Ok((self.read_u32(iov)?, self.read_u32(iov.wrapping_add(4))?))
+            })
+            .collect()
+    }
+
+    fn write(&mut self, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+        self.memory
+            .write(self.ctx.as_context_mut(), ptr as usize, bytes)
+            .map_err(|_| Errno::FAULT)
+    }
+
+    fn write_u32(&mut self, ptr: u32, value: u32) -> Result<(), Errno> {
+        self.write(ptr, &value.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, ptr: u32, value: u64) -> Result<(), Errno> {
+        self.write(ptr, &value.to_le_bytes())
+    }
+
+    /// Writes the number of `strings` to `count` and their total
+    /// nul-terminated length to `buf_size`
+    fn write_string_sizes(
+        &mut self,
+        count: u32,
+        buf_size: u32,
+        strings: &[String],
+    ) -> Result<(), Errno> {
+        let size = strings.iter().map(|string| string.len() + 1).sum::<usize>();
+
+        self.write_u32(
+            count,
+            u32::try_from(strings.len()).map_err(|_| Errno::INVAL)?,
+        )?;
+        self.write_u32(buf_size, u32::try_from(size).map_err(|_| Errno::INVAL)?)
+    }
+
+    /// Writes the nul-terminated `strings` into `buf` and pointers to them
+    /// into the `ptrs` array
+    fn write_strings(&mut self, ptrs: u32, buf: u32, strings: &[String]) -> Result<(), Errno> {
+        let (mut ptr, mut string_ptr) = (ptrs, buf);
+
+        for string in strings {
+            let mut bytes = Vec::with_capacity(string.len() + 1);
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+
+            self.write_u32(ptr, string_ptr)?;
+            self.write(string_ptr, &bytes)?;
+
+            ptr = ptr.wrapping_add(4);
+            string_ptr =
+                string_ptr.wrapping_add(u32::try_from(bytes.len()).map_err(|_| Errno::INVAL)?);
+        }
+
+        Ok(())
+    }
+
+    /// Writes a WASI `filestat` with the `metadata` to `ptr`
+    fn write_filestat(&mut self, ptr: u32, filetype: u8, size: u64) -> Result<(), Errno> {
+        let mut filestat = [0_u8; 64];
+        filestat[16] = filetype;
+        filestat[24..32].copy_from_slice(&1_u64.to_le_bytes());
+        filestat[32..40].copy_from_slice(&size.to_le_bytes());
+        self.write(ptr, &filestat)
+    }
+}
+
+/// A WASI syscall implementation
+type Syscall<T> = fn(&mut WasiState, &mut GuestMemory<T>, &[Value<Engine>]) -> Result<(), Errno>;
+
+/// The named parameters of a WASI syscall
+type SyscallParams = &'static [(&'static str, ValueType)];
+
+/// The parameters of syscalls on a file descriptor and a buffer
+const FD_BUF: SyscallParams = &[("fd", ValueType::I32), ("buf", ValueType::I32)];
+/// The parameters of syscalls on a path relative to a directory
+const FD_PATH: SyscallParams = &[
+    ("fd", ValueType::I32),
+    ("path", ValueType::I32),
+    ("path_len", ValueType::I32),
+];
+
+/// The parameters of syscalls that return the sizes of a list of strings
+const SIZES: SyscallParams = &[("count", ValueType::I32), ("buf_size", ValueType::I32)];
+/// The parameters of syscalls that return a list of strings
+const STRINGS: SyscallParams = &[("ptrs", ValueType::I32), ("buf", ValueType::I32)];
+
+/// Returns the name, parameters, and implementation of all WASI syscalls
+/// that the shim provides
+fn syscalls<T>() -> [(&'static str, SyscallParams, Syscall<T>); 22] {
+    [
+        (
+            "clock_res_get",
+            &[("id", ValueType::I32), ("resolution", ValueType::I32)],
+            clock_res_get,
+        ),
+        (
+            "clock_time_get",
+            &[
+                ("id", ValueType::I32),
+                ("precision", ValueType::I64),
+                ("time", ValueType::I32),
+            ],
+            clock_time_get,
+        ),
+        ("args_sizes_get", SIZES, args_sizes_get),
+        ("args_get", STRINGS, args_get),
+        ("environ_sizes_get", SIZES, environ_sizes_get),
+        ("environ_get", STRINGS, environ_get),
+        ("fd_prestat_get", FD_BUF, fd_prestat_get),
+        ("fd_prestat_dir_name", FD_PATH, fd_prestat_dir_name),
+        ("fd_fdstat_get", FD_BUF, fd_fdstat_get),
+        ("fd_close", &[("fd", ValueType::I32)], fd_close),
+        ("fd_sync", &[("fd", ValueType::I32)], fd_sync),
+        (
+            "path_open",
+            &[
+                ("fd", ValueType::I32),
+                ("dirflags", ValueType::I32),
+                ("path", ValueType::I32),
+                ("path_len", ValueType::I32),
+                ("oflags", ValueType::I32),
+                ("fs_rights_base", ValueType::I64),
+                ("fs_rights_inheriting", ValueType::I64),
+                ("fdflags", ValueType::I32),
+                ("opened_fd", ValueType::I32),
+            ],
+            path_open,
+        ),
+        (
+            "fd_read",
+            &[
+                ("fd", ValueType::I32),
+                ("iovs", ValueType::I32),
+                ("iovs_len", ValueType::I32),
+                ("nread", ValueType::I32),
+            ],
+            fd_read,
+        ),
+        (
+            "fd_write",
+            &[
+                ("fd", ValueType::I32),
+                ("iovs", ValueType::I32),
+                ("iovs_len", ValueType::I32),
+                ("nwritten", ValueType::I32),
+            ],
+            fd_write,
+        ),
+        (
+            "fd_seek",
+            &[
+                ("fd", ValueType::I32),
+                ("offset", ValueType::I64),
+                ("whence", ValueType::I32),
+                ("newoffset", ValueType::I32),
+            ],
+            fd_seek,
+        ),
+        ("fd_tell", FD_BUF, fd_tell),
+        ("fd_filestat_get", FD_BUF, fd_filestat_get),
+        (
+            "path_filestat_get",
+            &[
+                ("fd", ValueType::I32),
+                ("flags", ValueType::I32),
+                ("path", ValueType::I32),
+                ("path_len", ValueType::I32),
+                ("buf", ValueType::I32),
+            ],
+            path_filestat_get,
+        ),
+        ("path_create_directory", FD_PATH, path_create_directory),
+        ("path_unlink_file", FD_PATH, path_unlink_file),
+        ("path_remove_directory", FD_PATH, path_remove_directory),
+        (
+            "fd_readdir",
+            &[
+                ("fd", ValueType::I32),
+                ("buf", ValueType::I32),
+                ("buf_len", ValueType::I32),
+                ("cookie", ValueType::I64),
+                ("bufused", ValueType::I32),
+            ],
+            fd_readdir,
+        ),
+    ]
+}
+
+/// The WASI `filetype` of a character device
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+/// The WASI `filetype` of a directory
+const FILETYPE_DIRECTORY: u8 = 3;
+/// The WASI `filetype` of a regular file
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+/// The WASI `clockid` of the wall-clock time
+const CLOCKID_REALTIME: u32 = 0;
+/// The WASI `clockid` of the monotonic time
+const CLOCKID_MONOTONIC: u32 = 1;
+/// The WASI `clockid` of the process CPU time
+const CLOCKID_PROCESS_CPUTIME: u32 = 2;
+/// The WASI `clockid` of the thread CPU time
+const CLOCKID_THREAD_CPUTIME: u32 = 3;
+
+/// The WASI `oflags` flag to create a file if it does not exist
+const OFLAGS_CREAT: u32 = 1 << 0;
+/// The WASI `oflags` flag to fail if the path is not a directory
+const OFLAGS_DIRECTORY: u32 = 1 << 1;
+/// The WASI `oflags` flag to fail if the file already exists
+const OFLAGS_EXCL: u32 = 1 << 2;
+/// The WASI `oflags` flag to truncate the file
+const OFLAGS_TRUNC: u32 = 1 << 3;
+/// The WASI `fdflags` flag to append all writes
+const FDFLAGS_APPEND: u32 = 1 << 0;
+
+const fn filetype(metadata: WasiMetadata) -> u8 {
+    match metadata.file_type {
+        WasiFileType::File => FILETYPE_REGULAR_FILE,
+        WasiFileType::Directory => FILETYPE_DIRECTORY,
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn u32_arg(args: &[Value<Engine>], index: usize) -> Result<u32, Errno> {
+    match args.get(index) {
+        Some(Value::I32(value)) => Ok(*value as u32),
+        _ => Err(Errno::INVAL),
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn u64_arg(args: &[Value<Engine>], index: usize) -> Result<u64, Errno> {
+    match args.get(index) {
+        Some(Value::I64(value)) => Ok(*value as u64),
+        _ => Err(Errno::INVAL),
+    }
+}
+
+fn args_sizes_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (count, buf_size) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    memory.write_string_sizes(count, buf_size, &state.args)
+}
+
+fn args_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (ptrs, buf) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    memory.write_strings(ptrs, buf, &state.args)
+}
+
+fn environ_sizes_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (count, buf_size) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    memory.write_string_sizes(count, buf_size, &state.envs)
+}
+
+fn environ_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (ptrs, buf) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    memory.write_strings(ptrs, buf, &state.envs)
+}
+
+/// Returns the time of the WASI clock `id` in nanoseconds
+fn clock_time(clock: &Clock, id: u32) -> Result<u64, Errno> {
+    let time = match id {
+        CLOCKID_REALTIME => clock
+            .now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default(),
+        CLOCKID_MONOTONIC | CLOCKID_PROCESS_CPUTIME | CLOCKID_THREAD_CPUTIME => clock.monotonic(),
+        _ => return Err(Errno::INVAL),
+    };
+
+    Ok(u64::try_from(time.as_nanos()).unwrap_or(u64::MAX))
+}
+
+fn clock_res_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (id, resolution) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    clock_time(&state.clock, id)?;
+    let nanos = u64::try_from(state.clock.resolution().as_nanos()).unwrap_or(u64::MAX);
+
+    memory.write_u64(resolution, nanos)
+}
+
+fn clock_time_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (id, time) = (u32_arg(args, 0)?, u32_arg(args, 2)?);
+
+    let nanos = clock_time(&state.clock, id)?;
+
+    memory.write_u64(time, nanos)
+}
+
+fn fd_prestat_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, buf) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    let Some(FileDescriptor::Directory {
+        preopen: Some(guest_path),
+        ..
+    }) = state.fds.get(&fd)
+    else {
+        return Err(Errno::BADF);
+    };
+
+    let len = u32::try_from(guest_path.len()).map_err(|_| Errno::INVAL)?;
+
+    memory.write_u32(buf, 0)?;
+    memory.write_u32(buf.wrapping_add(4), len)
+}
+
+fn fd_prestat_dir_name<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, path, path_len) = (u32_arg(args, 0)?, u32_arg(args, 1)?, u32_arg(args, 2)?);
+
+    let Some(FileDescriptor::Directory {
+        preopen: Some(guest_path),
+        ..
+    }) = state.fds.get(&fd)
+    else {
+        return Err(Errno::BADF);
+    };
+
+    let Some(name) = guest_path.as_bytes().get(..path_len as usize) else {
+        return Err(Errno::INVAL);
+    };
+
+    memory.write(path, name)
+}
+
+fn fd_fdstat_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, buf) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    let (filetype, flags) = match state.fds.get(&fd) {
+        Some(FileDescriptor::Stdin | FileDescriptor::Stdout | FileDescriptor::Stderr) => {
+            (FILETYPE_CHARACTER_DEVICE, 0)
+        },
+        Some(FileDescriptor::Directory { .. }) => (FILETYPE_DIRECTORY, 0),
+        Some(FileDescriptor::File(file)) => (
+            FILETYPE_REGULAR_FILE,
+            if file.append { FDFLAGS_APPEND } else { 0 },
+        ),
+        None => return Err(Errno::BADF),
+    };
+
+    let mut fdstat = [0_u8; 24];
+    fdstat[0] = filetype;
+    #[allow(clippy::cast_possible_truncation)]
+    fdstat[2..4].copy_from_slice(&(flags as u16).to_le_bytes());
+    fdstat[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    fdstat[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    memory.write(buf, &fdstat)
+}
+
+fn fd_close<T>(
+    state: &mut WasiState,
+    _memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let fd = u32_arg(args, 0)?;
+
+    match state.fds.remove(&fd) {
+        Some(FileDescriptor::File(mut file)) => file.sync(&*state.fs),
+        Some(_) => Ok(()),
+        None => Err(Errno::BADF),
+    }
+}
+
+fn fd_sync<T>(
+    state: &mut WasiState,
+    _memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let fd = u32_arg(args, 0)?;
+
+    let fs = Arc::clone(&state.fs);
+    state.file(fd)?.sync(&*fs)
+}
+
+fn path_open<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, path, path_len, oflags) = (
+        u32_arg(args, 0)?,
+        u32_arg(args, 2)?,
+        u32_arg(args, 3)?,
+        u32_arg(args, 4)?,
+    );
+    let (fdflags, opened_fd) = (u32_arg(args, 7)?, u32_arg(args, 8)?);
+
+    let path = state.resolve(fd, &memory.read_str(path, path_len)?)?;
+
+    let create = (oflags & OFLAGS_CREAT) != 0;
+    let exclusive = create && (oflags & OFLAGS_EXCL) != 0;
+    let truncate = (oflags & OFLAGS_TRUNC) != 0;
+
+    let descriptor = match state.fs.metadata(&path) {
+        Ok(_) if exclusive => return Err(Errno::EXIST),
+        Ok(WasiMetadata {
+            file_type: WasiFileType::Directory,
+            ..
+        }) => {
+            if truncate {
+                return Err(Errno::ISDIR);
+            }
+
+            FileDescriptor::Directory {
+                path,
+                preopen: None,
+            }
+        },
+        Ok(_) if (oflags & OFLAGS_DIRECTORY) != 0 => return Err(Errno::NOTDIR),
+        Ok(_) => {
+            let contents = if truncate {
+                Vec::new()
+            } else {
+                state.fs.read_file(&path)?
+            };
+
+            FileDescriptor::File(OpenFile {
+                path,
+                contents,
+                position: 0,
+                append: (fdflags & FDFLAGS_APPEND) != 0,
+                dirty: truncate,
+            })
+        },
+        Err(WasiFsError::NotFound) if create => {
+            state.fs.write_file(&path, &[])?;
+
+            FileDescriptor::File(OpenFile {
+                path,
+                contents: Vec::new(),
+                position: 0,
+                append: (fdflags & FDFLAGS_APPEND) != 0,
+                dirty: false,
+            })
+        },
+        Err(err) => return Err(err.into()),
+    };
+
+    let fd = state.insert(descriptor);
+
+    if let Err(errno) = memory.write_u32(opened_fd, fd) {
+        state.fds.remove(&fd);
+        return Err(errno);
+    }
+
+    Ok(())
+}
+
+fn fd_read<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, iovs, iovs_len, nread) = (
+        u32_arg(args, 0)?,
+        u32_arg(args, 1)?,
+        u32_arg(args, 2)?,
+        u32_arg(args, 3)?,
+    );
+
+    let iovs = memory.read_iovs(iovs, iovs_len)?;
+
+    let mut read = 0_usize;
+
+    match state.fds.get_mut(&fd) {
+        Some(FileDescriptor::Stdin) => {
+            for (buf, buf_len) in iovs {
+                let remaining = state.stdin.get(state.stdin_position..).unwrap_or_default();
+                let chunk = &remaining[..remaining.len().min(buf_len as usize)];
+
+                memory.write(buf, chunk)?;
+                state.stdin_position += chunk.len();
+                read += chunk.len();
+
+                if chunk.len() < buf_len as usize {
+                    break;
+                }
+            }
+        },
+        Some(FileDescriptor::File(file)) => {
+            for (buf, buf_len) in iovs {
+                let remaining = file.contents.get(file.position..).unwrap_or_default();
+                let chunk = &remaining[..remaining.len().min(buf_len as usize)];
+
+                memory.write(buf, chunk)?;
+                file.position += chunk.len();
+                read += chunk.len();
+
+                if chunk.len() < buf_len as usize {
+                    break;
+                }
+            }
+        },
+        Some(FileDescriptor::Directory { .. }) => return Err(Errno::ISDIR),
+        Some(_) | None => return Err(Errno::BADF),
+    }
+
+    memory.write_u32(nread, u32::try_from(read).map_err(|_| Errno::INVAL)?)
+}
+
+fn fd_write<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, iovs, iovs_len, nwritten) = (
+        u32_arg(args, 0)?,
+        u32_arg(args, 1)?,
+        u32_arg(args, 2)?,
+        u32_arg(args, 3)?,
+    );
+
+    let mut bytes = Vec::new();
+    for (buf, buf_len) in memory.read_iovs(iovs, iovs_len)? {
+        if bytes.len().saturating_add(buf_len as usize) > MAX_FILE_SIZE {
+            return Err(Errno::FBIG);
+        }
+
+        bytes.extend_from_slice(&memory.read(buf, buf_len)?);
+    }
+
+    match state.fds.get_mut(&fd) {
+        Some(FileDescriptor::Stdout) => state.stdout.extend_from_slice(&bytes),
+        Some(FileDescriptor::Stderr) => state.stderr.extend_from_slice(&bytes),
+        Some(FileDescriptor::File(file)) => {
+            if file.append {
+                file.position = file.contents.len();
+            }
+
+            let end = file
+                .position
+                .checked_add(bytes.len())
+                .filter(|end| *end <= MAX_FILE_SIZE)
+                .ok_or(Errno::FBIG)?;
+            if file.contents.len() < end {
+                file.contents.resize(end, 0);
+            }
+
+            file.contents[file.position..end].copy_from_slice(&bytes);
+            file.position = end;
+            file.dirty = true;
+        },
+        Some(FileDescriptor::Directory { .. }) => return Err(Errno::ISDIR),
+        Some(FileDescriptor::Stdin) | None => return Err(Errno::BADF),
+    }
+
+    memory.write_u32(
+        nwritten,
+        u32::try_from(bytes.len()).map_err(|_| Errno::INVAL)?,
+    )
+}
+
+fn fd_seek<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, offset, whence, newoffset) = (
+        u32_arg(args, 0)?,
+        u64_arg(args, 1)?,
+        u32_arg(args, 2)?,
+        u32_arg(args, 3)?,
+    );
+
+    let file = state.file(fd)?;
+
+    let base = match whence {
+        0 => 0,
+        1 => file.position as u64,
+        2 => file.contents.len() as u64,
+        _ => return Err(Errno::INVAL),
+    };
+
+    #[allow(clippy::cast_possible_wrap)]
+    let position = base
+        .checked_add_signed(offset as i64)
+        .and_then(|position| usize::try_from(position).ok())
+        .filter(|position| *position <= MAX_FILE_SIZE)
+        .ok_or(Errno::INVAL)?;
+    file.position = position;
+
+    memory.write_u64(newoffset, position as u64)
+}
+
+fn fd_tell<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, offset) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    let position = state.file(fd)?.position;
+
+    memory.write_u64(offset, position as u64)
+}
+
+fn fd_filestat_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, buf) = (u32_arg(args, 0)?, u32_arg(args, 1)?);
+
+    let (filetype, size) = match state.fds.get(&fd) {
+        Some(FileDescriptor::Stdin | FileDescriptor::Stdout | FileDescriptor::Stderr) => {
+            (FILETYPE_CHARACTER_DEVICE, 0)
+        },
+        Some(FileDescriptor::Directory { path, .. }) => {
+            let metadata = state.fs.metadata(path)?;
+            (filetype(metadata), metadata.len)
+        },
+        Some(FileDescriptor::File(file)) => (FILETYPE_REGULAR_FILE, file.contents.len() as u64),
+        None => return Err(Errno::BADF),
+    };
+
+    memory.write_filestat(buf, filetype, size)
+}
+
+fn path_filestat_get<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, path, path_len, buf) = (
+        u32_arg(args, 0)?,
+        u32_arg(args, 2)?,
+        u32_arg(args, 3)?,
+        u32_arg(args, 4)?,
+    );
+
+    let path = state.resolve(fd, &memory.read_str(path, path_len)?)?;
+    let metadata = state.fs.metadata(&path)?;
+
+    memory.write_filestat(buf, filetype(metadata), metadata.len)
+}
+
+fn path_create_directory<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, path, path_len) = (u32_arg(args, 0)?, u32_arg(args, 1)?, u32_arg(args, 2)?);
+
+    let path = state.resolve(fd, &memory.read_str(path, path_len)?)?;
+
+    state.fs.create_dir(&path).map_err(Errno::from)
+}
+
+fn path_unlink_file<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, path, path_len) = (u32_arg(args, 0)?, u32_arg(args, 1)?, u32_arg(args, 2)?);
+
+    let path = state.resolve(fd, &memory.read_str(path, path_len)?)?;
+
+    state.fs.remove_file(&path).map_err(Errno::from)
+}
+
+fn path_remove_directory<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, path, path_len) = (u32_arg(args, 0)?, u32_arg(args, 1)?, u32_arg(args, 2)?);
+
+    let path = state.resolve(fd, &memory.read_str(path, path_len)?)?;
+
+    state.fs.remove_dir(&path).map_err(Errno::from)
+}
+
+fn fd_readdir<T>(
+    state: &mut WasiState,
+    memory: &mut GuestMemory<T>,
+    args: &[Value<Engine>],
+) -> Result<(), Errno> {
+    let (fd, buf, buf_len, cookie, bufused) = (
+        u32_arg(args, 0)?,
+        u32_arg(args, 1)?,
+        u32_arg(args, 2)?,
+        u64_arg(args, 3)?,
+        u32_arg(args, 4)?,
+    );
+
+    let path = match state.fds.get(&fd) {
+        Some(FileDescriptor::Directory { path, .. }) => path,
+        Some(_) => return Err(Errno::NOTDIR),
+        None => return Err(Errno::BADF),
+    };
+
+    let mut dirents = Vec::new();
+
+    for (index, (name, metadata)) in (0_u64..)
+        .zip(state.fs.read_dir(path)?)
+        .skip(usize::try_from(cookie).unwrap_or(usize::MAX))
+    {
+        if dirents.len() >= buf_len as usize {
+            break;
+        }
+
+        dirents.extend_from_slice(&(index + 1).to_le_bytes());
+        dirents.extend_from_slice(&(index + 1).to_le_bytes());
+        dirents.extend_from_slice(
+            &u32::try_from(name.len())
+                .map_err(|_| Errno::INVAL)?
+                .to_le_bytes(),
+        );
+        dirents.extend_from_slice(&[filetype(metadata), 0, 0, 0]);
+        dirents.extend_from_slice(name.as_bytes());
+    }
+
+    dirents.truncate(buf_len as usize);
+
+    memory.write(buf, &dirents)?;
+    memory.write_u32(
+        bufused,
+        u32::try_from(dirents.len()).map_err(|_| Errno::INVAL)?,
+    )
+}
+
+/// Creates the directory at the normalized `path` and all of its missing
+/// parents
+fn create_dir_all(fs: &dyn WasiFs, path: &str) -> Result<(), WasiFsError> {
+    let mut current = String::from("/");
+
+    for component in path.split('/').filter(|component| !component.is_empty()) {
+        current = join(&current, component);
+
+        match fs.create_dir(&current) {
+            Ok(()) => (),
+            Err(WasiFsError::AlreadyExists)
+                if matches!(
+                    fs.metadata(&current),
+                    Ok(WasiMetadata {
+                        file_type: WasiFileType::Directory,
+                        ..
+                    })
+                ) => {},
+            Err(WasiFsError::AlreadyExists) => return Err(WasiFsError::NotADirectory),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Normalizes the `path` into an absolute path without `.` or `..`
+/// components
+fn normalize(path: &str) -> String {
+    let mut components = Vec::new();
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            },
+            component => components.push(component),
+        }
+    }
+
+    components
+        .into_iter()
+        .fold(String::from("/"), |path, component| join(&path, component))
+}
+
+/// Joins the normalized `path` with the `component`
+fn join(path: &str, component: &str) -> String {
+    if path == "/" {
+        format!("/{component}")
+    } else {
+        format!("{path}/{component}")
+    }
+}
@@ -0,0 +1,512 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use pyo3::{intern, prelude::*, sync::GILOnceCell, types::PyBytes};
+use wasm_runtime_layer::{
+    backend::{AsContext, AsContextMut, Extern, Imports, Value, WasmMemory},
+    ValueType,
+};
+
+use crate::{
+    conversion::js_uint8_array_new,
+    http::{read_bytes, read_string, url_origin},
+    Engine, Func, FuncSignature, Instance, Memory,
+};
+
+/// A standard import set that lets guests use browser [`WebSocket`]s,
+/// restricted by a [`WebSocketPolicy`].
+///
+/// The sockets are owned by the import set, which closes them once they are
+/// closed by the guest or once the import set is dropped. Since incoming
+/// messages are queued instead of calling back into the guest, the import
+/// set provides a poll-based ABI in the `websocket` import module, where all
+/// pointers and lengths are `i32`s:
+///
+/// - `open(url_ptr, url_len) -> handle` connects to the `url` and returns the
+///   non-negative socket handle, or [`WebSocketImports::DENIED`] if the policy
+///   rejects it.
+/// - `ready_state(handle) -> state` returns the [`readyState`] of the socket,
+///   i.e. `0` while connecting, `1` when open, `2` while closing, and `3` once
+///   closed.
+/// - `send(handle, ptr, len) -> errno` sends the bytes at `ptr..ptr+len` as a
+///   binary message.
+/// - `peek_len(handle) -> len` returns the length of the next queued message,
+///   or [`WebSocketImports::NO_MESSAGE`] if there is none.
+/// - `recv(handle, ptr, len) -> read` removes the next queued message, copies
+///   up to `len` bytes of it to `ptr`, and returns the number of copied bytes.
+/// - `close(handle) -> errno` closes and releases the socket.
+///
+/// All functions return [`WebSocketImports::UNKNOWN_HANDLE`] for unknown
+/// handles. Text messages are queued as their UTF-8 bytes.
+///
+/// Since the guest's memory only exists after instantiation, it must be
+/// provided using [`WebSocketImports::bind_memory`] or
+/// [`WebSocketImports::bind_instance`] before the guest opens any sockets.
+///
+/// [`WebSocket`]: https://developer.mozilla.org/en-US/docs/Web/API/WebSocket
+/// [`readyState`]: https://developer.mozilla.org/en-US/docs/Web/API/WebSocket/readyState
+#[derive(Debug, Clone)]
+pub struct WebSocketImports {
+    /// The state of the import set, which is shared with its imports
+    state: Arc<Mutex<WebSocketState>>,
+}
+
+#[derive(Debug)]
+/// The shared state of a [`WebSocketImports`]
+struct WebSocketState {
+    /// The policy that sockets must satisfy
+    policy: WebSocketPolicy,
+    /// The guest memory through which messages are exchanged
+    memory: Option<Memory>,
+    /// The JavaScript wrappers of the open sockets, by handle
+    sockets: BTreeMap<i32, Py<PyAny>>,
+    /// The handle of the next socket
+    next_handle: i32,
+}
+
+impl WebSocketImports {
+    /// The error code of a socket that the policy denied
+    pub const DENIED: i32 = -1;
+    /// The error code of a socket that could not be opened or used
+    pub const FAILED: i32 = -3;
+    /// The import module name of the WebSocket import set
+    pub const MODULE: &'static str = "websocket";
+    /// The error code of a socket without any queued messages
+    pub const NO_MESSAGE: i32 = -5;
+    /// The error code of a message that exceeds the policy's maximum length
+    pub const TOO_LARGE: i32 = -4;
+    /// The error code of an unknown socket handle
+    pub const UNKNOWN_HANDLE: i32 = -2;
+
+    /// Creates a new WebSocket import set that enforces the `policy`.
+    #[must_use]
+    pub fn new(policy: WebSocketPolicy) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(WebSocketState {
+                policy,
+                memory: None,
+                sockets: BTreeMap::new(),
+                next_handle: 0,
+            })),
+        }
+    }
+
+    /// Defines the `websocket` imports in the `imports`.
+    #[allow(clippy::too_many_lines)]
+    pub fn define<T: 'static>(
+        &self,
+        mut ctx: impl AsContextMut<Engine, UserState = T>,
+        imports: &mut Imports<Engine>,
+    ) {
+        let state = Arc::clone(&self.state);
+        let open = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("open")
+                .param("url_ptr", ValueType::I32)
+                .param("url_len", ValueType::I32)
+                .result("handle", ValueType::I32)
+                .build(),
+            move |ctx, args, results| {
+                let [url_ptr, url_len] = args else {
+                    anyhow::bail!("websocket.open called with invalid arguments {args:?}");
+                };
+
+                let memory = lock(&state).memory()?;
+                let url = read_string(&memory, &ctx, url_ptr, url_len)?;
+
+                let handle = lock(&state).open(&url);
+                results[0] = Value::I32(handle);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let ready_state = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("ready_state")
+                .param("handle", ValueType::I32)
+                .result("state", ValueType::I32)
+                .build(),
+            move |_ctx, args, results| {
+                let [Value::I32(handle)] = args else {
+                    anyhow::bail!("websocket.ready_state called with invalid arguments {args:?}");
+                };
+
+                let ready_state = lock(&state).with_socket(*handle, |socket| {
+                    let py = socket.py();
+                    Ok(socket
+                        .getattr(intern!(py, "socket"))?
+                        .getattr(intern!(py, "readyState"))?
+                        .extract()?)
+                })?;
+                results[0] = Value::I32(ready_state);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let send = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("send")
+                .param("handle", ValueType::I32)
+                .param("ptr", ValueType::I32)
+                .param("len", ValueType::I32)
+                .result("errno", ValueType::I32)
+                .build(),
+            move |ctx, args, results| {
+                let [Value::I32(handle), ptr, len] = args else {
+                    anyhow::bail!("websocket.send called with invalid arguments {args:?}");
+                };
+
+                let memory = lock(&state).memory()?;
+                let message = read_bytes(&memory, &ctx, ptr, len)?;
+
+                let errno = lock(&state).with_socket(*handle, |socket| {
+                    let py = socket.py();
+
+                    let array = js_uint8_array_new(py)?.call1((message.len(),))?;
+                    array.call_method1(intern!(py, "assign"), (message.as_slice(),))?;
+
+                    Ok(match socket.call_method1(intern!(py, "send"), (array,)) {
+                        Ok(_) => 0,
+                        Err(_) => Self::FAILED,
+                    })
+                })?;
+                results[0] = Value::I32(errno);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let peek_len = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("peek_len")
+                .param("handle", ValueType::I32)
+                .result("len", ValueType::I32)
+                .build(),
+            move |_ctx, args, results| {
+                let [Value::I32(handle)] = args else {
+                    anyhow::bail!("websocket.peek_len called with invalid arguments {args:?}");
+                };
+
+                let state = lock(&state);
+                let max_message_len = state.policy.max_message_len;
+                let len = state.with_socket(*handle, |socket| {
+                    let py = socket.py();
+
+                    let message = socket.call_method0(intern!(py, "peek"))?;
+                    if message.is_none() {
+                        return Ok(Self::NO_MESSAGE);
+                    }
+
+                    let len: usize = message.getattr(intern!(py, "length"))?.extract()?;
+                    if max_message_len.is_some_and(|max| len > max) {
+                        return Ok(Self::TOO_LARGE);
+                    }
+
+                    Ok(i32::try_from(len).unwrap_or(i32::MAX))
+                })?;
+                drop(state);
+
+                results[0] = Value::I32(len);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let recv = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("recv")
+                .param("handle", ValueType::I32)
+                .param("ptr", ValueType::I32)
+                .param("len", ValueType::I32)
+                .result("read", ValueType::I32)
+                .build(),
+            move |mut ctx, args, results| {
+                let [Value::I32(handle), Value::I32(ptr), Value::I32(len)] = args else {
+                    anyhow::bail!("websocket.recv called with invalid arguments {args:?}");
+                };
+
+                let state = lock(&state);
+                let memory = state.memory()?;
+
+                #[allow(clippy::cast_sign_loss)]
+                let (ptr, len) = (*ptr as u32 as usize, *len as u32 as usize);
+
+                let read = state.with_socket(*handle, |socket| {
+                    let py = socket.py();
+
+                    let message = socket.call_method0(intern!(py, "shift"))?;
+                    if message.is_none() {
+                        return Ok(Self::NO_MESSAGE);
+                    }
+
+                    let message: Bound<PyBytes> =
+                        message.call_method0(intern!(py, "to_bytes"))?.extract()?;
+                    let message = message.as_bytes();
+                    let message = &message[..message.len().min(len)];
+
+                    memory.write(ctx.as_context_mut(), ptr, message)?;
+
+                    Ok(i32::try_from(message.len()).unwrap_or(i32::MAX))
+                })?;
+                drop(state);
+
+                results[0] = Value::I32(read);
+
+                Ok(())
+            },
+        );
+
+        let state = Arc::clone(&self.state);
+        let close = Func::new_with_signature(
+            ctx.as_context_mut(),
+            FuncSignature::builder()
+                .name("close")
+                .param("handle", ValueType::I32)
+                .result("errno", ValueType::I32)
+                .build(),
+            move |_ctx, args, results| {
+                let [Value::I32(handle)] = args else {
+                    anyhow::bail!("websocket.close called with invalid arguments {args:?}");
+                };
+
+                let socket = lock(&state).sockets.remove(handle);
+                results[0] = Value::I32(socket.map_or(Self::UNKNOWN_HANDLE, |socket| {
+                    Python::with_gil(|py| close_socket(py, &socket));
+                    0
+                }));
+
+                Ok(())
+            },
+        );
+
+        for (name, func) in [
+            ("open", open),
+            ("ready_state", ready_state),
+            ("send", send),
+            ("peek_len", peek_len),
+            ("recv", recv),
+            ("close", close),
+        ] {
+            imports.define(Self::MODULE, name, Extern::Func(func));
+        }
+    }
+
+    /// Binds the import set to the guest `memory` through which messages
+    /// are exchanged.
+    pub fn bind_memory(&self, memory: &Memory) {
+        lock(&self.state).memory = Some(memory.clone());
+    }
+
+    /// Binds the import set to the memory that the `instance` exports under
+    /// the `name`, e.g. `"memory"`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `instance` does not export a memory with the
+    /// `name`.
+    pub fn bind_instance(
+        &self,
+        ctx: impl AsContext<Engine>,
+        instance: &Instance,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let memory = instance.get_memory(ctx, name)?;
+
+        self.bind_memory(&memory);
+
+        Ok(())
+    }
+}
+
+impl WebSocketState {
+    fn memory(&self) -> anyhow::Result<Memory> {
+        self.memory.clone().ok_or_else(|| {
+            anyhow::anyhow!("websocket imports called before the guest memory was bound")
+        })
+    }
+
+    /// Opens a socket to the `url` if the policy allows it and returns its
+    /// handle
+    fn open(&mut self, url: &str) -> i32 {
+        if !self.policy.allows(url) {
+            return WebSocketImports::DENIED;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(url, "guest WebSocket open");
+
+        let Ok(socket) = Python::with_gil(|py| -> Result<Py<PyAny>, PyErr> {
+            Ok(js_web_socket(py)?.call1((url,))?.unbind())
+        }) else {
+            return WebSocketImports::FAILED;
+        };
+
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.checked_add(1).unwrap_or(0);
+        self.sockets.insert(handle, socket);
+
+        handle
+    }
+
+    /// Calls `f` with the JavaScript wrapper of the socket with the `handle`
+    fn with_socket(
+        &self,
+        handle: i32,
+        f: impl FnOnce(&Bound<PyAny>) -> anyhow::Result<i32>,
+    ) -> anyhow::Result<i32> {
+        let Some(socket) = self.sockets.get(&handle) else {
+            return Ok(WebSocketImports::UNKNOWN_HANDLE);
+        };
+
+        Python::with_gil(|py| f(socket.bind(py)))
+    }
+}
+
+impl Drop for WebSocketState {
+    fn drop(&mut self) {
+        if self.sockets.is_empty() {
+            return;
+        }
+
+        Python::with_gil(|py| {
+            for socket in self.sockets.values() {
+                close_socket(py, socket);
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The policy that the `WebSocket`s of guests must satisfy, see
+/// [`WebSocketImports`].
+///
+/// The default policy denies all sockets.
+pub struct WebSocketPolicy {
+    /// The allowed origins, or `None` if all origins are allowed
+    origins: Option<Vec<String>>,
+    /// The maximum length of a received message, if limited
+    max_message_len: Option<usize>,
+}
+
+impl Default for WebSocketPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebSocketPolicy {
+    /// Creates a new policy that denies all sockets.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            origins: Some(Vec::new()),
+            max_message_len: None,
+        }
+    }
+
+    /// Allows sockets to the `origin`, e.g. `"wss://example.com"`.
+    #[must_use]
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        if let Some(origins) = &mut self.origins {
+            origins.push(origin.trim_end_matches('/').to_ascii_lowercase());
+        }
+        self
+    }
+
+    /// Allows sockets to any origin.
+    #[must_use]
+    pub fn allow_any_origin(mut self) -> Self {
+        self.origins = None;
+        self
+    }
+
+    /// Limits the length of received messages that guests can read to
+    /// `max_message_len` bytes.
+    #[must_use]
+    pub const fn max_message_len(mut self, max_message_len: usize) -> Self {
+        self.max_message_len = Some(max_message_len);
+        self
+    }
+
+    /// Checks whether a socket to the `url` is allowed
+    fn allows(&self, url: &str) -> bool {
+        let Some(origins) = &self.origins else {
+            return true;
+        };
+
+        let allowed = url_origin(url).is_some_and(|origin| origins.contains(&origin));
+
+        #[cfg(feature = "tracing")]
+        if !allowed {
+            tracing::warn!(url, "denied guest WebSocket");
+        }
+
+        allowed
+    }
+}
+
+fn lock(state: &Mutex<WebSocketState>) -> MutexGuard<'_, WebSocketState> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Closes the `socket`, ignoring any errors since the socket is released
+fn close_socket(py: Python, socket: &Py<PyAny>) {
+    if let Err(err) = socket.bind(py).call_method0(intern!(py, "close")) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%err, "failed to close WebSocket");
+        #[cfg(not(feature = "tracing"))]
+        drop(err);
+    }
+}
+
+fn js_web_socket(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_WEB_SOCKET: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_WEB_SOCKET
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r"
+function webSocket(url) {
+    const socket = new WebSocket(url);
+    socket.binaryType = 'arraybuffer';
+    const queue = [];
+    const encoder = new TextEncoder();
+    socket.onmessage = (event) => {
+        queue.push(typeof event.data === 'string'
+            ? encoder.encode(event.data)
+            : new Uint8Array(event.data));
+    };
+    return {
+        socket,
+        send(message) { socket.send(message); },
+        peek() { return queue.length > 0 ? queue[0] : null; },
+        shift() { return queue.length > 0 ? queue.shift() : null; },
+        close() {
+            socket.onmessage = null;
+            queue.length = 0;
+            socket.close();
+        },
+    };
+}
+webSocket
+",))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
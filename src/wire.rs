@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use wasm_runtime_layer::{backend::Value, ValueType};
+
+use crate::{conversion::ValueExt, Engine, Error, ExternRef, Func};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+/// A serializable representation of a [`Value`], e.g. to ship arguments and
+/// results of guest calls over `postMessage` or WebSocket RPC layers.
+///
+/// Numeric values are serialized as-is. References cannot be serialized
+/// since they only exist inside the context that created them. Instead, the
+/// two reference strategies are:
+///
+/// - **error**: converting with [`WireValue::try_from`] and
+///   [`WireValue::try_into_value`] only supports null references and returns an
+///   [`Error::Conversion`] for any non-null reference.
+/// - **handle-id**: converting with [`RefHandles::to_wire`] and
+///   [`RefHandles::from_wire`] keeps non-null references alive in the
+///   [`RefHandles`] of the sending context and only ships their handle ids,
+///   which the other context can send back to refer to the same reference.
+///
+/// Null references are always serialized without a handle id.
+pub enum WireValue {
+    /// A 32-bit integer value
+    I32(i32),
+    /// A 64-bit integer value
+    I64(i64),
+    /// A 32-bit floating point value
+    F32(f32),
+    /// A 64-bit floating point value
+    F64(f64),
+    /// A function reference, identified by its optional handle id
+    FuncRef(Option<u64>),
+    /// An extern reference, identified by its optional handle id
+    ExternRef(Option<u64>),
+}
+
+impl WireValue {
+    /// Returns the type of this value.
+    #[must_use]
+    pub const fn ty(&self) -> ValueType {
+        match self {
+            Self::I32(_) => ValueType::I32,
+            Self::I64(_) => ValueType::I64,
+            Self::F32(_) => ValueType::F32,
+            Self::F64(_) => ValueType::F64,
+            Self::FuncRef(_) => ValueType::FuncRef,
+            Self::ExternRef(_) => ValueType::ExternRef,
+        }
+    }
+
+    /// Converts this value back into a [`Value`], using the error strategy
+    /// for references.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Conversion`] if this value is a non-null
+    /// reference, which must instead be resolved with
+    /// [`RefHandles::from_wire`].
+    pub fn try_into_value(self) -> Result<Value<Engine>, Error> {
+        match self {
+            Self::I32(v) => Ok(Value::I32(v)),
+            Self::I64(v) => Ok(Value::I64(v)),
+            Self::F32(v) => Ok(Value::F32(v)),
+            Self::F64(v) => Ok(Value::F64(v)),
+            Self::FuncRef(None) => Ok(Value::FuncRef(None)),
+            Self::ExternRef(None) => Ok(Value::ExternRef(None)),
+            Self::FuncRef(Some(handle)) | Self::ExternRef(Some(handle)) => {
+                Err(Error::Conversion(format!(
+                    "{} handle {handle} can only be resolved by its RefHandles",
+                    self.ty()
+                )))
+            },
+        }
+    }
+}
+
+impl TryFrom<&Value<Engine>> for WireValue {
+    type Error = Error;
+
+    /// Converts the `value`, using the error strategy for references.
+    fn try_from(value: &Value<Engine>) -> Result<Self, Self::Error> {
+        match value {
+            Value::I32(v) => Ok(Self::I32(*v)),
+            Value::I64(v) => Ok(Self::I64(*v)),
+            Value::F32(v) => Ok(Self::F32(*v)),
+            Value::F64(v) => Ok(Self::F64(*v)),
+            Value::FuncRef(None) => Ok(Self::FuncRef(None)),
+            Value::ExternRef(None) => Ok(Self::ExternRef(None)),
+            Value::FuncRef(Some(_)) | Value::ExternRef(Some(_)) => Err(Error::Conversion(format!(
+                "non-null {} cannot be serialized without RefHandles",
+                ValueExt::ty(value)
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A registry of the references that have been sent to another context as
+/// [`WireValue`] handle ids.
+///
+/// The registry keeps every sent reference alive until it is cleared, such
+/// that the handle ids which the other context sends back can still be
+/// resolved.
+pub struct RefHandles {
+    /// The sent function references, indexed by their handle id
+    funcs: Vec<Func>,
+    /// The sent extern references, indexed by their handle id
+    externs: Vec<ExternRef>,
+}
+
+impl RefHandles {
+    /// Creates a new empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts the `value`, registering any non-null reference under a new
+    /// handle id.
+    pub fn to_wire(&mut self, value: &Value<Engine>) -> WireValue {
+        match value {
+            Value::I32(v) => WireValue::I32(*v),
+            Value::I64(v) => WireValue::I64(*v),
+            Value::F32(v) => WireValue::F32(*v),
+            Value::F64(v) => WireValue::F64(*v),
+            Value::FuncRef(func) => WireValue::FuncRef(func.as_ref().map(|func| {
+                self.funcs.push(func.clone());
+                (self.funcs.len() - 1) as u64
+            })),
+            Value::ExternRef(extern_ref) => {
+                WireValue::ExternRef(extern_ref.as_ref().map(|extern_ref| {
+                    self.externs.push(extern_ref.clone());
+                    (self.externs.len() - 1) as u64
+                }))
+            },
+        }
+    }
+
+    /// Converts the `value` back into a [`Value`], resolving any reference
+    /// handle id that was registered by [`RefHandles::to_wire`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Conversion`] if a handle id is unknown to this
+    /// registry.
+    pub fn from_wire(&self, value: WireValue) -> Result<Value<Engine>, Error> {
+        let unknown = |handle| Error::Conversion(format!("unknown {} handle {handle}", value.ty()));
+
+        match value {
+            WireValue::FuncRef(Some(handle)) => usize::try_from(handle)
+                .ok()
+                .and_then(|index| self.funcs.get(index))
+                .map(|func| Value::FuncRef(Some(func.clone())))
+                .ok_or_else(|| unknown(handle)),
+            WireValue::ExternRef(Some(handle)) => usize::try_from(handle)
+                .ok()
+                .and_then(|index| self.externs.get(index))
+                .map(|extern_ref| Value::ExternRef(Some(extern_ref.clone())))
+                .ok_or_else(|| unknown(handle)),
+            value => value.try_into_value(),
+        }
+    }
+
+    /// Returns the number of registered references.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.funcs.len() + self.externs.len()
+    }
+
+    /// Returns `true` if no references are registered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.funcs.is_empty() && self.externs.is_empty()
+    }
+
+    /// Releases all registered references, which invalidates all handle ids
+    /// that have been handed out so far.
+    pub fn clear(&mut self) {
+        self.funcs.clear();
+        self.externs.clear();
+    }
+}
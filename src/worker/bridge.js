@@ -0,0 +1,119 @@
+// Worker-side bridge for the exports that are exposed to another JavaScript
+// context by `pyodide-webassembly-runtime-layer`'s `WorkerBridge`.
+//
+// Inside a worker, the bridge installs itself on the worker's global scope as
+// `self.wasmBridge`. Inside an audio worklet, call `wasmBridge(this.port)` in
+// the processor's constructor and use the returned bridge's `exports` once its
+// `ready` promise has resolved. If the module cannot be instantiated, the
+// `ready` promise rejects and the error is reported to the other context.
+function wasmBridge(port) {
+    let resolveReady;
+    let rejectReady;
+
+    const bridge = {
+        exports: null,
+        instance: null,
+        ready: new Promise((resolve, reject) => {
+            resolveReady = resolve;
+            rejectReady = reject;
+        }),
+    };
+    // the failure is reported to the other context, so it is handled
+    bridge.ready.catch(() => {});
+
+    port.onmessage = (event) => {
+        const message = event.data;
+
+        switch (message.type) {
+            case "init": {
+                let instance;
+                try {
+                    instance = new WebAssembly.Instance(message.module, message.imports);
+                } catch (err) {
+                    port.postMessage({
+                        type: "failed",
+                        name: (err instanceof Error) ? err.name : "Error",
+                        message: (err instanceof Error) ? err.message : String(err),
+                    });
+                    rejectReady(err);
+                    break;
+                }
+
+                bridge.instance = instance;
+                bridge.exports = Object.fromEntries(
+                    message.exports.map((name) => [name, instance.exports[name]]),
+                );
+
+                port.postMessage({ type: "ready" });
+                resolveReady(bridge.exports);
+                break;
+            }
+            case "call": {
+                try {
+                    const func = (bridge.exports === null) ? undefined : bridge.exports[message.name];
+
+                    if (typeof func !== "function") {
+                        throw new Error(`export ${message.name} is not bridged`);
+                    }
+
+                    const result = func(...message.args);
+                    const results = (result === undefined) ? [] : (
+                        Array.isArray(result) ? result : [result]
+                    );
+
+                    port.postMessage({ type: "result", id: message.id, results });
+                } catch (err) {
+                    port.postMessage({ type: "error", id: message.id, message: String(err) });
+                }
+                break;
+            }
+            case "write": {
+                try {
+                    const memory = exportedMemory(bridge, message.memory);
+
+                    new Uint8Array(memory.buffer, message.offset, message.bytes.byteLength)
+                        .set(new Uint8Array(message.bytes));
+
+                    port.postMessage({ type: "result", id: message.id, results: [] });
+                } catch (err) {
+                    port.postMessage({ type: "error", id: message.id, message: String(err) });
+                }
+                break;
+            }
+            case "read": {
+                try {
+                    const memory = exportedMemory(bridge, message.memory);
+
+                    if ((message.offset + message.len) > memory.buffer.byteLength) {
+                        throw new RangeError(`read of ${message.len} bytes at ${message.offset} is out of bounds`);
+                    }
+
+                    // transfer the copied bytes back instead of copying them again
+                    const bytes = memory.buffer.slice(message.offset, message.offset + message.len);
+                    const transfer = (bytes instanceof ArrayBuffer) ? [bytes] : [];
+
+                    port.postMessage({ type: "bytes", id: message.id, bytes }, transfer);
+                } catch (err) {
+                    port.postMessage({ type: "error", id: message.id, message: String(err) });
+                }
+                break;
+            }
+        }
+    };
+
+    return bridge;
+}
+
+function exportedMemory(bridge, name) {
+    const memory = (bridge.instance === null) ? undefined : bridge.instance.exports[name];
+
+    if (!(memory instanceof WebAssembly.Memory)) {
+        throw new Error(`export ${name} is not a memory`);
+    }
+
+    return memory;
+}
+
+if ((typeof WorkerGlobalScope !== "undefined") && (self instanceof WorkerGlobalScope)) {
+    self.wasmBridge = wasmBridge(self);
+}
@@ -0,0 +1,190 @@
+use pyo3::{intern, prelude::*, sync::GILOnceCell};
+use wasm_runtime_layer::{backend::WasmModule, ExternType, ImportType};
+
+mod remote;
+
+pub use remote::{PendingTransfer, RemoteInstance};
+
+use crate::{
+    capabilities::check_cross_origin_isolation,
+    conversion::{create_js_object, instanceof, ToPy},
+    Memory, Module,
+};
+
+/// A bridge that exposes selected function exports of a [`Module`] to another
+/// JavaScript context, e.g. a web worker or an audio worklet.
+///
+/// The other context must run the [`WorkerBridge::WORKER_SCRIPT`], which
+/// instantiates the module once it receives it through
+/// [`WorkerBridge::attach`] and then answers `postMessage`-based calls to the
+/// bridged exports. Since host functions cannot be transferred to another
+/// context, the module may only import memories, which must be shared so that
+/// both contexts can access the same contents.
+#[derive(Debug, Clone)]
+pub struct WorkerBridge {
+    /// The module that is instantiated in the other context
+    module: Module,
+    /// The names of the bridged function exports
+    exports: Vec<String>,
+    /// The memories that are provided as imports, by module and name
+    memories: Vec<(String, String, Memory)>,
+}
+
+impl WorkerBridge {
+    /// The JavaScript source code that must be run inside the other context.
+    ///
+    /// Inside a web worker, the script installs the bridge on the worker's
+    /// global scope. Inside an audio worklet, the processor's constructor
+    /// should call `wasmBridge(this.port)` and use the `exports` of the
+    /// returned bridge once its `ready` promise has resolved.
+    pub const WORKER_SCRIPT: &'static str = include_str!("bridge.js");
+
+    /// Creates a new bridge for the function `exports` of the `module`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the `exports` is not a function export of
+    /// the `module`.
+    pub fn new(module: &Module, exports: &[&str]) -> anyhow::Result<Self> {
+        for name in exports {
+            match module.get_export(name) {
+                Some(ExternType::Func(_)) => (),
+                Some(ty) => {
+                    anyhow::bail!("export `{name}` is not a function but {ty:?}")
+                },
+                None => anyhow::bail!("module has no export named `{name}`"),
+            }
+        }
+
+        Ok(Self {
+            module: module.clone(),
+            exports: exports.iter().map(|name| String::from(*name)).collect(),
+            memories: Vec::new(),
+        })
+    }
+
+    /// Provides the shared `memory` as the `module`.`name` import.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the module has no memory import with this name.
+    pub fn with_memory(
+        mut self,
+        module: &str,
+        name: &str,
+        memory: &Memory,
+    ) -> anyhow::Result<Self> {
+        if !self.module.imports().any(|import| {
+            import.module == module
+                && import.name == name
+                && matches!(import.ty, ExternType::Memory(_))
+        }) {
+            anyhow::bail!("module has no memory import named `{module}`.`{name}`");
+        }
+
+        self.memories
+            .push((String::from(module), String::from(name), memory.clone()));
+
+        Ok(self)
+    }
+
+    /// Sends the module and its memories through the `port`, e.g. a
+    /// `Worker` or `MessagePort`, to the other context.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the module has imports that are not provided
+    /// memories, if any provided memory is not shared, or if posting the
+    /// message fails.
+    pub fn attach(&self, port: &Bound<PyAny>) -> anyhow::Result<()> {
+        let py = port.py();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(port = %port, exports = ?self.exports, "WorkerBridge::attach");
+
+        if let Some(ImportType { module, name, .. }) = self.module.imports().find(|import| {
+            !self
+                .memories
+                .iter()
+                .any(|(module, name, _)| (import.module == module) && (import.name == name))
+        }) {
+            anyhow::bail!(
+                "import `{module}`.`{name}` cannot be provided in another context, only shared \
+                 memories can be imported"
+            );
+        }
+
+        let imports = create_js_object(py)?;
+
+        for (module, name, memory) in &self.memories {
+            let memory = memory.to_py(py);
+            let buffer = memory.bind(py).getattr(intern!(py, "buffer"))?;
+
+            if !instanceof(&buffer, js_shared_array_buffer(py)?, "SharedArrayBuffer")? {
+                check_cross_origin_isolation(py, "sharing a memory with another context")?;
+
+                anyhow::bail!(
+                    "memory import `{module}`.`{name}` must be shared to be accessible from \
+                     another context"
+                );
+            }
+
+            if !imports.hasattr(module.as_str())? {
+                imports.setattr(module.as_str(), create_js_object(py)?)?;
+            }
+            imports
+                .getattr(module.as_str())?
+                .setattr(name.as_str(), memory)?;
+        }
+
+        let message = create_js_object(py)?;
+        message.setattr(intern!(py, "type"), intern!(py, "init"))?;
+        message.setattr(intern!(py, "module"), self.module.module(py))?;
+        message.setattr(intern!(py, "imports"), imports)?;
+        message.setattr(
+            intern!(py, "exports"),
+            js_array_from(py)?.call1((self.exports.clone(),))?,
+        )?;
+
+        port.call_method1(intern!(py, "postMessage"), (message,))?;
+
+        Ok(())
+    }
+
+    /// Attaches the bridge to the `port`, see [`WorkerBridge::attach`], and
+    /// returns a [`RemoteInstance`] through which the bridged exports can be
+    /// called from this context.
+    ///
+    /// The `port` must not be used for other messages since the returned
+    /// remote instance handles all messages that it receives.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if attaching the bridge fails.
+    pub fn connect(&self, port: &Bound<PyAny>) -> anyhow::Result<RemoteInstance> {
+        let exports = self
+            .exports
+            .iter()
+            .filter_map(|name| match self.module.get_export(name) {
+                Some(ExternType::Func(ty)) => Some((name.as_str(), ty)),
+                _ => None,
+            });
+
+        // listen for the ready message before the module is sent
+        let remote = RemoteInstance::connect(port, exports)?;
+
+        self.attach(port)?;
+
+        Ok(remote)
+    }
+}
+
+fn js_shared_array_buffer(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_SHARED_ARRAY_BUFFER: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_SHARED_ARRAY_BUFFER.import(py, "js", "SharedArrayBuffer")
+}
+
+fn js_array_from(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_ARRAY_FROM: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    JS_ARRAY_FROM.import(py, "js.Array", "from")
+}
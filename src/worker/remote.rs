@@ -0,0 +1,475 @@
+use std::sync::Arc;
+
+use fxhash::FxHashMap;
+use pyo3::{
+    intern,
+    prelude::*,
+    sync::GILOnceCell,
+    types::{PyBytes, PyList},
+};
+use wasm_runtime_layer::{backend::Value, ExternType, FuncType};
+
+use super::js_array_from;
+use crate::{
+    conversion::{js_uint8_array_new, ToPy},
+    func::pyodide_run_sync,
+    Engine, Error, FuncSignature, PendingCall,
+};
+
+/// A proxy for an instance that lives in another JavaScript context, e.g. a
+/// web worker, and that has been created there by a [`WorkerBridge`].
+///
+/// A remote instance implements a subset of the instance API: it lists the
+/// bridged function exports and forwards calls to them via `postMessage`.
+/// Since the other context answers asynchronously, every call returns a
+/// [`PendingCall`], which must be awaited from Python or waited upon.
+///
+/// Only numeric values, null references, and structured-cloneable extern
+/// references, see [`ExternRef::is_cloneable`], can be sent to the other
+/// context. Extern references that are received from the other context are
+/// opaque. Large byte buffers can be transferred into and out of
+/// the remote instance's exported memories without extra copies using
+/// [`RemoteInstance::write_memory`] and [`RemoteInstance::read_memory`].
+///
+/// [`WorkerBridge`]: crate::WorkerBridge
+/// [`ExternRef::is_cloneable`]: crate::ExternRef::is_cloneable
+#[derive(Debug)]
+pub struct RemoteInstance {
+    /// The JavaScript proxy that forwards calls through the port
+    remote: Py<PyAny>,
+    /// The signatures of the bridged function exports, by name
+    exports: Arc<FxHashMap<String, FuncSignature>>,
+}
+
+impl Clone for RemoteInstance {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| Self {
+            remote: self.remote.clone_ref(py),
+            exports: Arc::clone(&self.exports),
+        })
+    }
+}
+
+impl RemoteInstance {
+    /// The maximum number of bytes that are transferred in a single message
+    /// by [`RemoteInstance::write_memory`] and [`RemoteInstance::read_memory`]
+    pub const TRANSFER_CHUNK_SIZE: usize = 1 << 22;
+
+    /// Creates a new remote instance that forwards calls through the `port`,
+    /// which must be connected before the bridge is attached to it
+    pub(crate) fn connect<'a>(
+        port: &Bound<PyAny>,
+        exports: impl IntoIterator<Item = (&'a str, FuncType)>,
+    ) -> Result<Self, PyErr> {
+        let remote = js_remote_instance(port.py())?.call1((port,))?;
+
+        Ok(Self {
+            remote: remote.unbind(),
+            exports: Arc::new(
+                exports
+                    .into_iter()
+                    .map(|(name, ty)| (String::from(name), FuncSignature::from(ty)))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Returns a JavaScript promise, which is awaitable from Python, that
+    /// resolves once the other context has instantiated the module.
+    ///
+    /// The promise rejects if the other context fails to instantiate the
+    /// module, e.g. since a provided memory does not match its import or the
+    /// start function traps. All calls and transfers then fail with the same
+    /// error.
+    ///
+    /// Since the other context handles messages in order, calls can already
+    /// be started before the instance is ready.
+    #[must_use]
+    pub fn ready(&self, py: Python) -> Py<PyAny> {
+        self.remote
+            .bind(py)
+            .getattr(intern!(py, "ready"))
+            .map_or_else(|_| py.None(), Bound::unbind)
+    }
+
+    /// Blocks until the other context has instantiated the module, see
+    /// [`RemoteInstance::ready`].
+    ///
+    /// Blocking requires `pyodide.ffi.run_sync`, see [`PendingCall::wait`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if blocking is not supported in the current context
+    /// or if the other context fails to instantiate the module.
+    pub fn wait_ready(&self) -> anyhow::Result<()> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("RemoteInstance::wait_ready").entered();
+
+            let run_sync = pyodide_run_sync(py).map_err(|err| {
+                Error::EnvironmentUnavailable(format!(
+                    "blocking on a remote instance requires pyodide.ffi.run_sync: {err}"
+                ))
+            })?;
+
+            let ready = self.remote.bind(py).getattr(intern!(py, "ready"))?;
+
+            run_sync
+                .call1((ready,))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::Link))?;
+
+            Ok(())
+        })
+    }
+
+    /// Returns the names and types of the bridged function exports.
+    pub fn exports(&self) -> impl Iterator<Item = (&str, ExternType)> {
+        self.exports
+            .iter()
+            .map(|(name, signature)| (name.as_str(), ExternType::Func(signature.ty().clone())))
+    }
+
+    /// Returns the type of the bridged function export with the `name`, if
+    /// it exists.
+    #[must_use]
+    pub fn get_export(&self, name: &str) -> Option<ExternType> {
+        self.exports
+            .get(name)
+            .map(|signature| ExternType::Func(signature.ty().clone()))
+    }
+
+    /// Starts a call of the bridged function export with the `name` and the
+    /// `args` in the other context.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no bridged function export with the
+    /// `name`, if the `args` do not match its signature, if any argument is
+    /// a non-null reference that is not a structured-cloneable extern
+    /// reference, or if posting the call fails.
+    pub fn call(&self, name: &str, args: &[Value<Engine>]) -> anyhow::Result<PendingCall<'static>> {
+        let Some(signature) = self.exports.get(name) else {
+            anyhow::bail!("remote instance has no bridged export named `{name}`");
+        };
+
+        signature.validate_args(args)?;
+
+        if let Some(index) = args.iter().position(|arg| match arg {
+            Value::FuncRef(Some(_)) => true,
+            Value::ExternRef(Some(extern_ref)) => !extern_ref.is_cloneable(),
+            _ => false,
+        }) {
+            anyhow::bail!(Error::Conversion(format!(
+                "{} of {signature} cannot be sent to another context since it is a non-null \
+                 reference that is not structured-cloneable",
+                signature.describe_param(index)
+            )));
+        }
+
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("RemoteInstance::call", name, ?args).entered();
+
+            let args = PyList::new(
+                py,
+                args.iter().map(|arg| match arg {
+                    Value::ExternRef(Some(extern_ref)) => extern_ref
+                        .to_cloneable(py)
+                        .unwrap_or_else(|| extern_ref.to_py(py)),
+                    arg => arg.to_py(py),
+                }),
+            )?;
+            let args = js_array_from(py)?.call1((args,))?;
+
+            let promise = self
+                .remote
+                .bind(py)
+                .call_method1(intern!(py, "call"), (name, args))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::Trap))?;
+
+            Ok(PendingCall::new(promise.unbind(), signature.clone()))
+        })
+    }
+
+    /// Starts writing the `bytes` into the memory that the remote instance
+    /// exports under the `memory` name, starting at the `offset`.
+    ///
+    /// The bytes are copied into JavaScript buffers of at most
+    /// [`RemoteInstance::TRANSFER_CHUNK_SIZE`] bytes, which are then
+    /// transferred to the other context instead of being copied again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes cannot be copied or posting the write
+    /// fails.
+    pub fn write_memory(
+        &self,
+        memory: &str,
+        offset: usize,
+        bytes: &[u8],
+    ) -> anyhow::Result<PendingTransfer> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!(
+                "RemoteInstance::write_memory",
+                memory,
+                offset,
+                len = bytes.len()
+            )
+            .entered();
+
+            let chunks = bytes
+                .chunks(Self::TRANSFER_CHUNK_SIZE)
+                .map(|chunk| -> Result<_, PyErr> {
+                    let array = js_uint8_array_new(py)?.call1((chunk.len(),))?;
+                    array.call_method1(intern!(py, "assign"), (chunk,))?;
+                    array.getattr(intern!(py, "buffer"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let chunks = js_array_from(py)?.call1((PyList::new(py, chunks)?,))?;
+
+            let promise = self
+                .remote
+                .bind(py)
+                .call_method1(
+                    intern!(py, "write"),
+                    (memory, offset, chunks, Self::TRANSFER_CHUNK_SIZE),
+                )
+                .map_err(|err| Error::from_js_exception(py, &err, Error::Trap))?;
+
+            Ok(PendingTransfer {
+                promise: promise.unbind(),
+                len: 0,
+            })
+        })
+    }
+
+    /// Starts reading `len` bytes from the memory that the remote instance
+    /// exports under the `memory` name, starting at the `offset`.
+    ///
+    /// The other context copies the bytes into JavaScript buffers of at most
+    /// [`RemoteInstance::TRANSFER_CHUNK_SIZE`] bytes, which are then
+    /// transferred back instead of being copied again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if posting the read fails.
+    pub fn read_memory(
+        &self,
+        memory: &str,
+        offset: usize,
+        len: usize,
+    ) -> anyhow::Result<PendingTransfer> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span =
+                tracing::debug_span!("RemoteInstance::read_memory", memory, offset, len).entered();
+
+            let promise = self
+                .remote
+                .bind(py)
+                .call_method1(
+                    intern!(py, "read"),
+                    (memory, offset, len, Self::TRANSFER_CHUNK_SIZE),
+                )
+                .map_err(|err| Error::from_js_exception(py, &err, Error::Trap))?;
+
+            Ok(PendingTransfer {
+                promise: promise.unbind(),
+                len,
+            })
+        })
+    }
+}
+
+/// An asynchronous memory transfer to or from a [`RemoteInstance`] that has
+/// been started with [`RemoteInstance::write_memory`] or
+/// [`RemoteInstance::read_memory`].
+#[derive(Debug)]
+pub struct PendingTransfer {
+    /// The JavaScript promise that settles once the transfer has completed
+    promise: Py<PyAny>,
+    /// The number of bytes that are read
+    len: usize,
+}
+
+impl PendingTransfer {
+    /// Returns the JavaScript promise of the transfer, which is awaitable
+    /// from Python.
+    ///
+    /// The value that the awaitable resolves to can be converted into the
+    /// transferred bytes using [`PendingTransfer::finish`].
+    #[must_use]
+    pub fn as_awaitable(&self, py: Python) -> Py<PyAny> {
+        self.promise.clone_ref(py)
+    }
+
+    /// Converts the `resolved` value of the awaited promise, see
+    /// [`PendingTransfer::as_awaitable`], into the bytes that were read.
+    ///
+    /// Completed writes produce no bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the resolved value does not contain the expected
+    /// number of bytes.
+    pub fn finish(&self, resolved: &Bound<PyAny>) -> anyhow::Result<Vec<u8>> {
+        let py = resolved.py();
+
+        let mut bytes = Vec::with_capacity(self.len);
+
+        if !resolved.is_none() {
+            for chunk in resolved.try_iter()? {
+                let chunk: Bound<PyBytes> = js_uint8_array_new(py)?
+                    .call1((chunk?,))?
+                    .call_method0(intern!(py, "to_bytes"))?
+                    .extract()?;
+                bytes.extend_from_slice(chunk.as_bytes());
+            }
+        }
+
+        if bytes.len() != self.len {
+            anyhow::bail!(Error::Conversion(format!(
+                "transfer produced {} bytes but {} were expected",
+                bytes.len(),
+                self.len
+            )));
+        }
+
+        Ok(bytes)
+    }
+
+    /// Blocks until the transfer has completed and returns the bytes that
+    /// were read.
+    ///
+    /// Blocking requires `pyodide.ffi.run_sync`, see [`PendingCall::wait`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if blocking is not supported in the current context,
+    /// if the transfer fails, or if its bytes cannot be converted.
+    pub fn wait(self) -> anyhow::Result<Vec<u8>> {
+        Python::with_gil(|py| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("PendingTransfer::wait", len = self.len).entered();
+
+            let run_sync = pyodide_run_sync(py).map_err(|err| {
+                Error::EnvironmentUnavailable(format!(
+                    "blocking on an asynchronous transfer requires pyodide.ffi.run_sync: {err}"
+                ))
+            })?;
+
+            let resolved = run_sync
+                .call1((self.promise.bind(py),))
+                .map_err(|err| Error::from_js_exception(py, &err, Error::Trap))?;
+
+            self.finish(&resolved)
+        })
+    }
+}
+
+fn js_remote_instance(py: Python<'_>) -> Result<&Bound<'_, PyAny>, PyErr> {
+    static JS_REMOTE_INSTANCE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+    JS_REMOTE_INSTANCE
+        .get_or_try_init(py, || {
+            Ok(py
+                .import(intern!(py, "pyodide"))?
+                .getattr(intern!(py, "code"))?
+                .getattr(intern!(py, "run_js"))?
+                .call1((r#"
+function remoteInstance(port) {
+    const pending = new Map();
+    let nextId = 0;
+    let resolveReady;
+    let rejectReady;
+    // the error with which the other context failed to instantiate the module
+    let failure = null;
+
+    function request(message, transfer) {
+        if (failure !== null) {
+            return Promise.reject(failure);
+        }
+
+        const id = nextId++;
+        return new Promise((resolve, reject) => {
+            pending.set(id, { resolve, reject });
+            port.postMessage({ ...message, id }, transfer);
+        });
+    }
+
+    const remote = {
+        ready: new Promise((resolve, reject) => {
+            resolveReady = resolve;
+            rejectReady = reject;
+        }),
+        call(name, args) {
+            return request({ type: "call", name, args }, []);
+        },
+        write(memory, offset, chunks, chunkSize) {
+            // transfer each chunk's buffer instead of copying it
+            return Promise.all(Array.from(chunks, (bytes, i) => request(
+                { type: "write", memory, offset: offset + (i * chunkSize), bytes }, [bytes],
+            ))).then(() => undefined);
+        },
+        read(memory, offset, len, chunkSize) {
+            const chunks = [];
+            for (let start = 0; start < len; start += chunkSize) {
+                chunks.push(request({
+                    type: "read", memory, offset: offset + start,
+                    len: Math.min(chunkSize, len - start),
+                }, []));
+            }
+            return Promise.all(chunks);
+        },
+    };
+
+    port.onmessage = (event) => {
+        const message = event.data;
+
+        switch (message.type) {
+            case "ready": {
+                resolveReady();
+                break;
+            }
+            case "failed": {
+                failure = new Error(message.message);
+                // keep the name, e.g. LinkError, to classify the failure
+                failure.name = message.name;
+                rejectReady(failure);
+                break;
+            }
+            case "result": {
+                const call = pending.get(message.id);
+                pending.delete(message.id);
+                const results = message.results;
+                call.resolve((results.length === 0) ? undefined : (
+                    (results.length === 1) ? results[0] : results
+                ));
+                break;
+            }
+            case "bytes": {
+                const call = pending.get(message.id);
+                pending.delete(message.id);
+                call.resolve(message.bytes);
+                break;
+            }
+            case "error": {
+                const call = pending.get(message.id);
+                pending.delete(message.id);
+                // requests that were sent before the failure was known
+                // fail with it instead of the missing export
+                call.reject(failure ?? new WebAssembly.RuntimeError(message.message));
+                break;
+            }
+        }
+    };
+
+    return remote;
+}
+remoteInstance
+"#,))?
+                .unbind())
+        })
+        .map(|x| x.bind(py))
+}
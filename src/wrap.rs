@@ -0,0 +1,209 @@
+use wasm_runtime_layer::{
+    backend::{AsContextMut, Value},
+    FuncType, ValueType,
+};
+
+use crate::{store::StoreContextMut, Engine, ExternRef, Func};
+
+impl Func {
+    /// Creates a new host function from a Rust closure, whose [`FuncType`] is
+    /// inferred from the types of its parameters and results.
+    ///
+    /// The closure receives the store context, followed by its parameters,
+    /// which must implement [`WasmTy`]. It may return nothing, a single
+    /// [`WasmTy`], or a tuple of them, optionally wrapped in an
+    /// [`anyhow::Result`] to trap. The type of the store context parameter
+    /// must be annotated, e.g.
+    ///
+    /// ```rust,ignore
+    /// let add = Func::wrap(
+    ///     store.as_context_mut(),
+    ///     |_caller: StoreContextMut<()>, a: i32, b: i64| -> f32 { (i64::from(a) + b) as f32 },
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host function cannot be created, like
+    /// [`Func::new_with_signature`].
+    pub fn wrap<T, Params, Results>(
+        ctx: impl AsContextMut<Engine, UserState = T>,
+        func: impl IntoFunc<T, Params, Results>,
+    ) -> Self {
+        func.into_func(ctx)
+    }
+}
+
+/// A Rust type that corresponds to a WASM [`ValueType`], which can be used as
+/// a parameter or result of a host function created with [`Func::wrap`].
+///
+/// This trait is implemented for [`i32`], [`i64`], [`f32`], [`f64`],
+/// `Option<Func>` for `funcref`s, and `Option<ExternRef>` for `externref`s.
+pub trait WasmTy: private::Sealed + Sized {
+    /// The WASM value type that corresponds to this Rust type
+    const TYPE: ValueType;
+
+    #[doc(hidden)]
+    fn from_value(value: &Value<Engine>) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn into_value(self) -> Value<Engine>;
+}
+
+macro_rules! impl_wasm_ty {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl private::Sealed for $ty {}
+
+            impl WasmTy for $ty {
+                const TYPE: ValueType = ValueType::$variant;
+
+                fn from_value(value: &Value<Engine>) -> Option<Self> {
+                    match value {
+                        Value::$variant(value) => Some(value.clone()),
+                        _ => None,
+                    }
+                }
+
+                fn into_value(self) -> Value<Engine> {
+                    Value::$variant(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_wasm_ty! {
+    i32 => I32, i64 => I64, f32 => F32, f64 => F64,
+    Option<Func> => FuncRef, Option<ExternRef> => ExternRef
+}
+
+/// The results of a host function created with [`Func::wrap`], i.e. nothing,
+/// a single [`WasmTy`], or a tuple of them.
+pub trait WasmResults: private::Sealed {
+    #[doc(hidden)]
+    fn types() -> Vec<ValueType>;
+
+    #[doc(hidden)]
+    fn store(self, results: &mut [Value<Engine>]);
+}
+
+impl<R: WasmTy> WasmResults for R {
+    fn types() -> Vec<ValueType> {
+        vec![R::TYPE]
+    }
+
+    fn store(self, results: &mut [Value<Engine>]) {
+        if let [result] = results {
+            *result = self.into_value();
+        }
+    }
+}
+
+/// The return type of a host function created with [`Func::wrap`], i.e.
+/// [`WasmResults`], optionally wrapped in an [`anyhow::Result`] to trap.
+pub trait HostFuncResult: private::Sealed {
+    #[doc(hidden)]
+    fn types() -> Vec<ValueType>;
+
+    #[doc(hidden)]
+    fn into_results(self, results: &mut [Value<Engine>]) -> anyhow::Result<()>;
+}
+
+impl<R: WasmResults> HostFuncResult for R {
+    fn types() -> Vec<ValueType> {
+        R::types()
+    }
+
+    fn into_results(self, results: &mut [Value<Engine>]) -> anyhow::Result<()> {
+        self.store(results);
+        Ok(())
+    }
+}
+
+impl<R: WasmResults> private::Sealed for anyhow::Result<R> {}
+
+impl<R: WasmResults> HostFuncResult for anyhow::Result<R> {
+    fn types() -> Vec<ValueType> {
+        R::types()
+    }
+
+    fn into_results(self, results: &mut [Value<Engine>]) -> anyhow::Result<()> {
+        self?.store(results);
+        Ok(())
+    }
+}
+
+/// A Rust closure that can be converted into a host function with
+/// [`Func::wrap`], whose [`FuncType`] is inferred from the `Params` and
+/// `Results` types.
+///
+/// This trait is implemented for closures that take the store context
+/// followed by up to eight [`WasmTy`] parameters, and that return a
+/// [`HostFuncResult`].
+pub trait IntoFunc<T, Params, Results> {
+    #[doc(hidden)]
+    fn into_func(self, ctx: impl AsContextMut<Engine, UserState = T>) -> Func;
+}
+
+macro_rules! impl_wasm_results_and_into_func {
+    ($($param:ident),*) => {
+        impl<$($param: WasmTy),*> private::Sealed for ($($param,)*) {}
+
+        impl<$($param: WasmTy),*> WasmResults for ($($param,)*) {
+            fn types() -> Vec<ValueType> {
+                vec![$($param::TYPE),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn store(self, results: &mut [Value<Engine>]) {
+                let ($($param,)*) = self;
+                let values: Vec<Value<Engine>> = vec![$($param.into_value()),*];
+
+                for (result, value) in results.iter_mut().zip(values) {
+                    *result = value;
+                }
+            }
+        }
+
+        impl<T, F, R, $($param: WasmTy),*> IntoFunc<T, ($($param,)*), R> for F
+        where
+            F: 'static + Send + Sync + Fn(StoreContextMut<T>, $($param),*) -> R,
+            R: HostFuncResult,
+        {
+            #[allow(non_snake_case)]
+            fn into_func(self, ctx: impl AsContextMut<Engine, UserState = T>) -> Func {
+                let ty = FuncType::new([$($param::TYPE),*], R::types());
+
+                Func::new_with_signature(ctx, ty.into(), move |store, args, results| {
+                    // the arguments have already been checked against the
+                    //  function type, so the conversions cannot fail
+                    let [$($param),*] = args else {
+                        anyhow::bail!("host func called with {} arguments", args.len());
+                    };
+                    $(
+                        let $param = $param::from_value($param).ok_or_else(|| {
+                            anyhow::anyhow!("host func called with a mistyped argument")
+                        })?;
+                    )*
+
+                    self(store, $($param),*).into_results(results)
+                })
+            }
+        }
+    };
+}
+
+impl_wasm_results_and_into_func!();
+impl_wasm_results_and_into_func!(A1);
+impl_wasm_results_and_into_func!(A1, A2);
+impl_wasm_results_and_into_func!(A1, A2, A3);
+impl_wasm_results_and_into_func!(A1, A2, A3, A4);
+impl_wasm_results_and_into_func!(A1, A2, A3, A4, A5);
+impl_wasm_results_and_into_func!(A1, A2, A3, A4, A5, A6);
+impl_wasm_results_and_into_func!(A1, A2, A3, A4, A5, A6, A7);
+impl_wasm_results_and_into_func!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+mod private {
+    pub trait Sealed {}
+}